@@ -70,6 +70,8 @@ async fn when_create_events_with_aggregate(
                 aggregate_id: Some(aggregate_id.clone()),
                 event_hash: None,
                 previous_hash: None,
+                schema_version: None,
+                aggregate_version: None,
             };
 
             store.append_envelope(&envelope).expect("Failed to append envelope");
@@ -111,6 +113,8 @@ async fn when_create_events_without_aggregate(world: &mut LithairWorld, count: u
                 aggregate_id: None, // Global!
                 event_hash: None,
                 previous_hash: None,
+                schema_version: None,
+                aggregate_version: None,
             };
 
             store.append_envelope(&envelope).expect("Failed to append envelope");
@@ -531,6 +535,8 @@ async fn when_measure_distributed_creation(
                     aggregate_id: Some(aggregate_id.clone()),
                     event_hash: None,
                     previous_hash: None,
+                    schema_version: None,
+                    aggregate_version: None,
                 };
 
                 store.append_envelope(&envelope).expect("Failed to append");
@@ -676,6 +682,8 @@ async fn when_launch_concurrent_tasks(
                     aggregate_id: Some(aggregate_id.clone()),
                     event_hash: None,
                     previous_hash: None,
+                    schema_version: None,
+                    aggregate_version: None,
                 };
 
                 store.append_envelope(&envelope).expect("Failed to append");
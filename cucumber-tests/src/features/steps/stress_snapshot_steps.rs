@@ -34,6 +34,8 @@ async fn when_create_events_with_throughput(
                 aggregate_id: Some(aggregate_id.clone()),
                 event_hash: None,
                 previous_hash: None,
+                schema_version: None,
+                aggregate_version: None,
             };
 
             store.append_envelope(&envelope).expect("Failed to append envelope");
@@ -99,6 +101,8 @@ async fn when_create_events_by_batch(
                     aggregate_id: Some(aggregate_id.clone()),
                     event_hash: None,
                     previous_hash: None,
+                    schema_version: None,
+                    aggregate_version: None,
                 };
 
                 store.append_envelope(&envelope).expect("Failed to append envelope");
@@ -168,6 +172,8 @@ async fn when_create_distributed_events(
                     aggregate_id: Some(aggregate_id.clone()),
                     event_hash: None,
                     previous_hash: None,
+                    schema_version: None,
+                    aggregate_version: None,
                 };
 
                 store.append_envelope(&envelope).expect("Failed to append envelope");
@@ -220,8 +220,7 @@ async fn main() -> Result<()> {
         .init();
 
     let args = ClusterArgs::parse();
-    let peer_ports = args.peers.clone().unwrap_or_default();
-    let peers: Vec<String> = peer_ports.iter().map(|p| format!("127.0.0.1:{}", p)).collect();
+    let peers = args.cluster_peers();
 
     // Auto-configure data directories (honor EXPERIMENT_DATA_BASE when provided)
     let base_dir = std::env::var("EXPERIMENT_DATA_BASE").unwrap_or_else(|_| "data".to_string());
@@ -269,8 +268,12 @@ async fn main() -> Result<()> {
 
     // Enable Raft cluster if peers are provided
     if !peers.is_empty() {
+        let peer_count = peers.len();
         server = server.with_raft_cluster(args.node_id, peers);
-        log::info!("🔗 Cluster mode enabled with {} peers", peer_ports.len());
+        if let Some(address) = &args.address {
+            server = server.with_node_address(address);
+        }
+        log::info!("🔗 Cluster mode enabled with {} peers", peer_count);
     } else {
         log::info!("📦 Single-node mode (no peers)");
     }
@@ -0,0 +1,239 @@
+//! Transactional writes across models
+//!
+//! `POST /_batch` takes creates, updates and deletes on any registered models and
+//! applies all of them or none:
+//!
+//! ```json
+//! {"operations": [
+//!   {"op": "create", "model": "Order", "data": {"product_id": "p1", "quantity": 2}},
+//!   {"op": "update", "model": "Product", "id": "p1", "data": {"stock": 8}, "if_match": "\"4\""},
+//!   {"op": "delete", "model": "/api/carts", "id": "c9"}
+//! ]}
+//! ```
+//!
+//! `model` is a model name or base path. An update sets the fields of `data` on the
+//! stored item and is checked like a PATCH of those fields, so it cannot change the
+//! primary key or an immutable field; `if_match` makes an update or delete conditional,
+//! like the `If-Match` header. Every operation is checked (permissions, validation,
+//! unique keys, preconditions) before anything is written, and the first failure rejects
+//! the whole batch with its index.
+//!
+//! The batch then applies under the event log and storage locks of every model it
+//! touches, so readers see all of it or none of it. Updates and deletes only apply to
+//! the item version they were checked against: an item written in between fails the
+//! batch with 412. A write failing midway rolls back the ones before it. Each model logs
+//! its events between `BatchBegun` and `BatchCommitted` markers, the commit markers
+//! written once every model logged its events, and replay skips a batch without one. In
+//! a cluster the batch is a single consensus log entry (`CrudOperation::Batch`) that
+//! every node applies whole.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Most operations one batch may carry
+pub const MAX_BATCH_OPERATIONS: usize = 1000;
+
+/// Body of `POST /_batch`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+/// One write of a batch
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOperation {
+    Create {
+        model: String,
+        data: Value,
+    },
+    Update {
+        model: String,
+        id: String,
+        data: Value,
+        #[serde(default)]
+        if_match: Option<String>,
+    },
+    Delete {
+        model: String,
+        id: String,
+        #[serde(default)]
+        if_match: Option<String>,
+    },
+}
+
+impl BatchOperation {
+    /// Model name or base path the operation targets
+    pub fn model(&self) -> &str {
+        match self {
+            BatchOperation::Create { model, .. }
+            | BatchOperation::Update { model, .. }
+            | BatchOperation::Delete { model, .. } => model,
+        }
+    }
+
+    /// Id of the item written, None for a create without one
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            BatchOperation::Create { data, .. } => data.get("id").and_then(Value::as_str),
+            BatchOperation::Update { id, .. } | BatchOperation::Delete { id, .. } => Some(id),
+        }
+    }
+
+    pub fn if_match(&self) -> Option<&str> {
+        match self {
+            BatchOperation::Create { .. } => None,
+            BatchOperation::Update { if_match, .. } | BatchOperation::Delete { if_match, .. } => {
+                if_match.as_deref()
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchOperation::Create { .. } => "create",
+            BatchOperation::Update { .. } => "update",
+            BatchOperation::Delete { .. } => "delete",
+        }
+    }
+}
+
+impl BatchRequest {
+    /// Parse a request body: one to `MAX_BATCH_OPERATIONS` operations, with object
+    /// `data`
+    pub fn parse(body: &[u8]) -> Result<Self, BatchRejection> {
+        let request: Self = serde_json::from_slice(body)
+            .map_err(|e| BatchRejection::invalid(format!("Invalid batch: {}", e)))?;
+        if request.operations.is_empty() {
+            return Err(BatchRejection::invalid("batch has no operations"));
+        }
+        if request.operations.len() > MAX_BATCH_OPERATIONS {
+            return Err(BatchRejection::invalid(format!(
+                "batch has more than {} operations",
+                MAX_BATCH_OPERATIONS
+            )));
+        }
+        for (index, operation) in request.operations.iter().enumerate() {
+            if let BatchOperation::Create { data, .. } | BatchOperation::Update { data, .. } =
+                operation
+            {
+                if !data.is_object() {
+                    let error = json!({ "error": "data must be a JSON object" });
+                    return Err(BatchRejection::new(index, 400, error));
+                }
+            }
+        }
+        Ok(request)
+    }
+}
+
+/// Why a batch was rejected; nothing of it was written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRejection {
+    /// Operation that failed, None when the batch as a whole is malformed
+    pub index: Option<usize>,
+    pub status: u16,
+    /// Error body the operation got, as its single-item endpoint would answer it
+    pub error: Value,
+}
+
+impl BatchRejection {
+    pub fn new(index: usize, status: u16, error: Value) -> Self {
+        Self { index: Some(index), status, error }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self { index: None, status: 400, error: json!({ "error": message.into() }) }
+    }
+
+    /// Response body
+    pub fn to_json(&self) -> Value {
+        let message = match self.index {
+            Some(index) => format!("operation {} failed, nothing was written", index),
+            None => "invalid batch, nothing was written".to_string(),
+        };
+        json!({
+            "error": "batch_rejected",
+            "message": message,
+            "index": self.index,
+            "cause": self.error
+        })
+    }
+}
+
+/// Give a new item the id and timestamps a clustered create needs to be identical on
+/// every node
+pub fn stamp_new_item(data: &mut Value, now: &str) {
+    if data.get("id").is_none_or(Value::is_null) {
+        data["id"] = Value::String(uuid::Uuid::new_v4().to_string());
+    }
+    for field in ["created_at", "updated_at"] {
+        if data.get(field).is_none() {
+            data[field] = Value::String(now.to_string());
+        }
+    }
+}
+
+/// `item` with the top-level fields of `changes` set
+pub fn merge_fields(item: &Value, changes: &Value) -> Value {
+    let mut merged = item.clone();
+    if let (Some(object), Some(changes)) = (merged.as_object_mut(), changes.as_object()) {
+        for (key, value) in changes {
+            object.insert(key.clone(), value.clone());
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch() {
+        let body = br#"{"operations": [
+            {"op": "create", "model": "Order", "data": {"id": "o1", "total": 20}},
+            {"op": "update", "model": "Product", "id": "p1", "data": {"stock": 8},
+             "if_match": "\"4\""},
+            {"op": "delete", "model": "/api/carts", "id": "c9"}
+        ]}"#;
+        let request = BatchRequest::parse(body).unwrap();
+        let ops = &request.operations;
+        assert_eq!(
+            ops.iter().map(|op| op.name()).collect::<Vec<_>>(),
+            ["create", "update", "delete"]
+        );
+        assert_eq!(
+            ops.iter().map(|op| op.id()).collect::<Vec<_>>(),
+            [Some("o1"), Some("p1"), Some("c9")]
+        );
+        assert_eq!(ops[1].if_match(), Some("\"4\""));
+        assert_eq!(ops[2].model(), "/api/carts");
+
+        let rejected = |body: &[u8]| BatchRequest::parse(body).unwrap_err();
+        assert_eq!(rejected(br#"{"operations": []}"#).index, None);
+        assert_eq!(rejected(br#"{"operations": [{"op": "upsert"}]}"#).status, 400);
+        let not_object = br#"{"operations": [
+            {"op": "delete", "model": "Order", "id": "o1"},
+            {"op": "create", "model": "Order", "data": [1]}
+        ]}"#;
+        let rejection = rejected(not_object);
+        assert_eq!(rejection.index, Some(1));
+        assert_eq!(rejection.to_json()["cause"]["error"], "data must be a JSON object");
+    }
+
+    #[test]
+    fn test_stamp_and_merge() {
+        let mut data = json!({"total": 20, "created_at": "2026-01-01T00:00:00Z"});
+        stamp_new_item(&mut data, "2026-02-01T00:00:00Z");
+        assert!(data["id"].is_string());
+        assert_eq!(data["created_at"], "2026-01-01T00:00:00Z");
+        assert_eq!(data["updated_at"], "2026-02-01T00:00:00Z");
+
+        let item = json!({"id": "p1", "name": "Lamp", "stock": 10});
+        assert_eq!(
+            merge_fields(&item, &json!({"stock": 8})),
+            json!({"id": "p1", "name": "Lamp", "stock": 8})
+        );
+    }
+}
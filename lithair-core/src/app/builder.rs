@@ -3,7 +3,7 @@
 use super::{CustomRoute, LithairServer};
 use crate::config::LithairConfig;
 use crate::session::{PersistentSessionStore, SessionManager};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::sync::Arc;
 
 /// Builder for LithairServer
@@ -14,6 +14,10 @@ pub struct LithairServerBuilder {
     custom_routes: Vec<CustomRoute>,
     not_found_handler: Option<super::RouteHandler>,
     model_infos: Vec<crate::app::ModelRegistrationInfo>,
+    // Upcasters per model name, read by the declarative model factories in serve()
+    schema_migrations: Arc<
+        std::sync::RwLock<std::collections::HashMap<String, Arc<crate::schema::SchemaMigrations>>>,
+    >,
 
     // HTTP Features
     logging_config: Option<crate::logging::LoggingConfig>,
@@ -46,6 +50,9 @@ pub struct LithairServerBuilder {
 
     // OpenAPI spec generation
     openapi_enabled: bool,
+
+    // First error a builder method could not return itself, reported by build()
+    setup_error: Option<anyhow::Error>,
 }
 
 impl LithairServerBuilder {
@@ -63,6 +70,7 @@ impl LithairServerBuilder {
             custom_routes: Vec::new(),
             not_found_handler: None,
             model_infos: Vec::new(),
+            schema_migrations: Arc::default(),
             logging_config: None,
             readiness_config: None,
             observe_config: None,
@@ -83,6 +91,7 @@ impl LithairServerBuilder {
             schema_vote_policy: None,
             openapi_enabled: false,
             sse_enabled: false,
+            setup_error: None,
         }
     }
 
@@ -95,6 +104,7 @@ impl LithairServerBuilder {
             custom_routes: Vec::new(),
             not_found_handler: None,
             model_infos: Vec::new(),
+            schema_migrations: Arc::default(),
             logging_config: None,
             readiness_config: None,
             observe_config: None,
@@ -115,6 +125,7 @@ impl LithairServerBuilder {
             schema_vote_policy: None,
             openapi_enabled: false,
             sse_enabled: false,
+            setup_error: None,
         }
     }
 
@@ -281,6 +292,96 @@ impl LithairServerBuilder {
         self
     }
 
+    /// Join a running cluster as a new node, reaching it through any of `peers`
+    ///
+    /// The node asks the leader to add it as a learner. The leader pushes it a snapshot,
+    /// replicates the rest of the log to it, then promotes it to voter. `node_id` must be
+    /// unused in the cluster and not 0, and the address the node advertises
+    /// (`with_node_address`) must be reachable from the other nodes.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// LithairServer::new()
+    ///     .with_cluster_join(4, vec!["https://db-1.internal:8443"])
+    ///     .with_node_address("https://db-4.internal:8443")
+    ///     .serve()
+    ///     .await?;
+    /// ```
+    pub fn with_cluster_join(self, node_id: u64, peers: Vec<impl Into<String>>) -> Self {
+        let mut builder = self.with_raft_cluster(node_id, peers);
+        builder.config.raft.join = true;
+        builder
+    }
+
+    /// Set the address this node advertises to its peers and to the clients it redirects
+    ///
+    /// Peers given to `with_raft_cluster` may be `host:port` or `scheme://host:port`.
+    /// Without it, nodes advertise `http://127.0.0.1:{port}` and only work as a cluster on
+    /// one host.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// LithairServer::new()
+    ///     .with_port(8443)
+    ///     .with_raft_cluster(1, vec!["https://db-1.internal:8443", "https://db-3.internal:8443"])
+    ///     .with_node_address("https://db-2.internal:8443")
+    ///     .serve()
+    ///     .await?;
+    /// ```
+    pub fn with_node_address(mut self, address: impl Into<String>) -> Self {
+        self.config.raft.advertise_address = Some(address.into());
+        self
+    }
+
+    /// Have followers forward writes to the leader and relay its response, instead of
+    /// answering 307 with the leader's address
+    ///
+    /// For clients that cannot follow the redirect, such as those behind a load balancer
+    /// that only reach the balancer. Tune with `RaftConfig::forward_timeout_ms` and
+    /// `forward_retries`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// LithairServer::new()
+    ///     .with_raft_cluster(2, vec!["10.0.0.1:8080", "10.0.0.3:8080"])
+    ///     .with_node_address("http://10.0.0.2:8080")
+    ///     .with_leader_forwarding(true)
+    ///     .serve()
+    ///     .await?;
+    /// ```
+    pub fn with_leader_forwarding(mut self, enabled: bool) -> Self {
+        self.config.raft.forward_writes = enabled;
+        self
+    }
+
+    /// Authenticate and encrypt traffic between nodes with mutual TLS
+    ///
+    /// Every node certificate is signed by the CA in `ca_cert_path` and issued for
+    /// `node-{id}.lithair.cluster`. The node serves HTTPS with it, so peers and the node
+    /// address must be `https://` ones. Certificates are reloaded when their files change.
+    /// See `ClusterTlsConfig` for the node domain and reload interval.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// LithairServer::new()
+    ///     .with_port(8443)
+    ///     .with_raft_cluster(2, vec!["https://db-1.internal:8443", "https://db-3.internal:8443"])
+    ///     .with_node_address("https://db-2.internal:8443")
+    ///     .with_cluster_tls("certs/ca.pem", "certs/node-2.pem", "certs/node-2-key.pem")
+    ///     .serve()
+    ///     .await?;
+    /// ```
+    pub fn with_cluster_tls(
+        mut self,
+        ca_cert_path: impl Into<String>,
+        cert_path: impl Into<String>,
+        key_path: impl Into<String>,
+    ) -> Self {
+        let tls = crate::config::ClusterTlsConfig::new(ca_cert_path, cert_path, key_path);
+        self.config.raft.tls = Some(tls);
+        self
+    }
+
     /// Set the Raft configuration (path, auth, timeouts)
     ///
     /// # Example
@@ -542,6 +643,8 @@ impl LithairServerBuilder {
     /// - Registers GET /auth/validate handler (session validation)
     /// - Returns PermissionChecker for use with models
     ///
+    /// A user store that cannot be opened or seeded makes `build`/`serve` fail.
+    ///
     /// # Example
     /// ```ignore
     /// use lithair_core::rbac::{ServerRbacConfig, RbacUser};
@@ -562,7 +665,9 @@ impl LithairServerBuilder {
     ///     .await?;
     /// ```
     pub fn with_rbac_config(mut self, config: crate::rbac::ServerRbacConfig) -> Self {
-        use crate::rbac::{handle_rbac_login, handle_rbac_logout};
+        use crate::rbac::{
+            handle_rbac_login, handle_rbac_logout, handle_user_store_login, UserStore,
+        };
         use std::sync::Arc;
 
         // Create session store path (default if not provided)
@@ -595,18 +700,38 @@ impl LithairServerBuilder {
             }
         };
 
+        // Event-sourced accounts, seeded from the static user list
+        let open_user_store = |path: &String| -> Result<UserStore> {
+            let store = UserStore::new(std::path::PathBuf::from(path))
+                .with_context(|| format!("Cannot open user store at {}", path))?;
+            let seeded = store.seed(&config.users).context("Failed to seed user store")?;
+            if seeded > 0 {
+                log::info!("Seeded {} users into user store", seeded);
+            }
+            Ok(store)
+        };
+        let user_store = match config.user_store_path.as_ref().map(open_user_store).transpose() {
+            Ok(store) => store,
+            Err(e) => {
+                self.setup_error.get_or_insert(e);
+                None
+            }
+        };
+
         // Store session manager AND permission checker for use by models
         self.session_manager = Some(session_store_shared.clone());
-        self.permission_checker = Some(permission_checker);
+        self.permission_checker = Some(permission_checker.clone());
 
         // Add login route
         let session_store_login = session_store_shared.clone();
         let mfa_storage_login = self.mfa_storage.clone();
+        let user_store_login = user_store.clone();
         self = self.with_route(http::Method::POST, "/auth/login", move |req| {
             let users = users_login.clone();
             let duration = session_duration;
             let store_clone = session_store_login.clone();
             let mfa_clone = mfa_storage_login.clone();
+            let user_store = user_store_login.clone();
 
             Box::pin(async move {
                 // Use shared session store (already created above)
@@ -614,7 +739,16 @@ impl LithairServerBuilder {
                     .downcast()
                     .map_err(|_| anyhow::anyhow!("Failed to downcast session store"))?;
 
-                match handle_rbac_login(req, session_store, &users, duration, mfa_clone).await {
+                let result = match &user_store {
+                    Some(user_store) => {
+                        handle_user_store_login(req, session_store, user_store, duration, mfa_clone)
+                            .await
+                    }
+                    None => {
+                        handle_rbac_login(req, session_store, &users, duration, mfa_clone).await
+                    }
+                };
+                match result {
                     Ok(resp) => Ok(resp),
                     Err(e) => {
                         log::error!("Login error: {}", e);
@@ -707,12 +841,104 @@ impl LithairServerBuilder {
         log::info!(
             "RBAC configured with {} roles and {} users",
             config.roles.len(),
-            users_clone.len()
+            user_store.as_ref().map_or(users_clone.len(), |store| store.len())
         );
         log::info!("   POST /auth/login - Authentication endpoint");
         log::info!("   POST /auth/logout - Logout endpoint");
         log::info!("   GET /auth/validate - Session validation endpoint");
 
+        if let Some(user_store) = user_store {
+            self = self.with_user_routes(
+                user_store,
+                session_store_shared,
+                permission_checker,
+                config.role_names(),
+                config.registration_role.clone(),
+            );
+        }
+
+        self
+    }
+
+    /// Register the `UserStore` endpoints: `/auth/password`, `/_admin/users/**` and,
+    /// when a registration role is configured, `/auth/register`
+    fn with_user_routes(
+        mut self,
+        user_store: crate::rbac::UserStore,
+        session_store: Arc<dyn std::any::Any + Send + Sync>,
+        permission_checker: Arc<dyn crate::rbac::PermissionChecker>,
+        roles: Vec<String>,
+        registration_role: Option<String>,
+    ) -> Self {
+        use crate::rbac::{
+            handle_admin_users, handle_change_password, handle_register, USER_ADMIN_PERMISSION,
+        };
+        use bytes::Bytes;
+        use http_body_util::Full;
+
+        fn internal_error(context: &str, e: anyhow::Error) -> hyper::Response<Full<Bytes>> {
+            log::error!("{} error: {}", context, e);
+            hyper::Response::builder()
+                .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Full::new(Bytes::from(format!("Internal error: {}", e))))
+                .unwrap()
+        }
+
+        if let Some(role) = registration_role {
+            let user_store = user_store.clone();
+            log::info!("   POST /auth/register - Self-registration as {}", role);
+            self = self.with_route(http::Method::POST, "/auth/register", move |req| {
+                let user_store = user_store.clone();
+                let role = role.clone();
+                Box::pin(async move {
+                    Ok(handle_register(req, &user_store, &role)
+                        .await
+                        .unwrap_or_else(|e| internal_error("Register", e)))
+                })
+            });
+        }
+
+        let password_store = user_store.clone();
+        let password_sessions = session_store.clone();
+        self = self.with_route(http::Method::POST, "/auth/password", move |req| {
+            let user_store = password_store.clone();
+            let store_clone = password_sessions.clone();
+            Box::pin(async move {
+                let session_store: Arc<PersistentSessionStore> = store_clone
+                    .downcast()
+                    .map_err(|_| anyhow::anyhow!("Failed to downcast session store"))?;
+                Ok(handle_change_password(req, session_store, &user_store)
+                    .await
+                    .unwrap_or_else(|e| internal_error("Password change", e)))
+            })
+        });
+
+        let roles = Arc::new(roles);
+        use http::Method;
+        for method in [Method::GET, Method::POST, Method::PUT, Method::DELETE] {
+            let user_store = user_store.clone();
+            let session_store = session_store.clone();
+            let checker = permission_checker.clone();
+            let roles = roles.clone();
+            self = self.with_route(method, "/_admin/users/**", move |req| {
+                let user_store = user_store.clone();
+                let store_clone = session_store.clone();
+                let checker = checker.clone();
+                let roles = roles.clone();
+                Box::pin(async move {
+                    let session_store: Arc<PersistentSessionStore> = store_clone
+                        .downcast()
+                        .map_err(|_| anyhow::anyhow!("Failed to downcast session store"))?;
+                    Ok(handle_admin_users(req, session_store, &user_store, &*checker, &roles)
+                        .await
+                        .unwrap_or_else(|e| internal_error("User admin", e)))
+                })
+            });
+        }
+
+        log::info!("   POST /auth/password - Change own password");
+        log::info!("   /_admin/users - User administration ({})", USER_ADMIN_PERMISSION);
+
         self
     }
 
@@ -1409,12 +1635,18 @@ impl LithairServerBuilder {
         let base_path_str = base_path.into();
 
         // Create factory that will create the handler async in serve()
+        let registry = Arc::clone(&self.schema_migrations);
         let factory: crate::app::ModelFactory = Arc::new(move |data_path: String| {
+            let migrations =
+                registry.read().ok().and_then(|m| m.get(name).cloned()).unwrap_or_default();
             Box::pin(async move {
-                let handler = DeclarativeModelHandler::<T>::new(data_path)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Failed to create handler: {}", e))?
-                    .with_schema_spec(T::schema_spec());
+                let handler = DeclarativeModelHandler::<T>::new_with_schema(
+                    data_path,
+                    T::schema_spec(),
+                    migrations,
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to create handler: {}", e))?;
                 Ok(Arc::new(handler) as Arc<dyn crate::app::ModelHandler>)
             })
         });
@@ -1433,6 +1665,32 @@ impl LithairServerBuilder {
         self
     }
 
+    /// Register data migrations for a model added with `with_declarative_model`
+    ///
+    /// Upcasters bring events written under an older `ModelSpec.version` (set with
+    /// `#[schema(version = N)]`) up to the current one before they are deserialized,
+    /// on replay and when a schema change is approved. Call order does not matter.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// LithairServer::new()
+    ///     .with_declarative_model::<Product>("./data/products", "/api/products")
+    ///     .with_schema_migrations::<Product>(
+    ///         SchemaMigrations::new().upcast(1, Upcaster::new().rename_field("name", "title")),
+    ///     )
+    ///     .serve()
+    ///     .await?;
+    /// ```
+    pub fn with_schema_migrations<T>(self, migrations: crate::schema::SchemaMigrations) -> Self
+    where
+        T: crate::schema::HasSchemaSpec,
+    {
+        if let Ok(mut registry) = self.schema_migrations.write() {
+            registry.insert(T::model_name().to_string(), Arc::new(migrations));
+        }
+        self
+    }
+
     // ========================================================================
     // DATA ADMIN - Database management API for admin dashboards
     // ========================================================================
@@ -1573,6 +1831,9 @@ impl LithairServerBuilder {
 
     /// Build the server
     pub fn build(self) -> Result<LithairServer> {
+        if let Some(e) = self.setup_error {
+            return Err(e);
+        }
         Ok(LithairServer {
             config: self.config,
             session_manager: self.session_manager,
@@ -1597,6 +1858,7 @@ impl LithairServerBuilder {
             deprecation_warnings: self.deprecation_warnings,
             openapi_enabled: self.openapi_enabled,
             openapi_spec_cache: std::sync::OnceLock::new(),
+            peer_clients: Arc::new(crate::cluster::PeerClients::default()),
             // Raft cluster
             cluster_peers: self.cluster_peers.clone(),
             node_id: self.node_id,
@@ -1657,6 +1919,7 @@ impl LithairServerBuilder {
             },
             // Resync stats for observability
             resync_stats: Arc::new(crate::cluster::ResyncStats::new()),
+            references: tokio::sync::RwLock::new(()),
             // Schema sync state for cluster-wide schema consensus
             schema_sync_state: Arc::new(tokio::sync::RwLock::new(
                 self.schema_vote_policy
@@ -0,0 +1,361 @@
+//! Read consistency levels
+//!
+//! Reads are served from the memory of the node that gets them. On a follower that state
+//! lags the leader's by however long replication takes, and more when the follower was
+//! cut off. A read asks for more with the `X-Lithair-Consistency` header or the
+//! `consistency` query parameter:
+//!
+//! - `any` (default): this node's state, however old
+//! - `bounded(ms)`: state at most `ms` milliseconds behind the leader's. A follower that
+//!   heard the leader's commit index within `ms` (heartbeats and log appends carry it) and
+//!   applied up to it serves the read at once; otherwise it proceeds as for `linearizable`
+//! - `leader`: served by the leader; followers forward or redirect the read like a write
+//! - `linearizable`: sees every write acknowledged before the read arrived (ReadIndex).
+//!   The leader notes its commit index, checks it still leads by having a majority of
+//!   voters confirm they follow it in its term, and serves the read once it applied up to that
+//!   index. A follower gets that index from the leader (`GET /_raft/read-index`) and
+//!   waits to apply up to it.
+//!
+//! A node that cannot reach the leader or catch up within `RaftConfig::read_timeout_ms`
+//! answers `503` with `Retry-After` rather than stale data. Without cluster mode every
+//! level reads the local state, which is the only one.
+
+use super::LithairServer;
+use crate::cluster::{ConsensusLog, RaftLeadershipState};
+use anyhow::Result;
+use bytes::Bytes;
+use futures::stream::{FuturesUnordered, StreamExt};
+use http_body_util::Full;
+use hyper::{Request, Response, StatusCode};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Header a read names its consistency level in
+pub const CONSISTENCY_HEADER: &str = "x-lithair-consistency";
+
+/// How often a waiting read checks whether this node caught up
+const APPLY_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How fresh the state a read sees must be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadConsistency {
+    /// This node's state
+    #[default]
+    Any,
+    /// At most this far behind the leader
+    Bounded(Duration),
+    /// The leader's state
+    Leader,
+    /// Every write acknowledged before the read
+    Linearizable,
+}
+
+impl ReadConsistency {
+    /// Parse `linearizable`, `leader`, `bounded(ms)` or `any`
+    pub fn parse(value: &str) -> std::result::Result<Self, String> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "any" => Ok(Self::Any),
+            "leader" => Ok(Self::Leader),
+            "linearizable" => Ok(Self::Linearizable),
+            _ => value
+                .strip_prefix("bounded(")
+                .and_then(|v| v.strip_suffix(')'))
+                .map(|v| v.trim().trim_end_matches("ms"))
+                .and_then(|v| v.parse().ok())
+                .map(|millis| Self::Bounded(Duration::from_millis(millis)))
+                .ok_or_else(|| {
+                    format!("`{}` is not one of linearizable, leader, bounded(ms) or any", value)
+                }),
+        }
+    }
+
+    /// Level `req` asks for: its header, else its `consistency` query parameter
+    pub fn from_request<B>(req: &Request<B>) -> std::result::Result<Self, String> {
+        if let Some(value) = req.headers().get(CONSISTENCY_HEADER) {
+            let value = value.to_str().map_err(|_| "invalid header value".to_string())?;
+            return Self::parse(value);
+        }
+        let query = req.uri().query().unwrap_or("");
+        match query.split('&').find_map(|pair| pair.strip_prefix("consistency=")) {
+            Some(value) => Self::parse(&urlencoding::decode(value).map_err(|e| e.to_string())?),
+            None => Ok(Self::Any),
+        }
+    }
+}
+
+impl fmt::Display for ReadConsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Bounded(bound) => write!(f, "bounded({})", bound.as_millis()),
+            Self::Leader => write!(f, "leader"),
+            Self::Linearizable => write!(f, "linearizable"),
+        }
+    }
+}
+
+fn error_response(
+    status: StatusCode,
+    error: &str,
+    mut body: serde_json::Value,
+) -> Response<Full<Bytes>> {
+    body["error"] = error.into();
+    let mut response =
+        Response::builder().status(status).header("Content-Type", "application/json");
+    if status == StatusCode::SERVICE_UNAVAILABLE {
+        response = response.header("Retry-After", "1");
+    }
+    response
+        .body(Full::new(Bytes::from(body.to_string())))
+        .expect("valid HTTP response")
+}
+
+/// 503 for a read this node cannot serve at `consistency` in time
+fn unavailable(consistency: ReadConsistency, message: String) -> Response<Full<Bytes>> {
+    log::debug!("Refusing {} read: {}", consistency, message);
+    let body = serde_json::json!({
+        "consistency": consistency.to_string(),
+        "message": message,
+    });
+    error_response(StatusCode::SERVICE_UNAVAILABLE, "consistency_unavailable", body)
+}
+
+impl LithairServer {
+    /// Hold the read `req` until this node can serve it at the consistency it asks for;
+    /// `Err` is the response to send instead (the leader's answer, a redirect, a 400 or a
+    /// 503)
+    pub(crate) async fn read_barrier(
+        &self,
+        req: Request<hyper::body::Incoming>,
+    ) -> Result<std::result::Result<Request<hyper::body::Incoming>, Response<Full<Bytes>>>> {
+        let consistency = match ReadConsistency::from_request(&req) {
+            Ok(consistency) => consistency,
+            Err(e) => {
+                let body = serde_json::json!({ "message": e });
+                return Ok(Err(error_response(
+                    StatusCode::BAD_REQUEST,
+                    "invalid_consistency",
+                    body,
+                )));
+            }
+        };
+        let (Some(raft_state), Some(consensus_log)) = (&self.raft_state, &self.consensus_log)
+        else {
+            return Ok(Ok(req));
+        };
+        let deadline = Instant::now() + Duration::from_millis(self.config.raft.read_timeout_ms);
+
+        let read_index = match consistency {
+            ReadConsistency::Any => return Ok(Ok(req)),
+            ReadConsistency::Bounded(_) | ReadConsistency::Leader if raft_state.is_leader() => {
+                return Ok(Ok(req));
+            }
+            ReadConsistency::Leader => {
+                if self.forwards_write(&req) {
+                    return self.forward_to_leader(req, raft_state).await.map(Err);
+                }
+                let path = req.uri().path_and_query().map_or("/", |pq| pq.as_str());
+                return Ok(Err(Self::not_leader_response(raft_state, path)));
+            }
+            ReadConsistency::Bounded(bound) => {
+                let (leader_commit, age) = raft_state.leader_commit();
+                if age <= bound && consensus_log.applied_index() >= leader_commit {
+                    return Ok(Ok(req));
+                }
+                self.read_index(raft_state, consensus_log, deadline).await
+            }
+            ReadConsistency::Linearizable => {
+                self.read_index(raft_state, consensus_log, deadline).await
+            }
+        };
+        let read_index = match read_index {
+            Ok(read_index) => read_index,
+            Err(message) => return Ok(Err(unavailable(consistency, message))),
+        };
+
+        loop {
+            let applied = consensus_log.applied_index();
+            if applied >= read_index {
+                return Ok(Ok(req));
+            }
+            if Instant::now() >= deadline {
+                let message = format!(
+                    "node {} applied the log up to {}, the read needs {}",
+                    raft_state.node_id, applied, read_index
+                );
+                return Ok(Err(unavailable(consistency, message)));
+            }
+            tokio::time::sleep(APPLY_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Log index a linearizable read waits for: the leader's commit index, once the leader
+    /// checked it still leads
+    async fn read_index(
+        &self,
+        raft_state: &RaftLeadershipState,
+        consensus_log: &ConsensusLog,
+        deadline: Instant,
+    ) -> std::result::Result<u64, String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if raft_state.is_leader() {
+            let commit_index = consensus_log.commit_index();
+            self.confirm_leadership(raft_state, commit_index, timeout).await?;
+            return Ok(commit_index);
+        }
+
+        let Some(leader) = raft_state.known_leader_address().filter(|_| !timeout.is_zero()) else {
+            return Err("no leader is known".to_string());
+        };
+        let mut request = self.peer_clients.client(timeout).get(leader.url("/_raft/read-index"));
+        if let Some(ref token) = self.config.raft.auth_token {
+            request = request.header("X-Raft-Token", token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("leader {} did not answer: {}", leader, e))?;
+        if !response.status().is_success() {
+            return Err(format!("leader {} answered {}", leader, response.status()));
+        }
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("invalid read index from {}: {}", leader, e))?;
+        let read_index = body["read_index"]
+            .as_u64()
+            .ok_or_else(|| format!("invalid read index from {}", leader))?;
+        raft_state.record_leader_commit(read_index);
+        Ok(read_index)
+    }
+
+    /// Check that a majority of voters still takes this node for leader, by sending them a
+    /// leadership check. A voter acknowledges only if it follows this node in this term
+    async fn confirm_leadership(
+        &self,
+        raft_state: &RaftLeadershipState,
+        commit_index: u64,
+        timeout: Duration,
+    ) -> std::result::Result<(), String> {
+        let membership = raft_state.membership();
+        let own = raft_state.self_address.to_string();
+        let mut acked = vec![own.clone()];
+        if membership.has_quorum(&acked) {
+            return Ok(());
+        }
+        if !timeout.is_zero() {
+            let client = self.peer_clients.client(timeout);
+            let path = self.config.raft.heartbeat_path();
+            let term = self.consensus_log.as_ref().map_or(0, |l| l.current_term());
+            let mut heartbeat = raft_state.heartbeat_message(commit_index, term);
+            // Peers only answer whether they follow this node, changing nothing
+            heartbeat["check"] = true.into();
+            let mut requests: FuturesUnordered<_> = membership
+                .voter_peers(&own)
+                .into_iter()
+                .map(|peer| {
+                    let mut request =
+                        client.post(crate::cluster::peer_url(&peer, &path)).json(&heartbeat);
+                    if let Some(ref token) = self.config.raft.auth_token {
+                        request = request.header("X-Raft-Token", token);
+                    }
+                    async move {
+                        let confirmed = match request.send().await {
+                            Ok(response) if response.status().is_success() => response
+                                .json::<serde_json::Value>()
+                                .await
+                                .is_ok_and(|reply| raft_state.acknowledges(&reply, term)),
+                            _ => false,
+                        };
+                        (peer, confirmed)
+                    }
+                })
+                .collect();
+            while let Some((peer, confirmed)) = requests.next().await {
+                if confirmed {
+                    acked.push(peer);
+                    if membership.has_quorum(&acked) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Err(format!(
+            "a majority of voters did not confirm node {} as leader",
+            raft_state.node_id
+        ))
+    }
+
+    /// `GET /_raft/read-index`: the commit index a follower's linearizable read waits for
+    pub(crate) async fn handle_raft_read_index(
+        &self,
+        req: Request<hyper::body::Incoming>,
+    ) -> Result<Response<Full<Bytes>>> {
+        let token = req.headers().get("X-Raft-Token").and_then(|v| v.to_str().ok());
+        let (Some(raft_state), Some(consensus_log)) = (&self.raft_state, &self.consensus_log)
+        else {
+            let body = serde_json::json!({ "message": "Cluster mode is not enabled" });
+            return Ok(error_response(StatusCode::NOT_FOUND, "not_found", body));
+        };
+        if !self.config.raft.validate_token(token) {
+            let body = serde_json::json!({ "message": "Invalid Raft token" });
+            return Ok(error_response(StatusCode::UNAUTHORIZED, "unauthorized", body));
+        }
+        if !raft_state.is_leader() {
+            return Ok(Self::not_leader_response(raft_state, "/_raft/read-index"));
+        }
+
+        let commit_index = consensus_log.commit_index();
+        let timeout = Duration::from_millis(self.config.raft.read_timeout_ms);
+        if let Err(message) = self.confirm_leadership(raft_state, commit_index, timeout).await {
+            let body = serde_json::json!({ "message": message });
+            return Ok(error_response(StatusCode::SERVICE_UNAVAILABLE, "not_confirmed", body));
+        }
+        let body = serde_json::json!({
+            "read_index": commit_index,
+            "leader_id": raft_state.node_id,
+        });
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .expect("valid HTTP response"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ReadConsistency::parse("linearizable"), Ok(ReadConsistency::Linearizable));
+        assert_eq!(ReadConsistency::parse(" Leader "), Ok(ReadConsistency::Leader));
+        assert_eq!(ReadConsistency::parse("any"), Ok(ReadConsistency::Any));
+        let bounded = ReadConsistency::Bounded(Duration::from_millis(250));
+        assert_eq!(ReadConsistency::parse("bounded(250)"), Ok(bounded));
+        assert_eq!(ReadConsistency::parse("bounded(250ms)"), Ok(bounded));
+        assert_eq!(bounded.to_string(), "bounded(250)");
+        for invalid in ["strong", "bounded", "bounded(-1)", "bounded(1s)", ""] {
+            assert!(ReadConsistency::parse(invalid).is_err(), "{} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn test_from_request() {
+        let req = Request::get("/api/accounts/1?consistency=bounded%28100%29").body(()).unwrap();
+        let bounded = ReadConsistency::Bounded(Duration::from_millis(100));
+        assert_eq!(ReadConsistency::from_request(&req), Ok(bounded));
+
+        // The header wins over the query
+        let req = Request::get("/api/accounts?consistency=any")
+            .header("X-Lithair-Consistency", "linearizable")
+            .body(())
+            .unwrap();
+        assert_eq!(ReadConsistency::from_request(&req), Ok(ReadConsistency::Linearizable));
+
+        let req = Request::get("/api/accounts?status=open").body(()).unwrap();
+        assert_eq!(ReadConsistency::from_request(&req), Ok(ReadConsistency::Any));
+    }
+}
@@ -0,0 +1,180 @@
+//! Forwarding follower writes to the leader
+//!
+//! By default a follower answers a write with a 307 to the leader's address, which
+//! clients behind a load balancer often cannot follow (they only reach the balancer, or
+//! drop the body on redirect). With `RaftConfig::forward_writes` the follower sends the
+//! write to the leader itself and relays the leader's answer.
+//!
+//! The forwarded request keeps the client's method, path, query, body and headers
+//! (`Authorization`, cookies, `If-Match`...), minus the hop-by-hop ones. It carries the
+//! client address in `X-Forwarded-For`, so the leader's firewall, rate limits and access
+//! log see the client rather than the follower, and `X-Lithair-Forwarded-By` naming the
+//! follower, plus the Raft token when the cluster has one. A node that gets a forwarded
+//! write while not leader answers 307 instead of forwarding it again; the follower then
+//! retries at the leader that 307 names. The header only counts on a request from a
+//! node (its certificate under cluster TLS, else the Raft token): a client setting it
+//! is forwarded as usual.
+//!
+//! Writes are only retried when they did not reach a leader: the connection failed, or
+//! the node reached was not leader (during an election). A write that timed out may have
+//! been applied, so it answers 504 rather than risk applying it twice.
+//!
+//! The leader's answer is relayed as it arrives, minus its hop-by-hop headers, rather than
+//! read whole first. Handlers answer with buffered bodies; a forwarded write is sent on
+//! before any of them runs, so responses are served as a `ServedBody`, either buffered or
+//! the leader's stream.
+
+use bytes::Bytes;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Either, Full, StreamBody};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, HOST};
+use hyper::Response;
+use std::time::Duration;
+
+/// Header naming the node that forwarded a write
+pub const FORWARDED_BY: &str = "x-lithair-forwarded-by";
+
+/// Longest wait between two attempts
+const MAX_BACKOFF: Duration = Duration::from_millis(1000);
+
+/// Headers to send the leader for a client request with `headers`
+///
+/// `client_ip` is appended to `X-Forwarded-For`; `tls` tells whether the client reached
+/// this node over TLS.
+pub fn request_headers(
+    headers: &HeaderMap,
+    client_ip: Option<&str>,
+    tls: bool,
+    node_id: u64,
+) -> HeaderMap {
+    let mut forwarded = headers.clone();
+    crate::proxy::utils::strip_hop_by_hop(&mut forwarded);
+    forwarded.remove(CONTENT_LENGTH);
+    if let Some(host) = forwarded.remove(HOST) {
+        if !forwarded.contains_key("x-forwarded-host") {
+            forwarded.insert("x-forwarded-host", host);
+        }
+    }
+    if let Some(client) = client_ip {
+        // The client address was resolved from the existing chain, which it replaces
+        if let Ok(value) = HeaderValue::from_str(client) {
+            forwarded.insert("x-forwarded-for", value);
+        }
+    }
+    if !forwarded.contains_key("x-forwarded-proto") {
+        let proto = if tls { "https" } else { "http" };
+        forwarded.insert("x-forwarded-proto", HeaderValue::from_static(proto));
+    }
+    forwarded.insert(HeaderName::from_static(FORWARDED_BY), HeaderValue::from(node_id));
+    forwarded
+}
+
+/// Headers of the leader's response to relay to the client
+pub fn response_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut relayed = headers.clone();
+    crate::proxy::utils::strip_hop_by_hop(&mut relayed);
+    relayed.remove(CONTENT_LENGTH);
+    relayed
+}
+
+/// Body of a leader response, streamed from the leader
+pub type RelayBody = UnsyncBoxBody<Bytes, reqwest::Error>;
+
+/// Body of a response served to a client: buffered, or relayed from the leader
+pub type ServedBody = Either<Full<Bytes>, RelayBody>;
+
+/// The leader's `response` to a forwarded write, as this node answers it: its status
+/// and end-to-end headers now, its body as it arrives
+pub fn relay(response: reqwest::Response) -> Response<ServedBody> {
+    let status = response.status();
+    let headers = response_headers(response.headers());
+    let stream = async_stream::stream! {
+        let mut response = response;
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => yield Ok::<_, reqwest::Error>(http_body::Frame::data(chunk)),
+                Ok(None) => break,
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+    };
+    let mut relayed = Response::new(Either::Right(StreamBody::new(stream).boxed_unsync()));
+    *relayed.status_mut() = status;
+    *relayed.headers_mut() = headers;
+    relayed
+}
+
+/// A response answered by this node, as served
+pub fn buffered(response: Response<Full<Bytes>>) -> Response<ServedBody> {
+    response.map(Either::Left)
+}
+
+/// Wait before attempt `attempt` (1 for the first retry): 100ms doubling, capped
+pub fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(100u64 << attempt.saturating_sub(1).min(4)).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("api.example.com"));
+        headers.insert("authorization", HeaderValue::from_static("Bearer abc"));
+        headers.insert("connection", HeaderValue::from_static("keep-alive, x-trace"));
+        headers.insert("x-trace", HeaderValue::from_static("1"));
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("12"));
+        headers.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.9, 10.0.0.2"));
+
+        let forwarded = request_headers(&headers, Some("203.0.113.9"), true, 2);
+        assert_eq!(forwarded["authorization"], "Bearer abc");
+        assert_eq!(forwarded["x-forwarded-for"], "203.0.113.9");
+        assert_eq!(forwarded["x-forwarded-host"], "api.example.com");
+        assert_eq!(forwarded["x-forwarded-proto"], "https");
+        assert_eq!(forwarded[FORWARDED_BY], "2");
+        for removed in ["host", "connection", "x-trace", "content-length"] {
+            assert!(!forwarded.contains_key(removed), "{} was forwarded", removed);
+        }
+
+        let mut response = HeaderMap::new();
+        response.insert("etag", HeaderValue::from_static("\"3\""));
+        response.insert("transfer-encoding", HeaderValue::from_static("chunked"));
+        let relayed = response_headers(&response);
+        assert_eq!(relayed["etag"], "\"3\"");
+        assert!(!relayed.contains_key("transfer-encoding"));
+    }
+
+    #[tokio::test]
+    async fn test_relay_streams_leader_body() {
+        let leader = hyper::Response::builder()
+            .status(201)
+            .header("etag", "\"1\"")
+            .header("connection", "close")
+            .header(CONTENT_LENGTH, "13")
+            .body("{\"id\":\"a1\"}")
+            .unwrap();
+        let relayed = relay(reqwest::Response::from(leader));
+        assert_eq!(relayed.status(), 201);
+        assert_eq!(relayed.headers()["etag"], "\"1\"");
+        assert!(!relayed.headers().contains_key("connection"));
+        assert!(!relayed.headers().contains_key(CONTENT_LENGTH));
+        assert!(matches!(relayed.body(), Either::Right(_)));
+        let body = relayed.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "{\"id\":\"a1\"}");
+
+        let served = buffered(Response::new(Full::new(Bytes::from("ok"))));
+        assert!(matches!(served.body(), Either::Left(_)));
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), Duration::from_millis(100));
+        assert_eq!(backoff(3), Duration::from_millis(400));
+        assert_eq!(backoff(10), MAX_BACKOFF);
+    }
+}
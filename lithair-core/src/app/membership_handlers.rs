@@ -0,0 +1,490 @@
+//! Cluster membership handlers
+//!
+//! - `POST /_raft/join` - a node started with `with_cluster_join` asks to be added
+//! - `GET /_admin/cluster/nodes` - voters and learners, as this node knows them
+//! - `POST /_admin/cluster/nodes` - add a learner, catch it up, then promote it
+//! - `POST /_admin/cluster/nodes/{node}/promote` - promote a learner to voter
+//! - `DELETE /_admin/cluster/nodes/{node}` - remove a node
+//!
+//! `{node}` is a node id or a `host:port`. Changes are made by the leader (followers
+//! redirect or forward them like other writes), one at a time, as log entries (see
+//! `cluster::membership`).
+
+use super::LithairServer;
+use crate::cluster::membership::{same_address, LEARNER_CATCH_UP_TIMEOUT};
+use crate::cluster::{
+    CrudOperation, Member, MembershipConfig, PeerClients, PeerNode, RaftLeadershipState,
+};
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request, Response, StatusCode};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a joining node waits for the leader to answer: it catches the node up first
+const JOIN_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Wait between two join attempts
+const JOIN_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Body of `POST /_raft/join` and `POST /_admin/cluster/nodes`
+#[derive(Debug, Deserialize)]
+struct AddNode {
+    node_id: u64,
+    /// Address the other nodes reach the node at
+    address: String,
+    /// Promote the node to voter once it caught up
+    #[serde(default = "default_promote")]
+    promote: bool,
+    /// Voter the node replaces, removed in the change that promotes it
+    #[serde(default)]
+    replaces: Option<String>,
+}
+
+fn default_promote() -> bool {
+    true
+}
+
+/// Body of `POST /_admin/cluster/nodes/{node}/promote`
+#[derive(Debug, Default, Deserialize)]
+struct Promote {
+    #[serde(default)]
+    replaces: Option<String>,
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .expect("valid HTTP response")
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response<Full<Bytes>> {
+    json_response(status, serde_json::json!({ "error": message.into() }))
+}
+
+impl LithairServer {
+    /// POST /_raft/join - Add the calling node as a learner, then promote it
+    pub(crate) async fn handle_raft_join(
+        &self,
+        req: Request<hyper::body::Incoming>,
+        peer_node: Option<PeerNode>,
+    ) -> Result<Response<Full<Bytes>>> {
+        let Some(raft_state) = self.membership_state(&req) else {
+            return Ok(self.membership_unavailable());
+        };
+        // A node certificate identifies the caller under cluster TLS
+        if peer_node.is_none() {
+            if let Some(response) = self.membership_change_denied(&req) {
+                return Ok(response);
+            }
+        }
+        if !raft_state.is_leader() {
+            return Ok(Self::not_leader_response(raft_state, "/_raft/join"));
+        }
+
+        let body = req.into_body().collect().await?.to_bytes();
+        let request: AddNode = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                let message = format!("Invalid join request: {}", e);
+                return Ok(error_response(StatusCode::BAD_REQUEST, message));
+            }
+        };
+        if let Some(response) = Self::impersonation_response(peer_node, request.node_id) {
+            return Ok(response);
+        }
+        Ok(self.add_node(raft_state, request).await.unwrap_or_else(|e| e))
+    }
+
+    /// /_admin/cluster/nodes[/{node}[/promote]]
+    pub(crate) async fn handle_admin_cluster_nodes(
+        &self,
+        req: Request<hyper::body::Incoming>,
+        path: &str,
+    ) -> Result<Response<Full<Bytes>>> {
+        let Some(raft_state) = self.membership_state(&req) else {
+            return Ok(self.membership_unavailable());
+        };
+        let method = req.method().clone();
+        let node = path
+            .strip_prefix("/_admin/cluster/nodes")
+            .unwrap_or_default()
+            .trim_matches('/')
+            .to_string();
+
+        if node.is_empty() && method == Method::GET {
+            return Ok(json_response(StatusCode::OK, Self::membership_json(raft_state)));
+        }
+        if method != Method::GET {
+            if let Some(response) = self.membership_change_denied(&req) {
+                return Ok(response);
+            }
+            if !raft_state.is_leader() {
+                return Ok(Self::not_leader_response(raft_state, path));
+            }
+        }
+
+        let body = req.into_body().collect().await?.to_bytes();
+        let result = match (method, node.strip_suffix("/promote")) {
+            (Method::POST, _) if node.is_empty() => match serde_json::from_slice(&body) {
+                Ok(request) => self.add_node(raft_state, request).await,
+                Err(e) => Err(error_response(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid node: {} (expected node_id and address)", e),
+                )),
+            },
+            (Method::POST, Some(learner)) => {
+                let promote: Promote = if body.is_empty() {
+                    Promote::default()
+                } else {
+                    match serde_json::from_slice(&body) {
+                        Ok(promote) => promote,
+                        Err(e) => {
+                            let message = format!("Invalid promotion: {}", e);
+                            return Ok(error_response(StatusCode::BAD_REQUEST, message));
+                        }
+                    }
+                };
+                self.promote_node(raft_state, learner, promote.replaces.as_deref()).await
+            }
+            (Method::DELETE, None) if !node.is_empty() => self.remove_node(raft_state, &node).await,
+            _ => Err(error_response(StatusCode::NOT_FOUND, "Not found")),
+        };
+        Ok(result.unwrap_or_else(|e| e))
+    }
+
+    /// Raft state of a clustered node, for a request carrying a valid Raft token
+    fn membership_state<B>(&self, req: &Request<B>) -> Option<&Arc<RaftLeadershipState>> {
+        let token = req.headers().get("X-Raft-Token").and_then(|v| v.to_str().ok());
+        self.raft_state.as_ref().filter(|_| self.config.raft.validate_token(token))
+    }
+
+    /// 403 when no Raft token is configured, else 401 unless `req` carries it: a change
+    /// can hand the cluster's data to any address, so it is never open
+    fn membership_change_denied<B>(&self, req: &Request<B>) -> Option<Response<Full<Bytes>>> {
+        let raft = &self.config.raft;
+        if !raft.auth_required || raft.auth_token.is_none() {
+            let message =
+                "Membership changes need a Raft token (RaftConfig::with_auth, LITHAIR_RAFT_TOKEN)";
+            return Some(error_response(StatusCode::FORBIDDEN, message));
+        }
+        let token = req.headers().get("X-Raft-Token").and_then(|v| v.to_str().ok());
+        if raft.validate_token(token) {
+            return None;
+        }
+        Some(error_response(StatusCode::UNAUTHORIZED, "Invalid Raft token"))
+    }
+
+    /// 404 without cluster mode, else 401 for an invalid Raft token
+    fn membership_unavailable(&self) -> Response<Full<Bytes>> {
+        match self.raft_state {
+            None => error_response(StatusCode::NOT_FOUND, "Cluster mode is not enabled"),
+            Some(_) => error_response(StatusCode::UNAUTHORIZED, "Invalid Raft token"),
+        }
+    }
+
+    fn membership_json(raft_state: &RaftLeadershipState) -> serde_json::Value {
+        serde_json::json!({
+            "node_id": raft_state.node_id,
+            "is_leader": raft_state.is_leader(),
+            "leader_address": raft_state.known_leader_address(),
+            "membership": raft_state.membership(),
+        })
+    }
+
+    /// Add a learner, push it a snapshot, wait for it to catch up with the log, then
+    /// promote it (unless `promote` is false)
+    async fn add_node(
+        &self,
+        raft_state: &RaftLeadershipState,
+        request: AddNode,
+    ) -> std::result::Result<Response<Full<Bytes>>, Response<Full<Bytes>>> {
+        if let Err(e) = crate::cluster::NodeAddress::parse(&request.address) {
+            let message = format!("Invalid node address {}", e);
+            return Err(error_response(StatusCode::BAD_REQUEST, message));
+        }
+        let _change = raft_state.membership_change.lock().await;
+        self.finish_joint_change(raft_state).await?;
+
+        let current = raft_state.membership();
+        let node = request.node_id.to_string();
+        match current.find(&node) {
+            // Joining again after a restart, or added beforehand: nothing to change
+            Some(member) if same_address(&member.address, &request.address) => {
+                return Ok(json_response(StatusCode::OK, Self::membership_json(raft_state)));
+            }
+            Some(member) => {
+                let message = format!("Node {} is already a member at {}", node, member.address);
+                return Err(error_response(StatusCode::CONFLICT, message));
+            }
+            None => {}
+        }
+        let member = Member::new(Some(request.node_id), request.address.clone());
+        let config = current
+            .with_learner(member)
+            .map_err(|e| error_response(StatusCode::CONFLICT, e))?;
+        self.commit_membership(config).await?;
+        log::info!("Node {} ({}) added as learner", node, request.address);
+
+        if let Err(e) = self.catch_up_learner(&request.address).await {
+            log::warn!("Learner {} is not caught up: {}", node, e);
+            let mut body = Self::membership_json(raft_state);
+            body["warning"] = serde_json::json!(format!(
+                "Added as learner but not caught up ({}); promote it once it is",
+                e
+            ));
+            return Ok(json_response(StatusCode::ACCEPTED, body));
+        }
+        if request.promote {
+            self.change_voters(Some(&node), request.replaces.as_deref(), raft_state).await?;
+            log::info!("Node {} promoted to voter", node);
+        }
+        Ok(json_response(StatusCode::CREATED, Self::membership_json(raft_state)))
+    }
+
+    /// Promote the learner `node` once it caught up, replacing the voter `replaces`
+    async fn promote_node(
+        &self,
+        raft_state: &RaftLeadershipState,
+        node: &str,
+        replaces: Option<&str>,
+    ) -> std::result::Result<Response<Full<Bytes>>, Response<Full<Bytes>>> {
+        let _change = raft_state.membership_change.lock().await;
+        self.finish_joint_change(raft_state).await?;
+
+        let config = raft_state.membership();
+        let Some(learner) = config.learners.iter().find(|m| m.matches(node)).cloned() else {
+            let message = format!("{} is not a learner", node);
+            return Err(error_response(StatusCode::CONFLICT, message));
+        };
+        if let Err(e) = self.wait_for_learner(&learner.address).await {
+            let message = format!("{} is not caught up: {}", node, e);
+            return Err(error_response(StatusCode::CONFLICT, message));
+        }
+        self.change_voters(Some(node), replaces, raft_state).await?;
+        log::info!("Node {} promoted to voter", node);
+        Ok(json_response(StatusCode::OK, Self::membership_json(raft_state)))
+    }
+
+    /// Remove `node`: at once for a learner, through a joint change for a voter
+    async fn remove_node(
+        &self,
+        raft_state: &RaftLeadershipState,
+        node: &str,
+    ) -> std::result::Result<Response<Full<Bytes>>, Response<Full<Bytes>>> {
+        let _change = raft_state.membership_change.lock().await;
+        self.finish_joint_change(raft_state).await?;
+
+        let config = raft_state.membership();
+        let Some(member) = config.find(node).cloned() else {
+            return Err(error_response(StatusCode::NOT_FOUND, format!("{} is not a member", node)));
+        };
+        if same_address(&member.address, &raft_state.self_address.to_string()) {
+            return Err(error_response(
+                StatusCode::CONFLICT,
+                "The leader cannot remove itself: stop it, then remove it from the next leader",
+            ));
+        }
+        if config.is_learner(&member.address) {
+            let config = config
+                .without_learner(node)
+                .map_err(|e| error_response(StatusCode::CONFLICT, e))?;
+            self.commit_membership(config).await?;
+        } else {
+            self.change_voters(None, Some(node), raft_state).await?;
+        }
+        log::info!("Node {} removed from the cluster", member.address);
+        Ok(json_response(StatusCode::OK, Self::membership_json(raft_state)))
+    }
+
+    /// Commit the joint configuration that promotes `promote` and removes `remove`, then
+    /// the new voters alone
+    async fn change_voters(
+        &self,
+        promote: Option<&str>,
+        remove: Option<&str>,
+        raft_state: &RaftLeadershipState,
+    ) -> std::result::Result<(), Response<Full<Bytes>>> {
+        let joint = raft_state
+            .membership()
+            .begin_change(promote, remove)
+            .map_err(|e| error_response(StatusCode::CONFLICT, e))?;
+        self.commit_membership(joint.clone()).await?;
+        // From here on, entries need a majority of the old voters and of the new ones
+        self.commit_membership(joint.finish_change()).await
+    }
+
+    /// Finish a change that stopped between its joint and final entries
+    async fn finish_joint_change(
+        &self,
+        raft_state: &RaftLeadershipState,
+    ) -> std::result::Result<(), Response<Full<Bytes>>> {
+        let current = raft_state.membership();
+        if !current.is_joint() {
+            return Ok(());
+        }
+        log::warn!("Finishing an interrupted membership change");
+        self.commit_membership(current.finish_change()).await
+    }
+
+    /// Replicate `config` as a log entry and apply it once committed
+    async fn commit_membership(
+        &self,
+        config: MembershipConfig,
+    ) -> std::result::Result<(), Response<Full<Bytes>>> {
+        let Some(consensus_log) = self.consensus_log.as_ref() else {
+            return Err(error_response(StatusCode::SERVICE_UNAVAILABLE, "No consensus log"));
+        };
+        let operation = CrudOperation::MembershipChange { config };
+        self.commit_and_apply(consensus_log, &operation, |_| ()).await.map(|_| ())
+    }
+
+    /// Push a snapshot to the learner at `address`, then wait for the log entries after it
+    async fn catch_up_learner(&self, address: &str) -> std::result::Result<(), String> {
+        let (Some(consensus_log), Some(snapshots)) = (&self.consensus_log, &self.snapshot_manager)
+        else {
+            return Err("snapshots are not enabled".to_string());
+        };
+
+        // Under the apply lock, the snapshot holds exactly the entries up to its index
+        let index = {
+            let _apply = consensus_log.lock_apply().await;
+            let index = consensus_log.applied_index();
+            let term = consensus_log.current_term();
+            Self::create_snapshot_from_models(&self.models, snapshots, term, index).await?;
+            index
+        };
+        self.resync_stats.record_snapshot_created();
+        let timeout = self.config.replication.snapshot_send_timeout_secs;
+        Self::send_snapshot_to_follower_with_timeout(
+            address,
+            snapshots,
+            timeout,
+            &self.peer_clients,
+        )
+        .await?;
+        if let Some(ref batcher) = self.replication_batcher {
+            batcher.record_success(address, index, 0).await;
+        }
+        self.wait_for_learner(address).await
+    }
+
+    /// Wait until the learner at `address` replicated the log up to the commit index;
+    /// background replication sends it what it misses
+    async fn wait_for_learner(&self, address: &str) -> std::result::Result<(), String> {
+        let (Some(consensus_log), Some(batcher)) = (&self.consensus_log, &self.replication_batcher)
+        else {
+            return Ok(());
+        };
+        let deadline = Instant::now() + LEARNER_CATCH_UP_TIMEOUT;
+        loop {
+            let replicated = match batcher.get_follower(address).await {
+                Some(follower) => {
+                    follower.last_replicated_index.load(std::sync::atomic::Ordering::SeqCst)
+                }
+                None => 0,
+            };
+            let committed = consensus_log.commit_index();
+            if replicated >= committed {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("at index {} of {}", replicated, committed));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Ask the leader, through `peers`, to add this node; retries until it answers
+    pub(crate) async fn join_cluster(
+        raft_state: Arc<RaftLeadershipState>,
+        peers: Vec<String>,
+        token: Option<String>,
+        clients: Arc<PeerClients>,
+    ) {
+        let request = serde_json::json!({
+            "node_id": raft_state.node_id,
+            "address": raft_state.self_address,
+        });
+        let mut leader: Option<String> = None;
+        for attempt in 0usize.. {
+            let target = leader.take().unwrap_or_else(|| peers[attempt % peers.len()].clone());
+            let url = crate::cluster::peer_url(&target, "/_raft/join");
+            let mut req = clients.client(JOIN_TIMEOUT).post(&url).json(&request);
+            if let Some(ref token) = token {
+                req = req.header("X-Raft-Token", token);
+            }
+            match req.send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    let body = resp.json::<serde_json::Value>().await.unwrap_or_default();
+                    match serde_json::from_value::<MembershipConfig>(body["membership"].clone()) {
+                        Ok(config) => {
+                            raft_state.set_membership(config);
+                            log::info!("Joined the cluster as node {}", raft_state.node_id);
+                            return;
+                        }
+                        Err(e) => log::warn!("Invalid join response from {}: {}", target, e),
+                    }
+                }
+                Ok(resp) if resp.status() == StatusCode::TEMPORARY_REDIRECT => {
+                    leader = resp
+                        .headers()
+                        .get("X-Raft-Leader-Address")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    if leader.is_some() {
+                        continue;
+                    }
+                }
+                Ok(resp) if resp.status().is_client_error() => {
+                    let status = resp.status();
+                    let body = resp.text().await.unwrap_or_default();
+                    log::error!("Cluster refused node {}: {} {}", raft_state.node_id, status, body);
+                    return;
+                }
+                Ok(resp) => log::warn!("Join through {} failed: {}", target, resp.status()),
+                Err(e) => log::warn!("Join through {} failed: {}", target, e),
+            }
+            tokio::time::sleep(JOIN_RETRY_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, token: Option<&str>) -> Request<()> {
+        let mut request = Request::builder().method(method).uri("/_admin/cluster/nodes/2");
+        if let Some(token) = token {
+            request = request.header("X-Raft-Token", token);
+        }
+        request.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_membership_changes_need_raft_token() {
+        // Without a configured token, changes are refused whatever the request carries
+        let open = LithairServer::default();
+        for method in [Method::POST, Method::DELETE] {
+            for token in [None, Some("secret")] {
+                let denied = open.membership_change_denied(&request(method.clone(), token));
+                assert_eq!(denied.unwrap().status(), StatusCode::FORBIDDEN);
+            }
+        }
+
+        let mut secured = LithairServer::default();
+        secured.config.raft = crate::config::RaftConfig::new().with_auth("secret");
+        for method in [Method::POST, Method::DELETE] {
+            for token in [None, Some("guess")] {
+                let denied = secured.membership_change_denied(&request(method.clone(), token));
+                assert_eq!(denied.unwrap().status(), StatusCode::UNAUTHORIZED);
+            }
+            assert!(secured.membership_change_denied(&request(method, Some("secret"))).is_none());
+        }
+    }
+}
@@ -26,19 +26,24 @@
 
 use crate::cluster::RaftLeadershipState;
 use crate::config::LithairConfig;
+use crate::http::etag;
 use crate::proxy::tls::CertificateFingerprint;
 use anyhow::{Context, Result};
 use bytes::Bytes;
 use std::sync::Arc;
 
+pub mod batch;
 pub mod builder;
+mod consistency;
+mod forward;
+mod membership_handlers;
 pub mod model_handler;
 pub mod response;
 pub mod router;
 mod schema_handlers;
 
 pub use builder::LithairServerBuilder;
-pub use model_handler::{DeclarativeModelHandler, ModelHandler};
+pub use model_handler::{DeclarativeModelHandler, ModelBatch, ModelHandler};
 
 // ============================================================================
 // TLS support types
@@ -129,6 +134,46 @@ pub struct ModelRegistration {
     pub schema_extractor: Option<SchemaSpecExtractor>,
 }
 
+/// Resolves the foreign keys of single-node writes against the registered models
+///
+/// Every handler holds one, so it only holds the registrations weakly.
+struct ModelReferences {
+    models: std::sync::Weak<tokio::sync::RwLock<Vec<ModelRegistration>>>,
+}
+
+#[async_trait::async_trait]
+impl crate::http::ReferenceResolver for ModelReferences {
+    async fn missing(&self, model: &str, field: &str, values: &[String]) -> Option<Vec<String>> {
+        let models = self.models.upgrade()?;
+        let models = models.read().await;
+        LithairServer::missing_keys(&models, model, field, values).await
+    }
+}
+
+/// Collection routes of a model, each its own `route` label in request metrics
+const MODEL_OPERATIONS: &[&str] = &["count", "stream", "random-id", "_aggregate", "_bulk"];
+
+/// Item routes of a model (`/api/products/:id/_restore`), each its own metrics label
+const ITEM_OPERATIONS: &[&str] = &["_restore"];
+
+/// Built-in endpoints, labelled in request metrics by the prefix they start with
+const BUILT_IN_ROUTES: &[&str] = &[
+    "/_batch",
+    "/_admin/schema",
+    "/_admin/data",
+    "/_admin/cluster",
+    "/_raft/append",
+    "/_raft/snapshot",
+    "/_raft/join",
+    "/_raft/read-index",
+    "/_raft/health",
+    "/_raft/migrate",
+    "/_raft/sync-status",
+    "/_raft/force-resync",
+    "/_raft/resync_stats",
+    "/_raft/schema",
+];
+
 /// Lithair multi-model server
 pub struct LithairServer {
     config: LithairConfig,
@@ -155,12 +200,16 @@ pub struct LithairServer {
     route_policies: std::collections::HashMap<String, crate::http::declarative_server::RoutePolicy>,
     firewall_config: Option<crate::http::FirewallConfig>,
     anti_ddos_config: Option<crate::security::anti_ddos::AntiDDoSConfig>,
+    // Materialized in serve(); kept for /metrics
+    anti_ddos: Option<Arc<crate::security::anti_ddos::AntiDDoSProtection>>,
     access_log: bool,
     access_log_capacity: usize,
     legacy_endpoints: bool,
     deprecation_warnings: bool,
     openapi_enabled: bool,
     openapi_spec_cache: std::sync::OnceLock<serde_json::Value>,
+    // HTTP clients for calls to other nodes (mutual TLS under cluster TLS)
+    peer_clients: Arc<crate::cluster::PeerClients>,
 
     // Raft cluster (distributed consensus)
     cluster_peers: Vec<String>,
@@ -190,6 +239,11 @@ pub struct LithairServer {
     // Resync statistics for observability
     resync_stats: Arc<crate::cluster::ResyncStats>,
 
+    // Foreign keys are checked before the write takes any lock: writes checking them
+    // share this, deletes of items they could reference hold it alone (see
+    // `lock_references`), so no delete lands between a check and its write
+    references: tokio::sync::RwLock<()>,
+
     // Schema synchronization state for cluster-wide schema consensus
     schema_sync_state: Arc<tokio::sync::RwLock<crate::schema::SchemaSyncState>>,
 
@@ -197,6 +251,12 @@ pub struct LithairServer {
     sse_broadcaster: Option<Arc<crate::http::sse::SseEventBroadcaster>>,
 }
 
+/// Hold on `LithairServer::references`: shared, exclusive, or none
+type ReferencesGuard<'a> = (
+    Option<tokio::sync::RwLockReadGuard<'a, ()>>,
+    Option<tokio::sync::RwLockWriteGuard<'a, ()>>,
+);
+
 /// A CRUD operation to be submitted through Raft consensus
 #[derive(Debug)]
 pub struct RaftCrudOperation {
@@ -581,6 +641,10 @@ impl LithairServer {
             log::info!("Creating handler for model: {}", info.name);
             match (info.factory)(info.data_path.clone()).await {
                 Ok(mut handler) => {
+                    handler.set_snapshot_policy(
+                        self.config.storage.snapshot_interval,
+                        self.config.storage.compaction_enabled,
+                    );
                     // Wire SSE broadcaster into each model handler
                     if let Some(ref broadcaster) = self.sse_broadcaster {
                         if let Some(h) = Arc::get_mut(&mut handler) {
@@ -592,6 +656,9 @@ impl LithairServer {
                             );
                         }
                     }
+                    let models_ref = Arc::downgrade(&self.models);
+                    handler
+                        .set_reference_resolver(Arc::new(ModelReferences { models: models_ref }));
                     let mut models = self.models.write().await;
                     models.push(ModelRegistration {
                         name: info.name.clone(),
@@ -608,6 +675,43 @@ impl LithairServer {
                 }
             }
         }
+
+        // Retention worker: expires records and trims field history per `#[lifecycle]`.
+        // Not in cluster mode: a purge rewrites only this node's log and snapshot, while
+        // the consensus log and the other nodes keep the purged records
+        let retention_interval = self.config.storage.retention_interval_secs;
+        let is_cluster = self.config.raft.enabled && !self.cluster_peers.is_empty();
+        if retention_interval > 0 && is_cluster {
+            log::warn!(
+                "Retention purges are not supported in cluster mode: #[lifecycle] retention \
+                 and versioned limits are not enforced"
+            );
+        } else if retention_interval > 0 {
+            let models = Arc::clone(&self.models);
+            tokio::spawn(async move {
+                let mut ticker =
+                    tokio::time::interval(std::time::Duration::from_secs(retention_interval));
+                loop {
+                    ticker.tick().await;
+                    let handlers: Vec<_> = models
+                        .read()
+                        .await
+                        .iter()
+                        .map(|m| (m.name.clone(), Arc::clone(&m.handler)))
+                        .collect();
+                    for (name, handler) in handlers {
+                        match handler.enforce_retention().await {
+                            Ok(report) if !report.is_empty() => {
+                                log::info!("Retention purge on {}: {:?}", name, report)
+                            }
+                            Ok(_) => {}
+                            Err(e) => log::error!("Retention purge on {} failed: {}", name, e),
+                        }
+                    }
+                }
+            });
+        }
+
         self.model_infos.clear(); // Clear infos, we have the models now
                                   // Initialize default logger if not already initialized
         let _ = env_logger::Builder::from_default_env()
@@ -717,6 +821,10 @@ impl LithairServer {
             if let Some(node_id) = self.node_id {
                 let port = self.config.server.port;
 
+                if let Some(tls_config) = self.config.raft.tls.clone() {
+                    self.init_cluster_tls(node_id, tls_config)?;
+                }
+
                 log::info!("Initializing Raft cluster...");
                 log::info!("   Node ID: {}", node_id);
                 log::info!("   Peers: {:?}", self.cluster_peers);
@@ -725,28 +833,59 @@ impl LithairServer {
                     "   Raft auth: {}",
                     if self.config.raft.auth_required { "enabled" } else { "disabled" }
                 );
+                log::info!(
+                    "   Cluster TLS: {}",
+                    if self.peer_clients.tls().is_some() { "mutual" } else { "disabled" }
+                );
 
-                let raft_state =
-                    Arc::new(RaftLeadershipState::new(node_id, port, self.cluster_peers.clone()));
+                if self.config.raft.join && node_id == 0 {
+                    anyhow::bail!("Node 0 boots a cluster as its leader: joining needs another id");
+                }
+
+                let address = self.advertised_address()?;
+                log::info!("   Advertised address: {}", address);
+                let raft_state = Arc::new(
+                    RaftLeadershipState::new(node_id, port, self.cluster_peers.clone())
+                        .with_self_address(address),
+                );
+
+                // The WAL keeps every membership change: the last one is the cluster now
+                let restored = self.wal.as_ref().and_then(|wal| match wal.last_membership() {
+                    Ok(config) => config,
+                    Err(e) => {
+                        log::warn!("Failed to read membership from WAL: {}", e);
+                        None
+                    }
+                });
+                if let Some(config) = restored {
+                    log::info!(
+                        "   Membership restored from WAL: {} voters, {} learners",
+                        config.voters.len(),
+                        config.learners.len()
+                    );
+                    raft_state.set_membership(config);
+                } else if self.config.raft.join {
+                    // A joining node votes once the leader promotes it
+                    let mut config = raft_state.membership();
+                    let own = config.voters.remove(0);
+                    config.learners.push(own);
+                    raft_state.set_membership(config);
+                    log::info!("   Joining the cluster as a learner");
+                }
 
                 if raft_state.is_leader() {
                     log::info!("THIS NODE IS THE LEADER");
                 } else {
-                    log::info!(
-                        "This node is a FOLLOWER (leader port: {})",
-                        raft_state.get_leader_port()
-                    );
+                    log::info!("This node is a FOLLOWER (leader known from its first heartbeat)");
                 }
 
-                self.raft_state = Some(raft_state);
+                self.raft_state = Some(Arc::clone(&raft_state));
 
                 // Initialize replication batcher with peers
                 if let Some(ref batcher) = self.replication_batcher {
-                    batcher.initialize(&self.cluster_peers).await;
-                    log::info!(
-                        "Replication batcher initialized with {} peers",
-                        self.cluster_peers.len()
-                    );
+                    let peers = raft_state.peers();
+                    batcher.initialize(&peers).await;
+                    log::info!("Replication batcher initialized with {} peers", peers.len());
                 }
 
                 // Start WAL background flush task (group commit)
@@ -763,7 +902,6 @@ impl LithairServer {
                 // Start background replication task for lagging followers
                 if let Some(ref batcher) = self.replication_batcher {
                     let batcher_clone = Arc::clone(batcher);
-                    let peers = self.cluster_peers.clone();
                     let consensus_log = self.consensus_log.clone();
                     let node_id = self.node_id.unwrap_or(0);
                     let raft_state = self.raft_state.clone();
@@ -771,6 +909,7 @@ impl LithairServer {
                     let models = Arc::clone(&self.models);
                     let replication_config = self.config.replication.clone();
                     let resync_stats = Arc::clone(&self.resync_stats);
+                    let peer_clients = Arc::clone(&self.peer_clients);
 
                     tokio::spawn(async move {
                         use std::collections::HashMap;
@@ -790,12 +929,12 @@ impl LithairServer {
                         loop {
                             ticker.tick().await;
 
-                            // Only leader should do background replication
-                            if let Some(ref state) = raft_state {
-                                if !state.is_leader() {
-                                    continue;
-                                }
-                            }
+                            // Only leader should do background replication, to the
+                            // members of the moment
+                            let peers = match raft_state {
+                                Some(ref state) if state.is_leader() => state.peers(),
+                                _ => continue,
+                            };
 
                             let consensus_log_ref = match &consensus_log {
                                 Some(log) => log,
@@ -883,6 +1022,7 @@ impl LithairServer {
                                                     let batcher_resync =
                                                         Arc::clone(&batcher_for_resync);
                                                     let stats_clone = Arc::clone(&resync_stats);
+                                                    let clients = Arc::clone(&peer_clients);
 
                                                     // Track send attempt
                                                     resync_stats.record_send_attempt(commit_index);
@@ -897,6 +1037,7 @@ impl LithairServer {
                                                     &peer_clone,
                                                     &snapshot_mgr_clone,
                                                     snapshot_timeout_secs,
+                                                    &clients,
                                                 )
                                                 .await
                                                 {
@@ -977,13 +1118,9 @@ impl LithairServer {
                                     let entries = missing_entries;
                                     let batcher = Arc::clone(&batcher_clone);
                                     let commit = commit_index;
+                                    let client = peer_clients.client(Duration::from_secs(5));
 
                                     tokio::spawn(async move {
-                                        let client = reqwest::Client::builder()
-                                            .timeout(Duration::from_secs(5))
-                                            .build()
-                                            .unwrap_or_else(|_| reqwest::Client::new());
-
                                         let request =
                                             crate::cluster::consensus_log::AppendEntriesRequest {
                                                 term,
@@ -995,7 +1132,7 @@ impl LithairServer {
                                             };
 
                                         let start = std::time::Instant::now();
-                                        let url = format!("http://{}/_raft/append", peer);
+                                        let url = crate::cluster::peer_url(&peer, "/_raft/append");
 
                                         match client.post(&url).json(&request).send().await {
                                             Ok(resp) if resp.status().is_success() => {
@@ -1052,13 +1189,9 @@ impl LithairServer {
                                 let batcher = Arc::clone(&batcher_clone);
                                 let max_entry_index =
                                     entries.iter().map(|e| e.log_id.index).max().unwrap_or(0);
+                                let client = peer_clients.client(Duration::from_secs(5));
 
                                 tokio::spawn(async move {
-                                    let client = reqwest::Client::builder()
-                                        .timeout(Duration::from_secs(5))
-                                        .build()
-                                        .unwrap_or_else(|_| reqwest::Client::new());
-
                                     let request =
                                         crate::cluster::consensus_log::AppendEntriesRequest {
                                             term,
@@ -1070,7 +1203,7 @@ impl LithairServer {
                                         };
 
                                     let start = std::time::Instant::now();
-                                    let url = format!("http://{}/_raft/append", peer);
+                                    let url = crate::cluster::peer_url(&peer, "/_raft/append");
 
                                     match client.post(&url).json(&request).send().await {
                                         Ok(resp) if resp.status().is_success() => {
@@ -1137,7 +1270,8 @@ impl LithairServer {
                 }
                 _ => None,
             };
-        let tls_active = tls_acceptor.is_some();
+        let cluster_tls = self.peer_clients.tls().cloned();
+        let tls_active = tls_acceptor.is_some() || cluster_tls.is_some();
 
         // Start server
         let listener = tokio::net::TcpListener::bind(&addr)
@@ -1150,40 +1284,46 @@ impl LithairServer {
         // Start Raft background tasks if cluster mode enabled
         if let Some(ref raft_state) = self.raft_state {
             let state_clone = Arc::clone(raft_state);
-            let peers = self.cluster_peers.clone();
             let raft_config = self.config.raft.clone();
+            let peer_clients = Arc::clone(&self.peer_clients);
+
+            if raft_config.join {
+                tokio::spawn(Self::join_cluster(
+                    Arc::clone(raft_state),
+                    self.cluster_peers.clone(),
+                    raft_config.auth_token.clone(),
+                    Arc::clone(&peer_clients),
+                ));
+            }
 
+            let consensus_log = self.consensus_log.clone();
             if raft_state.is_leader() {
                 // Leader: send heartbeats to followers
                 tokio::spawn(async move {
-                    use reqwest::Client as HttpClient;
                     use std::time::Duration;
                     use tokio::time::sleep;
 
-                    let client = HttpClient::builder()
-                        .timeout(Duration::from_secs(2))
-                        .build()
-                        .unwrap_or_else(|_| HttpClient::new());
-
                     let heartbeat_interval =
                         Duration::from_secs(raft_config.heartbeat_interval_secs);
 
                     loop {
                         sleep(heartbeat_interval).await;
+                        let client = peer_clients.client(Duration::from_secs(2));
 
                         if !state_clone.is_leader() {
                             log::info!("No longer leader, stopping heartbeat sender");
                             break;
                         }
 
-                        let heartbeat_msg = serde_json::json!({
-                            "leader_id": state_clone.node_id,
-                            "leader_port": state_clone.self_port,
-                            "term": 1
-                        });
+                        let commit_index = consensus_log.as_ref().map_or(0, |l| l.commit_index());
+                        let term = consensus_log.as_ref().map_or(0, |l| l.current_term());
+                        let heartbeat_msg = state_clone.heartbeat_message(commit_index, term);
 
-                        for peer in &peers {
-                            let url = format!("http://{}{}/heartbeat", peer, raft_config.path);
+                        for peer in &state_clone.peers() {
+                            let url = crate::cluster::peer_url(
+                                peer,
+                                &format!("{}/heartbeat", raft_config.path),
+                            );
                             let mut req = client.post(&url).json(&heartbeat_msg);
 
                             if let Some(ref token) = raft_config.auth_token {
@@ -1206,13 +1346,17 @@ impl LithairServer {
                         if state_clone.should_start_election() {
                             log::info!("⏰ Heartbeat timeout detected! Starting election...");
 
-                            let (should_become_leader, new_leader_id, new_leader_port) =
-                                state_clone.start_election().await;
+                            let (should_become_leader, new_leader_id, new_leader_address) =
+                                state_clone.start_election(&peer_clients).await;
 
                             if should_become_leader {
+                                // A new leadership starts a new term
+                                if let Some(ref consensus_log) = consensus_log {
+                                    consensus_log.set_term(consensus_log.current_term() + 1);
+                                }
                                 state_clone.become_leader();
                             } else {
-                                state_clone.become_follower(new_leader_id, new_leader_port);
+                                state_clone.become_follower(new_leader_id, new_leader_address);
                             }
                         }
                     }
@@ -1246,6 +1390,9 @@ impl LithairServer {
             crate::http::init_access_log_buffer(self.access_log_capacity);
         }
 
+        let metrics_enabled = self.config.admin.metrics_enabled;
+        self.anti_ddos = anti_ddos.clone();
+
         // Share server state
         let server = Arc::new(self);
 
@@ -1257,6 +1404,8 @@ impl LithairServer {
             if let Some(ref protection) = anti_ddos {
                 if !protection.is_connection_allowed(remote_addr.ip()).await {
                     log::warn!("Anti-DDoS: rejected connection from {}", remote_addr.ip());
+                    crate::metrics::global()
+                        .record_rejection(crate::metrics::Rejection::DdosConnection);
                     drop(stream);
                     continue;
                 }
@@ -1265,7 +1414,12 @@ impl LithairServer {
             let server = server.clone();
             let firewall = firewall.clone();
             let anti_ddos = anti_ddos.clone();
-            let tls_acceptor = tls_acceptor.clone();
+            // Under cluster TLS, each connection takes the current node certificate
+            let tls_acceptor = match cluster_tls {
+                Some(ref tls) => Some(tls.acceptor()),
+                None => tls_acceptor.clone(),
+            };
+            let cluster_tls = cluster_tls.clone();
 
             tokio::spawn(async move {
                 // TLS handshake (if configured) or plain TCP
@@ -1290,10 +1444,26 @@ impl LithairServer {
                     MaybeTlsStream::Plain(stream)
                 };
 
+                // Node that authenticated with a cluster certificate, if any
+                let peer_node = match (&maybe_tls, &cluster_tls) {
+                    (MaybeTlsStream::Tls(stream), Some(tls)) => {
+                        tls.peer_node(stream.get_ref().1.peer_certificates())
+                    }
+                    _ => None,
+                };
+
                 let io = hyper_util::rt::TokioIo::new(maybe_tls);
 
                 let service = hyper::service::service_fn(
-                    move |req: hyper::Request<hyper::body::Incoming>| {
+                    move |mut req: hyper::Request<hyper::body::Incoming>| {
+                        // Connection details, for writes forwarded to the leader
+                        req.extensions_mut().insert(remote_addr);
+                        if tls_active {
+                            req.extensions_mut().insert(crate::proxy::reverse::TlsConnection);
+                        }
+                        if let Some(peer_node) = peer_node {
+                            req.extensions_mut().insert(peer_node);
+                        }
                         let server = server.clone();
                         let firewall = firewall.clone();
                         let anti_ddos = anti_ddos.clone();
@@ -1304,6 +1474,7 @@ impl LithairServer {
                             // Resolve real client IP (trusts proxy headers only from loopback/private)
                             let client_ip = crate::http::resolve_client_ip(&req, remote_addr);
 
+                            let route_server = server.clone();
                             let result = (async move {
                                 // Firewall check
                                 if let Err(_denied) = firewall.check(
@@ -1311,6 +1482,8 @@ impl LithairServer {
                                     req.method(),
                                     req.uri().path(),
                                 ) {
+                                    crate::metrics::global()
+                                        .record_rejection(crate::metrics::Rejection::Firewall);
                                     return Ok::<_, std::convert::Infallible>(
                                         Self::add_security_headers(
                                             hyper::Response::builder()
@@ -1319,7 +1492,8 @@ impl LithairServer {
                                                 .body(http_body_util::Full::new(
                                                     bytes::Bytes::from(r#"{"error":"Forbidden"}"#),
                                                 ))
-                                                .expect("valid HTTP response"),
+                                                .expect("valid HTTP response")
+                                                .map(http_body_util::Either::Left),
                                             tls_active,
                                         ),
                                     );
@@ -1328,6 +1502,8 @@ impl LithairServer {
                                 // Anti-DDoS request rate check
                                 if let Some(ref protection) = anti_ddos {
                                     if !protection.is_request_allowed(remote_addr.ip()).await {
+                                        crate::metrics::global()
+                                            .record_rejection(crate::metrics::Rejection::DdosRate);
                                         return Ok(Self::add_security_headers(
                                             hyper::Response::builder()
                                                 .status(429)
@@ -1338,7 +1514,8 @@ impl LithairServer {
                                                         r#"{"error":"Rate limit exceeded"}"#,
                                                     ),
                                                 ))
-                                                .expect("valid HTTP response"),
+                                                .expect("valid HTTP response")
+                                                .map(http_body_util::Either::Left),
                                             tls_active,
                                         ));
                                     }
@@ -1348,6 +1525,9 @@ impl LithairServer {
                                 if let Some(cl) = req.headers().get(hyper::header::CONTENT_LENGTH) {
                                     if let Ok(len) = cl.to_str().unwrap_or("0").parse::<usize>() {
                                         if len > max_body_size {
+                                            crate::metrics::global().record_rejection(
+                                                crate::metrics::Rejection::BodyTooLarge,
+                                            );
                                             return Ok(Self::add_security_headers(
                                                 hyper::Response::builder()
                                                     .status(413)
@@ -1357,7 +1537,8 @@ impl LithairServer {
                                                             r#"{"error":"Request body too large"}"#,
                                                         ),
                                                     ))
-                                                    .expect("valid HTTP response"),
+                                                    .expect("valid HTTP response")
+                                                    .map(http_body_util::Either::Left),
                                                 tls_active,
                                             ));
                                         }
@@ -1379,7 +1560,8 @@ impl LithairServer {
                                                         r#"{"error":"Internal server error"}"#,
                                                     ),
                                                 ))
-                                                .expect("valid HTTP response"),
+                                                .expect("valid HTTP response")
+                                                .map(http_body_util::Either::Left),
                                             tls_active,
                                         ))
                                     }
@@ -1387,6 +1569,16 @@ impl LithairServer {
                             })
                             .await;
 
+                            if metrics_enabled {
+                                let Ok(ref resp) = result;
+                                crate::metrics::global().record_request(
+                                    &req_method,
+                                    &route_server.route_label(&req_path),
+                                    resp.status().as_u16(),
+                                    start.elapsed(),
+                                );
+                            }
+
                             if access_log {
                                 let Ok(ref resp) = result;
                                 crate::http::log_access_ip(
@@ -1419,10 +1611,7 @@ impl LithairServer {
     /// Add security headers to a response.
     /// Uses `entry().or_insert()` so handlers that explicitly set a header are not overridden.
     /// When `tls_active` is true, adds HSTS header.
-    fn add_security_headers(
-        resp: hyper::Response<http_body_util::Full<bytes::Bytes>>,
-        tls_active: bool,
-    ) -> hyper::Response<http_body_util::Full<bytes::Bytes>> {
+    fn add_security_headers<B>(resp: hyper::Response<B>, tls_active: bool) -> hyper::Response<B> {
         let (mut parts, body) = resp.into_parts();
         let h = &mut parts.headers;
         h.entry("x-content-type-options")
@@ -1499,10 +1688,22 @@ impl LithairServer {
         false
     }
 
-    /// Handle incoming HTTP request
+    /// Handle incoming HTTP request: sent on to the leader when this follower forwards
+    /// it, answered here otherwise
     async fn handle_request(
         &self,
         req: hyper::Request<hyper::body::Incoming>,
+    ) -> Result<hyper::Response<forward::ServedBody>> {
+        if let Some(raft_state) = self.forwarding_to_leader(&req).await {
+            return self.forward_to_leader(req, raft_state).await;
+        }
+        Ok(forward::buffered(self.route_request(req).await?))
+    }
+
+    /// Answer a request on this node
+    async fn route_request(
+        &self,
+        req: hyper::Request<hyper::body::Incoming>,
     ) -> Result<hyper::Response<http_body_util::Full<bytes::Bytes>>> {
         use bytes::Bytes;
         use http_body_util::Full;
@@ -1512,6 +1713,21 @@ impl LithairServer {
 
         log::debug!("{} {}", method, path);
 
+        // Under cluster TLS, only nodes reach node-to-node routes
+        if self.peer_clients.tls().is_some()
+            && self.is_peer_route(&path)
+            && req.extensions().get::<crate::cluster::PeerNode>().is_none()
+        {
+            return Ok(hyper::Response::builder()
+                .status(hyper::StatusCode::UNAUTHORIZED)
+                .header("Content-Type", "application/json")
+                .body(Full::new(Bytes::from(
+                    r#"{"error":"A cluster node certificate is required"}"#,
+                )))
+                .expect("valid HTTP response"));
+        }
+        let peer_node = req.extensions().get::<crate::cluster::PeerNode>().copied();
+
         // Raft Cluster: Check for write redirection and Raft endpoints
         if let Some(ref raft_state) = self.raft_state {
             let heartbeat_path = self.config.raft.heartbeat_path();
@@ -1530,38 +1746,53 @@ impl LithairServer {
                         .expect("valid HTTP response"));
                 }
 
-                // Update heartbeat timestamp
-                raft_state.update_heartbeat();
-
-                // Parse heartbeat to update leader info if needed
                 use http_body_util::BodyExt;
                 let body_bytes =
                     req.into_body().collect().await.map(|c| c.to_bytes()).unwrap_or_default();
-                if let Ok(heartbeat) = serde_json::from_slice::<serde_json::Value>(&body_bytes) {
-                    let leader_id =
-                        heartbeat.get("leader_id").and_then(|v| v.as_u64()).unwrap_or(0);
-                    let leader_port =
-                        heartbeat.get("leader_port").and_then(|v| v.as_u64()).unwrap_or(0) as u16;
-
-                    if !raft_state.is_leader()
-                        && leader_id
-                            != raft_state
-                                .current_leader_id
-                                .load(std::sync::atomic::Ordering::Relaxed)
+                let heartbeat =
+                    serde_json::from_slice::<serde_json::Value>(&body_bytes).unwrap_or_default();
+                let leader_id = heartbeat.get("leader_id").and_then(|v| v.as_u64()).unwrap_or(0);
+                if let Some(response) = Self::impersonation_response(peer_node, leader_id) {
+                    return Ok(response);
+                }
+                let leader_port =
+                    heartbeat.get("leader_port").and_then(|v| v.as_u64()).unwrap_or(0) as u16;
+                // Nodes that advertise no address are on this host
+                let leader_address = heartbeat
+                    .get("leader_address")
+                    .and_then(|v| serde_json::from_value(v.clone()).ok())
+                    .unwrap_or_else(|| crate::cluster::NodeAddress::local(leader_port));
+                let term = heartbeat.get("term").and_then(|v| v.as_u64()).unwrap_or(0);
+                // A leadership check of a linearizable read, answered without side effects
+                let check = heartbeat.get("check").and_then(|v| v.as_bool()).unwrap_or(false);
+
+                let our_term = self.consensus_log.as_ref().map_or(term, |l| l.current_term());
+                let acked =
+                    raft_state.receive_heartbeat(leader_id, leader_address, term, our_term, check);
+                if acked && !check {
+                    if let Some(consensus_log) = self.consensus_log.as_ref() {
+                        if term > our_term {
+                            consensus_log.set_term(term);
+                        }
+                    }
+                    if let Some(commit_index) =
+                        heartbeat.get("commit_index").and_then(|v| v.as_u64())
                     {
-                        log::info!(
-                            "Heartbeat: updating leader to node {} (port {})",
-                            leader_id,
-                            leader_port
-                        );
-                        raft_state.become_follower(leader_id, leader_port);
+                        raft_state.record_leader_commit(commit_index);
                     }
                 }
 
+                let body = serde_json::json!({
+                    "status": if acked { "ok" } else { "rejected" },
+                    "term": self.consensus_log.as_ref().map_or(term, |l| l.current_term()),
+                    "leader_id": raft_state.current_leader_id.load(std::sync::atomic::Ordering::SeqCst),
+                });
+                let status =
+                    if acked { hyper::StatusCode::OK } else { hyper::StatusCode::CONFLICT };
                 return Ok(hyper::Response::builder()
-                    .status(hyper::StatusCode::OK)
+                    .status(status)
                     .header("Content-Type", "application/json")
-                    .body(Full::new(Bytes::from(r#"{"status":"ok"}"#)))
+                    .body(Full::new(Bytes::from(body.to_string())))
                     .expect("valid HTTP response"));
             }
 
@@ -1581,6 +1812,7 @@ impl LithairServer {
                 let response = serde_json::json!({
                     "leader_id": raft_state.current_leader_id.load(std::sync::atomic::Ordering::Relaxed),
                     "leader_port": raft_state.get_leader_port(),
+                    "leader_address": raft_state.known_leader_address(),
                     "is_current_node_leader": raft_state.is_leader(),
                     "node_id": raft_state.node_id
                 });
@@ -1599,18 +1831,20 @@ impl LithairServer {
             let is_internal = path.starts_with("/internal/") || path.starts_with("/_raft/");
 
             if is_write && !raft_state.is_leader() && !is_internal {
-                let leader_port = raft_state.get_leader_port();
-                let redirect_url = format!(
-                    "http://127.0.0.1:{}{}",
-                    leader_port,
-                    req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or(&path)
-                );
+                let Some(leader_address) = raft_state.known_leader_address() else {
+                    return Ok(Self::no_leader_response(
+                        "No leader is known yet, nothing was written",
+                    ));
+                };
+                let redirect_url = leader_address
+                    .url(req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or(&path));
 
-                log::debug!("Redirecting write to leader on port {}", leader_port);
+                log::debug!("Redirecting write to leader at {}", leader_address);
 
                 return Ok(hyper::Response::builder()
                     .status(hyper::StatusCode::TEMPORARY_REDIRECT)
                     .header(hyper::header::LOCATION, redirect_url.clone())
+                    .header("X-Raft-Leader-Address", leader_address.to_string())
                     .header("Content-Type", "application/json")
                     .body(Full::new(Bytes::from(format!(
                         r#"{{"message":"Redirected to leader","leader_url":"{}"}}"#,
@@ -1660,11 +1894,21 @@ impl LithairServer {
             return self.handle_resync_stats().await;
         }
 
+        // Nodes started with `with_cluster_join` ask the leader to add them
+        if path == "/_raft/join" && method == hyper::Method::POST {
+            return self.handle_raft_join(req, peer_node).await;
+        }
+
         // Migration operation endpoint (for rolling upgrades)
         if path == "/_raft/migrate" && method == hyper::Method::POST {
             return self.handle_migrate_operation(req).await;
         }
 
+        // Read index for followers serving linearizable reads (see `consistency`)
+        if path == "/_raft/read-index" && method == hyper::Method::GET {
+            return self.handle_raft_read_index(req).await;
+        }
+
         // Sync status endpoint (detailed follower sync state for ops)
         if path == "/_raft/sync-status" && method == hyper::Method::GET {
             return self.handle_sync_status().await;
@@ -1751,6 +1995,11 @@ impl LithairServer {
             }
         }
 
+        // Cluster membership admin endpoints (add, promote, remove nodes)
+        if path == "/_admin/cluster/nodes" || path.starts_with("/_admin/cluster/nodes/") {
+            return self.handle_admin_cluster_nodes(req, &path).await;
+        }
+
         // Status endpoint (for health checks and cluster discovery)
         if path == "/status" && method == hyper::Method::GET {
             let mut status = serde_json::json!({
@@ -1766,7 +2015,10 @@ impl LithairServer {
                     "node_id": raft_state.node_id,
                     "is_leader": raft_state.is_leader(),
                     "leader_port": raft_state.get_leader_port(),
-                    "peers": self.cluster_peers.len()
+                    "leader_address": raft_state.known_leader_address(),
+                    "address": raft_state.self_address,
+                    "peers": raft_state.peers().len(),
+                    "voter": raft_state.is_voter()
                 });
             }
 
@@ -1852,6 +2104,11 @@ impl LithairServer {
             }
         }
 
+        // Transactional writes across models
+        if path == "/_batch" && method == hyper::Method::POST {
+            return self.handle_batch_request(req).await;
+        }
+
         // Model routes (DeclarativeModel CRUD endpoints)
         let models = self.models.read().await;
         for model in models.iter() {
@@ -2044,6 +2301,13 @@ impl LithairServer {
                         update_data.get("item").cloned().unwrap_or(serde_json::Value::Null);
                     let primary_key =
                         update_data.get("primary_key").and_then(|v| v.as_str()).unwrap_or("");
+                    let expected_version =
+                        update_data.get("expected_version").and_then(|v| v.as_u64());
+                    if let Some(skipped) =
+                        Self::stale_write(model.handler.as_ref(), primary_key, expected_version)
+                    {
+                        return Ok(Self::replicate_skipped_response(&skipped));
+                    }
                     match model.handler.apply_replicated_update_json(primary_key, item_data).await {
                         Ok(()) => {
                             log::debug!("UPDATE replication applied for model {}", model.name);
@@ -2065,6 +2329,13 @@ impl LithairServer {
                 } else if let Some(delete_data) = op.get("Delete") {
                     let primary_key =
                         delete_data.get("primary_key").and_then(|v| v.as_str()).unwrap_or("");
+                    let expected_version =
+                        delete_data.get("expected_version").and_then(|v| v.as_u64());
+                    if let Some(skipped) =
+                        Self::stale_write(model.handler.as_ref(), primary_key, expected_version)
+                    {
+                        return Ok(Self::replicate_skipped_response(&skipped));
+                    }
                     match model.handler.apply_replicated_delete_json(primary_key).await {
                         Ok(_) => {
                             log::debug!("DELETE replication applied for model {}", model.name);
@@ -2127,6 +2398,20 @@ impl LithairServer {
         }
     }
 
+    /// Answer to a replicated write skipped by `stale_write`: a success, so the sender
+    /// does not retry it
+    fn replicate_skipped_response(
+        skipped: &serde_json::Value,
+    ) -> hyper::Response<http_body_util::Full<Bytes>> {
+        let mut body = skipped.clone();
+        body["status"] = serde_json::json!("skipped");
+        hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(http_body_util::Full::new(Bytes::from(body.to_string())))
+            .expect("valid HTTP response")
+    }
+
     /// Handle bulk internal replication request from leader
     /// POST /internal/replicate_bulk
     /// Body: { "model": "products", "items": [...], "batch_id": "..." }
@@ -2429,8 +2714,9 @@ impl LithairServer {
         use http_body_util::Full;
 
         // Parse request body
-        let (_parts, body) = req.into_parts();
+        let (parts, body) = req.into_parts();
         let body_bytes = body.collect().await?.to_bytes();
+        let peer_node = parts.extensions.get::<crate::cluster::PeerNode>().copied();
 
         let request: crate::cluster::consensus_log::AppendEntriesRequest =
             match serde_json::from_slice(&body_bytes) {
@@ -2446,6 +2732,9 @@ impl LithairServer {
                         .expect("valid HTTP response"));
                 }
             };
+        if let Some(response) = Self::impersonation_response(peer_node, request.leader_id) {
+            return Ok(response);
+        }
 
         // Check if we have a consensus log
         let consensus_log = match &self.consensus_log {
@@ -2481,6 +2770,7 @@ impl LithairServer {
         // Update heartbeat (if we have raft_state)
         if let Some(ref raft_state) = self.raft_state {
             raft_state.update_heartbeat();
+            raft_state.record_leader_commit(request.leader_commit);
         }
 
         // Append entries to local log (can happen concurrently)
@@ -2513,16 +2803,28 @@ impl LithairServer {
                 crate::cluster::CrudOperation::Create { .. } => "CREATE",
                 crate::cluster::CrudOperation::Update { .. } => "UPDATE",
                 crate::cluster::CrudOperation::Delete { .. } => "DELETE",
+                crate::cluster::CrudOperation::Patch { .. } => "PATCH",
+                crate::cluster::CrudOperation::Restore { .. } => "RESTORE",
+                crate::cluster::CrudOperation::Batch { .. } => "BATCH",
                 crate::cluster::CrudOperation::MigrationBegin { .. } => "MIGRATION_BEGIN",
                 crate::cluster::CrudOperation::MigrationStep { .. } => "MIGRATION_STEP",
                 crate::cluster::CrudOperation::MigrationCommit { .. } => "MIGRATION_COMMIT",
                 crate::cluster::CrudOperation::MigrationRollback { .. } => "MIGRATION_ROLLBACK",
+                crate::cluster::CrudOperation::MembershipChange { .. } => "MEMBERSHIP_CHANGE",
             };
             log::debug!("FOLLOWER: Applying {} entry index={}", op_type, entry.log_id.index);
             match self.apply_crud_operation(&entry.operation).await {
                 Ok(_) => {
                     consensus_log.mark_applied(entry.log_id.index);
                     log::debug!("Applied entry index={}", entry.log_id.index);
+                    // Followers keep membership changes too, to know the cluster on restart
+                    if let (crate::cluster::CrudOperation::MembershipChange { .. }, Some(wal)) =
+                        (&entry.operation, &self.wal)
+                    {
+                        if let Err(e) = wal.append(&entry).await {
+                            log::error!("Failed to write membership change to WAL: {}", e);
+                        }
+                    }
                 }
                 Err(e) => {
                     // CRITICAL: Stop processing here! If we continue, we'd skip this entry
@@ -2711,7 +3013,8 @@ impl LithairServer {
                     if let Some(model) = models.iter().find(|m| m.base_path == *model_path) {
                         let items: Vec<serde_json::Value> =
                             serde_json::from_str(json_data).unwrap_or_default();
-                        if let Err(e) = model.handler.apply_replicated_items_json(items).await {
+                        let versions = snapshot_data.get_model_versions(model_path);
+                        if let Err(e) = model.handler.install_snapshot_json(items, versions).await {
                             log::error!("Failed to apply snapshot data for {}: {}", model_path, e);
                         }
                     }
@@ -2765,11 +3068,12 @@ impl LithairServer {
     ) -> Result<hyper::Response<http_body_util::Full<bytes::Bytes>>> {
         use http_body_util::Full;
 
+        let peers = self.raft_state.as_ref().map_or(self.cluster_peers.len(), |s| s.peers().len());
         let mut health_data = serde_json::json!({
             "status": "ok",
             "node_id": self.node_id,
             "is_leader": self.raft_state.as_ref().map(|s| s.is_leader()).unwrap_or(false),
-            "cluster_peers": self.cluster_peers.len(),
+            "cluster_peers": peers,
         });
 
         // Add consensus log info if present
@@ -3017,7 +3321,13 @@ impl LithairServer {
 
         // Trigger immediate snapshot send
         let snapshot_result = if let Some(snapshot_manager) = &self.snapshot_manager {
-            Self::send_snapshot_to_follower_with_timeout(&target, snapshot_manager, 60).await
+            Self::send_snapshot_to_follower_with_timeout(
+                &target,
+                snapshot_manager,
+                60,
+                &self.peer_clients,
+            )
+            .await
         } else {
             Err("Snapshot manager not available".to_string())
         };
@@ -3068,20 +3378,8 @@ impl LithairServer {
 
         // Only leader can accept migration operations
         let is_leader = self.raft_state.as_ref().map(|s| s.is_leader()).unwrap_or(true);
-        if !is_leader {
-            let leader_port = self.raft_state.as_ref().map(|s| s.get_leader_port()).unwrap_or(0);
-            return Ok(hyper::Response::builder()
-                .status(hyper::StatusCode::TEMPORARY_REDIRECT)
-                .header("Content-Type", "application/json")
-                .header("Location", format!("http://127.0.0.1:{}/_raft/migrate", leader_port))
-                .body(Full::new(Bytes::from(
-                    serde_json::json!({
-                        "error": "Not leader",
-                        "leader_port": leader_port
-                    })
-                    .to_string(),
-                )))
-                .expect("valid HTTP response"));
+        if let Some(raft_state) = self.raft_state.as_ref().filter(|_| !is_leader) {
+            return Ok(Self::not_leader_response(raft_state, "/_raft/migrate"));
         }
 
         // Parse the operation from request body
@@ -3147,13 +3445,16 @@ impl LithairServer {
             let term = consensus_log.current_term();
             let leader_id = self.node_id.unwrap_or(0);
 
+            let (membership, own_address) = self.membership();
             let replication_result = Self::replicate_log_entries_to_followers(
-                &self.cluster_peers,
+                &membership,
+                &own_address,
                 vec![entry],
                 commit_index,
                 term,
                 leader_id,
                 self.replication_batcher.clone(),
+                &self.peer_clients,
             )
             .await;
 
@@ -3166,18 +3467,22 @@ impl LithairServer {
                     let apply_result = self.apply_crud_operation(&operation).await;
 
                     match apply_result {
-                        Ok(result) => Ok(hyper::Response::builder()
-                            .status(hyper::StatusCode::OK)
-                            .header("Content-Type", "application/json")
-                            .body(Full::new(Bytes::from(
-                                serde_json::json!({
-                                    "success": true,
-                                    "commit_index": new_commit,
-                                    "result": result
-                                })
-                                .to_string(),
-                            )))
-                            .expect("valid HTTP response")),
+                        Ok(result) => {
+                            // Reads waiting on the applied index see the migration
+                            consensus_log.mark_applied(new_commit);
+                            Ok(hyper::Response::builder()
+                                .status(hyper::StatusCode::OK)
+                                .header("Content-Type", "application/json")
+                                .body(Full::new(Bytes::from(
+                                    serde_json::json!({
+                                        "success": true,
+                                        "commit_index": new_commit,
+                                        "result": result
+                                    })
+                                    .to_string(),
+                                )))
+                                .expect("valid HTTP response"))
+                        }
                         Err(e) => Ok(hyper::Response::builder()
                             .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
                             .header("Content-Type", "application/json")
@@ -3231,21 +3536,173 @@ impl LithairServer {
         }
     }
 
-    /// Handle metrics request
+    /// Handle metrics request (Prometheus text exposition format)
     async fn handle_metrics_request(
         &self,
         _req: hyper::Request<hyper::body::Incoming>,
     ) -> Result<hyper::Response<http_body_util::Full<bytes::Bytes>>> {
+        use crate::metrics::PrometheusText;
         use http_body_util::Full;
 
-        // Note: Prometheus metrics are not yet implemented; returns a stub response
+        let mut out = PrometheusText::new();
+        crate::metrics::global().render(&mut out);
+
+        // Models
+        {
+            let models = self.models.read().await;
+            let mut counts = Vec::with_capacity(models.len());
+            for model in models.iter() {
+                counts.push((
+                    model.name.as_str(),
+                    model.handler.get_count().await,
+                    model.handler.get_event_count().await,
+                ));
+            }
+            out.header("lithair_model_items", "Items held in memory per model", "gauge");
+            for (name, items, _) in &counts {
+                out.sample("lithair_model_items", &[("model", *name)], items);
+            }
+            out.header("lithair_model_events", "Events in each model's event store", "gauge");
+            for (name, _, events) in &counts {
+                out.sample("lithair_model_events", &[("model", *name)], events);
+            }
+        }
+
+        // Sessions
+        if let Some(store) = self
+            .session_manager
+            .clone()
+            .and_then(|manager| manager.downcast::<crate::session::PersistentSessionStore>().ok())
+        {
+            let sessions = store.metrics();
+            out.header("lithair_sessions_active", "Stored sessions", "gauge");
+            out.sample("lithair_sessions_active", &[], sessions.active_sessions);
+            out.header("lithair_sessions_created_total", "Sessions created", "counter");
+            out.sample("lithair_sessions_created_total", &[], sessions.total_created);
+            out.header("lithair_sessions_deleted_total", "Sessions deleted or revoked", "counter");
+            out.sample("lithair_sessions_deleted_total", &[], sessions.total_deleted);
+        }
+
+        // Anti-DDoS
+        if let Some(protection) = &self.anti_ddos {
+            let stats = protection.get_stats().await;
+            out.header("lithair_ddos_tracked_ips", "Client IPs with a rate-limit window", "gauge");
+            out.sample("lithair_ddos_tracked_ips", &[], stats.tracked_ips);
+            out.header("lithair_ddos_connections", "Open connections seen by anti-DDoS", "gauge");
+            out.sample("lithair_ddos_connections", &[], stats.global_connections);
+            out.header(
+                "lithair_ddos_circuit_breaker_state",
+                "Circuit breaker state (0 closed, 1 open, 2 half-open)",
+                "gauge",
+            );
+            out.sample("lithair_ddos_circuit_breaker_state", &[], stats.circuit_breaker_state);
+            out.header(
+                "lithair_ddos_circuit_breaker_failures",
+                "Failures counted by the circuit breaker",
+                "gauge",
+            );
+            let failures = stats.circuit_breaker_failures;
+            out.sample("lithair_ddos_circuit_breaker_failures", &[], failures);
+        }
+
+        // Replication (leader only: followers have no batcher stats)
+        if let Some(batcher) = &self.replication_batcher {
+            let commit_index = self.consensus_log.as_ref().map_or(0, |log| log.commit_index());
+            out.header("lithair_raft_commit_index", "Commit index of the consensus log", "gauge");
+            out.sample("lithair_raft_commit_index", &[], commit_index);
+
+            let followers = batcher.get_all_follower_stats().await;
+            out.header(
+                "lithair_replication_lag_entries",
+                "Log entries the follower is behind the commit index",
+                "gauge",
+            );
+            for f in &followers {
+                let health = f.health.to_string();
+                let labels = [("follower", f.address.as_str()), ("health", health.as_str())];
+                let lag = commit_index.saturating_sub(f.last_replicated_index);
+                out.sample("lithair_replication_lag_entries", &labels, lag);
+            }
+            out.header(
+                "lithair_replication_latency_ms",
+                "Latency of the last replication round-trip",
+                "gauge",
+            );
+            for f in &followers {
+                out.sample(
+                    "lithair_replication_latency_ms",
+                    &[("follower", f.address.as_str())],
+                    f.last_latency_ms,
+                );
+            }
+            out.header("lithair_replication_pending", "Entries queued for the follower", "gauge");
+            for f in &followers {
+                out.sample(
+                    "lithair_replication_pending",
+                    &[("follower", f.address.as_str())],
+                    f.pending_count,
+                );
+            }
+        }
+
         Ok(hyper::Response::builder()
             .status(200)
-            .header("Content-Type", "text/plain")
-            .body(Full::new(Bytes::from("# Metrics endpoint\n")))
+            .header("Content-Type", crate::metrics::CONTENT_TYPE)
+            .body(Full::new(Bytes::from(out.finish())))
             .expect("valid HTTP response"))
     }
 
+    /// Route pattern used as the `route` label of request metrics
+    ///
+    /// Model paths collapse item ids (`/api/products/:id`), custom routes use their
+    /// registered pattern and built-in endpoints their fixed prefix. Anything else the
+    /// client chose, such as an unknown sub-route, is grouped under `other`, so label
+    /// cardinality stays bounded.
+    fn route_label(&self, path: &str) -> String {
+        for info in &self.model_infos {
+            let base = info.base_path.trim_end_matches('/');
+            let Some(rest) = path.strip_prefix(base) else { continue };
+            if !rest.is_empty() && !rest.starts_with('/') {
+                continue;
+            }
+            return Self::model_route_label(base, rest);
+        }
+
+        if let Some(route) = self.custom_routes.iter().find(|r| Self::path_matches(&r.path, path)) {
+            return route.path.clone();
+        }
+
+        let raft = &self.config.raft;
+        let raft_routes = [raft.heartbeat_path(), raft.leader_path(), raft.election_path()];
+        if let Some(route) = raft_routes.into_iter().find(|route| route == path) {
+            return route;
+        }
+        if let Some(route) = BUILT_IN_ROUTES.iter().find(|route| {
+            path.strip_prefix(**route).is_some_and(|r| r.is_empty() || r.starts_with('/'))
+        }) {
+            return route.to_string();
+        }
+        if path == self.config.admin.metrics_path || path == "/health" {
+            return path.to_string();
+        }
+        "other".to_string()
+    }
+
+    /// Metrics label of `rest`, the part of a model path after its `base`
+    fn model_route_label(base: &str, rest: &str) -> String {
+        let mut segments = rest.split('/').filter(|s| !s.is_empty());
+        match (segments.next(), segments.next(), segments.next()) {
+            (None, _, _) => base.to_string(),
+            (Some(op), None, _) if MODEL_OPERATIONS.contains(&op) => format!("{base}/{op}"),
+            (Some(op), _, _) if op.starts_with('_') => format!("{base}/other"),
+            (Some(_), None, _) => format!("{base}/:id"),
+            (Some(_), Some(sub), None) if ITEM_OPERATIONS.contains(&sub) => {
+                format!("{base}/:id/{sub}")
+            }
+            (Some(_), Some(_), _) => format!("{base}/:id/other"),
+        }
+    }
+
     /// Handle data admin API requests (/_admin/data/*)
     ///
     /// Endpoints:
@@ -3636,23 +4093,39 @@ impl LithairServer {
         let is_update = (method == hyper::Method::PUT || method == hyper::Method::PATCH)
             && !segments.is_empty();
         let is_delete = method == hyper::Method::DELETE && !segments.is_empty();
-        let is_write = is_create || is_bulk_create || is_update || is_delete;
+        let is_restore =
+            method == hyper::Method::POST && segments.len() == 2 && segments[1] == "_restore";
+        let is_write = is_create || is_bulk_create || is_update || is_delete || is_restore;
+
+        // Extract the resource ID for UPDATE, DELETE and RESTORE operations
+        let resource_id = if is_update || is_delete || is_restore {
+            segments.first().map(|s| s.to_string())
+        } else {
+            None
+        };
+
+        let is_checked_write = is_create || is_bulk_create || is_update;
+        let _references = self.lock_references(model, is_delete, is_checked_write).await;
 
-        // Extract the resource ID for UPDATE and DELETE operations
-        let resource_id =
-            if is_update || is_delete { segments.first().map(|s| s.to_string()) } else { None };
+        // Deletes that cascade apply as one batch with the writes they cascade to
+        if is_delete && segments.len() == 1 {
+            if let Some(response) = self.cascade_delete(&req, model, segments[0]).await {
+                return Ok(response);
+            }
+        }
 
         // ==================== CLUSTER MODE WITH CONSENSUS LOG ====================
         // If we have a consensus log (cluster mode), write operations go through Raft
         if is_write && !self.cluster_peers.is_empty() {
             if let Some(ref consensus_log_ref) = self.consensus_log {
                 log::debug!(
-                    "CLUSTER MODE: {} {} (create={}, update={}, delete={})",
+                    "CLUSTER MODE: {} {} (create={}, update={}, delete={}, restore={})",
                     method,
                     path,
                     is_create,
                     is_update,
-                    is_delete
+                    is_delete,
+                    is_restore
                 );
 
                 // Check if we are the leader
@@ -3661,25 +4134,47 @@ impl LithairServer {
                 if !is_leader {
                     // Redirect to leader
                     if let Some(ref raft_state) = self.raft_state {
-                        let leader_port = raft_state.get_leader_port();
-                        return Ok(hyper::Response::builder()
-                        .status(307) // Temporary Redirect
-                        .header("Location", format!("http://127.0.0.1:{}{}", leader_port, path))
-                        .header("X-Raft-Leader", format!("{}", leader_port))
-                        .body(Full::new(Bytes::from(format!(r#"{{"error":"Not leader","leader_port":{}}}"#, leader_port))))
-                        .expect("valid HTTP response"));
+                        return Ok(Self::not_leader_response(raft_state, &path));
                     }
                 }
 
                 // We are the leader - process through consensus log
                 let consensus_log = consensus_log_ref;
 
-                // Read request body for write operations
+                // A restore is checked as the delete it undoes, on the soft-deleted item
+                let restored = if is_restore {
+                    let id = resource_id.as_deref().unwrap_or_default();
+                    let Some(item) = model.handler.deleted_item_json(id).await else {
+                        return Ok(hyper::Response::builder()
+                            .status(404)
+                            .header("Content-Type", "application/json")
+                            .body(Full::new(Bytes::from(r#"{"error":"Not found"}"#)))
+                            .expect("valid HTTP response"));
+                    };
+                    if let Some(denied) =
+                        model.handler.authorize_batch_write(&req, &item, None).await
+                    {
+                        return Ok(Self::buffered_response(denied).await);
+                    }
+                    Some(item)
+                } else {
+                    None
+                };
+
+                // Read request body for write operations, in place: the model checks a
+                // PATCH against the request
                 use http_body_util::BodyExt;
-                let (_parts, body) = req.into_parts();
-                let body_bytes = body.collect().await?.to_bytes();
+                let mut req = req;
+                let body_bytes = req.body_mut().collect().await?.to_bytes();
                 let body_json: serde_json::Value =
                     serde_json::from_slice(&body_bytes).unwrap_or(serde_json::Value::Null);
+                let if_match = req.headers().get("if-match").and_then(|v| v.to_str().ok());
+                let mut patched = None;
+
+                // Bulk creates are checked item by item and logged as one batch
+                if is_bulk_create {
+                    return Ok(self.bulk_create_clustered(consensus_log, model, &body_json).await);
+                }
 
                 // Create the CRUD operation
                 // For CREATE operations: generate ID on leader to ensure all nodes have same ID
@@ -3687,38 +4182,64 @@ impl LithairServer {
                 let now = chrono::Utc::now().to_rfc3339();
                 let operation = if is_create {
                     let mut data = body_json.clone();
-                    // Generate ID on leader if not provided, so followers get the same ID
-                    if data.get("id").is_none() || data.get("id") == Some(&serde_json::Value::Null)
-                    {
-                        data["id"] = serde_json::Value::String(uuid::Uuid::new_v4().to_string());
-                    }
-                    // Add timestamps for consistency across all nodes
-                    if data.get("created_at").is_none() {
-                        data["created_at"] = serde_json::Value::String(now.clone());
-                    }
-                    if data.get("updated_at").is_none() {
-                        data["updated_at"] = serde_json::Value::String(now.clone());
-                    }
+                    // ID and timestamps generated on the leader, so followers get the same
+                    batch::stamp_new_item(&mut data, &now);
                     crate::cluster::CrudOperation::Create {
                         model_path: model.base_path.clone(),
                         data,
                     }
+                } else if is_update && method == hyper::Method::PATCH {
+                    let id = resource_id.clone().unwrap_or_default();
+                    log::info!("CLUSTER: Creating PATCH operation for id={}", id);
+                    let expected_version =
+                        match Self::expected_version(model.handler.as_ref(), &id, if_match) {
+                            Ok(version) => version,
+                            Err(failed) => return Ok(failed),
+                        };
+                    // Checked as on a single node (permissions, immutable fields,
+                    // validation); followers merge the same diff into their copy
+                    let diff = match model.handler.prepare_patch_json(&req, &id, &body_bytes).await
+                    {
+                        Ok(Some((item, diff))) => {
+                            patched = Some(item);
+                            diff
+                        }
+                        Ok(None) => {
+                            let current = model.handler.get_item_json(&id).await;
+                            let mut response = hyper::Response::builder()
+                                .status(200)
+                                .header("Content-Type", "application/json");
+                            if let Some(version) = model.handler.item_version(&id) {
+                                response = response.header("ETag", etag::etag(version));
+                            }
+                            return Ok(response
+                                .body(Full::new(Bytes::from(
+                                    current.unwrap_or_default().to_string(),
+                                )))
+                                .expect("valid HTTP response"));
+                        }
+                        Err(rejected) => return Ok(Self::buffered_response(rejected).await),
+                    };
+                    crate::cluster::CrudOperation::Patch {
+                        model_path: model.base_path.clone(),
+                        id,
+                        diff,
+                        expected_version,
+                    }
                 } else if is_update {
                     let id = resource_id.clone().unwrap_or_default();
                     log::info!("CLUSTER: Creating UPDATE operation for id={}", id);
+                    let expected_version =
+                        match Self::expected_version(model.handler.as_ref(), &id, if_match) {
+                            Ok(version) => version,
+                            Err(failed) => return Ok(failed),
+                        };
                     // For UPDATE: merge delta with existing item to send complete object
                     // This ensures followers can deserialize the full item
                     let existing = model.handler.get_item_json(&id).await;
-                    let mut merged_data = if let Some(mut existing_json) = existing {
+                    let mut merged_data = if let Some(existing_json) = existing {
                         // Merge delta into existing (delta overwrites existing fields)
-                        if let Some(obj) = existing_json.as_object_mut() {
-                            if let Some(delta_obj) = body_json.as_object() {
-                                for (key, value) in delta_obj {
-                                    obj.insert(key.clone(), value.clone());
-                                }
-                            }
-                        }
-                        existing_json
+                        batch::merge_fields(&existing_json, &body_json)
                     } else {
                         // Item doesn't exist - use delta as-is (will likely fail on follower too)
                         body_json.clone()
@@ -3729,285 +4250,1896 @@ impl LithairServer {
                         model_path: model.base_path.clone(),
                         id,
                         data: merged_data,
+                        expected_version,
+                    }
+                } else if is_restore {
+                    let id = resource_id.clone().unwrap_or_default();
+                    log::info!("CLUSTER: Creating RESTORE operation for id={}", id);
+                    crate::cluster::CrudOperation::Restore {
+                        model_path: model.base_path.clone(),
+                        id,
                     }
-                } else if is_delete {
+                } else {
                     let id = resource_id.clone().unwrap_or_default();
                     log::info!("CLUSTER: Creating DELETE operation for id={}", id);
+                    let expected_version =
+                        match Self::expected_version(model.handler.as_ref(), &id, if_match) {
+                            Ok(version) => version,
+                            Err(failed) => return Ok(failed),
+                        };
                     crate::cluster::CrudOperation::Delete {
                         model_path: model.base_path.clone(),
                         id,
+                        expected_version,
                     }
-                } else {
-                    // Bulk create - currently handled as a single operation
-                    // Note: Proper BatchOperation support is not yet available
-                    let mut data = body_json.clone();
-                    if data.get("id").is_none() || data.get("id") == Some(&serde_json::Value::Null)
-                    {
-                        data["id"] = serde_json::Value::String(uuid::Uuid::new_v4().to_string());
+                };
+
+                // Step 0: Validate and reserve unique keys before the entry enters the log;
+                // once appended, followers apply it unconditionally
+                let reservation: Option<(Option<String>, serde_json::Value)> = match &operation {
+                    crate::cluster::CrudOperation::Create { data, .. } => {
+                        Some((None, data.clone()))
                     }
-                    // Add timestamps for consistency across all nodes
-                    if data.get("created_at").is_none() {
-                        data["created_at"] = serde_json::Value::String(now.clone());
+                    crate::cluster::CrudOperation::Update { id, data, .. } => {
+                        Some((Some(id.clone()), data.clone()))
                     }
-                    if data.get("updated_at").is_none() {
-                        data["updated_at"] = serde_json::Value::String(now);
+                    crate::cluster::CrudOperation::Patch { id, .. } => {
+                        patched.map(|item| (Some(id.clone()), item))
                     }
-                    crate::cluster::CrudOperation::Create {
-                        model_path: model.base_path.clone(),
-                        data,
+                    crate::cluster::CrudOperation::Restore { id, .. } => {
+                        restored.map(|item| (Some(id.clone()), item))
                     }
+                    _ => None,
                 };
-
-                // Step 1: Append to local consensus log (in-memory, fast)
-                let log_entry = consensus_log.append(operation.clone()).await;
-                let entry_index = log_entry.log_id.index;
-                let term = consensus_log.current_term();
-                let node_id = self.node_id.unwrap_or(0);
-                let _current_commit = consensus_log.commit_index(); // For debugging (window-based replication doesn't need this)
-                log::debug!("Appended to log: index={}, term={}", entry_index, term);
-
-                // Step 2: Queue for batcher (for lagging followers tracking)
-                if let Some(ref batcher) = self.replication_batcher {
-                    batcher.queue_entry(log_entry.clone()).await;
+                if let Some((ref id, ref data)) = reservation {
+                    let models = self.models.read().await;
+                    let target = models.iter().position(|m| m.base_path == model.base_path);
+                    let unresolved = match target {
+                        Some(target) => {
+                            Self::unresolved_references(&models, target, data, &[], &[]).await
+                        }
+                        None => None,
+                    };
+                    drop(models);
+                    let prepared = match unresolved {
+                        Some(error) => Err((422, error)),
+                        None => model.handler.prepare_write_json(id.as_deref(), data).await,
+                    };
+                    if let Err((status, body)) = prepared {
+                        return Ok(hyper::Response::builder()
+                            .status(status)
+                            .header("Content-Type", "application/json")
+                            .body(Full::new(Bytes::from(body.to_string())))
+                            .expect("valid HTTP response"));
+                    }
                 }
-
-                // Step 3: PARALLEL - WAL durability + Replication to followers
-                // We use tokio::join! to run both concurrently and wait for both to complete.
-                // This reduces latency since WAL fsync and network I/O happen simultaneously.
-                let wal_clone = self.wal.clone();
-                let log_entry_clone = log_entry.clone();
-                let peers_clone = self.cluster_peers.clone();
-                let batcher_clone = self.replication_batcher.clone();
-
-                // WAL write task (uses group commit for batching)
-                let wal_future = async {
-                    if let Some(ref wal) = wal_clone {
-                        // Use buffered append for group commit (higher throughput)
-                        wal.append_buffered(&log_entry_clone).await
-                    } else {
-                        Ok(())
+                let reservation_ref = &reservation;
+                let abort_reservation = move || async move {
+                    if let Some((id, data)) = reservation_ref {
+                        model.handler.abort_write_json(id.as_deref(), data).await;
                     }
                 };
 
-                // Replication task (returns when majority responds)
-                // Send ALL entries from beginning to ensure lagging followers can always catch up.
-                // This is critical: if we use a window, followers stuck on entry N will never receive
-                // entries N+1 to window_start, causing permanent divergence.
-                let consensus_log_clone = consensus_log.clone();
-                let replication_future = async move {
-                    // Always send ALL entries from index 1 to ensure no gaps
-                    let entries_to_send = consensus_log_clone.get_entries_from(1).await;
-
-                    if entries_to_send.is_empty() {
-                        return Ok(entry_index);
-                    }
-
-                    log::debug!(
-                        "Replicating {} entries (window {} to {}), target_commit={}",
-                        entries_to_send.len(),
-                        entries_to_send.first().map(|e| e.log_id.index).unwrap_or(0),
-                        entries_to_send.last().map(|e| e.log_id.index).unwrap_or(0),
-                        entry_index
-                    );
-
-                    Self::replicate_log_entries_to_followers(
-                        &peers_clone,
-                        entries_to_send,
-                        entry_index, // Commit up to this entry if majority responds
-                        term,
-                        node_id,
-                        batcher_clone,
-                    )
-                    .await
+                // Read under the apply lock: no later entry has applied yet
+                let read_version = |result: &serde_json::Value| {
+                    let key = resource_id
+                        .clone()
+                        .or_else(|| result.get("id").and_then(|v| v.as_str()).map(str::to_string));
+                    key.and_then(|key| model.handler.item_version(&key))
                 };
-
-                // Run WAL and replication in parallel
-                let (wal_result, replication_result) = tokio::join!(wal_future, replication_future);
-
-                // Check WAL result first (must succeed for durability)
-                if let Err(e) = wal_result {
-                    log::error!("WAL write failed: {}", e);
+                let (entry_index, result, version) =
+                    match self.commit_and_apply(consensus_log, &operation, read_version).await {
+                        Ok(applied) => applied,
+                        Err(failed) => {
+                            abort_reservation().await;
+                            return Ok(failed);
+                        }
+                    };
+                if result.get("precondition_failed").is_some() {
+                    // A write applied in between moved the item on
+                    abort_reservation().await;
+                    return Ok(Self::precondition_failed_response(version));
+                }
+                if result.get("not_found").is_some() {
+                    // Deleted, restored or purged by a write applied in between
+                    abort_reservation().await;
                     return Ok(hyper::Response::builder()
-                        .status(503)
-                        .body(Full::new(Bytes::from(format!(
-                            r#"{{"error":"WAL write failed: {}"}}"#,
-                            e
-                        ))))
+                        .status(404)
+                        .header("Content-Type", "application/json")
+                        .body(Full::new(Bytes::from(r#"{"error":"Not found"}"#)))
                         .expect("valid HTTP response"));
                 }
-                log::debug!("WAL entry durable: index={}", entry_index);
-
-                // Check replication result
-                match replication_result {
-                    Ok(new_commit_index) => {
-                        // Step 4: Commit the entry (majority achieved)
-                        consensus_log.commit(new_commit_index);
-                        log::debug!("Committed index: {}", new_commit_index);
-
-                        // Step 4.5: Send commit notification to followers IN PARALLEL (fire-and-forget)
-                        // Include the window of entries so followers get both data and commit in one shot
-                        let peers_for_notify = self.cluster_peers.clone();
-                        let commit_index_to_notify = new_commit_index;
-                        let term_for_notify = term;
-                        let node_id_for_notify = node_id;
-                        let consensus_log_for_notify = consensus_log.clone();
-                        tokio::spawn(async move {
-                            // Send ALL entries from index 1 to ensure followers can always catch up
-                            // This is critical: if we use a window, followers stuck on entry N will never
-                            // receive entries N+1 to window_start, causing permanent divergence
-                            let entries_for_notify =
-                                consensus_log_for_notify.get_entries_from(1).await;
-
-                            let client = reqwest::Client::builder()
-                                .timeout(std::time::Duration::from_secs(1))
-                                .build()
-                                .ok();
-                            if let Some(client) = client {
-                                let request = crate::cluster::consensus_log::AppendEntriesRequest {
-                                    term: term_for_notify,
-                                    leader_id: node_id_for_notify,
-                                    prev_log_index: 0,
-                                    prev_log_term: 0,
-                                    entries: entries_for_notify, // Include entries for catch-up
-                                    leader_commit: commit_index_to_notify,
-                                };
-                                // Send to ALL peers IN PARALLEL
-                                let futures: Vec<_> = peers_for_notify
-                                    .iter()
-                                    .map(|peer| {
-                                        let endpoint = format!("http://{}/_raft/append", peer);
-                                        let client = client.clone();
-                                        let request = request.clone();
-                                        async move {
-                                            let _ =
-                                                client.post(&endpoint).json(&request).send().await;
-                                        }
-                                    })
-                                    .collect();
-                                futures::future::join_all(futures).await;
-                            }
-                        });
+                let response_body = serde_json::to_vec(&result).unwrap_or_default();
+                let mut response = hyper::Response::builder()
+                    .status(if is_create { 201 } else { 200 })
+                    .header("Content-Type", "application/json")
+                    .header("X-Raft-Index", entry_index.to_string());
+                if let Some(version) = version.filter(|_| !is_delete) {
+                    response = response.header("ETag", etag::etag(version));
+                }
+                return Ok(response
+                    .body(Full::new(Bytes::from(response_body)))
+                    .expect("valid HTTP response"));
+            }
+        }
+
+        // ==================== SINGLE-NODE MODE OR READ OPERATIONS ====================
+        // Reads asking for a consistency level wait for this node to catch up
+        let req = if method == hyper::Method::GET {
+            match self.read_barrier(req).await? {
+                Ok(req) => req,
+                Err(response) => return Ok(response),
+            }
+        } else {
+            req
+        };
+
+        // `?expand=` on a list or item read: take every model's read permissions from the
+        // request before the model handler consumes it (malformed values are its to report)
+        let mut req = req;
+        let is_read = method == hyper::Method::GET
+            && segments.len() <= 1
+            && !segments.first().is_some_and(|s| s.starts_with('_'));
+        let expand = is_read
+            .then(|| crate::http::query::parse_item_query_params(req.uri().query().unwrap_or("")))
+            .and_then(|params| params.ok()?.expand);
+        let expansion = match expand {
+            Some(expand) => {
+                // The item's ETag does not cover what is embedded in it
+                req.headers_mut().remove(hyper::header::IF_NONE_MATCH);
+                // `fields=` could drop the keys relations are resolved by: the list comes
+                // back whole and is projected once expanded
+                let fields = segments
+                    .is_empty()
+                    .then(|| crate::http::query::parse_query_params(req.uri().query()?).ok())
+                    .flatten()
+                    .and_then(|params| params.fields);
+                if fields.is_some() {
+                    req.extensions_mut().insert(crate::http::expand::Unprojected);
+                }
+                let models = self.models.read().await;
+                let perms: Vec<Vec<String>> =
+                    models.iter().map(|m| m.handler.read_permissions(&req)).collect();
+                Some((expand, fields, perms))
+            }
+            None => None,
+        };
+
+        // No cluster or read operation - delegate directly to model handler
+        match model.handler.handle_request(req, &segments).await {
+            Ok(resp) => {
+                use http_body_util::BodyExt;
+
+                let (mut parts, body) = resp.into_parts();
+                let mut body_bytes = body.collect().await?.to_bytes();
+                if let Some((expand, fields, perms)) =
+                    expansion.filter(|_| parts.status.is_success())
+                {
+                    let is_list = segments.is_empty();
+                    let fields = fields.as_deref();
+                    let expanded = self
+                        .expand_response(model, is_list, &body_bytes, &expand, fields, &perms)
+                        .await;
+                    match expanded {
+                        Ok(expanded) => body_bytes = expanded,
+                        Err(e) => {
+                            return Ok(hyper::Response::builder()
+                                .status(400)
+                                .header("Content-Type", "application/json")
+                                .body(Full::new(Bytes::from(e.to_json().to_string())))
+                                .expect("valid HTTP response"));
+                        }
+                    }
+                    parts.headers.remove(hyper::header::ETAG);
+                    parts.headers.remove(hyper::header::CONTENT_LENGTH);
+                }
+                Ok(hyper::Response::from_parts(parts, Full::new(body_bytes)))
+            }
+            Err(_) => Ok(hyper::Response::builder()
+                .status(500)
+                .body(Full::new(Bytes::from(r#"{"error":"Internal error"}"#)))
+                .expect("valid HTTP response")),
+        }
+    }
+
+    /// Embed the relations of `expand` in a read response of `model`: the item, or the
+    /// `data` items of a list page (see `http::expand`), then project the list items on
+    /// `fields` and the expanded relations
+    async fn expand_response(
+        &self,
+        model: &ModelRegistration,
+        is_list: bool,
+        body: &Bytes,
+        expand: &crate::http::expand::Expand,
+        fields: Option<&[String]>,
+        perms: &[Vec<String>],
+    ) -> std::result::Result<Bytes, crate::http::query::QueryError> {
+        let Ok(mut response) = serde_json::from_slice::<serde_json::Value>(body) else {
+            return Ok(body.clone());
+        };
+        let models = self.models.read().await;
+        let Some(index) = models.iter().position(|m| m.base_path == model.base_path) else {
+            return Ok(body.clone());
+        };
+        let registry = Self::relation_registry(&models);
+        if !is_list {
+            let item = std::slice::from_mut(&mut response);
+            Self::expand_items(&models, &registry, index, item, expand, perms).await?;
+        } else if let Some(items) = response.get_mut("data").and_then(|d| d.as_array_mut()) {
+            Self::expand_items(&models, &registry, index, items, expand, perms).await?;
+            if let Some(fields) = fields {
+                let fields = crate::http::expand::projected_fields(fields, expand);
+                for item in items.iter_mut() {
+                    *item = crate::http::query::project_fields(item, &fields);
+                }
+            }
+        }
+        Ok(Bytes::from(response.to_string()))
+    }
+
+    /// Attach the relations of `expand` to `items` of model `model`, then expand the
+    /// related items in turn. Related items are fetched once per relation and level, and
+    /// filtered by the `can_read` of their model with the caller's `perms` for it.
+    fn expand_items<'a>(
+        models: &'a [ModelRegistration],
+        registry: &'a crate::schema::RelationRegistry,
+        model: usize,
+        items: &'a mut [serde_json::Value],
+        expand: &'a crate::http::expand::Expand,
+        perms: &'a [Vec<String>],
+    ) -> futures::future::BoxFuture<'a, std::result::Result<(), crate::http::query::QueryError>>
+    {
+        use crate::http::expand;
+        use crate::http::query::QueryError;
+
+        Box::pin(async move {
+            let name = &models[model].name;
+            let spec = registry.get_model_relations(name);
+            for (relation, nested) in &expand.relations {
+                let invalid = |message: String| QueryError { param: "expand".into(), message };
+                let (target, local, remote, many) = if let Some(fk) =
+                    spec.and_then(|spec| spec.foreign_key_for(relation))
+                {
+                    let target =
+                        Self::resolve_model(models, &fk.referenced_model).ok_or_else(|| {
+                            invalid(format!("unknown model `{}`", fk.referenced_model))
+                        })?;
+                    (target, fk.field_name.clone(), fk.referenced_field.clone(), false)
+                } else if let Some(inverse) = spec.and_then(|spec| spec.relations.get(relation)) {
+                    let target =
+                        Self::resolve_model(models, &inverse.source_model).ok_or_else(|| {
+                            invalid(format!("unknown model `{}`", inverse.source_model))
+                        })?;
+                    // The foreign key of the related model that points back at this one
+                    let back = registry.get_model_relations(&models[target].name).and_then(|s| {
+                        s.foreign_keys.values().find(|fk| match inverse.source_field.as_str() {
+                            "" => Self::resolve_model(models, &fk.referenced_model) == Some(model),
+                            field => fk.field_name == field,
+                        })
+                    });
+                    let Some(back) = back else {
+                        return Err(invalid(format!(
+                            "{} has no foreign key to {}",
+                            models[target].name, name
+                        )));
+                    };
+                    let many = inverse.relation_type != crate::schema::RelationType::OneToOne;
+                    (target, back.referenced_field.clone(), back.field_name.clone(), many)
+                } else {
+                    return Err(invalid(format!("`{}` is not a relation of {}", relation, name)));
+                };
+
+                let keys = expand::keys_of(items, &local);
+                let handler = &models[target].handler;
+                let mut related = if keys.is_empty() {
+                    Vec::new()
+                } else {
+                    handler.readable_items_json(&remote, &keys, &perms[target]).await
+                };
+                if !nested.is_empty() {
+                    Self::expand_items(models, registry, target, &mut related, nested, perms)
+                        .await?;
+                }
+                expand::attach(items, relation, &local, related, &remote, many);
+            }
+            Ok(())
+        })
+    }
+
+    /// Relations declared by the registered models
+    fn relation_registry(models: &[ModelRegistration]) -> crate::schema::RelationRegistry {
+        let mut registry = crate::schema::RelationRegistry::new();
+        for model in models {
+            registry.register_model(crate::schema::ModelRelationSpec {
+                model_name: model.name.clone(),
+                foreign_keys: model
+                    .handler
+                    .foreign_keys()
+                    .into_iter()
+                    .map(|fk| (fk.field_name.clone(), fk))
+                    .collect(),
+                relations: model
+                    .handler
+                    .relations()
+                    .into_iter()
+                    .map(|relation| (relation.relation_name.clone(), relation))
+                    .collect(),
+            });
+        }
+        registry
+    }
+
+    /// Registered model a relation names, by struct name (`Author`) or by collection,
+    /// the last segment of its base path (`authors`)
+    fn resolve_model(models: &[ModelRegistration], reference: &str) -> Option<usize> {
+        models.iter().position(|m| m.name == reference).or_else(|| {
+            models.iter().position(|m| {
+                let collection = m.base_path.rsplit('/').next().unwrap_or_default();
+                m.name.eq_ignore_ascii_case(reference) || collection == reference
+            })
+        })
+    }
+
+    /// Those of `values` that no item of `model` holds in `field`; None when no
+    /// registered model is named `model`
+    async fn missing_keys(
+        models: &[ModelRegistration],
+        model: &str,
+        field: &str,
+        values: &[String],
+    ) -> Option<Vec<String>> {
+        let target = Self::resolve_model(models, model)?;
+        let found = models[target].handler.items_json(field, values).await;
+        let found: std::collections::HashSet<String> = found
+            .iter()
+            .filter_map(|item| item.get(field).and_then(crate::http::expand::key_text))
+            .collect();
+        Some(values.iter().filter(|value| !found.contains(*value)).cloned().collect())
+    }
+
+    /// Foreign keys of `data`, written to `model`, that reference no item, as the 422
+    /// body rejecting the write; None when every key resolves
+    ///
+    /// `targets` and `operations` are the writes of its batch, if any: a key resolves to
+    /// an item the batch creates or updates, and not to one it deletes.
+    async fn unresolved_references(
+        models: &[ModelRegistration],
+        model: usize,
+        data: &serde_json::Value,
+        targets: &[usize],
+        operations: &[crate::cluster::CrudOperation],
+    ) -> Option<serde_json::Value> {
+        use crate::cluster::CrudOperation;
+        use crate::http::expand::key_text;
+
+        let mut errors = crate::http::ValidationErrors::new();
+        for fk in models[model].handler.foreign_keys() {
+            let Some(key) = data.get(&fk.field_name).and_then(key_text) else { continue };
+            let Some(parent) = Self::resolve_model(models, &fk.referenced_model) else {
+                continue;
+            };
+            let holds_key = |item: &serde_json::Value| {
+                item.get(&fk.referenced_field).and_then(key_text).as_ref() == Some(&key)
+            };
+            let mut resolved = None;
+            for (_, operation) in targets.iter().zip(operations).filter(|(t, _)| **t == parent) {
+                match operation {
+                    CrudOperation::Create { data, .. } | CrudOperation::Update { data, .. }
+                        if holds_key(data) =>
+                    {
+                        resolved = Some(true);
+                    }
+                    CrudOperation::Delete { id, .. } => {
+                        let deleted = models[parent].handler.get_item_json(id).await;
+                        if deleted.is_some_and(|item| holds_key(&item)) {
+                            resolved = Some(false);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let resolved = match resolved {
+                Some(resolved) => resolved,
+                None => {
+                    let keys = std::slice::from_ref(&key);
+                    let missing = Self::missing_keys(
+                        models,
+                        &fk.referenced_model,
+                        &fk.referenced_field,
+                        keys,
+                    );
+                    missing.await.is_none_or(|missing| missing.is_empty())
+                }
+            };
+            if !resolved {
+                let message = format!("references no {} '{}'", fk.referenced_model, key);
+                errors.add(&fk.field_name, "foreign_key", message);
+            }
+        }
+        errors.into_result().err().map(|errors| errors.to_json())
+    }
+
+    /// Foreign keys whose `cascade` is enforced on deletes, as (index of the model
+    /// declaring it, index of the model it references, key)
+    fn enforced_references(
+        models: &[ModelRegistration],
+    ) -> Vec<(usize, usize, crate::schema::RelationForeignKeySpec)> {
+        let mut references = Vec::new();
+        for (child, model) in models.iter().enumerate() {
+            for fk in model.handler.foreign_keys() {
+                if fk.cascade == crate::schema::CascadeStrategy::None {
+                    continue;
+                }
+                if let Some(parent) = Self::resolve_model(models, &fk.referenced_model) {
+                    references.push((child, parent, fk));
+                }
+            }
+        }
+        references
+    }
+
+    /// Writes the deletes among `planned` imply through the `cascade` of the foreign keys
+    /// referencing the deleted items
+    ///
+    /// `Delete` children are deleted in turn, `SetNull` children updated with the key
+    /// nulled; a `Restrict` child still referencing a deleted item rejects the delete with
+    /// 409. Children that `planned` writes itself are left to it, the foreign key check of
+    /// their write deciding. Cascaded writes expect the version they were planned against
+    /// and, unlike `planned`, are not checked against permissions. The plan reads without
+    /// the batch locks; `check_cascade` rejects it when it went stale before applying.
+    async fn plan_cascade(
+        models: &[ModelRegistration],
+        planned: &[(usize, crate::cluster::CrudOperation)],
+    ) -> std::result::Result<Vec<(usize, crate::cluster::CrudOperation)>, batch::BatchRejection>
+    {
+        use crate::cluster::CrudOperation;
+        use crate::http::expand::key_text;
+        use crate::schema::CascadeStrategy;
+
+        let references = Self::enforced_references(models);
+        let mut written = std::collections::HashSet::new();
+        let mut deleted = std::collections::HashSet::new();
+        let mut queue = std::collections::VecDeque::new();
+        for (index, (model, operation)) in planned.iter().enumerate() {
+            let id = match operation {
+                CrudOperation::Create { data, .. } => data.get("id").and_then(key_text),
+                CrudOperation::Update { id, .. } => Some(id.clone()),
+                CrudOperation::Delete { id, .. } => {
+                    deleted.insert((*model, id.clone()));
+                    queue.push_back((*model, id.clone(), index));
+                    Some(id.clone())
+                }
+                _ => None,
+            };
+            written.extend(id.map(|id| (*model, id)));
+        }
+
+        // Follow `Delete` cascades first: a child may be deleted through another path than
+        // the one that would null or restrict it
+        let mut deletes = Vec::new();
+        let mut held = Vec::new();
+        while let Some((model, id, index)) = queue.pop_front() {
+            let Some(item) = models[model].handler.get_item_json(&id).await else { continue };
+            for (child, _, fk) in references.iter().filter(|(_, parent, _)| *parent == model) {
+                let Some(key) = item.get(&fk.referenced_field).and_then(key_text) else {
+                    continue;
+                };
+                let handler = models[*child].handler.as_ref();
+                let keys = std::slice::from_ref(&key);
+                for child_item in handler.items_json(&fk.field_name, keys).await {
+                    let Some(child_id) = child_item.get("id").and_then(key_text) else {
+                        continue;
+                    };
+                    let node = (*child, child_id.clone());
+                    if written.contains(&node) || deleted.contains(&node) {
+                        continue;
+                    }
+                    if fk.cascade == CascadeStrategy::Delete {
+                        let expected_version = handler.item_version(&child_id);
+                        deleted.insert(node);
+                        deletes.push((
+                            *child,
+                            CrudOperation::Delete {
+                                model_path: models[*child].base_path.clone(),
+                                id: child_id.clone(),
+                                expected_version,
+                            },
+                        ));
+                        queue.push_back((*child, child_id, index));
+                    } else {
+                        held.push((node, fk, key.clone(), model, index));
+                    }
+                }
+            }
+        }
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut updates: Vec<(usize, CrudOperation)> = Vec::new();
+        for ((child, child_id), fk, key, parent, index) in held {
+            if deleted.contains(&(child, child_id.clone())) {
+                continue;
+            }
+            if fk.cascade == CascadeStrategy::Restrict {
+                let message = format!(
+                    "{} '{}' still references {} '{}'",
+                    models[child].name, child_id, models[parent].name, key
+                );
+                let error = serde_json::json!({ "error": "restricted", "message": message });
+                return Err(batch::BatchRejection::new(index, 409, error));
+            }
+            let existing = updates.iter_mut().find_map(|(model, operation)| match operation {
+                CrudOperation::Update { id, data, .. } if *model == child && *id == child_id => {
+                    Some(data)
+                }
+                _ => None,
+            });
+            if let Some(data) = existing {
+                data[fk.field_name.as_str()] = serde_json::Value::Null;
+                continue;
+            }
+            // Read the version before the item, as planned updates do
+            let handler = models[child].handler.as_ref();
+            let expected_version = handler.item_version(&child_id);
+            let Some(mut data) = handler.get_item_json(&child_id).await else { continue };
+            data[fk.field_name.as_str()] = serde_json::Value::Null;
+            data["updated_at"] = serde_json::Value::String(now.clone());
+            let model_path = models[child].base_path.clone();
+            let update = CrudOperation::Update { model_path, id: child_id, data, expected_version };
+            updates.push((child, update));
+        }
+        deletes.extend(updates);
+        Ok(deletes)
+    }
+
+    /// Take `references` for a write to `model`: alone to delete from a model others
+    /// reference with a `cascade`, shared to create or update items of a model with
+    /// foreign keys. Followers take nothing, as the leader decides their writes.
+    async fn lock_references(
+        &self,
+        model: &ModelRegistration,
+        deletes: bool,
+        writes: bool,
+    ) -> ReferencesGuard<'_> {
+        let clustered = self.consensus_log.is_some() && !self.cluster_peers.is_empty();
+        if clustered && !self.raft_state.as_ref().is_some_and(|s| s.is_leader()) {
+            return (None, None);
+        }
+        let referenced = deletes && {
+            let models = self.models.read().await;
+            let target = models.iter().position(|m| m.base_path == model.base_path);
+            let references = Self::enforced_references(&models);
+            references.iter().any(|(_, parent, _)| Some(*parent) == target)
+        };
+        if referenced {
+            return (None, Some(self.references.write().await));
+        }
+        if writes && !model.handler.foreign_keys().is_empty() {
+            return (Some(self.references.read().await), None);
+        }
+        (None, None)
+    }
+
+    /// Delete item `id` of `model` as a batch with the writes it cascades to, so they all
+    /// apply or none does; None when nothing cascades and the plain delete path applies
+    ///
+    /// Answers 200 with the deleted id and the cascaded writes, or the rejection of the
+    /// batch. Followers leave the delete to the normal path, which redirects it.
+    async fn cascade_delete(
+        &self,
+        req: &hyper::Request<hyper::body::Incoming>,
+        model: &ModelRegistration,
+        id: &str,
+    ) -> Option<hyper::Response<http_body_util::Full<Bytes>>> {
+        use crate::cluster::CrudOperation;
+        use http_body_util::Full;
+
+        let clustered = self.consensus_log.is_some() && !self.cluster_peers.is_empty();
+        if clustered && !self.raft_state.as_ref().is_some_and(|s| s.is_leader()) {
+            return None;
+        }
+        let models = self.models.read().await;
+        let target = models.iter().position(|m| m.base_path == model.base_path)?;
+        let references = Self::enforced_references(&models);
+        if !references.iter().any(|(_, parent, _)| *parent == target) {
+            return None;
+        }
+
+        // Checked as the plain delete checks them, before anything about references shows
+        let version = model.handler.item_version(id);
+        let item = model.handler.get_item_json(id).await?;
+        if let Some(denied) = model.handler.authorize_batch_write(req, &item, None).await {
+            return Some(Self::buffered_response(denied).await);
+        }
+        if let Some(header) = req.headers().get("if-match").and_then(|v| v.to_str().ok()) {
+            if !etag::if_match(header, version) {
+                return Some(Self::precondition_failed_response(version));
+            }
+        }
+
+        let model_path = model.base_path.clone();
+        let delete =
+            CrudOperation::Delete { model_path, id: id.to_string(), expected_version: version };
+        let mut planned = vec![(target, delete)];
+        match Self::plan_cascade(&models, &planned).await {
+            Ok(cascade) if cascade.is_empty() => return None,
+            Ok(cascade) => planned.extend(cascade),
+            Err(rejection) => {
+                return Some(
+                    hyper::Response::builder()
+                        .status(rejection.status)
+                        .header("Content-Type", "application/json")
+                        .body(Full::new(Bytes::from(rejection.error.to_string())))
+                        .expect("valid HTTP response"),
+                );
+            }
+        }
+
+        let (targets, operations): (Vec<usize>, Vec<CrudOperation>) = planned.into_iter().unzip();
+        let (result, entry_index) = match self.execute_batch(&models, &targets, operations).await {
+            Ok(executed) => executed,
+            Err(failed) => return Some(failed),
+        };
+        let cascade = result["results"].as_array().map(|r| r[1..].to_vec()).unwrap_or_default();
+        let body = serde_json::json!({ "deleted": id, "cascade": cascade });
+        let mut response = hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json");
+        if let Some(entry_index) = entry_index {
+            response = response.header("X-Raft-Index", entry_index.to_string());
+        }
+        Some(
+            response
+                .body(Full::new(Bytes::from(body.to_string())))
+                .expect("valid HTTP response"),
+        )
+    }
+
+    /// Append `operation` to the consensus log, make it durable and replicated on a
+    /// majority, then apply it locally once every earlier entry has applied
+    ///
+    /// `read` runs under the apply lock right after the entry applied, so what it reads
+    /// reflects this entry and no later one. Returns the entry index, the apply result
+    /// and what `read` returned, or the response to answer when the entry did not commit
+    /// or apply.
+    async fn commit_and_apply<R>(
+        &self,
+        consensus_log: &Arc<crate::cluster::ConsensusLog>,
+        operation: &crate::cluster::CrudOperation,
+        read: impl FnOnce(&serde_json::Value) -> R,
+    ) -> std::result::Result<
+        (u64, serde_json::Value, R),
+        hyper::Response<http_body_util::Full<Bytes>>,
+    > {
+        use http_body_util::Full;
+
+        // Step 1: Append to local consensus log (in-memory, fast)
+        let log_entry = consensus_log.append(operation.clone()).await;
+        let entry_index = log_entry.log_id.index;
+        let term = consensus_log.current_term();
+        let node_id = self.node_id.unwrap_or(0);
+        let _current_commit = consensus_log.commit_index(); // For debugging (window-based replication doesn't need this)
+        log::debug!("Appended to log: index={}, term={}", entry_index, term);
+
+        // Step 2: Queue for batcher (for lagging followers tracking)
+        if let Some(ref batcher) = self.replication_batcher {
+            batcher.queue_entry(log_entry.clone()).await;
+        }
+
+        // Step 3: PARALLEL - WAL durability + Replication to followers
+        // We use tokio::join! to run both concurrently and wait for both to complete.
+        // This reduces latency since WAL fsync and network I/O happen simultaneously.
+        let wal_clone = self.wal.clone();
+        let log_entry_clone = log_entry.clone();
+        let (membership, own_address) = self.membership();
+        let peers_for_notify = membership.peers(&own_address);
+        let clients_clone = Arc::clone(&self.peer_clients);
+        let batcher_clone = self.replication_batcher.clone();
+
+        // WAL write task (uses group commit for batching)
+        let wal_future = async {
+            if let Some(ref wal) = wal_clone {
+                // Use buffered append for group commit (higher throughput)
+                wal.append_buffered(&log_entry_clone).await
+            } else {
+                Ok(())
+            }
+        };
+
+        // Replication task (returns when majority responds)
+        // Send ALL entries from beginning to ensure lagging followers can always catch up.
+        // This is critical: if we use a window, followers stuck on entry N will never receive
+        // entries N+1 to window_start, causing permanent divergence.
+        let consensus_log_clone = consensus_log.clone();
+        let replication_future = async move {
+            // Always send ALL entries from index 1 to ensure no gaps
+            let entries_to_send = consensus_log_clone.get_entries_from(1).await;
+
+            if entries_to_send.is_empty() {
+                return Ok(entry_index);
+            }
+
+            log::debug!(
+                "Replicating {} entries (window {} to {}), target_commit={}",
+                entries_to_send.len(),
+                entries_to_send.first().map(|e| e.log_id.index).unwrap_or(0),
+                entries_to_send.last().map(|e| e.log_id.index).unwrap_or(0),
+                entry_index
+            );
+
+            Self::replicate_log_entries_to_followers(
+                &membership,
+                &own_address,
+                entries_to_send,
+                entry_index, // Commit up to this entry if majority responds
+                term,
+                node_id,
+                batcher_clone,
+                &clients_clone,
+            )
+            .await
+        };
+
+        // Run WAL and replication in parallel
+        let (wal_result, replication_result) = tokio::join!(wal_future, replication_future);
+
+        // Check WAL result first (must succeed for durability)
+        if let Err(e) = wal_result {
+            log::error!("WAL write failed: {}", e);
+            return Err(hyper::Response::builder()
+                .status(503)
+                .body(Full::new(Bytes::from(format!(r#"{{"error":"WAL write failed: {}"}}"#, e))))
+                .expect("valid HTTP response"));
+        }
+        log::debug!("WAL entry durable: index={}", entry_index);
+
+        // Check replication result
+        match replication_result {
+            Ok(new_commit_index) => {
+                // Step 4: Commit the entry (majority achieved)
+                consensus_log.commit(new_commit_index);
+                log::debug!("Committed index: {}", new_commit_index);
+
+                // Step 4.5: Send commit notification to followers IN PARALLEL (fire-and-forget)
+                // Include the window of entries so followers get both data and commit in one shot
+                let commit_index_to_notify = new_commit_index;
+                let term_for_notify = term;
+                let node_id_for_notify = node_id;
+                let consensus_log_for_notify = consensus_log.clone();
+                let client = self.peer_clients.client(std::time::Duration::from_secs(1));
+                tokio::spawn(async move {
+                    // Send ALL entries from index 1 to ensure followers can always catch up
+                    // This is critical: if we use a window, followers stuck on entry N will never
+                    // receive entries N+1 to window_start, causing permanent divergence
+                    let entries_for_notify = consensus_log_for_notify.get_entries_from(1).await;
+
+                    let request = crate::cluster::consensus_log::AppendEntriesRequest {
+                        term: term_for_notify,
+                        leader_id: node_id_for_notify,
+                        prev_log_index: 0,
+                        prev_log_term: 0,
+                        entries: entries_for_notify, // Include entries for catch-up
+                        leader_commit: commit_index_to_notify,
+                    };
+                    // Send to ALL peers IN PARALLEL
+                    let futures: Vec<_> = peers_for_notify
+                        .iter()
+                        .map(|peer| {
+                            let endpoint = crate::cluster::peer_url(peer, "/_raft/append");
+                            let client = client.clone();
+                            let request = request.clone();
+                            async move {
+                                let _ = client.post(&endpoint).json(&request).send().await;
+                            }
+                        })
+                        .collect();
+                    futures::future::join_all(futures).await;
+                });
+
+                // Step 5: Apply to local state machine
+                // CRITICAL: Wait for all earlier entries to be applied first.
+                // Without this, entries can be applied out of order when commits happen
+                // out of order, causing data inconsistency (e.g., DELETE before CREATE).
+                //
+                // Example race without this fix:
+                // 1. Entry 100 (CREATE X) appended, replication starts
+                // 2. Entry 101 (DELETE X) appended, replication starts
+                // 3. Entry 101 replication completes, commits 101
+                // 4. Entry 101 applies (DELETE X - but X doesn't exist yet!)
+                // 5. Entry 100 replication completes, commits 100
+                // 6. Entry 100 applies (CREATE X - X now exists!)
+                // Result: Leader has X, but followers applied in correct order (no X)
+
+                // Wait for earlier entries to be COMMITTED first
+                // This handles the case where entry N+1 commits before entry N
+                // (due to faster replication). We must wait for N to commit before applying N+1.
+                let expected_prior = entry_index.saturating_sub(1);
+                let mut commit_waited = 0u32;
+                while consensus_log.commit_index() < expected_prior {
+                    if commit_waited > 50000 {
+                        // 50000 * 100µs = 5 seconds max wait for commit
+                        log::error!(
+                            "Waited 5s for earlier entry {} to commit (current commit={})",
+                            expected_prior,
+                            consensus_log.commit_index()
+                        );
+                        // Return error - something is seriously wrong if commit takes this long
+                        return Err(hyper::Response::builder()
+                            .status(503)
+                            .body(Full::new(Bytes::from(format!(
+                                r#"{{"error":"Commit ordering timeout: entry {} waiting for {}"}}"#,
+                                entry_index, expected_prior
+                            ))))
+                            .expect("valid HTTP response"));
+                    }
+                    tokio::time::sleep(std::time::Duration::from_micros(100)).await;
+                    commit_waited += 1;
+                }
+
+                // Now wait for earlier entry to be APPLIED
+                // Once it's committed, its handler will apply it (no timeout - it WILL apply)
+                let mut apply_waited = 0u32;
+                while consensus_log.applied_index() < expected_prior {
+                    if apply_waited > 100000 {
+                        // 100000 * 100µs = 10 seconds max wait for apply
+                        // This should never happen if commit succeeded - log but continue waiting
+                        log::warn!(
+                            "Slow apply: entry {} waiting for {} (commit={}, applied={})",
+                            entry_index,
+                            expected_prior,
+                            consensus_log.commit_index(),
+                            consensus_log.applied_index()
+                        );
+                        apply_waited = 0; // Reset counter to keep waiting
+                    }
+                    tokio::time::sleep(std::time::Duration::from_micros(100)).await;
+                    apply_waited += 1;
+                }
+
+                // Now safe to acquire lock and apply
+                let _apply_guard = consensus_log.lock_apply().await;
+
+                // Now apply our entry
+                match self.apply_crud_operation(operation).await {
+                    Ok(result) => {
+                        consensus_log.mark_applied(entry_index);
+                        let read = read(&result);
+                        Ok((entry_index, result, read))
+                    }
+                    Err(e) => {
+                        log::error!("Failed to apply operation: {}", e);
+                        Err(hyper::Response::builder()
+                            .status(500)
+                            .body(Full::new(Bytes::from(format!(
+                                r#"{{"error":"Apply failed: {}"}}"#,
+                                e
+                            ))))
+                            .expect("valid HTTP response"))
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to replicate: {}", e);
+                Err(hyper::Response::builder()
+                    .status(503) // Service Unavailable
+                    .body(Full::new(Bytes::from(format!(
+                        r#"{{"error":"Replication failed: {}"}}"#,
+                        e
+                    ))))
+                    .expect("valid HTTP response"))
+            }
+        }
+    }
+
+    // NOTE: The old fire-and-forget replication methods (replicate_to_followers,
+    // replicate_update_to_followers, replicate_delete_to_followers) have been removed.
+    // They were replaced by the Raft consensus log approach which guarantees ordering.
+    // See: replicate_log_entries_to_followers() and handle_raft_append_entries()
+
+    /// Version a clustered UPDATE or DELETE of `id` must still find when applied, from
+    /// its `If-Match` header (None without one, or for `*`), or the 412 to answer now
+    fn expected_version(
+        handler: &dyn ModelHandler,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> std::result::Result<Option<u64>, hyper::Response<http_body_util::Full<Bytes>>> {
+        let Some(header) = if_match else {
+            return Ok(None);
+        };
+        let version = handler.item_version(id);
+        if !etag::if_match(header, version) {
+            return Err(Self::precondition_failed_response(version));
+        }
+        // `*` only asks for the item to exist
+        Ok(version.filter(|_| header.trim() != "*"))
+    }
+
+    /// Result of an entry whose item is no longer at `expected_version`
+    ///
+    /// The write is skipped rather than failed: every node reaches the same verdict at
+    /// the same log position, and followers stop at entries that fail to apply.
+    fn stale_write(
+        handler: &dyn ModelHandler,
+        id: &str,
+        expected_version: Option<u64>,
+    ) -> Option<serde_json::Value> {
+        let expected = expected_version?;
+        let current = handler.item_version(id);
+        (current != Some(expected)).then(|| {
+            log::debug!("Skipping write of {}: version {:?}, expected {}", id, current, expected);
+            serde_json::json!({ "precondition_failed": true, "id": id })
+        })
+    }
+
+    /// Address this node advertises in a cluster: `RaftConfig::advertise_address`, else
+    /// the configured host and port, on the loopback interface when the server listens on
+    /// every interface
+    fn advertised_address(&self) -> Result<crate::cluster::NodeAddress> {
+        if let Some(address) = &self.config.raft.advertise_address {
+            return crate::cluster::NodeAddress::parse(address)
+                .map_err(|e| anyhow::anyhow!("Invalid advertised node address {}", e));
+        }
+        let server = &self.config.server;
+        let tls = (server.tls_cert_path.is_some() && server.tls_key_path.is_some())
+            || self.config.raft.tls.is_some();
+        let host = match server.host.as_str() {
+            "" | "0.0.0.0" | "::" | "[::]" => "127.0.0.1".to_string(),
+            host if host.contains(':') && !host.starts_with('[') => format!("[{}]", host),
+            host => host.to_string(),
+        };
+        let scheme = if tls { "https" } else { "http" };
+        Ok(crate::cluster::NodeAddress::new(scheme, host, server.port))
+    }
+
+    /// Cluster configuration in force and this node's address in it; without Raft state,
+    /// this node and its boot peers, all voters
+    fn membership(&self) -> (crate::cluster::MembershipConfig, String) {
+        if let Some(ref raft_state) = self.raft_state {
+            return (raft_state.membership(), raft_state.self_address.to_string());
+        }
+        let own = self.advertised_address().map(|a| a.to_string()).unwrap_or_default();
+        let member = crate::cluster::Member::new(self.node_id, own.clone());
+        (crate::cluster::MembershipConfig::initial(member, &self.cluster_peers), own)
+    }
+
+    /// Load the cluster certificates, have calls to peers present them and watch them
+    /// for rotation (see `cluster::tls`)
+    fn init_cluster_tls(
+        &mut self,
+        node_id: u64,
+        config: crate::config::ClusterTlsConfig,
+    ) -> Result<()> {
+        let server = &self.config.server;
+        if server.tls_cert_path.is_some() || server.tls_key_path.is_some() {
+            anyhow::bail!(
+                "Cluster TLS serves the node certificate on every route: \
+                 remove the server TLS certificate"
+            );
+        }
+        let advertised = self.config.raft.advertise_address.iter();
+        for address in self.cluster_peers.iter().chain(advertised) {
+            let parsed = crate::cluster::NodeAddress::parse(address)
+                .map_err(|e| anyhow::anyhow!("Invalid node address {}", e))?;
+            if parsed.scheme != "https" {
+                anyhow::bail!("{} must be an https:// address under cluster TLS", address);
+            }
+        }
+
+        let tls = crate::cluster::ClusterTls::load(node_id, config)
+            .context("Failed to load cluster TLS certificates")?;
+        let tls = Arc::new(tls);
+        tls.spawn_reload_task();
+        self.peer_clients = Arc::new(crate::cluster::PeerClients::new(Some(tls)));
+        Ok(())
+    }
+
+    /// Routes only nodes call, which need a node certificate under cluster TLS
+    fn is_peer_route(&self, path: &str) -> bool {
+        path.starts_with("/internal/")
+            || path == "/_raft/append"
+            || path == "/_raft/snapshot"
+            || path == "/_raft/join"
+            || path == "/_raft/read-index"
+            || path == self.config.raft.heartbeat_path()
+            || path == self.config.raft.election_path()
+    }
+
+    /// 403 for a node request claiming to come from `claimed` over a connection whose
+    /// certificate is another node's; None when they match or without cluster TLS
+    fn impersonation_response(
+        peer: Option<crate::cluster::PeerNode>,
+        claimed: u64,
+    ) -> Option<hyper::Response<http_body_util::Full<Bytes>>> {
+        let crate::cluster::PeerNode(certified) = peer?;
+        if certified == claimed {
+            return None;
+        }
+        log::warn!("Node {} sent a request on behalf of node {}", certified, claimed);
+        let body = serde_json::json!({
+            "error": "node_mismatch",
+            "message": format!("certificate is for node {}, not node {}", certified, claimed)
+        });
+        Some(
+            hyper::Response::builder()
+                .status(hyper::StatusCode::FORBIDDEN)
+                .header("Content-Type", "application/json")
+                .body(http_body_util::Full::new(Bytes::from(body.to_string())))
+                .expect("valid HTTP response"),
+        )
+    }
+
+    /// 307 sending a write for `path` to the leader, at the address it advertises; 503
+    /// while no leader is known
+    fn not_leader_response(
+        raft_state: &RaftLeadershipState,
+        path: &str,
+    ) -> hyper::Response<http_body_util::Full<Bytes>> {
+        let Some(leader) = raft_state.known_leader_address() else {
+            return Self::no_leader_response("No leader is known yet, nothing was written");
+        };
+        let body = serde_json::json!({
+            "error": "Not leader",
+            "leader_port": leader.port,
+            "leader_address": leader
+        });
+        hyper::Response::builder()
+            .status(307) // Temporary Redirect
+            .header("Location", leader.url(path))
+            .header("X-Raft-Leader", leader.port.to_string())
+            .header("X-Raft-Leader-Address", leader.to_string())
+            .header("Content-Type", "application/json")
+            .body(http_body_util::Full::new(Bytes::from(body.to_string())))
+            .expect("valid HTTP response")
+    }
+
+    /// Whether this follower forwards `req` to the leader rather than redirecting it:
+    /// forwarding is on and no other node already forwarded it
+    fn forwards_write<B>(&self, req: &hyper::Request<B>) -> bool {
+        self.config.raft.forward_writes && !self.forwarded_by_node(req)
+    }
+
+    /// Leadership state to forward `req` with, when this follower sends it to the leader
+    /// instead of routing it
+    ///
+    /// Covers the writes a follower would otherwise redirect: POST, PUT and DELETE outside
+    /// the node-to-node routes, `/_raft/migrate`, and PATCH of a model item in cluster
+    /// mode that no custom route takes.
+    async fn forwarding_to_leader<B>(
+        &self,
+        req: &hyper::Request<B>,
+    ) -> Option<&RaftLeadershipState> {
+        let raft_state = self.raft_state.as_deref().filter(|state| !state.is_leader())?;
+        if !self.forwards_write(req) {
+            return None;
+        }
+        let path = req.uri().path();
+        let internal = path.starts_with("/internal/") || path.starts_with("/_raft/");
+        let forwarded = match *req.method() {
+            hyper::Method::POST if path == "/_raft/migrate" => true,
+            hyper::Method::POST | hyper::Method::PUT | hyper::Method::DELETE => {
+                !internal && !self.is_peer_route(path)
+            }
+            hyper::Method::PATCH => {
+                let custom = self.custom_routes.iter().any(|route| {
+                    route.method == hyper::Method::PATCH && Self::path_matches(&route.path, path)
+                });
+                let clustered = self.consensus_log.is_some() && !self.cluster_peers.is_empty();
+                let models = self.models.read().await;
+                let model = models.iter().find(|model| path.starts_with(&model.base_path));
+                let item = model.is_some_and(|model| {
+                    path[model.base_path.len()..].split('/').any(|segment| !segment.is_empty())
+                });
+                clustered && !custom && item
+            }
+            _ => false,
+        };
+        forwarded.then_some(raft_state)
+    }
+
+    /// Whether `req` was forwarded by another node: it carries `X-Lithair-Forwarded-By`
+    /// over a node's channel, a node certificate under cluster TLS or else the Raft token
+    ///
+    /// Clients can send the header too; without those credentials it is ignored.
+    fn forwarded_by_node<B>(&self, req: &hyper::Request<B>) -> bool {
+        if !req.headers().contains_key(forward::FORWARDED_BY) {
+            return false;
+        }
+        if self.peer_clients.tls().is_some() {
+            return req.extensions().get::<crate::cluster::PeerNode>().is_some();
+        }
+        let token = req.headers().get("X-Raft-Token").and_then(|v| v.to_str().ok());
+        self.config.raft.validate_token(token)
+    }
+
+    /// Send a write this follower cannot apply to the leader and relay its response
+    /// (see `forward`)
+    async fn forward_to_leader(
+        &self,
+        req: hyper::Request<hyper::body::Incoming>,
+        raft_state: &RaftLeadershipState,
+    ) -> Result<hyper::Response<forward::ServedBody>> {
+        use http_body_util::{BodyExt, Full};
+
+        let raft = &self.config.raft;
+        let client_ip = req
+            .extensions()
+            .get::<std::net::SocketAddr>()
+            .map(|addr| crate::http::resolve_client_ip(&req, *addr));
+        let tls = req.extensions().get::<crate::proxy::reverse::TlsConnection>().is_some();
+        let mut headers = forward::request_headers(
+            req.headers(),
+            client_ip.as_deref(),
+            tls,
+            self.node_id.unwrap_or(0),
+        );
+        // Lets the leader tell this forward from a client naming a node
+        if let Some(token) = &raft.auth_token {
+            if let Ok(value) = hyper::header::HeaderValue::from_str(token) {
+                headers.insert("x-raft-token", value);
+            }
+        }
+        let method = req.method().clone();
+        let path = req.uri().path_and_query().map_or("/", |pq| pq.as_str()).to_string();
+        let body = req.into_body().collect().await?.to_bytes();
+
+        let client = self
+            .peer_clients
+            .client(std::time::Duration::from_millis(raft.forward_timeout_ms));
+
+        // Leader named by the node that last answered "not leader"
+        let mut named: Option<crate::cluster::NodeAddress> = None;
+        let mut attempt = 0;
+        loop {
+            // Mid-election, or before the first heartbeat, no leader is known: wait for one
+            let leader = named.take().or_else(|| raft_state.known_leader_address());
+            if let Some(leader) = leader {
+                let sent = client
+                    .request(method.clone(), leader.url(&path))
+                    .headers(headers.clone())
+                    .body(body.clone())
+                    .send()
+                    .await;
+                match sent {
+                    Ok(response) if response.status() == hyper::StatusCode::TEMPORARY_REDIRECT => {
+                        // Not leader (anymore): it did not apply the write
+                        named = response
+                            .headers()
+                            .get("X-Raft-Leader-Address")
+                            .and_then(|v| v.to_str().ok())
+                            .and_then(|v| crate::cluster::NodeAddress::parse(v).ok());
+                        if named.is_none() {
+                            return Ok(forward::relay(response));
+                        }
+                        log::debug!("{} is not leader, forwarding to {:?}", leader, named);
+                    }
+                    Ok(response) => return Ok(forward::relay(response)),
+                    Err(e) if e.is_connect() => {
+                        log::debug!("Forwarding write to leader {} failed: {}", leader, e);
+                    }
+                    Err(e) => {
+                        // The leader may have applied the write: do not send it again
+                        log::warn!("Forwarded write to leader {} failed: {}", leader, e);
+                        let (status, error) = if e.is_timeout() {
+                            (504, "leader_timeout")
+                        } else {
+                            (502, "leader_error")
+                        };
+                        let body = serde_json::json!({
+                            "error": error,
+                            "message": "The leader did not answer; the write may have been applied",
+                            "leader_address": leader
+                        });
+                        return Ok(forward::buffered(
+                            hyper::Response::builder()
+                                .status(status)
+                                .header("Content-Type", "application/json")
+                                .body(Full::new(Bytes::from(body.to_string())))
+                                .expect("valid HTTP response"),
+                        ));
+                    }
+                }
+            }
+            if attempt >= raft.forward_retries {
+                break;
+            }
+            attempt += 1;
+            tokio::time::sleep(forward::backoff(attempt)).await;
+        }
+
+        let message = "No leader accepted the write, nothing was written";
+        Ok(forward::buffered(Self::no_leader_response(message)))
+    }
+
+    /// 503 for a write this follower can neither apply nor send on: no leader is known
+    fn no_leader_response(message: &str) -> hyper::Response<http_body_util::Full<Bytes>> {
+        let body = serde_json::json!({ "error": "no_leader", "message": message });
+        hyper::Response::builder()
+            .status(hyper::StatusCode::SERVICE_UNAVAILABLE)
+            .header("Content-Type", "application/json")
+            .header("Retry-After", "1")
+            .body(http_body_util::Full::new(Bytes::from(body.to_string())))
+            .expect("valid HTTP response")
+    }
+
+    fn precondition_failed_response(
+        version: Option<u64>,
+    ) -> hyper::Response<http_body_util::Full<Bytes>> {
+        let current = version.map(etag::etag);
+        let body = Self::precondition_failed_json(version);
+        let mut response = hyper::Response::builder()
+            .status(hyper::StatusCode::PRECONDITION_FAILED)
+            .header("Content-Type", "application/json");
+        if let Some(current) = &current {
+            response = response.header("ETag", current);
+        }
+        response
+            .body(http_body_util::Full::new(Bytes::from(body.to_string())))
+            .expect("valid HTTP response")
+    }
+
+    fn precondition_failed_json(version: Option<u64>) -> serde_json::Value {
+        serde_json::json!({
+            "error": "precondition_failed",
+            "message": "If-Match does not name the current version of the item",
+            "etag": version.map(etag::etag)
+        })
+    }
+
+    /// A response of a model handler, with its body collected
+    async fn buffered_response(
+        response: hyper::Response<
+            http_body_util::combinators::BoxBody<Bytes, std::convert::Infallible>,
+        >,
+    ) -> hyper::Response<http_body_util::Full<Bytes>> {
+        use http_body_util::BodyExt;
+
+        let (parts, body) = response.into_parts();
+        let Ok(body) = body.collect().await;
+        hyper::Response::from_parts(parts, http_body_util::Full::new(body.to_bytes()))
+    }
+
+    /// POST /_batch - Write to several models, all or nothing (see `batch`)
+    async fn handle_batch_request(
+        &self,
+        mut req: hyper::Request<hyper::body::Incoming>,
+    ) -> Result<hyper::Response<http_body_util::Full<bytes::Bytes>>> {
+        use crate::cluster::CrudOperation;
+        use http_body_util::{BodyExt, Full};
+
+        let consensus_log = self.consensus_log.as_ref().filter(|_| !self.cluster_peers.is_empty());
+        let is_leader = self.raft_state.as_ref().map(|s| s.is_leader()).unwrap_or(false);
+        if consensus_log.is_some() && !is_leader {
+            if let Some(ref raft_state) = self.raft_state {
+                return Ok(Self::not_leader_response(raft_state, "/_batch"));
+            }
+        }
+
+        // Read the body in place: model handlers check permissions on the request
+        let body = req.body_mut().collect().await?.to_bytes();
+        let request = match batch::BatchRequest::parse(&body) {
+            Ok(request) => request,
+            Err(rejection) => return Ok(Self::batch_rejected_response(&rejection)),
+        };
+
+        // Checked and applied in turn with single writes, as `lock_references` orders them
+        let deletes = request
+            .operations
+            .iter()
+            .any(|op| matches!(op, batch::BatchOperation::Delete { .. }));
+        let _references: ReferencesGuard<'_> = if deletes {
+            (None, Some(self.references.write().await))
+        } else {
+            (Some(self.references.read().await), None)
+        };
+        let models = self.models.read().await;
+        let (targets, operations): (Vec<usize>, Vec<CrudOperation>) =
+            match Self::plan_batch(&req, &models, request).await {
+                Ok(planned) => planned.into_iter().unzip(),
+                Err(rejection) => return Ok(Self::batch_rejected_response(&rejection)),
+            };
+
+        let (result, entry_index) = match self.execute_batch(&models, &targets, operations).await {
+            Ok(executed) => executed,
+            Err(failed) => return Ok(failed),
+        };
+        let mut response = hyper::Response::builder()
+            .status(200)
+            .header("Content-Type", "application/json");
+        if let Some(entry_index) = entry_index {
+            response = response.header("X-Raft-Index", entry_index.to_string());
+        }
+        Ok(response
+            .body(Full::new(Bytes::from(result.to_string())))
+            .expect("valid HTTP response"))
+    }
+
+    /// Check, reserve and apply planned batch writes: as one consensus log entry in a
+    /// cluster, under the locks of their models on a single node
+    ///
+    /// Returns the batch result with the index of its log entry, or the response that
+    /// rejects it. Unique keys are reserved before anything is written and released
+    /// unless the batch applies.
+    async fn execute_batch(
+        &self,
+        models: &[ModelRegistration],
+        targets: &[usize],
+        operations: Vec<crate::cluster::CrudOperation>,
+    ) -> std::result::Result<
+        (serde_json::Value, Option<u64>),
+        hyper::Response<http_body_util::Full<Bytes>>,
+    > {
+        use crate::cluster::CrudOperation;
+        use http_body_util::Full;
+
+        let consensus_log = self.consensus_log.as_ref().filter(|_| !self.cluster_peers.is_empty());
+
+        // Validate and reserve unique keys before anything is written, as single
+        // clustered writes do; the reservations are released unless the batch applies
+        for (index, (model, operation)) in targets.iter().zip(&operations).enumerate() {
+            let Some((id, data)) = Self::batch_write(operation) else { continue };
+            let unresolved =
+                Self::unresolved_references(models, *model, data, targets, &operations).await;
+            let prepared = match unresolved {
+                Some(error) => Err((422, error)),
+                None => models[*model].handler.prepare_write_json(id, data).await,
+            };
+            if let Err((status, error)) = prepared {
+                Self::release_batch(models, &targets[..index], &operations[..index]).await;
+                let rejection = batch::BatchRejection::new(index, status, error);
+                return Err(Self::batch_rejected_response(&rejection));
+            }
+        }
+
+        let batch_id = uuid::Uuid::new_v4().to_string();
+        let (applied, entry_index) = if let Some(consensus_log) = consensus_log {
+            let entry = CrudOperation::Batch { batch_id, operations: operations.clone() };
+            match self.commit_and_apply(consensus_log, &entry, |_| ()).await {
+                Ok((entry_index, result, ())) => {
+                    let applied = match result.get("rejected") {
+                        Some(rejection) => Err(serde_json::from_value(rejection.clone())
+                            .unwrap_or_else(|_| batch::BatchRejection::invalid("batch rejected"))),
+                        None => Ok(result),
+                    };
+                    (applied, Some(entry_index))
+                }
+                Err(failed) => {
+                    Self::release_batch(models, targets, &operations).await;
+                    return Err(failed);
+                }
+            }
+        } else {
+            match Self::apply_batch(models, &batch_id, &operations).await {
+                Ok(applied) => (applied, None),
+                Err(e) => {
+                    log::error!("Batch {} failed to apply: {}", batch_id, e);
+                    Self::release_batch(models, targets, &operations).await;
+                    return Err(hyper::Response::builder()
+                        .status(500)
+                        .header("Content-Type", "application/json")
+                        .body(Full::new(Bytes::from(
+                            serde_json::json!({ "error": format!("Apply failed: {}", e) })
+                                .to_string(),
+                        )))
+                        .expect("valid HTTP response"));
+                }
+            }
+        };
+
+        match applied {
+            Ok(result) => Ok((result, entry_index)),
+            Err(rejection) => {
+                Self::release_batch(models, targets, &operations).await;
+                Err(Self::batch_rejected_response(&rejection))
+            }
+        }
+    }
+
+    /// Clustered `POST {model}/_bulk`, on the leader: the items are checked one by one as
+    /// single clustered creates are, then logged as one batch entry, so that they apply
+    /// on every node or on none
+    ///
+    /// Answers as `handle_bulk_create` does on a single node: the created items with 201,
+    /// 422 listing the invalid items, 409 with the index of a duplicate.
+    async fn bulk_create_clustered(
+        &self,
+        consensus_log: &Arc<crate::cluster::ConsensusLog>,
+        model: &ModelRegistration,
+        body: &serde_json::Value,
+    ) -> hyper::Response<http_body_util::Full<Bytes>> {
+        use crate::cluster::CrudOperation;
+        use http_body_util::Full;
+
+        let respond = |status: u16, body: serde_json::Value| {
+            hyper::Response::builder()
+                .status(status)
+                .header("Content-Type", "application/json")
+                .body(Full::new(Bytes::from(body.to_string())))
+                .expect("valid HTTP response")
+        };
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let operations = match Self::bulk_creates(model, body, &now) {
+            Ok(operations) => operations,
+            Err((status, error)) => return respond(status, error),
+        };
+        let models = self.models.read().await;
+        let Some(target) = models.iter().position(|m| m.base_path == model.base_path) else {
+            return respond(404, serde_json::json!({ "error": "Model not found" }));
+        };
+        let reserved = Self::reserve_bulk(&models, target, &operations).await;
+        drop(models);
+        if let Err((status, error)) = reserved {
+            return respond(status, error);
+        }
+        let reservations = &operations;
+        let release = move || async move {
+            for operation in reservations {
+                if let Some((id, data)) = Self::batch_write(operation) {
+                    model.handler.abort_write_json(id, data).await;
+                }
+            }
+        };
 
-                        // Step 5: Apply to local state machine
-                        // CRITICAL: Wait for all earlier entries to be applied first.
-                        // Without this, entries can be applied out of order when commits happen
-                        // out of order, causing data inconsistency (e.g., DELETE before CREATE).
-                        //
-                        // Example race without this fix:
-                        // 1. Entry 100 (CREATE X) appended, replication starts
-                        // 2. Entry 101 (DELETE X) appended, replication starts
-                        // 3. Entry 101 replication completes, commits 101
-                        // 4. Entry 101 applies (DELETE X - but X doesn't exist yet!)
-                        // 5. Entry 100 replication completes, commits 100
-                        // 6. Entry 100 applies (CREATE X - X now exists!)
-                        // Result: Leader has X, but followers applied in correct order (no X)
-
-                        // Wait for earlier entries to be COMMITTED first
-                        // This handles the case where entry N+1 commits before entry N
-                        // (due to faster replication). We must wait for N to commit before applying N+1.
-                        let expected_prior = entry_index.saturating_sub(1);
-                        let mut commit_waited = 0u32;
-                        while consensus_log.commit_index() < expected_prior {
-                            if commit_waited > 50000 {
-                                // 50000 * 100µs = 5 seconds max wait for commit
-                                log::error!(
-                                    "Waited 5s for earlier entry {} to commit (current commit={})",
-                                    expected_prior,
-                                    consensus_log.commit_index()
-                                );
-                                // Return error - something is seriously wrong if commit takes this long
-                                return Ok(hyper::Response::builder()
-                                .status(503)
-                                .body(Full::new(Bytes::from(format!(
-                                    r#"{{"error":"Commit ordering timeout: entry {} waiting for {}"}}"#,
-                                    entry_index, expected_prior
-                                ))))
-                                .expect("valid HTTP response"));
-                            }
-                            tokio::time::sleep(std::time::Duration::from_micros(100)).await;
-                            commit_waited += 1;
-                        }
+        let batch_id = uuid::Uuid::new_v4().to_string();
+        let entry = CrudOperation::Batch { batch_id, operations: operations.clone() };
+        let (entry_index, result) = match self.commit_and_apply(consensus_log, &entry, |_| ()).await
+        {
+            Ok((entry_index, result, ())) => (entry_index, result),
+            Err(failed) => {
+                release().await;
+                return failed;
+            }
+        };
+        if let Some(rejection) = result.get("rejected") {
+            release().await;
+            let rejection: batch::BatchRejection = serde_json::from_value(rejection.clone())
+                .unwrap_or_else(|_| batch::BatchRejection::invalid("bulk create rejected"));
+            let mut error = rejection.error;
+            error["index"] = serde_json::json!(rejection.index);
+            return respond(rejection.status, error);
+        }
+        let created: Vec<&serde_json::Value> = result["results"]
+            .as_array()
+            .map(|results| results.iter().map(|r| &r["item"]).collect())
+            .unwrap_or_default();
+        hyper::Response::builder()
+            .status(201)
+            .header("Content-Type", "application/json")
+            .header("X-Raft-Index", entry_index.to_string())
+            .body(Full::new(Bytes::from(serde_json::to_vec(&created).unwrap_or_default())))
+            .expect("valid HTTP response")
+    }
 
-                        // Now wait for earlier entry to be APPLIED
-                        // Once it's committed, its handler will apply it (no timeout - it WILL apply)
-                        let mut apply_waited = 0u32;
-                        while consensus_log.applied_index() < expected_prior {
-                            if apply_waited > 100000 {
-                                // 100000 * 100µs = 10 seconds max wait for apply
-                                // This should never happen if commit succeeded - log but continue waiting
-                                log::warn!(
-                                    "Slow apply: entry {} waiting for {} (commit={}, applied={})",
-                                    entry_index,
-                                    expected_prior,
-                                    consensus_log.commit_index(),
-                                    consensus_log.applied_index()
-                                );
-                                apply_waited = 0; // Reset counter to keep waiting
-                            }
-                            tokio::time::sleep(std::time::Duration::from_micros(100)).await;
-                            apply_waited += 1;
-                        }
+    /// Creates of the items of a clustered `_bulk` body, each stamped as a single
+    /// clustered create is; or the 400 rejecting a body that is not an array of objects,
+    /// or that creates an id twice
+    fn bulk_creates(
+        model: &ModelRegistration,
+        body: &serde_json::Value,
+        now: &str,
+    ) -> std::result::Result<Vec<crate::cluster::CrudOperation>, (u16, serde_json::Value)> {
+        let Some(items) = body.as_array() else {
+            return Err((400, serde_json::json!({ "error": "Invalid JSON array" })));
+        };
+        let mut ids = std::collections::HashSet::new();
+        let mut operations = Vec::with_capacity(items.len());
+        for (index, item) in items.iter().enumerate() {
+            if !item.is_object() {
+                let error =
+                    serde_json::json!({ "error": "Item is not a JSON object", "index": index });
+                return Err((400, error));
+            }
+            let mut data = item.clone();
+            batch::stamp_new_item(&mut data, now);
+            if let Some(id) = data.get("id").and_then(crate::http::expand::key_text) {
+                if !ids.insert(id.clone()) {
+                    let message = format!("{} '{}' is written twice", model.name, id);
+                    return Err((400, serde_json::json!({ "error": message, "index": index })));
+                }
+            }
+            let model_path = model.base_path.clone();
+            operations.push(crate::cluster::CrudOperation::Create { model_path, data });
+        }
+        Ok(operations)
+    }
 
-                        // Now safe to acquire lock and apply
-                        let _apply_guard = consensus_log.lock_apply().await;
-
-                        // Now apply our entry
-                        match self.apply_crud_operation(&operation).await {
-                            Ok(result) => {
-                                consensus_log.mark_applied(entry_index);
-                                // _apply_guard dropped here
-                                let response_body = serde_json::to_vec(&result).unwrap_or_default();
-                                return Ok(hyper::Response::builder()
-                                    .status(if is_create { 201 } else { 200 })
-                                    .header("Content-Type", "application/json")
-                                    .header("X-Raft-Index", entry_index.to_string())
-                                    .body(Full::new(Bytes::from(response_body)))
-                                    .expect("valid HTTP response"));
-                            }
-                            Err(e) => {
-                                log::error!("Failed to apply operation: {}", e);
-                                return Ok(hyper::Response::builder()
-                                    .status(500)
-                                    .body(Full::new(Bytes::from(format!(
-                                        r#"{{"error":"Apply failed: {}"}}"#,
-                                        e
-                                    ))))
-                                    .expect("valid HTTP response"));
-                            }
-                        }
+    /// Check the creates of a clustered `_bulk` into model `target` as `handle_bulk_create`
+    /// checks its items (validation and foreign keys, then unique keys, duplicates within
+    /// the array included), reserving their unique keys before the entry enters the log
+    ///
+    /// Every item is checked, so that the 422 lists all the invalid ones. Nothing stays
+    /// reserved when the creates are rejected.
+    async fn reserve_bulk(
+        models: &[ModelRegistration],
+        target: usize,
+        operations: &[crate::cluster::CrudOperation],
+    ) -> std::result::Result<(), (u16, serde_json::Value)> {
+        let handler = models[target].handler.as_ref();
+        let targets = vec![target; operations.len()];
+        let mut reserved = Vec::new();
+        let mut invalid = Vec::new();
+        let mut rejected = None;
+        for (index, operation) in operations.iter().enumerate() {
+            let Some((id, data)) = Self::batch_write(operation) else { continue };
+            let unresolved =
+                Self::unresolved_references(models, target, data, &targets, operations).await;
+            let prepared = match unresolved {
+                Some(error) => Err((422, error)),
+                None => handler.prepare_write_json(id, data).await,
+            };
+            match prepared {
+                Ok(()) => reserved.push((id, data)),
+                Err((422, error)) => {
+                    invalid.push(serde_json::json!({ "index": index, "fields": error["fields"] }));
+                }
+                Err((status, mut error)) => {
+                    error["index"] = serde_json::json!(index);
+                    if rejected.is_none() {
+                        rejected = Some((status, error));
                     }
-                    Err(e) => {
-                        log::error!("Failed to replicate: {}", e);
-                        return Ok(hyper::Response::builder()
-                        .status(503) // Service Unavailable
-                        .body(Full::new(Bytes::from(format!(r#"{{"error":"Replication failed: {}"}}"#, e))))
-                        .expect("valid HTTP response"));
+                }
+            }
+        }
+        let rejected = match rejected {
+            _ if !invalid.is_empty() => {
+                let error = serde_json::json!({
+                    "error": "validation_failed",
+                    "message": format!("{} item(s) failed validation", invalid.len()),
+                    "items": invalid,
+                });
+                (422, error)
+            }
+            Some(rejected) => rejected,
+            None => return Ok(()),
+        };
+        for (id, data) in reserved {
+            handler.abort_write_json(id, data).await;
+        }
+        Err(rejected)
+    }
+
+    /// Turn the operations of a batch into log operations, each with the index of its
+    /// model: resolve the models, stamp creates, merge updates into the stored items,
+    /// and check permissions and `if_match`. Updates and deletes expect the item version
+    /// they were planned against. The writes the deletes cascade to follow the batch's own.
+    async fn plan_batch(
+        req: &hyper::Request<hyper::body::Incoming>,
+        models: &[ModelRegistration],
+        request: batch::BatchRequest,
+    ) -> std::result::Result<Vec<(usize, crate::cluster::CrudOperation)>, batch::BatchRejection>
+    {
+        use crate::cluster::CrudOperation;
+        use batch::{BatchOperation, BatchRejection};
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut written = std::collections::HashSet::new();
+        let mut planned = Vec::with_capacity(request.operations.len());
+        for (index, operation) in request.operations.into_iter().enumerate() {
+            let reject = |status: u16, message: String| {
+                BatchRejection::new(index, status, serde_json::json!({ "error": message }))
+            };
+            let Some(target) = models
+                .iter()
+                .position(|m| m.name == operation.model() || m.base_path == operation.model())
+            else {
+                return Err(reject(404, format!("Unknown model '{}'", operation.model())));
+            };
+            let model = &models[target];
+            let handler = model.handler.as_ref();
+
+            // Read the version before the item: a write in between makes the batch
+            // fail its precondition rather than overwrite it
+            let (version, current) = match operation.id() {
+                Some(id) if !matches!(operation, BatchOperation::Create { .. }) => {
+                    let version = handler.item_version(id);
+                    let Some(current) = handler.get_item_json(id).await else {
+                        return Err(reject(404, format!("{} '{}' not found", model.name, id)));
+                    };
+                    if let Some(header) = operation.if_match() {
+                        if !etag::if_match(header, version) {
+                            let error = Self::precondition_failed_json(version);
+                            return Err(BatchRejection::new(index, 412, error));
+                        }
                     }
+                    (version, current)
+                }
+                _ => (None, serde_json::Value::Null),
+            };
+
+            let model_path = model.base_path.clone();
+            let (operation, item) = match operation {
+                BatchOperation::Create { mut data, .. } => {
+                    batch::stamp_new_item(&mut data, &now);
+                    (CrudOperation::Create { model_path, data: data.clone() }, data)
+                }
+                BatchOperation::Update { id, data: changes, .. } => {
+                    // Checked as a PATCH of the fields it sets
+                    let updated = handler.prepare_batch_update_json(req, &id, &changes).await;
+                    let mut data = match updated {
+                        Ok(data) => data,
+                        Err(denied) => return Err(Self::batch_denial(index, denied).await),
+                    };
+                    data["updated_at"] = serde_json::Value::String(now.clone());
+                    let operation = CrudOperation::Update {
+                        model_path,
+                        id,
+                        data: data.clone(),
+                        expected_version: version,
+                    };
+                    (operation, data)
                 }
+                BatchOperation::Delete { id, .. } => {
+                    let operation =
+                        CrudOperation::Delete { model_path, id, expected_version: version };
+                    (operation, current)
+                }
+            };
+
+            let id = item.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            if !written.insert((target, id.clone())) {
+                return Err(reject(400, format!("{} '{}' is written twice", model.name, id)));
+            }
+
+            // Field write permissions are not checked on create, as for POST
+            let denied = match operation {
+                CrudOperation::Create { .. } => {
+                    handler.authorize_batch_write(req, &item, Some(&[])).await
+                }
+                CrudOperation::Delete { .. } => {
+                    handler.authorize_batch_write(req, &item, None).await
+                }
+                _ => None,
+            };
+            if let Some(denied) = denied {
+                return Err(Self::batch_denial(index, denied).await);
             }
+            planned.push((target, operation));
         }
+        let cascade = Self::plan_cascade(models, &planned).await?;
+        planned.extend(cascade);
+        Ok(planned)
+    }
 
-        // ==================== SINGLE-NODE MODE OR READ OPERATIONS ====================
-        // No cluster or read operation - delegate directly to model handler
-        match model.handler.handle_request(req, &segments).await {
-            Ok(resp) => {
-                use http_body_util::BodyExt;
+    /// Rejection of batch operation `index` with the response `denied` of its model
+    async fn batch_denial(
+        index: usize,
+        denied: hyper::Response<
+            http_body_util::combinators::BoxBody<Bytes, std::convert::Infallible>,
+        >,
+    ) -> batch::BatchRejection {
+        use http_body_util::BodyExt;
 
-                let (parts, body) = resp.into_parts();
-                let body_bytes = body.collect().await?.to_bytes();
-                Ok(hyper::Response::from_parts(parts, Full::new(body_bytes)))
+        let status = denied.status().as_u16();
+        let body = denied.into_body().collect().await.map(|c| c.to_bytes());
+        let error = body
+            .ok()
+            .and_then(|body| serde_json::from_slice(&body).ok())
+            .unwrap_or_else(|| serde_json::json!({ "error": "Forbidden" }));
+        batch::BatchRejection::new(index, status, error)
+    }
+
+    /// Id and item of a batched create (id None) or update, the writes that reserve
+    /// unique keys
+    fn batch_write(
+        operation: &crate::cluster::CrudOperation,
+    ) -> Option<(Option<&str>, &serde_json::Value)> {
+        match operation {
+            crate::cluster::CrudOperation::Create { data, .. } => Some((None, data)),
+            crate::cluster::CrudOperation::Update { id, data, .. } => Some((Some(id), data)),
+            _ => None,
+        }
+    }
+
+    /// Release the unique keys `prepare_write_json` reserved for batched writes
+    async fn release_batch(
+        models: &[ModelRegistration],
+        targets: &[usize],
+        operations: &[crate::cluster::CrudOperation],
+    ) {
+        for (model, operation) in targets.iter().zip(operations) {
+            if let Some((id, data)) = Self::batch_write(operation) {
+                models[*model].handler.abort_write_json(id, data).await;
             }
-            Err(_) => Ok(hyper::Response::builder()
-                .status(500)
-                .body(Full::new(Bytes::from(r#"{"error":"Internal error"}"#)))
-                .expect("valid HTTP response")),
         }
     }
 
-    // NOTE: The old fire-and-forget replication methods (replicate_to_followers,
-    // replicate_update_to_followers, replicate_delete_to_followers) have been removed.
-    // They were replaced by the Raft consensus log approach which guarantees ordering.
-    // See: replicate_log_entries_to_followers() and handle_raft_append_entries()
+    /// Apply the writes of a batch under the locks of every model they touch
+    ///
+    /// Runs on the single node, and on every node for a committed `CrudOperation::Batch`.
+    /// Locks are taken in registration order, so concurrent batches cannot deadlock. When
+    /// an update or delete finds another version than it expects, or an operation does not
+    /// apply to its model, the batch is rejected before anything is written; every node
+    /// reaches the same verdict. A write failing after that rolls every model back.
+    async fn apply_batch(
+        models: &[ModelRegistration],
+        batch_id: &str,
+        operations: &[crate::cluster::CrudOperation],
+    ) -> std::result::Result<std::result::Result<serde_json::Value, batch::BatchRejection>, String>
+    {
+        use crate::cluster::CrudOperation;
+
+        let targets = operations
+            .iter()
+            .map(|operation| {
+                let model_path = match operation {
+                    CrudOperation::Create { model_path, .. }
+                    | CrudOperation::Update { model_path, .. }
+                    | CrudOperation::Delete { model_path, .. } => model_path,
+                    _ => return Err("only creates, updates and deletes can be batched".to_string()),
+                };
+                models
+                    .iter()
+                    .position(|m| m.base_path == *model_path)
+                    .ok_or_else(|| format!("Model not found for path: {}", model_path))
+            })
+            .collect::<std::result::Result<Vec<usize>, String>>()?;
+
+        // Models referencing what the batch deletes are locked too, so that the cascade
+        // planned for the deletes can be checked against what they hold now
+        let references = Self::enforced_references(models);
+        let mut locked = std::collections::BTreeSet::from_iter(targets.iter().copied());
+        for (operation, model) in operations.iter().zip(&targets) {
+            if matches!(operation, CrudOperation::Delete { .. }) {
+                let children = references.iter().filter(|(_, parent, _)| parent == model);
+                locked.extend(children.map(|(child, ..)| *child));
+            }
+        }
+        let mut locks = std::collections::BTreeMap::new();
+        for model in locked {
+            let Some(lock) = models[model].handler.begin_batch(batch_id).await else {
+                let Some(index) = targets.iter().position(|&t| t == model) else { continue };
+                let message = format!("Model '{}' does not support batches", models[model].name);
+                let error = serde_json::json!({ "error": message });
+                return Ok(Err(batch::BatchRejection::new(index, 400, error)));
+            };
+            locks.insert(model, lock);
+        }
+
+        for (index, (operation, model)) in operations.iter().zip(&targets).enumerate() {
+            let (CrudOperation::Update { id, expected_version: Some(expected), .. }
+            | CrudOperation::Delete { id, expected_version: Some(expected), .. }) = operation
+            else {
+                continue;
+            };
+            let current = locks[model].item_version(id);
+            if current != Some(*expected) {
+                log::debug!("Rejecting batch {}: {} is at version {:?}", batch_id, id, current);
+                let error = Self::precondition_failed_json(current);
+                return Ok(Err(batch::BatchRejection::new(index, 412, error)));
+            }
+        }
+        for (index, (operation, model)) in operations.iter().zip(&targets).enumerate() {
+            if let Err(e) = locks[model].check(operation) {
+                log::debug!("Rejecting batch {}: {}", batch_id, e);
+                let error = serde_json::json!({ "error": e });
+                return Ok(Err(batch::BatchRejection::new(index, 400, error)));
+            }
+        }
+        if let Err(rejection) =
+            Self::check_cascade(models, &references, &targets, operations, &locks)
+        {
+            log::debug!("Rejecting batch {}: {}", batch_id, rejection.error);
+            return Ok(Err(rejection));
+        }
+
+        let results = match Self::apply_batch_writes(models, &targets, operations, &mut locks) {
+            Ok(results) => results,
+            Err(e) => {
+                for lock in locks.into_values() {
+                    lock.rollback();
+                }
+                return Err(e);
+            }
+        };
+        let mut finished = Ok(());
+        for lock in locks.into_values() {
+            finished = finished.and(lock.finish());
+        }
+        finished?;
+        Ok(Ok(serde_json::json!({ "batch_id": batch_id, "results": results })))
+    }
+
+    /// Check, under the batch locks, that no item still references an item the batch
+    /// deletes unless the batch writes it too
+    ///
+    /// The cascade of the deletes was planned from unlocked reads: an item referencing a
+    /// deleted one and written since then rejects the batch with 409, on every node alike.
+    fn check_cascade(
+        models: &[ModelRegistration],
+        references: &[(usize, usize, crate::schema::RelationForeignKeySpec)],
+        targets: &[usize],
+        operations: &[crate::cluster::CrudOperation],
+        locks: &std::collections::BTreeMap<usize, Box<dyn ModelBatch + '_>>,
+    ) -> std::result::Result<(), batch::BatchRejection> {
+        use crate::cluster::CrudOperation;
+        use crate::http::expand::key_text;
+
+        let written: std::collections::HashSet<(usize, &str)> = targets
+            .iter()
+            .zip(operations)
+            .filter_map(|(model, operation)| match operation {
+                CrudOperation::Update { id, .. } | CrudOperation::Delete { id, .. } => {
+                    Some((*model, id.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        for (index, (operation, model)) in operations.iter().zip(targets).enumerate() {
+            let CrudOperation::Delete { id, .. } = operation else { continue };
+            let Some(item) = locks[model].item_json(id) else { continue };
+            for (child, _, fk) in references.iter().filter(|(_, parent, _)| parent == model) {
+                let Some(key) = item.get(&fk.referenced_field).and_then(key_text) else {
+                    continue;
+                };
+                let Some(lock) = locks.get(child) else { continue };
+                for child_item in lock.items_json(&fk.field_name, std::slice::from_ref(&key)) {
+                    let Some(child_id) = child_item.get("id").and_then(key_text) else {
+                        continue;
+                    };
+                    if written.contains(&(*child, child_id.as_str())) {
+                        continue;
+                    }
+                    let message = format!(
+                        "{} '{}' references {} '{}' and was written after the delete was planned",
+                        models[*child].name, child_id, models[*model].name, key
+                    );
+                    let error = serde_json::json!({ "error": "conflict", "message": message });
+                    return Err(batch::BatchRejection::new(index, 409, error));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply checked batch writes in memory, then log them in every model; returns the
+    /// result of each write
+    fn apply_batch_writes(
+        models: &[ModelRegistration],
+        targets: &[usize],
+        operations: &[crate::cluster::CrudOperation],
+        locks: &mut std::collections::BTreeMap<usize, Box<dyn ModelBatch + '_>>,
+    ) -> std::result::Result<Vec<serde_json::Value>, String> {
+        use crate::cluster::CrudOperation;
+
+        let mut results = Vec::with_capacity(operations.len());
+        for (operation, model) in operations.iter().zip(targets) {
+            let lock = locks.get_mut(model).expect("every batched model is locked");
+            let written = lock.apply(operation)?;
+            let name = &models[*model].name;
+            results.push(match operation {
+                CrudOperation::Delete { id, .. } => {
+                    serde_json::json!({ "op": "delete", "model": name, "id": id })
+                }
+                _ => {
+                    let (op, id) = match operation {
+                        CrudOperation::Update { id, .. } => ("update", id.clone()),
+                        _ => ("create", written["id"].as_str().unwrap_or_default().to_string()),
+                    };
+                    let version = lock.item_version(&id);
+                    serde_json::json!({
+                        "op": op,
+                        "model": name,
+                        "id": id,
+                        "etag": version.map(etag::etag),
+                        "item": written
+                    })
+                }
+            });
+        }
+        for lock in locks.values_mut() {
+            lock.prepare()?;
+        }
+        Ok(results)
+    }
+
+    fn batch_rejected_response(
+        rejection: &batch::BatchRejection,
+    ) -> hyper::Response<http_body_util::Full<Bytes>> {
+        hyper::Response::builder()
+            .status(rejection.status)
+            .header("Content-Type", "application/json")
+            .body(http_body_util::Full::new(Bytes::from(rejection.to_json().to_string())))
+            .expect("valid HTTP response")
+    }
 
     /// Apply a CRUD operation from the consensus log to the appropriate model
     /// This is called when a log entry is committed and needs to be applied to the state machine
@@ -4030,24 +6162,71 @@ impl LithairServer {
                 model.handler.apply_replicated_item_json(data.clone()).await?;
                 Ok(data.clone())
             }
-            CrudOperation::Update { model_path, id, data } => {
+            CrudOperation::Update { model_path, id, data, expected_version } => {
                 let model = models
                     .iter()
                     .find(|m| model_path.starts_with(&m.base_path))
                     .ok_or_else(|| format!("Model not found for path: {}", model_path))?;
+                if let Some(skipped) =
+                    Self::stale_write(model.handler.as_ref(), id, *expected_version)
+                {
+                    return Ok(skipped);
+                }
 
                 model.handler.apply_replicated_update_json(id, data.clone()).await?;
                 Ok(data.clone())
             }
-            CrudOperation::Delete { model_path, id } => {
+            CrudOperation::Delete { model_path, id, expected_version } => {
                 let model = models
                     .iter()
                     .find(|m| model_path.starts_with(&m.base_path))
                     .ok_or_else(|| format!("Model not found for path: {}", model_path))?;
+                if let Some(skipped) =
+                    Self::stale_write(model.handler.as_ref(), id, *expected_version)
+                {
+                    return Ok(skipped);
+                }
 
                 model.handler.apply_replicated_delete_json(id).await?;
                 Ok(serde_json::json!({"deleted": id}))
             }
+            CrudOperation::Patch { model_path, id, diff, expected_version } => {
+                let model = models
+                    .iter()
+                    .find(|m| model_path.starts_with(&m.base_path))
+                    .ok_or_else(|| format!("Model not found for path: {}", model_path))?;
+                if let Some(skipped) =
+                    Self::stale_write(model.handler.as_ref(), id, *expected_version)
+                {
+                    return Ok(skipped);
+                }
+
+                Ok(model
+                    .handler
+                    .apply_replicated_patch_json(id, diff)
+                    .await?
+                    .unwrap_or_else(|| serde_json::json!({ "not_found": true, "id": id })))
+            }
+            CrudOperation::Restore { model_path, id } => {
+                let model = models
+                    .iter()
+                    .find(|m| model_path.starts_with(&m.base_path))
+                    .ok_or_else(|| format!("Model not found for path: {}", model_path))?;
+
+                // Skipped like a stale write when the item was restored or purged since
+                Ok(model
+                    .handler
+                    .apply_replicated_restore_json(id)
+                    .await?
+                    .unwrap_or_else(|| serde_json::json!({ "not_found": true, "id": id })))
+            }
+            CrudOperation::Batch { batch_id, operations } => {
+                // A rejected batch is skipped, not failed, like a stale single write
+                Ok(match Self::apply_batch(&models, batch_id, operations).await? {
+                    Ok(results) => results,
+                    Err(rejection) => serde_json::json!({ "rejected": rejection }),
+                })
+            }
             // === Migration Operations (Phase 2: Full implementation) ===
             CrudOperation::MigrationBegin { from_version, to_version, migration_id } => {
                 log::info!(
@@ -4220,6 +6399,26 @@ impl LithairServer {
                     }))
                 }
             }
+            CrudOperation::MembershipChange { config } => {
+                log::info!(
+                    "MEMBERSHIP_CHANGE: {} voters, {} learners{}",
+                    config.voters.len(),
+                    config.learners.len(),
+                    if config.is_joint() { " (joint)" } else { "" }
+                );
+                if let Some(ref raft_state) = self.raft_state {
+                    let before = raft_state.peers();
+                    raft_state.set_membership(config.clone());
+                    let after = raft_state.peers();
+                    if let Some(ref batcher) = self.replication_batcher {
+                        batcher.initialize(&after).await;
+                        for gone in before.iter().filter(|peer| !after.contains(peer)) {
+                            batcher.remove_follower(gone).await;
+                        }
+                    }
+                }
+                Ok(serde_json::json!({ "membership": config }))
+            }
         }
     }
 
@@ -4322,10 +6521,11 @@ impl LithairServer {
         // Collect all model data
         let models_read = models.read().await;
         for model in models_read.iter() {
-            let data_json = model.handler.get_all_data_json().await;
-            // get_all_data_json returns a JSON Value (array), convert to vec
+            let (data_json, versions) = model.handler.snapshot_data_json().await;
+            // snapshot_data_json returns a JSON Value (array), convert to vec
             if let serde_json::Value::Array(items) = data_json {
                 snapshot_data.add_model(&model.base_path, &items);
+                snapshot_data.add_model_versions(&model.base_path, &versions);
             }
         }
         drop(models_read);
@@ -4344,6 +6544,7 @@ impl LithairServer {
     async fn send_snapshot_to_follower(
         peer: &str,
         snapshot_manager: &Arc<tokio::sync::RwLock<crate::cluster::snapshot::SnapshotManager>>,
+        clients: &crate::cluster::PeerClients,
     ) -> Result<(), String> {
         let mgr = snapshot_manager.read().await;
 
@@ -4358,12 +6559,10 @@ impl LithairServer {
 
         drop(mgr);
 
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30)) // Longer timeout for large snapshots
-            .build()
-            .map_err(|e| e.to_string())?;
+        // Longer timeout for large snapshots
+        let client = clients.client(std::time::Duration::from_secs(30));
 
-        let url = format!("http://{}/_raft/snapshot", peer);
+        let url = crate::cluster::peer_url(peer, "/_raft/snapshot");
 
         let response = client
             .post(&url)
@@ -4397,6 +6596,7 @@ impl LithairServer {
         peer: &str,
         snapshot_manager: &Arc<tokio::sync::RwLock<crate::cluster::snapshot::SnapshotManager>>,
         timeout_secs: u64,
+        clients: &crate::cluster::PeerClients,
     ) -> Result<(), String> {
         let mgr = snapshot_manager.read().await;
 
@@ -4411,12 +6611,9 @@ impl LithairServer {
 
         drop(mgr);
 
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(timeout_secs))
-            .build()
-            .map_err(|e| e.to_string())?;
+        let client = clients.client(std::time::Duration::from_secs(timeout_secs));
 
-        let url = format!("http://{}/_raft/snapshot", peer);
+        let url = crate::cluster::peer_url(peer, "/_raft/snapshot");
 
         let response = client
             .post(&url)
@@ -4449,24 +6646,30 @@ impl LithairServer {
     /// This function sends requests to ALL followers IN PARALLEL and returns as soon as
     /// majority is reached. Slow nodes don't block the commit - they'll catch up later.
     ///
+    /// The majority is one of the voters of `membership` (of the old and the new voters
+    /// during a joint change), `own_address` counting for the leader; learners receive
+    /// the entries without counting.
+    ///
     /// Returns Ok(commit_index) when majority acknowledges, Err otherwise
+    #[allow(clippy::too_many_arguments)]
     async fn replicate_log_entries_to_followers(
-        peers: &[String],
+        membership: &crate::cluster::MembershipConfig,
+        own_address: &str,
         entries: Vec<crate::cluster::LogEntry>,
         leader_commit: u64,
         term: u64,
         leader_id: u64,
         batcher: Option<Arc<crate::cluster::ReplicationBatcher>>,
+        clients: &crate::cluster::PeerClients,
     ) -> Result<u64, String> {
+        let peers = membership.peers(own_address);
         if peers.is_empty() {
             // Single node cluster - commit immediately
             return Ok(leader_commit);
         }
 
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(10)) // Long timeout to ensure all nodes receive
-            .build()
-            .map_err(|e| e.to_string())?;
+        // Long timeout to ensure all nodes receive
+        let client = clients.client(std::time::Duration::from_secs(10));
 
         let request = crate::cluster::consensus_log::AppendEntriesRequest {
             term,
@@ -4479,11 +6682,10 @@ impl LithairServer {
 
         // Leader counts as 1 success
         let total_nodes = peers.len() + 1;
-        let majority = total_nodes / 2 + 1;
-        let needed_from_followers = majority.saturating_sub(1); // Already have leader's vote
+        let mut acked = vec![own_address.to_string()];
 
         // Early return if leader alone is majority (single node with empty peers already handled above)
-        if needed_from_followers == 0 {
+        if membership.has_quorum(&acked) {
             let new_commit = entries.last().map(|e| e.log_id.index).unwrap_or(leader_commit);
             return Ok(new_commit);
         }
@@ -4515,7 +6717,7 @@ impl LithairServer {
         // Spawn parallel requests to active (non-desynced) followers only
         let mut handles = Vec::with_capacity(active_peers.len());
         for peer in active_peers {
-            let endpoint = format!("http://{}/_raft/append", peer);
+            let endpoint = crate::cluster::peer_url(&peer, "/_raft/append");
             let client = client.clone();
             let request = request.clone();
             let peer_name = peer.clone();
@@ -4602,9 +6804,10 @@ impl LithairServer {
                 if success {
                     success_count += 1;
                     log::debug!("{}/{} followers acknowledged", success_count, peers.len());
+                    acked.push(peer.clone());
 
                     // Track when majority is reached, but DON'T return early
-                    if success_count >= needed_from_followers && !majority_reached {
+                    if !majority_reached && membership.has_quorum(&acked) {
                         majority_reached = true;
                         commit_index =
                             entries.last().map(|e| e.log_id.index).unwrap_or(leader_commit);
@@ -4636,10 +6839,10 @@ impl LithairServer {
             Ok(commit_index)
         } else {
             Err(format!(
-                "Failed to reach majority: {} of {} nodes responded successfully (need {})",
+                "Failed to reach majority: {} of {} nodes responded successfully{}",
                 success_count + 1, // +1 for leader
                 total_nodes,
-                majority
+                if membership.is_joint() { " (need both the old and new voters)" } else { "" }
             ))
         }
     }
@@ -4665,6 +6868,7 @@ impl Default for LithairServer {
             route_policies: std::collections::HashMap::new(),
             firewall_config: None,
             anti_ddos_config: None,
+            anti_ddos: None,
             access_log: false,
             access_log_capacity: crate::http::DEFAULT_ACCESS_LOG_CAPACITY,
             legacy_endpoints: false,
@@ -4679,11 +6883,13 @@ impl Default for LithairServer {
             snapshot_manager: None,
             migration_manager: None,
             resync_stats: Arc::new(crate::cluster::ResyncStats::new()),
+            references: tokio::sync::RwLock::new(()),
             schema_sync_state: Arc::new(tokio::sync::RwLock::new(
                 crate::schema::SchemaSyncState::default(),
             )),
             openapi_enabled: false,
             openapi_spec_cache: std::sync::OnceLock::new(),
+            peer_clients: Arc::new(crate::cluster::PeerClients::default()),
             sse_broadcaster: None,
         }
     }
@@ -4697,4 +6903,497 @@ mod tests {
     fn test_server_creation() {
         let _server = LithairServer::default();
     }
+
+    #[test]
+    fn test_forwarded_by_needs_node_credentials() {
+        let mut server = LithairServer::default();
+        server.config.raft.forward_writes = true;
+        server.config.raft.auth_required = true;
+        server.config.raft.auth_token = Some("secret".to_string());
+        let write = |headers: &[(&str, &str)]| {
+            let mut req = hyper::Request::builder().method("POST").uri("/api/notes");
+            for (name, value) in headers {
+                req = req.header(*name, *value);
+            }
+            req.body(()).unwrap()
+        };
+
+        assert!(server.forwards_write(&write(&[])));
+        // A client cannot stop the forward by naming a node
+        assert!(server.forwards_write(&write(&[(forward::FORWARDED_BY, "2")])));
+        let wrong_token = [(forward::FORWARDED_BY, "2"), ("X-Raft-Token", "guess")];
+        assert!(server.forwards_write(&write(&wrong_token)));
+        let from_node = [(forward::FORWARDED_BY, "2"), ("X-Raft-Token", "secret")];
+        assert!(!server.forwards_write(&write(&from_node)));
+    }
+
+    #[test]
+    fn test_build_reports_user_store_errors() {
+        let dir = tempfile::TempDir::new().unwrap();
+        // A file where the user store directory should be
+        let path = dir.path().join("users");
+        std::fs::write(&path, b"").unwrap();
+        let rbac = crate::rbac::ServerRbacConfig::new()
+            .with_session_store(dir.path().join("sessions").to_string_lossy())
+            .with_user_store(path.to_string_lossy());
+
+        let built = LithairServer::with_config(LithairConfig::default())
+            .with_rbac_config(rbac)
+            .build();
+        let error = built.err().expect("build must fail");
+        assert!(error.to_string().contains("Cannot open user store"), "{}", error);
+    }
+
+    #[test]
+    fn test_route_labels_stay_bounded() {
+        let label = |rest| LithairServer::model_route_label("/api/products", rest);
+        assert_eq!(label(""), "/api/products");
+        assert_eq!(label("/42"), "/api/products/:id");
+        assert_eq!(label("/_aggregate"), "/api/products/_aggregate");
+        assert_eq!(label("/count"), "/api/products/count");
+        assert_eq!(label("/42/_restore"), "/api/products/:id/_restore");
+        // Segments the client made up share one label
+        for rest in ["/_made-up-1", "/_made-up-2/x"] {
+            assert_eq!(label(rest), "/api/products/other");
+        }
+        for rest in ["/42/anything", "/43/else", "/42/_restore/more"] {
+            assert_eq!(label(rest), "/api/products/:id/other");
+        }
+
+        let server = LithairServer::default();
+        assert_eq!(server.route_label("/_admin/schema/approve/7"), "/_admin/schema");
+        assert_eq!(server.route_label("/_raft/append"), "/_raft/append");
+        assert_eq!(server.route_label("/raft/heartbeat"), "/raft/heartbeat");
+        assert_eq!(server.route_label("/health"), "/health");
+        for path in ["/_raft/x1", "/_anything/y2", "/_batches", "/raft/z3", "/nope"] {
+            assert_eq!(server.route_label(path), "other");
+        }
+    }
+
+    use crate::cluster::CrudOperation;
+    use crate::schema::{CascadeStrategy, RelationForeignKeySpec};
+    use serde::{Deserialize, Serialize};
+
+    /// Declares a test model stored under `$path` whose `author_id` references an
+    /// `Author` with `$cascade` (none for `Author` itself), with the `HttpExposable`
+    /// methods `$extra` on top
+    macro_rules! test_model {
+        ($name:ident, $path:literal, $cascade:expr, { $($field:ident: $ty:ty),* } $($extra:tt)*) => {
+            #[derive(Clone, Debug, Serialize, Deserialize)]
+            struct $name {
+                id: String,
+                $($field: $ty),*
+            }
+
+            impl crate::http::HttpExposable for $name {
+                fn http_base_path() -> &'static str {
+                    $path
+                }
+                fn primary_key_field() -> &'static str {
+                    "id"
+                }
+                fn get_primary_key(&self) -> String {
+                    self.id.clone()
+                }
+                fn validate(&self) -> std::result::Result<(), String> {
+                    Ok(())
+                }
+                fn foreign_keys() -> Vec<RelationForeignKeySpec> {
+                    let cascade: Option<CascadeStrategy> = $cascade;
+                    cascade.into_iter().map(author_key).collect()
+                }
+                $($extra)*
+            }
+
+            impl crate::consensus::ReplicatedModel for $name {
+                fn needs_replication() -> bool {
+                    false
+                }
+                fn replicated_fields() -> Vec<&'static str> {
+                    Vec::new()
+                }
+            }
+
+            impl crate::lifecycle::LifecycleAware for $name {
+                fn lifecycle_policy_for_field(
+                    &self,
+                    _field_name: &str,
+                ) -> Option<crate::lifecycle::FieldPolicy> {
+                    None
+                }
+                fn all_field_names(&self) -> Vec<&'static str> {
+                    vec!["id", $(stringify!($field)),*]
+                }
+                fn model_name(&self) -> &'static str {
+                    stringify!($name)
+                }
+            }
+        };
+    }
+
+    test_model!(Author, "authors", None, { name: String });
+    test_model!(Book, "books", Some(CascadeStrategy::Delete), { author_id: String });
+    test_model!(Quote, "quotes", Some(CascadeStrategy::SetNull), { author_id: Option<String> });
+    test_model!(Award, "awards", Some(CascadeStrategy::Restrict), { author_id: String });
+    test_model!(Member, "members", None, { email: String }
+        fn validate_fields(&self) -> std::result::Result<(), crate::http::ValidationErrors> {
+            let mut errors = crate::http::ValidationErrors::new();
+            if !self.email.contains('@') {
+                errors.add("email", "email", "must be an e-mail address");
+            }
+            errors.into_result()
+        }
+        fn unique_constraints() -> Vec<Vec<String>> {
+            vec![vec!["email".to_string()]]
+        }
+    );
+
+    fn author_key(cascade: CascadeStrategy) -> RelationForeignKeySpec {
+        RelationForeignKeySpec {
+            field_name: "author_id".to_string(),
+            referenced_model: "Author".to_string(),
+            referenced_field: "id".to_string(),
+            relation_type: crate::schema::RelationType::ManyToOne,
+            cascade,
+            nullable: true,
+            indexed: false,
+        }
+    }
+
+    async fn registration<T>(dir: &std::path::Path, name: &str) -> ModelRegistration
+    where
+        T: crate::http::HttpExposable
+            + crate::lifecycle::LifecycleAware
+            + crate::consensus::ReplicatedModel
+            + Serialize
+            + serde::de::DeserializeOwned
+            + 'static,
+    {
+        let data_path = dir.join(name).to_string_lossy().to_string();
+        let handler = DeclarativeModelHandler::<T>::new(data_path.clone()).await.unwrap();
+        ModelRegistration {
+            name: name.to_string(),
+            base_path: T::http_base_path().to_string(),
+            data_path,
+            handler: Arc::new(handler),
+            schema_extractor: None,
+        }
+    }
+
+    /// Authors, books (cascade delete), quotes (cascade null) and awards (restrict)
+    async fn library(dir: &std::path::Path) -> Vec<ModelRegistration> {
+        vec![
+            registration::<Author>(dir, "Author").await,
+            registration::<Book>(dir, "Book").await,
+            registration::<Quote>(dir, "Quote").await,
+            registration::<Award>(dir, "Award").await,
+        ]
+    }
+
+    fn create(model_path: &str, data: serde_json::Value) -> CrudOperation {
+        CrudOperation::Create { model_path: model_path.to_string(), data }
+    }
+
+    fn delete(model_path: &str, id: &str) -> CrudOperation {
+        let (model_path, id) = (model_path.to_string(), id.to_string());
+        CrudOperation::Delete { model_path, id, expected_version: None }
+    }
+
+    async fn write(models: &[ModelRegistration], operations: Vec<CrudOperation>) {
+        let applied = LithairServer::apply_batch(models, "seed", &operations).await;
+        applied.unwrap().unwrap();
+    }
+
+    /// Foreign key errors of writing book `data` in a batch with `operations`
+    async fn book_references(
+        models: &[ModelRegistration],
+        data: serde_json::Value,
+        operations: Vec<(usize, CrudOperation)>,
+    ) -> Option<serde_json::Value> {
+        let (targets, operations): (Vec<usize>, Vec<CrudOperation>) =
+            operations.into_iter().unzip();
+        LithairServer::unresolved_references(models, 1, &data, &targets, &operations).await
+    }
+
+    #[tokio::test]
+    async fn test_delete_cascades_and_nulls_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = library(dir.path()).await;
+        write(
+            &models,
+            vec![
+                create("authors", serde_json::json!({"id": "a1", "name": "Ann"})),
+                create("authors", serde_json::json!({"id": "a2", "name": "Bob"})),
+                create("books", serde_json::json!({"id": "b1", "author_id": "a1"})),
+                create("books", serde_json::json!({"id": "b2", "author_id": "a2"})),
+                create("quotes", serde_json::json!({"id": "q1", "author_id": "a1"})),
+            ],
+        )
+        .await;
+
+        let planned = vec![(0, delete("authors", "a1"))];
+        let cascade = LithairServer::plan_cascade(&models, &planned).await.unwrap();
+        assert_eq!(cascade.len(), 2);
+        assert!(matches!(&cascade[0], (1, CrudOperation::Delete { id, .. }) if id == "b1"));
+        assert!(matches!(
+            &cascade[1],
+            (2, CrudOperation::Update { id, data, .. }) if id == "q1" && data["author_id"].is_null()
+        ));
+
+        let operations: Vec<CrudOperation> =
+            planned.into_iter().chain(cascade).map(|(_, operation)| operation).collect();
+        let applied = LithairServer::apply_batch(&models, "cascade", &operations).await;
+        applied.unwrap().unwrap();
+        assert!(models[0].handler.get_item_json("a1").await.is_none());
+        assert!(models[1].handler.get_item_json("b1").await.is_none());
+        assert!(models[1].handler.get_item_json("b2").await.is_some());
+        let quote = models[2].handler.get_item_json("q1").await.unwrap();
+        assert!(quote["author_id"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_restrict_rejects_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = library(dir.path()).await;
+        write(
+            &models,
+            vec![
+                create("authors", serde_json::json!({"id": "a1", "name": "Ann"})),
+                create("awards", serde_json::json!({"id": "w1", "author_id": "a1"})),
+            ],
+        )
+        .await;
+
+        let planned = vec![(0, delete("authors", "a1"))];
+        let rejection = LithairServer::plan_cascade(&models, &planned).await.unwrap_err();
+        assert_eq!((rejection.index, rejection.status), (Some(0), 409));
+        assert_eq!(rejection.error["error"], "restricted");
+    }
+
+    #[tokio::test]
+    async fn test_reference_written_after_planning_rejects_cascade() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = library(dir.path()).await;
+        write(
+            &models,
+            vec![
+                create("authors", serde_json::json!({"id": "a1", "name": "Ann"})),
+                create("books", serde_json::json!({"id": "b1", "author_id": "a1"})),
+            ],
+        )
+        .await;
+
+        let planned = vec![(0, delete("authors", "a1"))];
+        let cascade = LithairServer::plan_cascade(&models, &planned).await.unwrap();
+        let operations: Vec<CrudOperation> =
+            planned.into_iter().chain(cascade).map(|(_, operation)| operation).collect();
+        // An award, which restricts the delete, arrives before the batch applies
+        write(
+            &models,
+            vec![create("awards", serde_json::json!({"id": "w1", "author_id": "a1"}))],
+        )
+        .await;
+
+        let applied = LithairServer::apply_batch(&models, "cascade", &operations).await;
+        let rejection = applied.unwrap().unwrap_err();
+        assert_eq!((rejection.index, rejection.status), (Some(0), 409));
+        assert!(models[0].handler.get_item_json("a1").await.is_some());
+        assert!(models[1].handler.get_item_json("b1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_dangling_reference_rejects_create_and_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = library(dir.path()).await;
+        write(
+            &models,
+            vec![
+                create("authors", serde_json::json!({"id": "a1", "name": "Ann"})),
+                create("books", serde_json::json!({"id": "b1", "author_id": "a1"})),
+            ],
+        )
+        .await;
+        let unresolved = |data, operations| book_references(&models, data, operations);
+
+        // Create
+        let error = unresolved(serde_json::json!({"id": "b2", "author_id": "a9"}), vec![]);
+        let error = error.await.unwrap();
+        assert_eq!(error["fields"][0]["field"], "author_id");
+        assert_eq!(error["fields"][0]["rule"], "foreign_key");
+        let resolved = unresolved(serde_json::json!({"id": "b2", "author_id": "a1"}), vec![]);
+        assert!(resolved.await.is_none());
+        // An author the same batch creates resolves, one it deletes does not
+        let created = create("authors", serde_json::json!({"id": "a2", "name": "Bob"}));
+        let data = serde_json::json!({"id": "b2", "author_id": "a2"});
+        assert!(unresolved(data, vec![(0, created)]).await.is_none());
+
+        // Update
+        let data = serde_json::json!({"id": "b1", "author_id": "a9"});
+        assert!(unresolved(data, vec![]).await.is_some());
+        let data = serde_json::json!({"id": "b1", "author_id": "a1"});
+        assert!(unresolved(data, vec![(0, delete("authors", "a1"))]).await.is_some());
+
+        // Single-node writes resolve through the registered models the same way
+        let books = crate::http::DeclarativeHttpHandler::<Book>::new(
+            dir.path().join("standalone").to_str().unwrap(),
+        )
+        .unwrap();
+        let models = Arc::new(tokio::sync::RwLock::new(models));
+        books.set_reference_resolver(Arc::new(ModelReferences { models: Arc::downgrade(&models) }));
+        let dangling = Book { id: "b3".to_string(), author_id: "a9".to_string() };
+        let errors = books.check_references(&dangling).await.unwrap_err();
+        assert_eq!(errors.fields[0].field, "author_id");
+        let book = Book { id: "b3".to_string(), author_id: "a1".to_string() };
+        assert!(books.check_references(&book).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_clustered_bulk_create_checks_every_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = vec![registration::<Member>(dir.path(), "Member").await];
+        let now = chrono::Utc::now().to_rfc3339();
+        let bulk = |body| LithairServer::bulk_creates(&models[0], &body, &now);
+
+        // Only arrays of objects, each id created once
+        assert_eq!(bulk(serde_json::json!({"email": "a@x.io"})).unwrap_err().0, 400);
+        let (status, error) = bulk(serde_json::json!([{"email": "a@x.io"}, 7])).unwrap_err();
+        assert_eq!((status, error["index"].clone()), (400, serde_json::json!(1)));
+        let twice =
+            serde_json::json!([{"id": "m1", "email": "a@x.io"}, {"id": "m1", "email": "b@x.io"}]);
+        assert_eq!(bulk(twice).unwrap_err().0, 400);
+
+        // Each item is stamped as a single clustered create
+        let operations = bulk(serde_json::json!([{"email": "a@x.io"}, {"email": "b@x.io"}]));
+        let operations = operations.unwrap();
+        for operation in &operations {
+            let CrudOperation::Create { data, .. } = operation else { panic!("not a create") };
+            assert!(data["id"].is_string() && data["created_at"].is_string());
+        }
+        assert!(LithairServer::reserve_bulk(&models, 0, &operations).await.is_ok());
+
+        // Every invalid item is listed
+        let invalid = serde_json::json!([{"email": "no"}, {"email": "c@x.io"}, {"email": "nope"}]);
+        let operations = bulk(invalid).unwrap();
+        let (status, error) =
+            LithairServer::reserve_bulk(&models, 0, &operations).await.unwrap_err();
+        assert_eq!((status, error["error"].as_str()), (422, Some("validation_failed")));
+        let indexes: Vec<&serde_json::Value> =
+            error["items"].as_array().unwrap().iter().map(|item| &item["index"]).collect();
+        assert_eq!(indexes, [0, 2]);
+        assert_eq!(error["items"][0]["fields"][0]["field"], "email");
+    }
+
+    #[tokio::test]
+    async fn test_clustered_bulk_create_reserves_unique_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = &vec![registration::<Member>(dir.path(), "Member").await];
+        let now = chrono::Utc::now().to_rfc3339();
+        let bulk = |body| LithairServer::bulk_creates(&models[0], &body, &now).unwrap();
+        let reserve = |operations| async move {
+            LithairServer::reserve_bulk(models, 0, &operations).await.map(|()| operations)
+        };
+        write(
+            models,
+            vec![create("members", serde_json::json!({"id": "m1", "email": "a@x.io"}))],
+        )
+        .await;
+
+        // A duplicate within the array, or of a stored item, is rejected with its index
+        let twice = bulk(serde_json::json!([{"email": "b@x.io"}, {"email": "b@x.io"}]));
+        let (status, error) = reserve(twice).await.unwrap_err();
+        assert_eq!((status, error["index"].clone()), (409, serde_json::json!(1)));
+        let stored = bulk(serde_json::json!([{"email": "c@x.io"}, {"email": "a@x.io"}]));
+        let (status, error) = reserve(stored).await.unwrap_err();
+        assert_eq!((status, error["existing_id"].as_str()), (409, Some("m1")));
+
+        // Nothing stays reserved by a rejected bulk; a reserved one holds its keys
+        let both = bulk(serde_json::json!([{"email": "b@x.io"}, {"email": "c@x.io"}]));
+        let reserved = reserve(both).await.unwrap();
+        let (status, _) =
+            reserve(bulk(serde_json::json!([{"email": "c@x.io"}]))).await.unwrap_err();
+        assert_eq!(status, 409);
+        LithairServer::release_batch(models, &[0, 0], &reserved).await;
+        assert!(reserve(bulk(serde_json::json!([{"email": "c@x.io"}]))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_clustered_bulk_create_checks_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = library(dir.path()).await;
+        write(&models, vec![create("authors", serde_json::json!({"id": "a1", "name": "Ann"}))])
+            .await;
+        let now = chrono::Utc::now().to_rfc3339();
+        let body = serde_json::json!([
+            {"author_id": "a1"},
+            {"author_id": "a9"},
+            {"author_id": "a1"}
+        ]);
+        let operations = LithairServer::bulk_creates(&models[1], &body, &now).unwrap();
+
+        let (status, error) =
+            LithairServer::reserve_bulk(&models, 1, &operations).await.unwrap_err();
+        assert_eq!((status, error["error"].as_str()), (422, Some("validation_failed")));
+        assert_eq!(error["items"][0]["index"], 1);
+        assert_eq!(error["items"][0]["fields"][0]["rule"], "foreign_key");
+        assert_eq!(error["items"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reference_checked_writes_wait_for_referenced_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = LithairServer::default();
+        *server.models.write().await = library(dir.path()).await;
+        let models = server.models.read().await;
+        let (author, book) = (&models[0], &models[1]);
+
+        // Writes checking foreign keys share the lock
+        let (shared, exclusive) = server.lock_references(book, false, true).await;
+        assert!(shared.is_some() && exclusive.is_none());
+        assert!(server.references.try_read().is_ok());
+        assert!(server.references.try_write().is_err());
+        drop(shared);
+
+        // Deleting a referenced author waits for them, and they for it
+        let (shared, exclusive) = server.lock_references(author, true, false).await;
+        assert!(shared.is_none() && exclusive.is_some());
+        assert!(server.references.try_read().is_err());
+        drop(exclusive);
+
+        // Writes with nothing to check take nothing
+        let (shared, exclusive) = server.lock_references(author, false, true).await;
+        assert!(shared.is_none() && exclusive.is_none());
+        let (shared, exclusive) = server.lock_references(book, true, false).await;
+        assert!(shared.is_none() && exclusive.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expand_with_projection_keeps_relation_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = LithairServer::default();
+        *server.models.write().await = library(dir.path()).await;
+        let models = server.models.read().await;
+        write(
+            &models,
+            vec![
+                create("authors", serde_json::json!({"id": "a1", "name": "Ann"})),
+                create("books", serde_json::json!({"id": "b1", "author_id": "a1"})),
+            ],
+        )
+        .await;
+
+        // `fields=id&expand=author`: the handler answers with whole items
+        let body = Bytes::from(r#"{"data":[{"id":"b1","author_id":"a1"}],"total":1}"#);
+        let expand = crate::http::expand::Expand::parse("author").unwrap();
+        let fields = ["id".to_string()];
+        let perms = vec![Vec::new(); models.len()];
+        let expanded = server
+            .expand_response(&models[1], true, &body, &expand, Some(&fields[..]), &perms)
+            .await
+            .unwrap();
+        let expanded: serde_json::Value = serde_json::from_slice(&expanded).unwrap();
+        assert_eq!(
+            expanded["data"],
+            serde_json::json!([{"id": "b1", "author": {"id": "a1", "name": "Ann"}}])
+        );
+    }
 }
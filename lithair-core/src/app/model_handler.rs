@@ -1,12 +1,15 @@
 //! Type-erased model handler for LithairServer
 
+use crate::cluster::CrudOperation;
 use crate::consensus::ReplicatedModel;
+use crate::http::declarative::BatchLock;
 use crate::http::{DeclarativeHttpHandler, HttpExposable};
 use crate::lifecycle::LifecycleAware;
 use bytes::Bytes;
 use http_body_util::combinators::BoxBody;
 use hyper::body::Incoming;
 use hyper::{Request, Response};
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
 
@@ -53,6 +56,11 @@ pub trait ModelHandler: Send + Sync {
     /// Get total event count for a specific entity
     async fn get_entity_event_count(&self, id: &str) -> usize;
 
+    /// Get total event count in this model's event store
+    async fn get_event_count(&self) -> usize {
+        0
+    }
+
     /// Submit an edit event (event-sourced update - never replaces, always appends)
     /// Returns the new state after applying the edit event
     async fn submit_edit_event(
@@ -87,6 +95,172 @@ pub trait ModelHandler: Send + Sync {
     /// Called by followers when receiving DELETE replication from leader
     async fn apply_replicated_delete_json(&self, id: &str) -> Result<bool, String>;
 
+    /// All items as a JSON array with the version of each, read together for a cluster
+    /// snapshot (no versions by default)
+    async fn snapshot_data_json(&self) -> (serde_json::Value, HashMap<String, u64>) {
+        (self.get_all_data_json().await, HashMap::new())
+    }
+
+    /// Replace all items with those of a cluster snapshot, restoring the versions it
+    /// recorded as they are; returns the number of items
+    async fn install_snapshot_json(
+        &self,
+        items_json: Vec<serde_json::Value>,
+        _versions: HashMap<String, u64>,
+    ) -> Result<usize, String> {
+        self.apply_replicated_items_json(items_json).await
+    }
+
+    /// Check a clustered PATCH of `id` with body `body` on the leader as a single-node
+    /// PATCH checks it; returns the patched item and the merge patch to replicate, None
+    /// when the patch changes nothing, or the response rejecting it
+    async fn prepare_patch_json(
+        &self,
+        _req: &Req,
+        _id: &str,
+        _body: &[u8],
+    ) -> Result<Option<(serde_json::Value, serde_json::Value)>, Resp> {
+        use http_body_util::BodyExt;
+
+        let body = r#"{"error":"PATCH is not supported for this model"}"#;
+        Err(Response::builder()
+            .status(405)
+            .header("content-type", "application/json")
+            .body(http_body_util::Full::new(Bytes::from_static(body.as_bytes())).boxed())
+            .expect("valid HTTP response"))
+    }
+
+    /// Apply a replicated PATCH; returns the patched item, None when `id` is not stored
+    async fn apply_replicated_patch_json(
+        &self,
+        _id: &str,
+        _diff: &serde_json::Value,
+    ) -> Result<Option<serde_json::Value>, String> {
+        Err("PATCH is not replicated for this model".to_string())
+    }
+
+    /// Apply a replicated `POST /{id}/_restore`; returns the restored item, None when
+    /// `id` is not soft-deleted (any more)
+    async fn apply_replicated_restore_json(
+        &self,
+        _id: &str,
+    ) -> Result<Option<serde_json::Value>, String> {
+        Ok(None)
+    }
+
+    /// Soft-deleted item `id` as JSON
+    async fn deleted_item_json(&self, _id: &str) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Check a clustered CREATE (`id` = None) or UPDATE on the leader before it is
+    /// appended to the consensus log, reserving its unique keys.
+    ///
+    /// Returns the HTTP status and JSON body to reject the write with (400, 409, 422).
+    /// Every successful call must be followed by the write being applied or by
+    /// `abort_write_json`.
+    async fn prepare_write_json(
+        &self,
+        _id: Option<&str>,
+        _data: &serde_json::Value,
+    ) -> Result<(), (u16, serde_json::Value)> {
+        Ok(())
+    }
+
+    /// Release the reservation taken by `prepare_write_json` for a write that was not applied
+    async fn abort_write_json(&self, _id: Option<&str>, _data: &serde_json::Value) {}
+
+    /// Current version of item `id`, sent as its `ETag` (None when unknown or untracked)
+    fn item_version(&self, _id: &str) -> Option<u64> {
+        None
+    }
+
+    // ========================================================================
+    // BATCH METHODS - For `POST /_batch` (see `app::batch`)
+    // ========================================================================
+
+    /// Check that the caller of `req` may write `item_json` as part of a batch:
+    /// `changed` names the fields a create or update sets, None for a delete.
+    /// Returns the response to reject the batch with.
+    async fn authorize_batch_write(
+        &self,
+        _req: &Req,
+        _item_json: &serde_json::Value,
+        _changed: Option<&[String]>,
+    ) -> Option<Resp> {
+        None
+    }
+
+    /// Check a batched update of `id` setting the fields of `changes`, as PATCH is
+    /// checked; returns the updated item, or the response to reject the batch with
+    async fn prepare_batch_update_json(
+        &self,
+        _req: &Req,
+        id: &str,
+        changes: &serde_json::Value,
+    ) -> Result<serde_json::Value, Resp> {
+        let current = self.get_item_json(id).await.unwrap_or(serde_json::Value::Null);
+        Ok(crate::app::batch::merge_fields(&current, changes))
+    }
+
+    /// Lock the model for its share of batch `batch_id`; None when the model does not
+    /// take part in batches
+    async fn begin_batch<'a>(&'a self, _batch_id: &str) -> Option<Box<dyn ModelBatch + 'a>> {
+        None
+    }
+
+    // ========================================================================
+    // RELATION METHODS - For `?expand=` (see `http::expand`)
+    // ========================================================================
+
+    /// Foreign keys declared on the model
+    fn foreign_keys(&self) -> Vec<crate::schema::RelationForeignKeySpec> {
+        Vec::new()
+    }
+
+    /// Inverse (`has_many` / `has_one`) relations declared on the model
+    fn relations(&self) -> Vec<crate::schema::RelationSpec> {
+        Vec::new()
+    }
+
+    /// Permissions the model's read checks grant the caller of `req`
+    fn read_permissions(&self, _req: &Req) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Items whose `field` equals one of `values`, restricted to those `user_perms`
+    /// can read
+    async fn readable_items_json(
+        &self,
+        _field: &str,
+        _values: &[String],
+        _user_perms: &[String],
+    ) -> Vec<serde_json::Value> {
+        Vec::new()
+    }
+
+    /// Items whose `field` equals one of `values`, whoever may read them
+    async fn items_json(&self, _field: &str, _values: &[String]) -> Vec<serde_json::Value> {
+        Vec::new()
+    }
+
+    /// Resolve the foreign keys of written items through `resolver`
+    fn set_reference_resolver(&self, _resolver: Arc<dyn crate::http::ReferenceResolver>) {}
+
+    /// Rebuild in-memory state from the event log, upcasting events to the current schema
+    ///
+    /// Returns the replayed event count and one message per event that could not be
+    /// migrated. Called when a schema change is approved.
+    async fn reload_from_events(&self) -> Result<(usize, Vec<String>), String> {
+        Ok((0, Vec::new()))
+    }
+
+    /// Expire records and trim field history per the model's `#[lifecycle]` retention
+    /// policy (nothing to do by default)
+    async fn enforce_retention(&self) -> Result<crate::lifecycle::RetentionReport, String> {
+        Ok(Default::default())
+    }
+
     /// Get the schema specification for this model (for OpenAPI generation)
     /// Returns None if no schema spec is available
     fn schema_spec(&self) -> Option<crate::schema::ModelSpec> {
@@ -95,6 +269,41 @@ pub trait ModelHandler: Send + Sync {
 
     /// Set the SSE broadcaster for real-time change notifications (no-op by default)
     fn set_sse_broadcaster(&mut self, _broadcaster: Arc<crate::http::sse::SseEventBroadcaster>) {}
+
+    /// Snapshot every `interval` events, compacting the event log after each snapshot
+    /// when `compaction` is set (no-op by default)
+    fn set_snapshot_policy(&self, _interval: usize, _compaction: bool) {}
+}
+
+/// One model's share of a batch, holding the model's locks until finished, rolled back
+/// or dropped
+///
+/// A batch checks every operation, applies them, prepares every model, then finishes
+/// them; on a failure before the finish each model rolls back.
+pub trait ModelBatch: Send {
+    /// Current version of item `id`
+    fn item_version(&self, id: &str) -> Option<u64>;
+
+    /// Stored item `id`
+    fn item_json(&self, id: &str) -> Option<serde_json::Value>;
+
+    /// Stored items whose `field` equals one of `values`
+    fn items_json(&self, field: &str, values: &[String]) -> Vec<serde_json::Value>;
+
+    /// Check that a `Create`, `Update` or `Delete` can be applied, before anything is
+    fn check(&self, operation: &CrudOperation) -> Result<(), String>;
+
+    /// Apply a checked operation in memory; returns the item stored, or `{"deleted": id}`
+    fn apply(&mut self, operation: &CrudOperation) -> Result<serde_json::Value, String>;
+
+    /// Log the events of the batch, not yet committed
+    fn prepare(&mut self) -> Result<(), String>;
+
+    /// Commit the logged events and release the locks
+    fn finish(self: Box<Self>) -> Result<(), String>;
+
+    /// Undo the writes applied in memory and release the locks
+    fn rollback(self: Box<Self>);
 }
 
 /// Wrapper for DeclarativeHttpHandler that implements ModelHandler
@@ -120,6 +329,34 @@ where
         Ok(Self { handler, model_name, base_path, cached_schema_spec: None })
     }
 
+    /// Create a handler whose replay upcasts older events to `spec.version`
+    pub async fn new_with_schema(
+        data_path: String,
+        spec: crate::schema::ModelSpec,
+        migrations: Arc<crate::schema::SchemaMigrations>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let handler = DeclarativeHttpHandler::<T>::new(&data_path)?
+            .with_schema_spec(spec.clone())
+            .with_migrations(migrations);
+        handler.replay_events().await?;
+        let model_name =
+            std::any::type_name::<T>().split("::").last().unwrap_or("Unknown").to_string();
+        let base_path = T::http_base_path().to_string();
+        Ok(Self { handler, model_name, base_path, cached_schema_spec: Some(spec) })
+    }
+
+    /// Storage key a replicated write ends up under (see `apply_replicated_item`)
+    fn write_key(id: Option<&str>, data: &serde_json::Value, item: &T) -> String {
+        match id {
+            Some(id) => id.to_string(),
+            None => data
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| item.get_primary_key()),
+        }
+    }
+
     /// Override the base path used in metadata and JSON responses (e.g., `export_json`).
     ///
     /// Note: This does NOT change HTTP routing. Routing is determined by
@@ -243,6 +480,10 @@ where
         self.handler.get_entity_event_count(id).await
     }
 
+    async fn get_event_count(&self) -> usize {
+        self.handler.get_event_store().read().await.event_count()
+    }
+
     async fn submit_edit_event(
         &self,
         id: &str,
@@ -295,6 +536,146 @@ where
         self.handler.apply_replicated_delete(id).await
     }
 
+    async fn snapshot_data_json(&self) -> (serde_json::Value, HashMap<String, u64>) {
+        let (items, versions) = self.handler.items_with_versions().await;
+        (serde_json::to_value(&items).unwrap_or(serde_json::json!([])), versions)
+    }
+
+    async fn install_snapshot_json(
+        &self,
+        items_json: Vec<serde_json::Value>,
+        versions: HashMap<String, u64>,
+    ) -> Result<usize, String> {
+        let items: Vec<T> = items_json
+            .into_iter()
+            .map(|json| serde_json::from_value::<T>(json))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to deserialize snapshot items: {}", e))?;
+        self.handler.install_snapshot(items, &versions).await
+    }
+
+    async fn prepare_patch_json(
+        &self,
+        req: &Req,
+        id: &str,
+        body: &[u8],
+    ) -> Result<Option<(serde_json::Value, serde_json::Value)>, Resp> {
+        let prepared = self.handler.prepare_patch(req, id, body).await?;
+        Ok(prepared.and_then(|(item, diff)| Some((serde_json::to_value(&item).ok()?, diff))))
+    }
+
+    async fn apply_replicated_patch_json(
+        &self,
+        id: &str,
+        diff: &serde_json::Value,
+    ) -> Result<Option<serde_json::Value>, String> {
+        let patched = self.handler.apply_replicated_patch(id, diff).await?;
+        patched
+            .map(|item| serde_json::to_value(&item).map_err(|e| e.to_string()))
+            .transpose()
+    }
+
+    async fn apply_replicated_restore_json(
+        &self,
+        id: &str,
+    ) -> Result<Option<serde_json::Value>, String> {
+        let restored = self.handler.apply_replicated_restore(id).await?;
+        restored
+            .map(|item| serde_json::to_value(&item).map_err(|e| e.to_string()))
+            .transpose()
+    }
+
+    async fn deleted_item_json(&self, id: &str) -> Option<serde_json::Value> {
+        self.handler.deleted_item(id).and_then(|item| serde_json::to_value(&item).ok())
+    }
+
+    async fn prepare_write_json(
+        &self,
+        id: Option<&str>,
+        data: &serde_json::Value,
+    ) -> Result<(), (u16, serde_json::Value)> {
+        let item: T = serde_json::from_value(data.clone())
+            .map_err(|e| (400, serde_json::json!({ "error": format!("Invalid JSON: {}", e) })))?;
+        if let Err(errors) = item.validate_fields() {
+            return Err((422, errors.to_json()));
+        }
+        let key = Self::write_key(id, data, &item);
+        self.handler.reserve_unique(&key, &item).await.map_err(|v| (409, v.to_json()))
+    }
+
+    async fn abort_write_json(&self, id: Option<&str>, data: &serde_json::Value) {
+        if let Ok(item) = serde_json::from_value::<T>(data.clone()) {
+            let key = Self::write_key(id, data, &item);
+            self.handler.release_unique(&key, &item).await;
+        }
+    }
+
+    fn item_version(&self, id: &str) -> Option<u64> {
+        self.handler.item_version(id)
+    }
+
+    async fn authorize_batch_write(
+        &self,
+        req: &Req,
+        item_json: &serde_json::Value,
+        changed: Option<&[String]>,
+    ) -> Option<Resp> {
+        // An item that does not deserialize is rejected when the batch is prepared
+        let item: T = serde_json::from_value(item_json.clone()).ok()?;
+        self.handler.authorize_batch_write(req, &item, changed).await
+    }
+
+    async fn prepare_batch_update_json(
+        &self,
+        req: &Req,
+        id: &str,
+        changes: &serde_json::Value,
+    ) -> Result<serde_json::Value, Resp> {
+        let item = self.handler.prepare_batch_update(req, id, changes).await?;
+        Ok(serde_json::to_value(&item).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn begin_batch<'a>(&'a self, batch_id: &str) -> Option<Box<dyn ModelBatch + 'a>> {
+        Some(Box::new(self.handler.lock_batch(batch_id).await))
+    }
+
+    fn foreign_keys(&self) -> Vec<crate::schema::RelationForeignKeySpec> {
+        T::foreign_keys()
+    }
+
+    fn relations(&self) -> Vec<crate::schema::RelationSpec> {
+        T::relations()
+    }
+
+    fn read_permissions(&self, req: &Req) -> Vec<String> {
+        self.handler.read_permissions(req)
+    }
+
+    async fn readable_items_json(
+        &self,
+        field: &str,
+        values: &[String],
+        user_perms: &[String],
+    ) -> Vec<serde_json::Value> {
+        self.handler.items_where(field, values, Some(user_perms)).await
+    }
+
+    async fn items_json(&self, field: &str, values: &[String]) -> Vec<serde_json::Value> {
+        self.handler.items_where(field, values, None).await
+    }
+
+    fn set_reference_resolver(&self, resolver: Arc<dyn crate::http::ReferenceResolver>) {
+        self.handler.set_reference_resolver(resolver);
+    }
+
+    async fn reload_from_events(&self) -> Result<(usize, Vec<String>), String> {
+        self.handler.reload_from_events().await.map_err(|e| e.to_string())
+    }
+
+    async fn enforce_retention(&self) -> Result<crate::lifecycle::RetentionReport, String> {
+        self.handler.enforce_retention().await.map_err(|e| e.to_string())
+    }
+
     fn schema_spec(&self) -> Option<crate::schema::ModelSpec> {
         self.cached_schema_spec.clone()
     }
@@ -302,4 +683,80 @@ where
     fn set_sse_broadcaster(&mut self, broadcaster: Arc<crate::http::sse::SseEventBroadcaster>) {
         self.handler.sse_broadcaster = Some(broadcaster);
     }
+
+    fn set_snapshot_policy(&self, interval: usize, compaction: bool) {
+        self.handler.set_snapshot_policy(interval, compaction);
+    }
+}
+
+impl<T> ModelBatch for BatchLock<'_, T>
+where
+    T: HttpExposable
+        + LifecycleAware
+        + ReplicatedModel
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + 'static,
+{
+    fn item_version(&self, id: &str) -> Option<u64> {
+        self.version(id)
+    }
+
+    fn item_json(&self, id: &str) -> Option<serde_json::Value> {
+        self.item(id).and_then(|item| serde_json::to_value(item).ok())
+    }
+
+    fn items_json(&self, field: &str, values: &[String]) -> Vec<serde_json::Value> {
+        self.items_where(field, values)
+    }
+
+    fn check(&self, operation: &CrudOperation) -> Result<(), String> {
+        match operation {
+            CrudOperation::Delete { .. } => Ok(()),
+            _ => Self::batched_item(operation).map(|_| ()),
+        }
+    }
+
+    fn apply(&mut self, operation: &CrudOperation) -> Result<serde_json::Value, String> {
+        if let CrudOperation::Delete { id, .. } = operation {
+            self.delete(id)?;
+            return Ok(serde_json::json!({ "deleted": id }));
+        }
+        let (id, data, item, event) = Self::batched_item(operation)?;
+        let key = DeclarativeModelHandler::<T>::write_key(id, data, &item);
+        let written = serde_json::to_value(&item).map_err(|e| e.to_string())?;
+        self.put(&key, item, event)?;
+        Ok(written)
+    }
+
+    fn prepare(&mut self) -> Result<(), String> {
+        BatchLock::prepare(self)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        BatchLock::finish(*self)
+    }
+
+    fn rollback(self: Box<Self>) {
+        BatchLock::rollback(*self)
+    }
+}
+
+impl<T> BatchLock<'_, T>
+where
+    T: HttpExposable + LifecycleAware + ReplicatedModel + serde::de::DeserializeOwned,
+{
+    /// Id, data, item and event of a batched create or update
+    fn batched_item(
+        operation: &CrudOperation,
+    ) -> Result<(Option<&str>, &serde_json::Value, T, &'static str), String> {
+        let (id, data, event) = match operation {
+            CrudOperation::Create { data, .. } => (None, data, "Created"),
+            CrudOperation::Update { id, data, .. } => (Some(id.as_str()), data, "Updated"),
+            _ => return Err("only creates, updates and deletes can be batched".to_string()),
+        };
+        let item: T = serde_json::from_value(data.clone())
+            .map_err(|e| format!("Failed to deserialize batched item: {}", e))?;
+        Ok((id, data, item, event))
+    }
 }
@@ -372,6 +372,42 @@ impl LithairServer {
             .unwrap())
     }
 
+    /// Replay a model's event log through its upcasters after a schema change is applied
+    ///
+    /// Returns a JSON summary (`replayed_events`, `failed_events`, first errors), or null
+    /// when the model has no handler yet.
+    async fn migrate_model_data(&self, model_name: &str) -> serde_json::Value {
+        let handler = {
+            let models = self.models.read().await;
+            models.iter().find(|m| m.name == model_name).map(|m| m.handler.clone())
+        };
+        let Some(handler) = handler else {
+            return serde_json::Value::Null;
+        };
+
+        match handler.reload_from_events().await {
+            Ok((replayed, failed)) => {
+                if !failed.is_empty() {
+                    log::warn!(
+                        "{} event(s) of '{}' could not be migrated: {}",
+                        failed.len(),
+                        model_name,
+                        failed[0]
+                    );
+                }
+                serde_json::json!({
+                    "replayed_events": replayed,
+                    "failed_events": failed.len(),
+                    "errors": failed.iter().take(10).collect::<Vec<_>>(),
+                })
+            }
+            Err(e) => {
+                log::error!("Failed to migrate data of '{}': {}", model_name, e);
+                serde_json::json!({ "error": e })
+            }
+        }
+    }
+
     /// POST /_admin/schema/approve/{change_id} - Manually approve a schema change
     pub(crate) async fn handle_admin_schema_approve(
         &self,
@@ -475,11 +511,16 @@ impl LithairServer {
             } else {
                 log::info!("Schema for '{}' persisted to disk", model_name_for_response);
             }
+            drop(state);
+
+            // Bring the model's data up to the approved schema
+            let migration = self.migrate_model_data(&model_name).await;
 
             let response = serde_json::json!({
                 "status": "applied",
                 "change_id": change_id,
                 "model": model_name_for_response,
+                "migration": migration,
                 "message": "Schema change approved, applied, and persisted"
             });
 
@@ -0,0 +1,130 @@
+//! Addresses nodes advertise to each other and to clients
+//!
+//! A node is reached at `scheme://host:port`. Followers redirect writes to the leader's
+//! address, and the leader pushes snapshots and log entries to its peers' addresses, so
+//! each address must be reachable from the other hosts, not just from the node itself.
+//! Peers given as `host:port` use plain HTTP.
+
+use serde::{Deserialize, Serialize};
+
+/// Where a node can be reached
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NodeAddress {
+    /// `http` or `https`
+    pub scheme: String,
+    /// Host name or IP; IPv6 addresses keep their brackets (`[::1]`)
+    pub host: String,
+    pub port: u16,
+}
+
+impl NodeAddress {
+    pub fn new(scheme: impl Into<String>, host: impl Into<String>, port: u16) -> Self {
+        Self { scheme: scheme.into(), host: host.into(), port }
+    }
+
+    /// Plain HTTP on the loopback interface, what single-host clusters use
+    pub fn local(port: u16) -> Self {
+        Self::new("http", "127.0.0.1", port)
+    }
+
+    /// Parse `host:port`, `http://host:port` or `https://host:port`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim().trim_end_matches('/');
+        let (scheme, authority) = match value.split_once("://") {
+            Some((scheme, authority)) => (scheme.to_ascii_lowercase(), authority),
+            None => ("http".to_string(), value),
+        };
+        if scheme != "http" && scheme != "https" {
+            return Err(format!("`{}`: scheme must be http or https", value));
+        }
+        let Some((host, port)) = authority.rsplit_once(':') else {
+            return Err(format!("`{}`: expected host:port", value));
+        };
+        if host.is_empty() || host.contains('/') || host.contains('@') {
+            return Err(format!("`{}`: invalid host", value));
+        }
+        if host.contains(':') && !(host.starts_with('[') && host.ends_with(']')) {
+            return Err(format!("`{}`: IPv6 hosts must be bracketed", value));
+        }
+        let port = port.parse().map_err(|_| format!("`{}`: invalid port", value))?;
+        Ok(Self::new(scheme, host, port))
+    }
+
+    /// `host:port`
+    pub fn authority(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// URL of `path` on the node (`path` starts with `/`)
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self, path)
+    }
+}
+
+impl std::fmt::Display for NodeAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}:{}", self.scheme, self.host, self.port)
+    }
+}
+
+impl std::str::FromStr for NodeAddress {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
+impl TryFrom<String> for NodeAddress {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<NodeAddress> for String {
+    fn from(address: NodeAddress) -> Self {
+        address.to_string()
+    }
+}
+
+/// URL of `path` on `peer`, a peer entry as given to `with_raft_cluster`
+///
+/// Entries that do not parse keep the historical `http://{peer}` form.
+pub fn peer_url(peer: &str, path: &str) -> String {
+    match NodeAddress::parse(peer) {
+        Ok(address) => address.url(path),
+        Err(_) => format!("http://{}{}", peer, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        let address = NodeAddress::parse("https://db-2.internal:8443/").unwrap();
+        assert_eq!(address, NodeAddress::new("https", "db-2.internal", 8443));
+        assert_eq!(address.url("/_raft/snapshot"), "https://db-2.internal:8443/_raft/snapshot");
+        assert_eq!(address.authority(), "db-2.internal:8443");
+
+        assert_eq!(
+            NodeAddress::parse("10.0.0.3:8080").unwrap().to_string(),
+            "http://10.0.0.3:8080"
+        );
+        assert_eq!(NodeAddress::parse("[::1]:8080").unwrap().host, "[::1]");
+
+        assert!(NodeAddress::parse("ftp://host:21").is_err());
+        assert!(NodeAddress::parse("host").is_err());
+        assert!(NodeAddress::parse("host:http").is_err());
+        assert!(NodeAddress::parse("::1:8080").is_err());
+        assert!(NodeAddress::parse("http://host:80/api").is_err());
+
+        assert_eq!(peer_url("127.0.0.1:8081", "/status"), "http://127.0.0.1:8081/status");
+        let json = serde_json::to_string(&NodeAddress::local(8080)).unwrap();
+        assert_eq!(json, "\"http://127.0.0.1:8080\"");
+    }
+}
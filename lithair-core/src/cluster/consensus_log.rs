@@ -9,6 +9,7 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::{Mutex, RwLock};
 use uuid::Uuid;
 
+use super::membership::MembershipConfig;
 use super::upgrade::{SchemaChange, Version};
 
 /// A unique identifier for a log entry (term, index)
@@ -35,10 +36,35 @@ pub enum CrudOperation {
         model_path: String,
         id: String,
         data: serde_json::Value,
+        /// Version the item must still be at (`If-Match`); every node checks it when
+        /// applying the entry and skips the write on mismatch
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_version: Option<u64>,
     },
     Delete {
         model_path: String,
         id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_version: Option<u64>,
+    },
+    /// PATCH of `id`: the merge patch of the fields it changed, checked on the leader
+    Patch {
+        model_path: String,
+        id: String,
+        diff: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_version: Option<u64>,
+    },
+    /// `POST /{id}/_restore` of a soft-deleted item
+    Restore {
+        model_path: String,
+        id: String,
+    },
+    /// Creates, updates and deletes of a `POST /_batch`, across models; every node
+    /// applies all of them or none
+    Batch {
+        batch_id: String,
+        operations: Vec<CrudOperation>,
     },
     // === Migration Operations (Phase 1: Foundation) ===
     /// Begin migration transaction
@@ -64,6 +90,10 @@ pub enum CrudOperation {
         failed_step: u32,
         reason: String,
     },
+    /// New cluster configuration (see `membership`), applied on every node
+    MembershipChange {
+        config: MembershipConfig,
+    },
 }
 
 /// A log entry containing a CRUD operation
@@ -344,6 +374,7 @@ mod tests {
                 model_path: "/api/products".to_string(),
                 id: "1".to_string(),
                 data: serde_json::json!({"id": "1", "name": "Updated"}),
+                expected_version: None,
             })
             .await;
 
@@ -351,6 +382,7 @@ mod tests {
             .append(CrudOperation::Delete {
                 model_path: "/api/products".to_string(),
                 id: "1".to_string(),
+                expected_version: None,
             })
             .await;
 
@@ -0,0 +1,298 @@
+//! Cluster membership: which nodes vote, which only follow the log
+//!
+//! The nodes given to `with_raft_cluster` are the first voters. Nodes join later as
+//! learners: they receive the log but their acknowledgments count towards no quorum, and
+//! they never stand for election. Once a learner caught up, the leader promotes it to
+//! voter.
+//!
+//! Voters change through joint consensus. The leader first commits a joint
+//! configuration, holding both the outgoing and the new voters; while it is in force an
+//! entry commits only on a majority of each. Then it commits the new configuration
+//! alone. At no point can two majorities that do not overlap decide, so a node can be
+//! removed or replaced while the cluster keeps taking writes.
+//!
+//! Every change is a log entry (`CrudOperation::MembershipChange`) carrying the whole
+//! configuration; nodes keep these entries in their WAL and restore the last one when
+//! they restart.
+
+use super::address::NodeAddress;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long the leader waits for a learner to catch up before promoting it
+pub const LEARNER_CATCH_UP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A node of the cluster
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    /// Known for nodes that joined, and for the node itself; peers given to
+    /// `with_raft_cluster` are only known by address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<u64>,
+    /// Address as given to `with_raft_cluster` or in the join request
+    pub address: String,
+}
+
+impl Member {
+    pub fn new(node_id: Option<u64>, address: impl Into<String>) -> Self {
+        Self { node_id, address: address.into() }
+    }
+
+    /// Whether `selector` names this node: its id, its address or its `host:port`
+    pub fn matches(&self, selector: &str) -> bool {
+        if let Ok(id) = selector.parse::<u64>() {
+            return self.node_id == Some(id);
+        }
+        same_address(&self.address, selector)
+            || NodeAddress::parse(&self.address).is_ok_and(|a| a.authority() == selector)
+    }
+}
+
+/// Whether two peer entries reach the same node (`127.0.0.1:8080` is
+/// `http://127.0.0.1:8080`)
+pub fn same_address(a: &str, b: &str) -> bool {
+    match (NodeAddress::parse(a), NodeAddress::parse(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Voters and learners of the cluster, possibly in the middle of a joint change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MembershipConfig {
+    /// Nodes whose acknowledgments commit entries and which may become leader
+    pub voters: Vec<Member>,
+    /// Nodes that receive the log without voting, until promoted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub learners: Vec<Member>,
+    /// During a joint change, the voters being left; entries then also need a majority
+    /// of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outgoing: Option<Vec<Member>>,
+}
+
+impl MembershipConfig {
+    /// Configuration a node boots with: itself and its peers, all voters
+    pub fn initial(own: Member, peers: &[String]) -> Self {
+        let peers = peers.iter().map(|peer| Member::new(None, peer.clone()));
+        Self { voters: std::iter::once(own).chain(peers).collect(), ..Self::default() }
+    }
+
+    /// Whether a joint change is under way
+    pub fn is_joint(&self) -> bool {
+        self.outgoing.is_some()
+    }
+
+    fn all(&self) -> impl Iterator<Item = &Member> {
+        let outgoing = self.outgoing.iter().flatten();
+        let outgoing = outgoing.filter(|m| !self.voters.iter().any(|v| v.address == m.address));
+        self.voters.iter().chain(&self.learners).chain(outgoing)
+    }
+
+    /// The member `selector` names (see `Member::matches`)
+    pub fn find(&self, selector: &str) -> Option<&Member> {
+        self.all().find(|m| m.matches(selector))
+    }
+
+    /// Whether the node at `address` votes, in either configuration of a joint change
+    pub fn is_voter(&self, address: &str) -> bool {
+        let outgoing = self.outgoing.iter().flatten();
+        self.voters.iter().chain(outgoing).any(|m| same_address(&m.address, address))
+    }
+
+    /// Whether the node at `address` is a learner
+    pub fn is_learner(&self, address: &str) -> bool {
+        self.learners.iter().any(|m| same_address(&m.address, address))
+    }
+
+    /// Addresses of every member but the node at `own`: where the log is replicated
+    pub fn peers(&self, own: &str) -> Vec<String> {
+        self.all()
+            .filter(|m| !same_address(&m.address, own))
+            .map(|m| m.address.clone())
+            .collect()
+    }
+
+    /// Addresses of the voters but the node at `own`: the candidates of an election
+    pub fn voter_peers(&self, own: &str) -> Vec<String> {
+        self.peers(own).into_iter().filter(|peer| self.is_voter(peer)).collect()
+    }
+
+    /// Whether the nodes at `acked` hold a majority of the voters, and of the outgoing
+    /// voters during a joint change
+    pub fn has_quorum(&self, acked: &[String]) -> bool {
+        let majority = |voters: &[Member]| {
+            let votes = voters
+                .iter()
+                .filter(|m| acked.iter().any(|a| same_address(&m.address, a)))
+                .count();
+            votes > voters.len() / 2
+        };
+        majority(&self.voters) && self.outgoing.as_deref().is_none_or(majority)
+    }
+
+    /// Add `member` as a learner
+    pub fn with_learner(&self, member: Member) -> Result<Self, String> {
+        if self.all().any(|m| same_address(&m.address, &member.address)) {
+            return Err(format!("{} is already a member", member.address));
+        }
+        if let Some(id) = member.node_id {
+            if self.all().any(|m| m.node_id == Some(id)) {
+                return Err(format!("node {} is already a member", id));
+            }
+        }
+        let mut config = self.clone();
+        config.learners.push(member);
+        Ok(config)
+    }
+
+    /// Joint configuration that promotes the learner `promote` and removes the voter
+    /// `remove`, either being optional
+    pub fn begin_change(
+        &self,
+        promote: Option<&str>,
+        remove: Option<&str>,
+    ) -> Result<Self, String> {
+        if self.is_joint() {
+            return Err("another membership change is in progress".to_string());
+        }
+        let mut config = self.clone();
+        if let Some(selector) = promote {
+            let index = config
+                .learners
+                .iter()
+                .position(|m| m.matches(selector))
+                .ok_or_else(|| format!("{} is not a learner", selector))?;
+            let learner = config.learners.remove(index);
+            config.voters.push(learner);
+        }
+        if let Some(selector) = remove {
+            let index = config
+                .voters
+                .iter()
+                .position(|m| m.matches(selector))
+                .ok_or_else(|| format!("{} is not a voter", selector))?;
+            config.voters.remove(index);
+        }
+        if config.voters.is_empty() {
+            return Err("the cluster needs at least one voter".to_string());
+        }
+        config.outgoing = Some(self.voters.clone());
+        Ok(config)
+    }
+
+    /// Configuration that ends the joint change: the new voters alone
+    pub fn finish_change(&self) -> Self {
+        Self { outgoing: None, ..self.clone() }
+    }
+
+    /// Remove the learner `selector`, which needs no joint change
+    pub fn without_learner(&self, selector: &str) -> Result<Self, String> {
+        let mut config = self.clone();
+        let index = config
+            .learners
+            .iter()
+            .position(|m| m.matches(selector))
+            .ok_or_else(|| format!("{} is not a learner", selector))?;
+        config.learners.remove(index);
+        Ok(config)
+    }
+
+    /// This node's entry, found by id; a node its peers list by address gets its id
+    /// filled in
+    pub fn with_own_id(mut self, node_id: u64, own: &str) -> Self {
+        let outgoing = self.outgoing.iter_mut().flatten();
+        for member in self.voters.iter_mut().chain(self.learners.iter_mut()).chain(outgoing) {
+            if member.node_id.is_none() && same_address(&member.address, own) {
+                member.node_id = Some(node_id);
+            }
+        }
+        self
+    }
+
+    /// Address of node `node_id` in this configuration
+    pub fn address_of(&self, node_id: u64) -> Option<&str> {
+        self.all().find(|m| m.node_id == Some(node_id)).map(|m| m.address.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn three_nodes() -> MembershipConfig {
+        let own = Member::new(Some(0), "http://127.0.0.1:8080");
+        MembershipConfig::initial(own, &["127.0.0.1:8081".into(), "127.0.0.1:8082".into()])
+    }
+
+    fn acked(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_initial_membership() {
+        let config = three_nodes();
+        assert_eq!(config.peers("127.0.0.1:8080"), vec!["127.0.0.1:8081", "127.0.0.1:8082"]);
+        assert!(config.is_voter("http://127.0.0.1:8081"));
+        assert_eq!(config.find("0").unwrap().address, "http://127.0.0.1:8080");
+        assert_eq!(config.find("127.0.0.1:8082").unwrap().address, "127.0.0.1:8082");
+        assert!(config.find("7").is_none());
+
+        assert!(config.has_quorum(&acked(&["http://127.0.0.1:8080", "127.0.0.1:8082"])));
+        assert!(!config.has_quorum(&acked(&["http://127.0.0.1:8080"])));
+    }
+
+    #[test]
+    fn test_learners_do_not_vote() {
+        let config = three_nodes()
+            .with_learner(Member::new(Some(3), "http://127.0.0.1:8083"))
+            .unwrap();
+        assert!(config.is_learner("127.0.0.1:8083"));
+        assert_eq!(config.peers("http://127.0.0.1:8080").len(), 3);
+        assert_eq!(config.voter_peers("http://127.0.0.1:8080").len(), 2);
+        assert!(!config.has_quorum(&acked(&["127.0.0.1:8080", "127.0.0.1:8083"])));
+
+        // Joining twice, under the same address or id, is refused
+        assert!(config.with_learner(Member::new(Some(4), "127.0.0.1:8083")).is_err());
+        assert!(config.with_learner(Member::new(Some(3), "127.0.0.1:9000")).is_err());
+        assert_eq!(config.without_learner("3").unwrap(), three_nodes());
+    }
+
+    #[test]
+    fn test_joint_change() {
+        // Replace 127.0.0.1:8082 with the learner node 3
+        let config = three_nodes()
+            .with_learner(Member::new(Some(3), "http://127.0.0.1:8083"))
+            .unwrap();
+        let joint = config.begin_change(Some("3"), Some("127.0.0.1:8082")).unwrap();
+        assert!(joint.is_joint());
+        assert!(joint.learners.is_empty());
+        // The removed node still receives the log and votes until the change ends
+        assert!(joint.is_voter("127.0.0.1:8082"));
+        assert_eq!(joint.peers("127.0.0.1:8080").len(), 3);
+        assert!(joint.begin_change(None, Some("0")).is_err());
+
+        // A majority of the new voters is not enough without one of the old
+        let new_only = acked(&["127.0.0.1:8080", "127.0.0.1:8083"]);
+        assert!(!joint.has_quorum(&new_only));
+        let both = acked(&["127.0.0.1:8080", "127.0.0.1:8081", "127.0.0.1:8083"]);
+        assert!(joint.has_quorum(&both));
+
+        let done = joint.finish_change();
+        assert!(!done.is_joint());
+        assert!(!done.is_voter("127.0.0.1:8082"));
+        assert!(done.has_quorum(&new_only));
+        assert_eq!(done.address_of(3), Some("http://127.0.0.1:8083"));
+
+        assert!(three_nodes().begin_change(Some("1"), None).is_err());
+        let single = MembershipConfig::initial(Member::new(Some(0), "127.0.0.1:8080"), &[]);
+        assert!(single.begin_change(None, Some("0")).is_err());
+    }
+
+    #[test]
+    fn test_own_id() {
+        let config = MembershipConfig::initial(Member::new(None, "127.0.0.1:8081"), &[]);
+        let config = config.with_own_id(1, "http://127.0.0.1:8081");
+        assert_eq!(config.voters[0].node_id, Some(1));
+    }
+}
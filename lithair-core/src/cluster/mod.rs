@@ -4,22 +4,27 @@
 //! Use `LithairServer::with_raft_cluster()` to enable clustering.
 
 use clap::Parser;
-use reqwest::Client as HttpClient;
 use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
+pub mod address;
 pub mod consensus_log;
+pub mod membership;
 pub mod replication_batcher;
 pub mod simple_replication;
 pub mod snapshot;
+pub mod tls;
 pub mod upgrade;
 pub mod wal;
 
+pub use address::{peer_url, NodeAddress};
 pub use consensus_log::{ApplyResult, ConsensusLog, CrudOperation, LogEntry, LogId};
+pub use membership::{Member, MembershipConfig};
 pub use replication_batcher::{BatcherConfig, FollowerHealth, FollowerStats, ReplicationBatcher};
 pub use snapshot::{
     InstallSnapshotRequest, InstallSnapshotResponse, SnapshotData, SnapshotManager, SnapshotMeta,
 };
+pub use tls::{ClusterTls, PeerClients, PeerNode};
 pub use upgrade::{
     FieldDefinition, FieldType, MigrationContext, MigrationManager, MigrationStatus, ModelSchema,
     NodeMode, RollbackOp, SchemaChange, Version,
@@ -41,15 +46,25 @@ pub enum RaftNodeState {
 /// - Leader election
 /// - Heartbeat tracking
 /// - State transitions (Follower -> Candidate -> Leader)
+/// - Cluster membership (voters and learners)
 pub struct RaftLeadershipState {
     pub node_id: u64,
     pub self_port: u16,
+    /// Address this node advertises to peers and redirected clients
+    pub self_address: NodeAddress,
     pub current_state: AtomicU64, // 0=Follower, 1=Candidate, 2=Leader
     pub is_leader: AtomicBool,
     pub current_leader_id: AtomicU64,
     pub leader_port: AtomicU16,
-    pub peers: Vec<String>,
+    /// Address of the current leader, where followers redirect writes
+    pub leader_address: std::sync::RwLock<NodeAddress>,
+    /// Voters and learners, starting with this node and its boot peers as voters
+    membership: std::sync::RwLock<MembershipConfig>,
+    /// Held during a membership change, so changes run one at a time
+    pub membership_change: tokio::sync::Mutex<()>,
     pub last_heartbeat: std::sync::Mutex<Instant>,
+    /// Last commit index the leader reported, and when it did
+    leader_commit: std::sync::Mutex<(u64, Instant)>,
     pub election_timeout: Duration,
 }
 
@@ -58,42 +73,80 @@ impl RaftLeadershipState {
     ///
     /// Uses static leader election: lowest node_id becomes leader initially.
     /// Dynamic election occurs when leader fails (heartbeat timeout).
+    ///
+    /// Peers are only known by address, so a follower does not know where node 0 is:
+    /// its leader stays unknown (see `known_leader_address`) until the first heartbeat.
     pub fn new(node_id: u64, self_port: u16, peers: Vec<String>) -> Self {
         // Simple leadership election: lowest node_id is leader
         // This is a static election - node_id 0 is always leader if present
         let is_leader = node_id == 0 || peers.is_empty();
 
-        // Find the leader port
-        // If we are leader, it's our port
-        // If not, find the smallest port among peers (leader is node_id=0, first allocated)
-        let leader_port = if is_leader {
-            self_port
-        } else {
-            // Find the smallest port among peers (that's the leader)
-            peers
-                .iter()
-                .filter_map(|peer| peer.split(':').nth(1))
-                .filter_map(|port_str| port_str.parse::<u16>().ok())
-                .min()
-                .unwrap_or(self_port)
-        };
+        // A node naming itself as leader while following knows no leader
+        let self_address = NodeAddress::local(self_port);
+        let leader_address = self_address.clone();
 
         // Find the leader node_id (always 0 in static election)
         let current_leader_id = 0u64;
+        let own = Member::new(Some(node_id), self_address.to_string());
+        let membership = MembershipConfig::initial(own, &peers);
 
         Self {
             node_id,
             self_port,
+            self_address,
             current_state: AtomicU64::new(if is_leader { 2 } else { 0 }), // 2=Leader, 0=Follower
             is_leader: AtomicBool::new(is_leader),
             current_leader_id: AtomicU64::new(current_leader_id),
-            leader_port: AtomicU16::new(leader_port),
-            peers,
+            leader_port: AtomicU16::new(leader_address.port),
+            leader_address: std::sync::RwLock::new(leader_address),
+            membership: std::sync::RwLock::new(membership),
+            membership_change: tokio::sync::Mutex::new(()),
             last_heartbeat: std::sync::Mutex::new(Instant::now()),
+            leader_commit: std::sync::Mutex::new((0, Instant::now())),
             election_timeout: Duration::from_secs(5), // 5 second timeout
         }
     }
 
+    /// Advertise `address` instead of `http://127.0.0.1:{self_port}`
+    pub fn with_self_address(mut self, address: NodeAddress) -> Self {
+        if self.is_leader() || self.known_leader_address().is_none() {
+            self.leader_port.store(address.port, Ordering::SeqCst);
+            *self.leader_address.get_mut().unwrap_or_else(|e| e.into_inner()) = address.clone();
+        }
+        let node_id = self.node_id;
+        let membership = self.membership.get_mut().unwrap_or_else(|e| e.into_inner());
+        for member in membership.voters.iter_mut().filter(|m| m.node_id == Some(node_id)) {
+            member.address = address.to_string();
+        }
+        self.self_address = address;
+        self
+    }
+
+    /// Cluster configuration in force on this node
+    pub fn membership(&self) -> MembershipConfig {
+        self.membership.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Switch to `config`, committed by the leader or restored from the WAL
+    pub fn set_membership(&self, config: MembershipConfig) {
+        let own = self.self_address.to_string();
+        if config.find(&self.node_id.to_string()).is_none() && config.find(&own).is_none() {
+            log::warn!("Node {} is no longer a member of the cluster", self.node_id);
+        }
+        let config = config.with_own_id(self.node_id, &own);
+        *self.membership.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    /// Addresses of the other members, voters and learners: where the log goes
+    pub fn peers(&self) -> Vec<String> {
+        self.membership().peers(&self.self_address.to_string())
+    }
+
+    /// Whether this node votes (a learner, or a removed node, does not)
+    pub fn is_voter(&self) -> bool {
+        self.membership().is_voter(&self.self_address.to_string())
+    }
+
     /// Check if this node is currently the leader
     pub fn is_leader(&self) -> bool {
         self.is_leader.load(Ordering::Relaxed)
@@ -114,6 +167,23 @@ impl RaftLeadershipState {
         self.leader_port.load(Ordering::Relaxed)
     }
 
+    /// Get the current leader's address
+    pub fn get_leader_address(&self) -> NodeAddress {
+        self.leader_address.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Get the current leader's address, None while this node knows no leader: from
+    /// boot until a follower's first heartbeat, and during an election
+    pub fn known_leader_address(&self) -> Option<NodeAddress> {
+        let leader = self.get_leader_address();
+        (self.is_leader() || leader != self.self_address).then_some(leader)
+    }
+
+    fn set_leader_address(&self, address: NodeAddress) {
+        self.leader_port.store(address.port, Ordering::SeqCst);
+        *self.leader_address.write().unwrap_or_else(|e| e.into_inner()) = address;
+    }
+
     /// Check if the provided id matches the current authoritative leader id
     #[allow(dead_code)]
     pub(crate) fn is_authoritative_leader_id(&self, leader_id: u64) -> bool {
@@ -127,9 +197,75 @@ impl RaftLeadershipState {
         }
     }
 
+    /// Record that the leader just reported committing up to `index` (in a heartbeat or
+    /// log append)
+    pub fn record_leader_commit(&self, index: u64) {
+        let mut leader_commit = self.leader_commit.lock().unwrap_or_else(|e| e.into_inner());
+        *leader_commit = (leader_commit.0.max(index), Instant::now());
+    }
+
+    /// Last commit index the leader reported, and how long ago
+    pub fn leader_commit(&self) -> (u64, Duration) {
+        let (index, at) = *self.leader_commit.lock().unwrap_or_else(|e| e.into_inner());
+        (index, at.elapsed())
+    }
+
+    /// Heartbeat the leader sends its peers, `commit_index` and `term` being its own
+    pub fn heartbeat_message(&self, commit_index: u64, term: u64) -> serde_json::Value {
+        serde_json::json!({
+            "leader_id": self.node_id,
+            "leader_port": self.self_port,
+            "leader_address": self.self_address,
+            "commit_index": commit_index,
+            "term": term
+        })
+    }
+
+    /// Whether this node follows node `leader_id`, having heard from it
+    pub fn recognises_leader(&self, leader_id: u64) -> bool {
+        !self.is_leader()
+            && self.current_leader_id.load(Ordering::SeqCst) == leader_id
+            && self.known_leader_address().is_some()
+    }
+
+    /// Take in a heartbeat of node `leader_id` leading in `term`, this node being at
+    /// `our_term`; returns whether this node acknowledges the sender as its leader
+    ///
+    /// A heartbeat of an older term is refused, and so is one of this node's own term
+    /// while it leads. Any other makes the sender this node's leader. A leadership check
+    /// (`check`, see `acknowledges`) changes nothing: only followers of the sender
+    /// acknowledge it.
+    pub fn receive_heartbeat(
+        &self,
+        leader_id: u64,
+        leader_address: NodeAddress,
+        term: u64,
+        our_term: u64,
+        check: bool,
+    ) -> bool {
+        if check {
+            return term >= our_term && self.recognises_leader(leader_id);
+        }
+        if term < our_term || (term == our_term && self.is_leader()) {
+            return false;
+        }
+        if !self.recognises_leader(leader_id) {
+            log::info!("Heartbeat: updating leader to node {} ({})", leader_id, leader_address);
+            self.become_follower(leader_id, leader_address);
+        }
+        self.update_heartbeat();
+        true
+    }
+
+    /// Whether a peer's `reply` to this node's heartbeat in `term` acknowledges it as
+    /// leader of that term
+    pub fn acknowledges(&self, reply: &serde_json::Value, term: u64) -> bool {
+        reply["leader_id"].as_u64() == Some(self.node_id) && reply["term"].as_u64() == Some(term)
+    }
+
     /// Check if election should be started (heartbeat timeout exceeded)
     pub fn should_start_election(&self) -> bool {
-        if self.is_leader() {
+        if self.is_leader() || !self.is_voter() {
             return false;
         }
 
@@ -145,55 +281,62 @@ impl RaftLeadershipState {
         self.is_leader.store(true, Ordering::SeqCst);
         self.current_state.store(2, Ordering::SeqCst); // 2 = Leader
         self.current_leader_id.store(self.node_id, Ordering::SeqCst);
-        self.leader_port.store(self.self_port, Ordering::SeqCst);
+        self.set_leader_address(self.self_address.clone());
         self.update_heartbeat();
         log::info!("Node {} is now the LEADER", self.node_id);
     }
 
     /// Become a follower - called when a new leader is detected
-    pub fn become_follower(&self, new_leader_id: u64, new_leader_port: u16) {
+    pub fn become_follower(&self, new_leader_id: u64, new_leader_address: NodeAddress) {
         self.is_leader.store(false, Ordering::SeqCst);
         self.current_state.store(0, Ordering::SeqCst); // 0 = Follower
         self.current_leader_id.store(new_leader_id, Ordering::SeqCst);
-        self.leader_port.store(new_leader_port, Ordering::SeqCst);
-        self.update_heartbeat();
         log::info!(
-            "Node {} is now a FOLLOWER (leader: node {} on port {})",
+            "Node {} is now a FOLLOWER (leader: node {} at {})",
             self.node_id,
             new_leader_id,
-            new_leader_port
+            new_leader_address
         );
+        self.set_leader_address(new_leader_address);
+        self.update_heartbeat();
     }
 
     /// Start election process - find the lowest available node_id to be leader
     ///
-    /// Returns (should_become_leader, new_leader_id, new_leader_port)
-    pub async fn start_election(&self) -> (bool, u64, u16) {
+    /// Only voters stand for election. Peers are reached with `clients`; under cluster TLS,
+    /// a peer whose certificate names another node than the one it reports is left out.
+    ///
+    /// Returns (should_become_leader, new_leader_id, new_leader_address)
+    pub async fn start_election(&self, clients: &PeerClients) -> (bool, u64, NodeAddress) {
         log::debug!("Node {} starting election...", self.node_id);
         self.current_state.store(1, Ordering::SeqCst); // 1 = Candidate
 
-        let client = HttpClient::builder()
-            .timeout(Duration::from_secs(2))
-            .build()
-            .unwrap_or_else(|_| HttpClient::new());
+        let client = clients.client(Duration::from_secs(2));
 
         // Check which peers are alive
-        let mut alive_peers: Vec<(u64, u16)> = Vec::new();
+        let mut alive_peers: Vec<(u64, NodeAddress)> = Vec::new();
 
-        for peer in &self.peers {
-            let url = format!("http://{}/status", peer);
+        let own = self.self_address.to_string();
+        for peer in &self.membership().voter_peers(&own) {
+            let url = peer_url(peer, "/status");
             match client.get(&url).send().await {
                 Ok(resp) if resp.status().is_success() => {
+                    let certified = clients.node_of(&resp);
                     if let Ok(status) = resp.json::<serde_json::Value>().await {
                         if let Some(raft) = status.get("raft") {
                             let peer_id =
                                 raft.get("node_id").and_then(|v| v.as_u64()).unwrap_or(u64::MAX);
-                            let peer_port = peer
-                                .split(':')
-                                .nth(1)
-                                .and_then(|p| p.parse::<u16>().ok())
-                                .unwrap_or(0);
-                            alive_peers.push((peer_id, peer_port));
+                            if certified.is_some_and(|id| id != peer_id) {
+                                log::warn!(
+                                    "Peer {} reports node {} but its certificate is for node {:?}",
+                                    peer,
+                                    peer_id,
+                                    certified
+                                );
+                                continue;
+                            }
+                            let Ok(peer_address) = NodeAddress::parse(peer) else { continue };
+                            alive_peers.push((peer_id, peer_address));
                             log::debug!("Peer {} (node {}) is alive", peer, peer_id);
                         }
                     }
@@ -205,16 +348,16 @@ impl RaftLeadershipState {
         }
 
         // Find the lowest node_id among alive nodes (including self)
-        let mut candidates: Vec<(u64, u16)> = alive_peers;
-        candidates.push((self.node_id, self.self_port));
+        let mut candidates: Vec<(u64, NodeAddress)> = alive_peers;
+        candidates.push((self.node_id, self.self_address.clone()));
         candidates.sort_by_key(|(id, _)| *id);
 
-        let (winner_id, winner_port) = candidates[0];
+        let (winner_id, winner_address) = candidates.swap_remove(0);
         let should_become_leader = winner_id == self.node_id;
 
-        log::info!("Election result: node {} wins (port {})", winner_id, winner_port);
+        log::info!("Election result: node {} wins ({})", winner_id, winner_address);
 
-        (should_become_leader, winner_id, winner_port)
+        (should_become_leader, winner_id, winner_address)
     }
 
     /// Get time since last heartbeat
@@ -348,18 +491,21 @@ pub struct ResyncStatsJson {
 /// use lithair_core::cluster::ClusterArgs;
 ///
 /// let args = ClusterArgs::parse();
-/// let peers: Vec<String> = args.peers
-///     .unwrap_or_default()
-///     .iter()
-///     .map(|p| format!("127.0.0.1:{}", p))
-///     .collect();
-///
-/// LithairServer::new()
+/// let mut server = LithairServer::new()
 ///     .with_port(args.port)
-///     .with_raft_cluster(args.node_id, peers)
-///     .build()?
-///     .serve()
-///     .await?;
+///     .with_raft_cluster(args.node_id, args.cluster_peers());
+/// if let Some(address) = &args.address {
+///     server = server.with_node_address(address);
+/// }
+/// server.serve().await?;
+/// ```
+///
+/// On one host, `--peers 8081,8082` is enough. Across hosts, every node advertises where
+/// it is reached and lists its peers the same way:
+///
+/// ```text
+/// --node-id 1 --port 8080 --address https://db-2.internal:8443 \
+///     --peer-addresses https://db-1.internal:8443,https://db-3.internal:8443
 /// ```
 #[derive(Parser, Debug)]
 #[command(name = "lithair-cluster")]
@@ -376,6 +522,32 @@ pub struct ClusterArgs {
     /// Comma-separated list of peer ports
     #[arg(long, value_delimiter = ',')]
     pub peers: Option<Vec<u16>>,
+
+    /// Address this node advertises to peers and redirected clients
+    /// (`host:port` or `scheme://host:port`; default `http://127.0.0.1:{port}`)
+    #[arg(long)]
+    pub address: Option<String>,
+
+    /// Comma-separated list of peer addresses (`host:port` or `scheme://host:port`), for
+    /// peers on other hosts; replaces `--peers`
+    #[arg(long, value_delimiter = ',')]
+    pub peer_addresses: Option<Vec<String>>,
+}
+
+impl ClusterArgs {
+    /// Peers to pass to `with_raft_cluster`: `--peer-addresses`, else the `--peers` ports on
+    /// the loopback interface
+    pub fn cluster_peers(&self) -> Vec<String> {
+        match &self.peer_addresses {
+            Some(addresses) => addresses.clone(),
+            None => self
+                .peers
+                .iter()
+                .flatten()
+                .map(|port| NodeAddress::local(*port).authority())
+                .collect(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -397,6 +569,11 @@ mod tests {
         let state = RaftLeadershipState::new(1, 8081, vec!["127.0.0.1:8080".to_string()]);
         assert!(!state.is_leader());
         assert_eq!(state.get_current_state(), RaftNodeState::Follower);
+        // Where node 0 runs is learnt from its first heartbeat
+        assert_eq!(state.known_leader_address(), None);
+        assert!(!state.recognises_leader(0));
+        assert!(state.receive_heartbeat(0, NodeAddress::local(8080), 1, 1, false));
+        assert_eq!(state.known_leader_address(), Some(NodeAddress::local(8080)));
         assert_eq!(state.get_leader_port(), 8080);
     }
 
@@ -424,12 +601,105 @@ mod tests {
         let state = RaftLeadershipState::new(0, 8080, vec!["127.0.0.1:8081".to_string()]);
         assert!(state.is_leader());
 
-        state.become_follower(1, 8081);
+        state.become_follower(1, NodeAddress::local(8081));
         assert!(!state.is_leader());
         assert_eq!(state.get_current_state(), RaftNodeState::Follower);
         assert_eq!(state.get_leader_port(), 8081);
     }
 
+    #[test]
+    fn test_heartbeat_terms() {
+        let state = RaftLeadershipState::new(2, 8082, vec!["127.0.0.1:8080".to_string()]);
+        let node = |port| NodeAddress::local(port);
+
+        // Node 1 took over in term 2
+        assert!(state.receive_heartbeat(1, node(8081), 2, 1, false));
+        assert!(state.recognises_leader(1));
+        assert_eq!(state.get_leader_port(), 8081);
+        // The former leader, still in term 1, is refused
+        assert!(!state.receive_heartbeat(0, node(8080), 1, 2, false));
+        assert!(state.recognises_leader(1));
+
+        // A leader keeps leading against a heartbeat of its own term, not a newer one
+        let leader = RaftLeadershipState::new(0, 8080, vec!["127.0.0.1:8081".to_string()]);
+        assert!(!leader.receive_heartbeat(1, node(8081), 1, 1, false));
+        assert!(leader.is_leader());
+        assert!(leader.receive_heartbeat(1, node(8081), 2, 1, false));
+        assert!(!leader.is_leader());
+    }
+
+    #[test]
+    fn test_leadership_check_changes_nothing() {
+        let state = RaftLeadershipState::new(2, 8082, vec!["127.0.0.1:8080".to_string()]);
+        // Not acknowledged before this node heard from the leader
+        assert!(!state.receive_heartbeat(0, NodeAddress::local(8080), 1, 1, true));
+        assert_eq!(state.known_leader_address(), None);
+        assert!(state.receive_heartbeat(0, NodeAddress::local(8080), 1, 1, false));
+        let last_heartbeat = *state.last_heartbeat.lock().unwrap();
+
+        // Acknowledged for the leader this node follows only
+        assert!(state.receive_heartbeat(0, NodeAddress::local(8080), 1, 1, true));
+        assert!(!state.receive_heartbeat(1, NodeAddress::local(8081), 1, 1, true));
+        assert!(!state.receive_heartbeat(0, NodeAddress::local(8080), 1, 2, true));
+        assert!(state.recognises_leader(0));
+        assert_eq!(state.get_leader_port(), 8080);
+        assert_eq!(*state.last_heartbeat.lock().unwrap(), last_heartbeat);
+
+        // A leader that is not followed any more is not acknowledged
+        let leader = RaftLeadershipState::new(0, 8080, vec!["127.0.0.1:8082".to_string()]);
+        assert!(leader.acknowledges(&serde_json::json!({"leader_id": 0, "term": 3}), 3));
+        assert!(!leader.acknowledges(&serde_json::json!({"leader_id": 1, "term": 3}), 3));
+        assert!(!leader.acknowledges(&serde_json::json!({"leader_id": 0, "term": 4}), 3));
+        assert!(!leader.acknowledges(&serde_json::json!({"status": "ok"}), 3));
+    }
+
+    #[test]
+    fn test_advertised_addresses() {
+        // Every node on the same port: none of them is taken for node 0 by guess
+        let peers = vec!["https://db-1.internal:8443".to_string()];
+        let state = RaftLeadershipState::new(1, 8443, peers)
+            .with_self_address(NodeAddress::parse("https://db-2.internal:8443").unwrap());
+        assert_eq!(state.known_leader_address(), None);
+        assert_eq!(state.get_leader_address(), state.self_address);
+
+        let leader = NodeAddress::parse("https://db-1.internal:8443").unwrap();
+        assert!(state.receive_heartbeat(0, leader, 1, 1, false));
+        assert_eq!(state.get_leader_address().host, "db-1.internal");
+        assert_eq!(state.get_leader_address().url("/api"), "https://db-1.internal:8443/api");
+
+        state.become_leader();
+        assert_eq!(state.get_leader_address(), state.self_address);
+        assert_eq!(state.get_leader_port(), 8443);
+    }
+
+    #[test]
+    fn test_membership() {
+        let state = RaftLeadershipState::new(1, 8081, vec!["127.0.0.1:8080".to_string()])
+            .with_self_address(NodeAddress::parse("http://localhost:8081").unwrap());
+        assert_eq!(state.peers(), vec!["127.0.0.1:8080"]);
+        assert_eq!(state.membership().find("1").unwrap().address, "http://localhost:8081");
+
+        // Demoted to learner: still replicated to, but stands for no election
+        let mut config = state.membership();
+        config.learners.push(config.voters.remove(0));
+        state.set_membership(config);
+        assert!(!state.is_voter());
+        assert!(!state.should_start_election());
+        assert_eq!(state.peers(), vec!["127.0.0.1:8080"]);
+    }
+
+    #[test]
+    fn test_leader_commit() {
+        let state = RaftLeadershipState::new(1, 8081, vec!["127.0.0.1:8080".to_string()]);
+        state.record_leader_commit(7);
+        // A late, older report keeps the index but counts as contact
+        state.record_leader_commit(5);
+        let (index, age) = state.leader_commit();
+        assert_eq!(index, 7);
+        assert!(age < Duration::from_secs(1));
+        assert_eq!(state.heartbeat_message(7)["commit_index"], 7);
+    }
+
     #[test]
     fn test_heartbeat_timeout() {
         let state = RaftLeadershipState::new(1, 8081, vec!["127.0.0.1:8080".to_string()]);
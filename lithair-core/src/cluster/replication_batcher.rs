@@ -212,6 +212,11 @@ impl ReplicationBatcher {
         }
     }
 
+    /// Stop tracking a node that left the cluster
+    pub async fn remove_follower(&self, address: &str) {
+        self.followers.write().await.remove(address);
+    }
+
     /// Get follower state
     pub async fn get_follower(&self, address: &str) -> Option<Arc<FollowerState>> {
         self.followers.read().await.get(address).cloned()
@@ -138,6 +138,12 @@ where
         }
     }
 
+    /// Send replication messages with `client`, e.g. `PeerClients::client` under cluster TLS
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Add data to cache and replicate to followers if leader
     pub async fn replicate_create(&self, data: T) -> Result<()> {
         let id = self.extract_id(&data);
@@ -236,7 +242,7 @@ where
     async fn send_bulk_to_followers(&self, message: ReplicationBulkMessage<T>) -> Result<()> {
         let max_retries = 5u32;
         for peer in &self.peers {
-            let url = format!("http://{}/internal/replicate_bulk", peer);
+            let url = super::peer_url(peer, "/internal/replicate_bulk");
 
             let mut attempt = 0u32;
             loop {
@@ -439,7 +445,7 @@ where
         // Simple retry: up to 5 attempts with exponential backoff
         let max_retries = 5u32;
         for peer in &self.peers {
-            let url = format!("http://{}/internal/replicate", peer);
+            let url = super::peer_url(peer, "/internal/replicate");
 
             let mut attempt = 0u32;
             loop {
@@ -6,6 +6,7 @@
 
 use rkyv::{rancor::Error as RkyvError, Archive, Deserialize, Serialize};
 use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
@@ -32,11 +33,14 @@ pub struct SnapshotData {
     /// Model data as JSON strings keyed by model_path
     /// e.g., "/api/products" -> "[{\"id\":1,...}, {\"id\":2,...}]"
     pub models: Vec<(String, String)>, // Use Vec of tuples instead of HashMap for rkyv compatibility
+    /// Item versions as JSON objects keyed by model_path, e.g. "/api/products" ->
+    /// "{\"1\":3}"; installing the snapshot restores them as they are
+    pub versions: Vec<(String, String)>,
 }
 
 impl SnapshotData {
     pub fn new() -> Self {
-        Self { models: Vec::new() }
+        Self { models: Vec::new(), versions: Vec::new() }
     }
 
     /// Add model data to snapshot
@@ -47,6 +51,22 @@ impl SnapshotData {
         self.models.push((model_path.to_string(), json));
     }
 
+    /// Add the item versions of a model to snapshot
+    pub fn add_model_versions(&mut self, model_path: &str, versions: &HashMap<String, u64>) {
+        let json = serde_json::to_string(versions).unwrap_or_else(|_| "{}".to_string());
+        self.versions.retain(|(path, _)| path != model_path);
+        self.versions.push((model_path.to_string(), json));
+    }
+
+    /// Get the item versions of a model from snapshot (empty when not recorded)
+    pub fn get_model_versions(&self, model_path: &str) -> HashMap<String, u64> {
+        self.versions
+            .iter()
+            .find(|(path, _)| path == model_path)
+            .and_then(|(_, json)| serde_json::from_str(json).ok())
+            .unwrap_or_default()
+    }
+
     /// Get model data from snapshot
     pub fn get_model(&self, model_path: &str) -> Vec<serde_json::Value> {
         self.models
@@ -343,6 +363,7 @@ mod tests {
         let mut leader_manager = SnapshotManager::new(dir1.path()).unwrap();
         let mut data = SnapshotData::new();
         data.add_model("/api/items", &[serde_json::json!({"id": "test"})]);
+        data.add_model_versions("/api/items", &HashMap::from([("test".to_string(), 7)]));
         let meta = leader_manager.create_snapshot(1, 50, data).unwrap();
 
         // Get bytes for transfer
@@ -355,6 +376,8 @@ mod tests {
         let items = installed_data.get_model("/api/items");
         assert_eq!(items.len(), 1);
         assert_eq!(items[0]["id"], "test");
+        assert_eq!(installed_data.get_model_versions("/api/items")["test"], 7);
+        assert!(installed_data.get_model_versions("/api/other").is_empty());
     }
 
     #[test]
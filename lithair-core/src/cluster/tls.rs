@@ -0,0 +1,393 @@
+//! Mutual TLS between cluster nodes
+//!
+//! With `RaftConfig::tls` set, every node holds a certificate signed by the cluster CA
+//! and issued for its node name, `node-{id}.{node_domain}` (a DNS subject alternative
+//! name). Nodes then:
+//!
+//! - serve HTTPS with their node certificate, asking clients for a certificate without
+//!   requiring one, so public routes stay open to ordinary clients;
+//! - answer the routes only nodes call (`/_raft/append`, `/_raft/snapshot`, `/internal/*`,
+//!   heartbeats) only on connections with a client certificate the CA signed for a node,
+//!   and reject requests that claim to come from another node than that certificate's;
+//! - call their peers with their certificate, and accept a peer only if its certificate
+//!   chains to the CA and names a node, whatever host the peer is reached at.
+//!
+//! The CA, certificate and key files are read again every `reload_interval_secs`. New
+//! files apply to the next connections in both directions, so certificates (and the CA)
+//! rotate without restarting the cluster; files that fail to load leave the current
+//! ones in place.
+
+use crate::config::ClusterTlsConfig;
+use anyhow::{Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Name node `node_id`'s certificate is issued for
+pub fn node_name(node_id: u64, domain: &str) -> String {
+    format!("node-{}.{}", node_id, domain)
+}
+
+/// Node a DER certificate was issued for, from its `node-{id}.{domain}` name
+pub fn node_id_of(cert: &[u8], domain: &str) -> Option<u64> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+    let san = cert.subject_alternative_name().ok()??;
+    let suffix = format!(".{}", domain.to_ascii_lowercase());
+    san.value.general_names.iter().find_map(|name| match name {
+        x509_parser::extensions::GeneralName::DNSName(dns) => dns
+            .to_ascii_lowercase()
+            .strip_suffix(&suffix)?
+            .strip_prefix("node-")?
+            .parse()
+            .ok(),
+        _ => None,
+    })
+}
+
+/// Request extension: the node whose certificate authenticated the connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerNode(pub u64);
+
+/// Files of a `ClusterTlsConfig`, parsed
+struct Files {
+    ca: Vec<CertificateDer<'static>>,
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    /// SHA-256 of the three files, to tell when they change
+    digest: Vec<u8>,
+}
+
+impl Files {
+    fn read(config: &ClusterTlsConfig) -> Result<Self> {
+        let read = |path: &str| {
+            std::fs::read(path)
+                .with_context(|| format!("Failed to read cluster TLS file: {}", path))
+        };
+        let (ca_pem, cert_pem, key_pem) =
+            (read(&config.ca_cert_path)?, read(&config.cert_path)?, read(&config.key_path)?);
+        let mut hasher = Sha256::new();
+        for pem in [&ca_pem, &cert_pem, &key_pem] {
+            hasher.update(pem);
+        }
+        let key = rustls_pemfile::private_key(&mut key_pem.as_slice())
+            .with_context(|| format!("Failed to parse TLS key from: {}", config.key_path))?
+            .ok_or_else(|| anyhow::anyhow!("No private key found in {}", config.key_path))?;
+        Ok(Self {
+            ca: pem_certs(&ca_pem, &config.ca_cert_path)?,
+            certs: pem_certs(&cert_pem, &config.cert_path)?,
+            key,
+            digest: hasher.finalize().to_vec(),
+        })
+    }
+}
+
+fn pem_certs(pem: &[u8], path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut &pem[..])
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse TLS certificates from: {}", path))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", path);
+    }
+    Ok(certs)
+}
+
+/// TLS configurations built from one version of the files
+struct Material {
+    acceptor: tokio_rustls::TlsAcceptor,
+    client_config: rustls::ClientConfig,
+    digest: Vec<u8>,
+}
+
+impl Material {
+    fn build(node_id: u64, config: &ClusterTlsConfig, files: Files) -> Result<Self> {
+        let own = node_id_of(&files.certs[0], &config.node_domain);
+        if own != Some(node_id) {
+            anyhow::bail!(
+                "{} is not issued for {}",
+                config.cert_path,
+                node_name(node_id, &config.node_domain)
+            );
+        }
+
+        let mut roots = rustls::RootCertStore::empty();
+        for ca in files.ca {
+            roots.add(ca).context("Invalid cluster CA certificate")?;
+        }
+        let roots = Arc::new(roots);
+
+        // Clients without a certificate are let in: only node routes require one
+        let client_verifier = rustls::server::WebPkiClientVerifier::builder(Arc::clone(&roots))
+            .allow_unauthenticated()
+            .build()
+            .context("Invalid cluster CA certificate")?;
+        let server = rustls::ServerConfig::builder()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(files.certs.clone(), files.key.clone_key())
+            .context("Invalid node certificate/key pair")?;
+
+        let server_verifier = rustls::client::WebPkiServerVerifier::builder(roots)
+            .build()
+            .context("Invalid cluster CA certificate")?;
+        let verifier = NodeVerifier { inner: server_verifier, domain: config.node_domain.clone() };
+        let client_config = rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_client_auth_cert(files.certs, files.key)
+            .context("Invalid node certificate/key pair")?;
+
+        Ok(Self {
+            acceptor: tokio_rustls::TlsAcceptor::from(Arc::new(server)),
+            client_config,
+            digest: files.digest,
+        })
+    }
+}
+
+/// Accepts a server certificate the cluster CA signed for a node name, whatever host the
+/// node was reached at
+#[derive(Debug)]
+struct NodeVerifier {
+    inner: Arc<rustls::client::WebPkiServerVerifier>,
+    domain: String,
+}
+
+impl ServerCertVerifier for NodeVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let name = node_id_of(end_entity, &self.domain)
+            .and_then(|id| ServerName::try_from(node_name(id, &self.domain)).ok())
+            .ok_or(rustls::Error::InvalidCertificate(rustls::CertificateError::NotValidForName))?;
+        self.inner
+            .verify_server_cert(end_entity, intermediates, &name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Certificates of this node and of the cluster CA, reloaded when their files change
+pub struct ClusterTls {
+    node_id: u64,
+    config: ClusterTlsConfig,
+    material: RwLock<Arc<Material>>,
+    /// Bumped on every reload, so clients built before it are rebuilt
+    generation: AtomicU64,
+}
+
+impl ClusterTls {
+    /// Load the files; the node certificate must be issued for `node_id`'s name
+    pub fn load(node_id: u64, config: ClusterTlsConfig) -> Result<Self> {
+        let material = Material::build(node_id, &config, Files::read(&config)?)?;
+        Ok(Self {
+            node_id,
+            config,
+            material: RwLock::new(Arc::new(material)),
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    pub fn config(&self) -> &ClusterTlsConfig {
+        &self.config
+    }
+
+    fn current(&self) -> Arc<Material> {
+        Arc::clone(&self.material.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Acceptor for the next incoming connection
+    pub fn acceptor(&self) -> tokio_rustls::TlsAcceptor {
+        self.current().acceptor.clone()
+    }
+
+    /// Node a connection's client certificate names; the acceptor already checked that
+    /// the CA signed it
+    pub fn peer_node(&self, certs: Option<&[CertificateDer<'_>]>) -> Option<PeerNode> {
+        let leaf = certs?.first()?;
+        node_id_of(leaf, &self.config.node_domain).map(PeerNode)
+    }
+
+    /// Read the files again and switch to them if they changed; true when they did
+    pub fn reload(&self) -> Result<bool> {
+        let files = Files::read(&self.config)?;
+        if files.digest == self.current().digest {
+            return Ok(false);
+        }
+        let material = Material::build(self.node_id, &self.config, files)?;
+        *self.material.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(material);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// Look for rotated certificates every `reload_interval_secs`
+    pub fn spawn_reload_task(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let tls = Arc::clone(self);
+        tokio::spawn(async move {
+            let period = Duration::from_secs(tls.config.reload_interval_secs);
+            loop {
+                tokio::time::sleep(period).await;
+                match tls.reload() {
+                    Ok(true) => log::info!("Cluster TLS certificates reloaded"),
+                    Ok(false) => {}
+                    Err(e) => {
+                        log::warn!("Cluster TLS reload failed, keeping current files: {:#}", e)
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// HTTP clients for calls to other nodes
+///
+/// Under cluster TLS they present this node's certificate and only accept peers the
+/// cluster CA signed, and are rebuilt after the certificates rotate. They never follow
+/// redirects.
+#[derive(Default)]
+pub struct PeerClients {
+    tls: Option<Arc<ClusterTls>>,
+    /// Client per timeout, with the certificate generation it was built with
+    clients: Mutex<HashMap<Duration, (u64, reqwest::Client)>>,
+}
+
+impl PeerClients {
+    pub fn new(tls: Option<Arc<ClusterTls>>) -> Self {
+        Self { tls, clients: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn tls(&self) -> Option<&Arc<ClusterTls>> {
+        self.tls.as_ref()
+    }
+
+    /// Client whose requests time out after `timeout`
+    pub fn client(&self, timeout: Duration) -> reqwest::Client {
+        let generation = self.tls.as_ref().map_or(0, |tls| tls.generation.load(Ordering::SeqCst));
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((built, client)) = clients.get(&timeout) {
+            if *built == generation {
+                return client.clone();
+            }
+        }
+        let mut builder = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(tls) = &self.tls {
+            builder = builder
+                .tls_backend_preconfigured(tls.current().client_config.clone())
+                .tls_info(true);
+        }
+        let client = builder.build().unwrap_or_else(|e| {
+            // Without the node certificate, peers under cluster TLS refuse the calls
+            log::error!("Failed to build peer HTTP client: {}", e);
+            reqwest::Client::new()
+        });
+        clients.insert(timeout, (generation, client.clone()));
+        client
+    }
+
+    /// Node that answered `response`, from its certificate; None without cluster TLS
+    pub fn node_of(&self, response: &reqwest::Response) -> Option<u64> {
+        let tls = self.tls.as_ref()?;
+        let info = response.extensions().get::<reqwest::tls::TlsInfo>()?;
+        node_id_of(info.peer_certificate()?, &tls.config.node_domain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed CA and a certificate it signs for each of `names`, as PEM files
+    fn write_pki(dir: &std::path::Path, names: &[&str]) -> (String, Vec<(String, String)>) {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let ca_path = dir.join("ca.pem");
+        std::fs::write(&ca_path, ca.pem()).unwrap();
+
+        let nodes = names
+            .iter()
+            .map(|name| {
+                let key = rcgen::KeyPair::generate().unwrap();
+                let params = rcgen::CertificateParams::new(vec![name.to_string()]).unwrap();
+                let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+                let cert_path = dir.join(format!("{}.pem", name));
+                let key_path = dir.join(format!("{}-key.pem", name));
+                std::fs::write(&cert_path, cert.pem()).unwrap();
+                std::fs::write(&key_path, key.serialize_pem()).unwrap();
+                (cert_path.display().to_string(), key_path.display().to_string())
+            })
+            .collect();
+        (ca_path.display().to_string(), nodes)
+    }
+
+    #[test]
+    fn test_node_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let (ca, nodes) = write_pki(dir.path(), &["node-2.lithair.cluster", "node-3.other.domain"]);
+        let (cert, key) = &nodes[0];
+
+        let pem = std::fs::read(cert).unwrap();
+        let der = pem_certs(&pem, cert).unwrap();
+        assert_eq!(node_id_of(&der[0], "lithair.cluster"), Some(2));
+        assert_eq!(node_id_of(&der[0], "other.domain"), None);
+
+        let config = ClusterTlsConfig::new(&ca, cert, key);
+        let tls = ClusterTls::load(2, config.clone()).unwrap();
+        assert_eq!(tls.peer_node(Some(der.as_slice())), Some(PeerNode(2)));
+        assert_eq!(tls.peer_node(None), None);
+        // A certificate for another node, or another domain, is not this node's
+        assert!(ClusterTls::load(1, config).is_err());
+        let (cert, key) = &nodes[1];
+        assert!(ClusterTls::load(3, ClusterTlsConfig::new(&ca, cert, key)).is_err());
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let (ca, nodes) = write_pki(dir.path(), &["node-1.lithair.cluster"]);
+        let (cert, key) = &nodes[0];
+        let tls = ClusterTls::load(1, ClusterTlsConfig::new(&ca, cert, key)).unwrap();
+        assert!(!tls.reload().unwrap());
+
+        // Rotated: a new CA and node certificate
+        write_pki(dir.path(), &["node-1.lithair.cluster"]);
+        assert!(tls.reload().unwrap());
+        assert_eq!(tls.generation.load(Ordering::SeqCst), 1);
+
+        // A broken file keeps the current certificates
+        std::fs::write(cert, "not a certificate").unwrap();
+        assert!(tls.reload().is_err());
+        assert_eq!(tls.generation.load(Ordering::SeqCst), 1);
+    }
+}
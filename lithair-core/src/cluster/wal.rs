@@ -21,6 +21,7 @@ use std::time::{Duration, Instant};
 use tokio::sync::{Notify, RwLock};
 
 use super::consensus_log::{CrudOperation, LogEntry, LogId};
+use super::membership::MembershipConfig;
 
 /// WAL entry header - fixed size for easy seeking
 const WAL_HEADER_SIZE: usize = 16; // 8 bytes length + 8 bytes checksum
@@ -63,22 +64,54 @@ pub enum WalOperation {
         migration_type: String, // "begin", "step", "commit", "rollback"
         payload: String,        // JSON-serialized migration data
     },
+    /// Operations of a `/_batch`, as a JSON array of `CrudOperation`
+    Batch {
+        batch_id: String,
+        operations: String,
+    },
+    /// Cluster configuration, as JSON; the last one is restored on restart
+    Membership {
+        config: String,
+    },
+    Restore {
+        model_path: String,
+        id: String,
+    },
+    Patch {
+        model_path: String,
+        id: String,
+        diff: String,
+    },
 }
 
 impl From<&CrudOperation> for WalOperation {
+    /// `expected_version` preconditions are not kept: entries are only read back by
+    /// `to_crud_operation`, which nothing replays through `apply_crud_operation`
     fn from(op: &CrudOperation) -> Self {
         match op {
             CrudOperation::Create { model_path, data } => {
                 WalOperation::Create { model_path: model_path.clone(), data: data.to_string() }
             }
-            CrudOperation::Update { model_path, id, data } => WalOperation::Update {
+            CrudOperation::Update { model_path, id, data, .. } => WalOperation::Update {
                 model_path: model_path.clone(),
                 id: id.clone(),
                 data: data.to_string(),
             },
-            CrudOperation::Delete { model_path, id } => {
+            CrudOperation::Delete { model_path, id, .. } => {
                 WalOperation::Delete { model_path: model_path.clone(), id: id.clone() }
             }
+            CrudOperation::Patch { model_path, id, diff, .. } => WalOperation::Patch {
+                model_path: model_path.clone(),
+                id: id.clone(),
+                diff: diff.to_string(),
+            },
+            CrudOperation::Restore { model_path, id } => {
+                WalOperation::Restore { model_path: model_path.clone(), id: id.clone() }
+            }
+            CrudOperation::Batch { batch_id, operations } => WalOperation::Batch {
+                batch_id: batch_id.clone(),
+                operations: serde_json::to_string(operations).unwrap_or_else(|_| "[]".into()),
+            },
             CrudOperation::MigrationBegin { from_version, to_version, migration_id } => {
                 WalOperation::Migration {
                     migration_type: "begin".to_string(),
@@ -120,6 +153,9 @@ impl From<&CrudOperation> for WalOperation {
                     .to_string(),
                 }
             }
+            CrudOperation::MembershipChange { config } => WalOperation::Membership {
+                config: serde_json::to_string(config).unwrap_or_else(|_| "{}".into()),
+            },
         }
     }
 }
@@ -135,10 +171,29 @@ impl WalOperation {
                 model_path: model_path.clone(),
                 id: id.clone(),
                 data: serde_json::from_str(data).unwrap_or(serde_json::Value::Null),
+                expected_version: None,
             },
-            WalOperation::Delete { model_path, id } => {
-                CrudOperation::Delete { model_path: model_path.clone(), id: id.clone() }
+            WalOperation::Delete { model_path, id } => CrudOperation::Delete {
+                model_path: model_path.clone(),
+                id: id.clone(),
+                expected_version: None,
+            },
+            WalOperation::Patch { model_path, id, diff } => CrudOperation::Patch {
+                model_path: model_path.clone(),
+                id: id.clone(),
+                diff: serde_json::from_str(diff).unwrap_or(serde_json::Value::Null),
+                expected_version: None,
+            },
+            WalOperation::Restore { model_path, id } => {
+                CrudOperation::Restore { model_path: model_path.clone(), id: id.clone() }
             }
+            WalOperation::Batch { batch_id, operations } => CrudOperation::Batch {
+                batch_id: batch_id.clone(),
+                operations: serde_json::from_str(operations).unwrap_or_default(),
+            },
+            WalOperation::Membership { config } => CrudOperation::MembershipChange {
+                config: serde_json::from_str(config).unwrap_or_default(),
+            },
             WalOperation::Migration { migration_type, payload } => {
                 let json: serde_json::Value =
                     serde_json::from_str(payload).unwrap_or(serde_json::Value::Null);
@@ -582,6 +637,14 @@ impl WriteAheadLog {
         Ok(entries)
     }
 
+    /// Configuration of the last membership change in the WAL, to restore on restart
+    pub fn last_membership(&self) -> std::io::Result<Option<MembershipConfig>> {
+        Ok(self.read_all()?.into_iter().rev().find_map(|entry| match entry.operation {
+            CrudOperation::MembershipChange { config } => Some(config),
+            _ => None,
+        }))
+    }
+
     /// Read entries from a specific index
     pub fn read_from(&self, from_index: u64) -> std::io::Result<Vec<LogEntry>> {
         let all_entries = self.read_all()?;
@@ -769,6 +832,37 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_wal_membership() {
+        let dir = tempdir().unwrap();
+        let wal_path = dir.path().join("test_membership.wal");
+        let own = crate::cluster::Member::new(Some(0), "http://127.0.0.1:8080");
+        let config = MembershipConfig::initial(own, &["127.0.0.1:8081".to_string()]);
+
+        {
+            let wal = WriteAheadLog::new(&wal_path).unwrap();
+            assert_eq!(wal.last_membership().unwrap(), None);
+            for (i, operation) in [
+                CrudOperation::MembershipChange { config: MembershipConfig::default() },
+                CrudOperation::MembershipChange { config: config.clone() },
+                CrudOperation::Create {
+                    model_path: "/api/test".to_string(),
+                    data: serde_json::json!({"id": 1}),
+                },
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let log_id = LogId::new(1, i as u64 + 1);
+                wal.append(&LogEntry { log_id, operation, timestamp_ms: 0 }).await.unwrap();
+            }
+        }
+
+        // The last configuration survives a restart
+        let wal = WriteAheadLog::new(&wal_path).unwrap();
+        assert_eq!(wal.last_membership().unwrap(), Some(config));
+    }
+
     #[tokio::test]
     async fn test_wal_truncate() {
         let dir = tempdir().unwrap();
@@ -46,7 +46,7 @@ pub use admin::AdminConfig;
 pub use frontend::FrontendConfig;
 pub use logging::LoggingConfig;
 pub use performance::PerformanceConfig;
-pub use raft::RaftConfig;
+pub use raft::{ClusterTlsConfig, RaftConfig};
 pub use rbac::RbacConfig;
 pub use replication::ReplicationConfig;
 pub use server::ServerConfig;
@@ -23,6 +23,95 @@ pub struct RaftConfig {
     pub heartbeat_interval_secs: u64,
     /// Election timeout in seconds (followers start election if no heartbeat)
     pub election_timeout_secs: u64,
+    /// Address this node advertises to peers and redirected clients
+    /// (`scheme://host:port`); None = `http://127.0.0.1:{port}`
+    #[serde(default)]
+    pub advertise_address: Option<String>,
+    /// Followers send writes to the leader and relay its response, instead of
+    /// redirecting the client with a 307
+    #[serde(default)]
+    pub forward_writes: bool,
+    /// Timeout of one forwarded write, in milliseconds
+    #[serde(default = "default_forward_timeout_ms")]
+    pub forward_timeout_ms: u64,
+    /// Retries of a forwarded write that found no leader (connection failed, election)
+    #[serde(default = "default_forward_retries")]
+    pub forward_retries: u32,
+    /// Mutual TLS between nodes; None = plain HTTP (with the optional token)
+    #[serde(default)]
+    pub tls: Option<ClusterTlsConfig>,
+    /// Join a running cluster as a learner through the peers, instead of booting it
+    #[serde(default)]
+    pub join: bool,
+    /// How long a read asking for `linearizable` or `bounded` consistency waits for this
+    /// node to catch up, in milliseconds, before answering 503
+    #[serde(default = "default_read_timeout_ms")]
+    pub read_timeout_ms: u64,
+}
+
+/// Certificates nodes authenticate each other with (see `cluster::tls`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterTlsConfig {
+    /// PEM file of the cluster CA that signs every node certificate
+    pub ca_cert_path: String,
+    /// PEM file of this node's certificate (and intermediates)
+    pub cert_path: String,
+    /// PEM file of this node's private key
+    pub key_path: String,
+    /// Domain of node names: node N's certificate is issued for `node-N.{domain}`
+    #[serde(default = "default_node_domain")]
+    pub node_domain: String,
+    /// How often the files are checked for new certificates, in seconds
+    #[serde(default = "default_tls_reload_interval")]
+    pub reload_interval_secs: u64,
+}
+
+impl ClusterTlsConfig {
+    pub fn new(
+        ca_cert_path: impl Into<String>,
+        cert_path: impl Into<String>,
+        key_path: impl Into<String>,
+    ) -> Self {
+        Self {
+            ca_cert_path: ca_cert_path.into(),
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            node_domain: default_node_domain(),
+            reload_interval_secs: default_tls_reload_interval(),
+        }
+    }
+
+    /// Set the domain of node names
+    pub fn with_node_domain(mut self, domain: impl Into<String>) -> Self {
+        self.node_domain = domain.into();
+        self
+    }
+
+    /// Set how often certificates are checked for rotation
+    pub fn with_reload_interval(mut self, secs: u64) -> Self {
+        self.reload_interval_secs = secs;
+        self
+    }
+}
+
+fn default_node_domain() -> String {
+    "lithair.cluster".to_string()
+}
+
+fn default_tls_reload_interval() -> u64 {
+    30
+}
+
+fn default_forward_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_forward_retries() -> u32 {
+    3
+}
+
+fn default_read_timeout_ms() -> u64 {
+    1000
 }
 
 impl std::fmt::Debug for RaftConfig {
@@ -34,6 +123,13 @@ impl std::fmt::Debug for RaftConfig {
             .field("auth_token", &self.auth_token.as_ref().map(|_| "[REDACTED]"))
             .field("heartbeat_interval_secs", &self.heartbeat_interval_secs)
             .field("election_timeout_secs", &self.election_timeout_secs)
+            .field("advertise_address", &self.advertise_address)
+            .field("forward_writes", &self.forward_writes)
+            .field("forward_timeout_ms", &self.forward_timeout_ms)
+            .field("forward_retries", &self.forward_retries)
+            .field("tls", &self.tls)
+            .field("join", &self.join)
+            .field("read_timeout_ms", &self.read_timeout_ms)
             .finish()
     }
 }
@@ -47,6 +143,13 @@ impl Default for RaftConfig {
             auth_token: None,
             heartbeat_interval_secs: 2,
             election_timeout_secs: 5,
+            advertise_address: None,
+            forward_writes: false,
+            forward_timeout_ms: default_forward_timeout_ms(),
+            forward_retries: default_forward_retries(),
+            tls: None,
+            join: false,
+            read_timeout_ms: default_read_timeout_ms(),
         }
     }
 }
@@ -82,6 +185,48 @@ impl RaftConfig {
         self
     }
 
+    /// Set the address this node advertises (`https://db-1.internal:8443`)
+    pub fn with_advertise_address(mut self, address: impl Into<String>) -> Self {
+        self.advertise_address = Some(address.into());
+        self
+    }
+
+    /// Forward follower writes to the leader instead of redirecting them
+    pub fn with_forward_writes(mut self, enabled: bool) -> Self {
+        self.forward_writes = enabled;
+        self
+    }
+
+    /// Set the timeout of one forwarded write
+    pub fn with_forward_timeout(mut self, millis: u64) -> Self {
+        self.forward_timeout_ms = millis;
+        self
+    }
+
+    /// Set how many times a forwarded write that found no leader is retried
+    pub fn with_forward_retries(mut self, retries: u32) -> Self {
+        self.forward_retries = retries;
+        self
+    }
+
+    /// Authenticate and encrypt traffic between nodes with mutual TLS
+    pub fn with_tls(mut self, tls: ClusterTlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Join a running cluster as a learner instead of booting with the peers
+    pub fn with_join(mut self, join: bool) -> Self {
+        self.join = join;
+        self
+    }
+
+    /// Set how long a consistent read waits for this node to catch up
+    pub fn with_read_timeout(mut self, millis: u64) -> Self {
+        self.read_timeout_ms = millis;
+        self
+    }
+
     /// Apply environment variables
     pub fn apply_env_vars(&mut self) {
         if let Ok(enabled) = env::var("LITHAIR_RAFT_ENABLED") {
@@ -110,6 +255,51 @@ impl RaftConfig {
                 self.election_timeout_secs = secs;
             }
         }
+
+        if let Ok(address) = env::var("LITHAIR_RAFT_ADVERTISE_ADDRESS") {
+            if !address.is_empty() {
+                self.advertise_address = Some(address);
+            }
+        }
+
+        if let Ok(forward) = env::var("LITHAIR_RAFT_FORWARD_WRITES") {
+            self.forward_writes = forward.parse().unwrap_or(false);
+        }
+
+        if let Ok(timeout) = env::var("LITHAIR_RAFT_FORWARD_TIMEOUT_MS") {
+            if let Ok(millis) = timeout.parse() {
+                self.forward_timeout_ms = millis;
+            }
+        }
+
+        if let Ok(retries) = env::var("LITHAIR_RAFT_FORWARD_RETRIES") {
+            if let Ok(retries) = retries.parse() {
+                self.forward_retries = retries;
+            }
+        }
+
+        if let Ok(join) = env::var("LITHAIR_RAFT_JOIN") {
+            self.join = join.parse().unwrap_or(false);
+        }
+
+        if let Ok(timeout) = env::var("LITHAIR_RAFT_READ_TIMEOUT_MS") {
+            if let Ok(millis) = timeout.parse() {
+                self.read_timeout_ms = millis;
+            }
+        }
+
+        // Cluster TLS needs all three files
+        if let (Ok(ca), Ok(cert), Ok(key)) = (
+            env::var("LITHAIR_RAFT_TLS_CA"),
+            env::var("LITHAIR_RAFT_TLS_CERT"),
+            env::var("LITHAIR_RAFT_TLS_KEY"),
+        ) {
+            let mut tls = ClusterTlsConfig::new(ca, cert, key);
+            if let Ok(domain) = env::var("LITHAIR_RAFT_TLS_NODE_DOMAIN") {
+                tls.node_domain = domain;
+            }
+            self.tls = Some(tls);
+        }
     }
 
     /// Get the full path for the leader endpoint
@@ -187,6 +377,28 @@ impl RaftConfig {
         if self.heartbeat_interval_secs >= self.election_timeout_secs {
             anyhow::bail!("Raft heartbeat_interval_secs must be less than election_timeout_secs");
         }
+        if let Some(address) = &self.advertise_address {
+            if let Err(e) = crate::cluster::NodeAddress::parse(address) {
+                anyhow::bail!("Raft advertise_address is invalid: {}", e);
+            }
+        }
+        if self.forward_writes && self.forward_timeout_ms == 0 {
+            anyhow::bail!("Raft forward_timeout_ms must be greater than 0");
+        }
+        if self.read_timeout_ms == 0 {
+            anyhow::bail!("Raft read_timeout_ms must be greater than 0");
+        }
+        if let Some(tls) = &self.tls {
+            if tls.ca_cert_path.is_empty() || tls.cert_path.is_empty() || tls.key_path.is_empty() {
+                anyhow::bail!("Raft tls needs ca_cert_path, cert_path and key_path");
+            }
+            if tls.node_domain.is_empty() || tls.node_domain.starts_with('.') {
+                anyhow::bail!("Raft tls node_domain is invalid: `{}`", tls.node_domain);
+            }
+            if tls.reload_interval_secs == 0 {
+                anyhow::bail!("Raft tls reload_interval_secs must be greater than 0");
+            }
+        }
         Ok(())
     }
 }
@@ -201,6 +413,8 @@ mod tests {
         assert_eq!(config.path, "/raft");
         assert!(!config.auth_required);
         assert!(config.auth_token.is_none());
+        assert_eq!(config.read_timeout_ms, 1000);
+        assert!(config.clone().with_read_timeout(0).validate().is_err());
     }
 
     #[test]
@@ -259,6 +473,42 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_validate_advertise_address() {
+        let config = RaftConfig::new().with_advertise_address("https://db-1.internal:8443");
+        assert!(config.validate().is_ok());
+        let config = RaftConfig::new().with_advertise_address("db-1.internal");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_forward_writes() {
+        let config: RaftConfig = serde_json::from_str(
+            r#"{"enabled": true, "path": "/raft", "auth_required": false,
+                "heartbeat_interval_secs": 2, "election_timeout_secs": 5}"#,
+        )
+        .unwrap();
+        assert!(!config.forward_writes);
+        assert_eq!(config.forward_timeout_ms, 5000);
+        assert_eq!(config.forward_retries, 3);
+
+        let config = RaftConfig::new().with_forward_writes(true).with_forward_timeout(0);
+        assert!(config.validate().is_err());
+        assert!(config.with_forward_timeout(2000).validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_tls() {
+        let tls = ClusterTlsConfig::new("ca.pem", "node.pem", "node-key.pem");
+        assert_eq!(tls.node_domain, "lithair.cluster");
+        assert!(RaftConfig::new().with_tls(tls.clone()).validate().is_ok());
+        let config = RaftConfig::new().with_tls(tls.clone().with_node_domain(""));
+        assert!(config.validate().is_err());
+        let config =
+            RaftConfig::new().with_tls(ClusterTlsConfig { key_path: String::new(), ..tls });
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_matches_path() {
         let config = RaftConfig::new().with_path("/cluster/raft");
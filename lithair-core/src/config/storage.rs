@@ -13,7 +13,7 @@ pub enum SchemaMigrationMode {
     Warn,
     /// Fail startup if breaking schema changes detected
     Strict,
-    /// Automatically save new schema (no actual data migration yet)
+    /// Automatically save new schema; data is migrated on replay by registered upcasters
     Auto,
     /// Require manual approval for all changes (creates pending, even in standalone)
     Manual,
@@ -22,8 +22,20 @@ pub enum SchemaMigrationMode {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageConfig {
     pub data_dir: String,
+    /// Events between snapshots of each declarative model; 0 disables snapshots
     pub snapshot_interval: usize,
+    /// Truncate a model's event log once its snapshot is written
+    ///
+    /// Entity history and hash-chain verification then only cover events written after
+    /// the latest snapshot; turn it off to keep the full log.
     pub compaction_enabled: bool,
+    /// Seconds between retention runs expiring records and trimming field history per
+    /// `#[lifecycle(retention, versioned)]`; 0 disables them
+    ///
+    /// Raft clusters do not run them: the consensus log and the other nodes would keep
+    /// what one node purged.
+    #[serde(default = "default_retention_interval")]
+    pub retention_interval_secs: u64,
     pub backup_enabled: bool,
     /// Enable schema validation at startup
     #[serde(default = "default_schema_validation")]
@@ -37,12 +49,17 @@ fn default_schema_validation() -> bool {
     true
 }
 
+fn default_retention_interval() -> u64 {
+    3600
+}
+
 impl Default for StorageConfig {
     fn default() -> Self {
         Self {
             data_dir: "./data".to_string(),
             snapshot_interval: 1000,
             compaction_enabled: true,
+            retention_interval_secs: default_retention_interval(),
             backup_enabled: false,
             schema_validation_enabled: true,
             schema_migration_mode: SchemaMigrationMode::Warn,
@@ -58,6 +75,19 @@ impl StorageConfig {
         if let Ok(dir) = env::var("LT_DATA_DIR") {
             self.data_dir = dir;
         }
+        if let Ok(val) = env::var("LT_SNAPSHOT_INTERVAL") {
+            if let Ok(interval) = val.parse() {
+                self.snapshot_interval = interval;
+            }
+        }
+        if let Ok(val) = env::var("LT_COMPACTION_ENABLED") {
+            self.compaction_enabled = val == "1" || val.eq_ignore_ascii_case("true");
+        }
+        if let Ok(val) = env::var("LT_RETENTION_INTERVAL") {
+            if let Ok(secs) = val.parse() {
+                self.retention_interval_secs = secs;
+            }
+        }
         if let Ok(val) = env::var("LT_SCHEMA_VALIDATION") {
             self.schema_validation_enabled = val.parse().unwrap_or(true);
         }
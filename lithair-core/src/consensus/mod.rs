@@ -34,9 +34,23 @@ pub enum CrudOperation<T>
 where
     T: Serialize + Clone,
 {
-    Create { item: T, primary_key: String },
-    Update { item: T, primary_key: String },
-    Delete { primary_key: String },
+    Create {
+        item: T,
+        primary_key: String,
+    },
+    Update {
+        item: T,
+        primary_key: String,
+        /// Version the item must still be at (`If-Match`); every node checks it when
+        /// applying the operation and skips the write on mismatch
+        #[serde(default)]
+        expected_version: Option<u64>,
+    },
+    Delete {
+        primary_key: String,
+        #[serde(default)]
+        expected_version: Option<u64>,
+    },
 }
 
 /// Lithair-specific AppData for HTTP replication
@@ -236,12 +250,23 @@ where
     }
 
     /// Propose update through HTTP consensus (HYPER-based replication)
-    pub async fn propose_update(&self, item: T, primary_key: String) -> anyhow::Result<()> {
+    ///
+    /// With `expected_version`, nodes where the item moved past that version skip it.
+    pub async fn propose_update(
+        &self,
+        item: T,
+        primary_key: String,
+        expected_version: Option<u64>,
+    ) -> anyhow::Result<()> {
         match &self.http_replicator {
             Some(replicator) => {
                 log::debug!("HYPER: Proposing UPDATE operation through HTTP replication...");
 
-                let operation = CrudOperation::Update { item, primary_key: primary_key.clone() };
+                let operation = CrudOperation::Update {
+                    item,
+                    primary_key: primary_key.clone(),
+                    expected_version,
+                };
 
                 let app_data = LithairAppData {
                     operation,
@@ -267,12 +292,21 @@ where
     }
 
     /// Propose delete through HTTP consensus (HYPER-based replication)
-    pub async fn propose_delete(&self, primary_key: String) -> anyhow::Result<()> {
+    ///
+    /// With `expected_version`, nodes where the item moved past that version skip it.
+    pub async fn propose_delete(
+        &self,
+        primary_key: String,
+        expected_version: Option<u64>,
+    ) -> anyhow::Result<()> {
         match &self.http_replicator {
             Some(replicator) => {
                 log::debug!("HYPER: Proposing DELETE operation through HTTP replication...");
 
-                let operation = CrudOperation::<T>::Delete { primary_key: primary_key.clone() };
+                let operation = CrudOperation::<T>::Delete {
+                    primary_key: primary_key.clone(),
+                    expected_version,
+                };
 
                 let app_data = LithairAppData {
                     operation,
@@ -481,6 +515,8 @@ impl HyperReplicationCoordinator {
             // Hash chain fields - computed automatically by EventStore when enabled
             event_hash: None,
             previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
         };
 
         {
@@ -77,12 +77,21 @@ pub struct EventEnvelope {
     pub aggregate_id: Option<String>,
     /// SHA256 hash of this event's content (for tamper detection)
     /// Computed from: event_type + event_id + timestamp + payload + previous_hash
+    /// (+ schema_version and aggregate_version when set)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub event_hash: Option<String>,
     /// SHA256 hash of the previous event in the chain (None for genesis event)
     /// Forms a hash chain for tamper-evident audit trail
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub previous_hash: Option<String>,
+    /// `ModelSpec.version` of the model that wrote the payload, used to pick the
+    /// upcasters that run on replay (None for events predating schema versions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u32>,
+    /// Version of the aggregate after this event (the item's `ETag`), restored on
+    /// replay (None for events predating item versions)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregate_version: Option<u64>,
 }
 
 impl EventEnvelope {
@@ -103,6 +112,8 @@ impl EventEnvelope {
             aggregate_id,
             event_hash: None,
             previous_hash,
+            schema_version: None,
+            aggregate_version: None,
         };
         // Compute and set the hash after all fields are populated
         envelope.event_hash = Some(envelope.compute_hash());
@@ -131,6 +142,15 @@ impl EventEnvelope {
         if let Some(ref prev) = self.previous_hash {
             hasher.update(prev.as_bytes());
         }
+        // Only hashed when present so chains written before versions stay valid
+        if let Some(version) = self.schema_version {
+            hasher.update(b"|v");
+            hasher.update(version.to_le_bytes());
+        }
+        if let Some(version) = self.aggregate_version {
+            hasher.update(b"|a");
+            hasher.update(version.to_le_bytes());
+        }
 
         let result = hasher.finalize();
         hex::encode(result)
@@ -565,10 +585,13 @@ impl EventStore {
 
     /// Flush the buffered writer and optionally fsync
     pub fn flush_events(&mut self) -> EngineResult<()> {
-        match &mut self.backend {
+        let start = std::time::Instant::now();
+        let result = match &mut self.backend {
             EventStoreBackend::Single(storage) => storage.flush_events(),
             EventStoreBackend::Multi(multi_store) => multi_store.flush_all(),
-        }
+        };
+        crate::metrics::global().record_flush(start.elapsed());
+        result
     }
 
     /// Truncate the events log file (compaction)
@@ -586,6 +609,50 @@ impl EventStore {
         Ok(())
     }
 
+    /// Replace the whole log with `events`, re-chaining their hashes
+    ///
+    /// Used to purge events (retention). With the hash chain enabled, each event is
+    /// re-linked to the one before it and rehashed; the first keeps the `previous_hash`
+    /// the old log started from, so a log that continues a compacted one still verifies.
+    pub fn rewrite_events(&mut self, mut events: Vec<EventEnvelope>) -> EngineResult<()> {
+        if self.binary_mode {
+            return Err(EngineError::InvalidOperation(
+                "rewrite_events not supported in binary mode".to_string(),
+            ));
+        }
+        let anchor = self.get_all_envelopes()?.first().and_then(|e| e.previous_hash.clone());
+        let EventStoreBackend::Single(storage) = &mut self.backend else {
+            return Err(EngineError::InvalidOperation(
+                "rewrite_events not supported in multi-file mode".to_string(),
+            ));
+        };
+
+        let mut last_hash = anchor;
+        if self.enable_hash_chain {
+            for envelope in &mut events {
+                envelope.previous_hash = last_hash.take();
+                envelope.event_hash = Some(envelope.compute_hash());
+                last_hash = envelope.event_hash.clone();
+            }
+        } else {
+            last_hash = events.last().and_then(|e| e.event_hash.clone());
+        }
+        let lines = events
+            .iter()
+            .map(|e| {
+                serde_json::to_string(e).map_err(|e| {
+                    EngineError::SerializationError(format!("Failed to serialize envelope: {}", e))
+                })
+            })
+            .collect::<EngineResult<Vec<_>>>()?;
+        storage.rewrite_events(&lines)?;
+
+        self.events_count = events.len();
+        self.pending_since_flush = 0;
+        self.last_event_hash = last_hash;
+        Ok(())
+    }
+
     /// Load a state snapshot
     pub fn load_snapshot(&self) -> EngineResult<Option<String>> {
         match &self.backend {
@@ -897,6 +964,8 @@ mod tests {
             aggregate_id: None,
             event_hash: None,
             previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
         };
 
         // Legacy events should be considered valid (no hash to verify)
@@ -908,5 +977,70 @@ mod tests {
 
         assert_eq!(deserialized.event_id, "legacy-001");
         assert!(deserialized.event_hash.is_none());
+        assert!(!json.contains("schema_version"));
+    }
+
+    #[test]
+    fn test_schema_version_is_hashed_when_present() {
+        let unversioned = EventEnvelope::new(
+            "ProductCreated".to_string(),
+            "evt-1".to_string(),
+            1234567890,
+            "{}".to_string(),
+            None,
+            None,
+        );
+        let mut versioned = unversioned.clone();
+        versioned.schema_version = Some(2);
+        let hash = versioned.compute_hash();
+        assert_ne!(Some(hash.clone()), unversioned.event_hash);
+        versioned.aggregate_version = Some(3);
+        assert_ne!(versioned.compute_hash(), hash);
+
+        versioned.event_hash = Some(versioned.compute_hash());
+        let json = serde_json::to_string(&versioned).unwrap();
+        let restored: EventEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.schema_version, Some(2));
+        assert_eq!(restored.aggregate_version, Some(3));
+        assert!(restored.verify_hash());
+    }
+
+    #[test]
+    fn test_rewrite_events_rechains_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = EventStore::new(dir.path().to_str().unwrap()).unwrap();
+        store.set_hash_chain(true);
+        for i in 0..3 {
+            let envelope = EventEnvelope {
+                event_type: "ItemCreated".to_string(),
+                event_id: format!("evt-{}", i),
+                timestamp: 1234567890 + i,
+                payload: "{}".to_string(),
+                aggregate_id: Some(format!("item-{}", i)),
+                event_hash: None,
+                previous_hash: None,
+                schema_version: None,
+                aggregate_version: None,
+            };
+            store.append_envelope(&envelope).unwrap();
+        }
+        store.flush_events().unwrap();
+
+        // Drop the middle event: the third no longer links to its predecessor
+        let mut envelopes = store.get_all_envelopes().unwrap();
+        envelopes.remove(1);
+        store.rewrite_events(envelopes).unwrap();
+
+        assert_eq!(store.event_count(), 2);
+        let result = store.verify_chain().unwrap();
+        assert!(result.is_valid);
+        assert_eq!(result.verified_events, 2);
+        let ids: Vec<_> =
+            store.get_all_envelopes().unwrap().into_iter().map(|e| e.event_id).collect();
+        assert_eq!(ids, vec!["evt-0", "evt-2"]);
+        assert_eq!(
+            store.get_last_event_hash(),
+            store.get_all_envelopes().unwrap()[1].event_hash.as_ref()
+        );
     }
 }
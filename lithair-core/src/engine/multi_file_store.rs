@@ -412,6 +412,8 @@ mod tests {
             aggregate_id: Some("category_a".to_string()),
             event_hash: None,
             previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
         };
 
         let envelope2 = EventEnvelope {
@@ -422,6 +424,8 @@ mod tests {
             aggregate_id: Some("category_b".to_string()),
             event_hash: None,
             previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
         };
 
         let envelope3 = EventEnvelope {
@@ -432,6 +436,8 @@ mod tests {
             aggregate_id: None, // Global
             event_hash: None,
             previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
         };
 
         // Append events
@@ -468,6 +474,8 @@ mod tests {
                 aggregate_id: Some("category_a".to_string()),
                 event_hash: None,
                 previous_hash: None,
+                schema_version: None,
+                aggregate_version: None,
             };
             store.append_envelope(&envelope).unwrap();
         }
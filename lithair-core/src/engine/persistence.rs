@@ -264,9 +264,11 @@ impl FileStorage {
 
             if self.fsync_on_append {
                 if let Ok(file) = writer.get_ref().try_clone() {
+                    let start = std::time::Instant::now();
                     file.sync_all().map_err(|e| {
                         EngineError::PersistenceError(format!("Failed to sync binary event: {}", e))
                     })?;
+                    crate::metrics::global().record_fsync(start.elapsed());
                 }
             }
         }
@@ -320,9 +322,11 @@ impl FileStorage {
             // Optional fsync for durability
             if self.fsync_on_append {
                 if let Ok(file) = writer.get_ref().try_clone() {
+                    let start = std::time::Instant::now();
                     file.sync_all().map_err(|e| {
                         EngineError::PersistenceError(format!("Failed to sync events file: {}", e))
                     })?;
+                    crate::metrics::global().record_fsync(start.elapsed());
                 }
             }
         }
@@ -466,6 +470,50 @@ impl FileStorage {
         self.writer = None;
         fs::write(&self.events_file, "")
             .map_err(|e| EngineError::PersistenceError(format!("Failed to truncate log: {}", e)))?;
+        // A rotated segment and the offset index describe the discarded events
+        let _ = fs::remove_file(format!("{}.1", &self.events_file));
+        self.index_writer = None;
+        fs::write(&self.index_file, "").map_err(|e| {
+            EngineError::PersistenceError(format!("Failed to truncate index: {}", e))
+        })?;
+        Ok(())
+    }
+
+    /// Replace the events log with `events` (JSON, one per line)
+    ///
+    /// The new log is written and synced next to the old one, then renamed over it. The
+    /// rotated segment and the offset index describe the old log and are dropped.
+    pub fn rewrite_events(&mut self, events: &[String]) -> EngineResult<()> {
+        if let Some(aw) = self.async_writer.take() {
+            let _ = aw.flush();
+            let _ = aw.shutdown();
+        }
+        self.flush_batch()?;
+        self.writer = None;
+
+        let io_err = |e: std::io::Error| {
+            EngineError::PersistenceError(format!("Failed to rewrite log: {}", e))
+        };
+        let tmp = format!("{}.tmp", &self.events_file);
+        {
+            let mut writer = BufWriter::new(fs::File::create(&tmp).map_err(io_err)?);
+            for event_json in events {
+                let line = if self.enable_checksums {
+                    format_event_with_crc32(event_json)
+                } else {
+                    event_json.clone()
+                };
+                writeln!(writer, "{}", line).map_err(io_err)?;
+            }
+            writer.flush().map_err(io_err)?;
+            writer.get_ref().sync_all().map_err(io_err)?;
+        }
+        fs::rename(&tmp, &self.events_file).map_err(io_err)?;
+        let _ = fs::remove_file(format!("{}.1", &self.events_file));
+        self.index_writer = None;
+        fs::write(&self.index_file, "").map_err(|e| {
+            EngineError::PersistenceError(format!("Failed to truncate index: {}", e))
+        })?;
         Ok(())
     }
 
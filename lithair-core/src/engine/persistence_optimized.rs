@@ -242,10 +242,12 @@ impl AsyncEventWriter {
 
             // Step 2: Fsync OS buffer → Physical disk (if enabled)
             if fsync {
+                let start = std::time::Instant::now();
                 if let Err(e) = file.get_ref().sync_all() {
                     log::error!("Failed to fsync events to disk: {}", e);
                     return;
                 }
+                crate::metrics::global().record_fsync(start.elapsed());
             }
 
             // Only log in debug builds to avoid performance impact
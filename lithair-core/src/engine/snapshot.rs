@@ -19,6 +19,7 @@
 //!                           └─────────────────┘
 //! ```
 
+use super::events::EventEnvelope;
 use super::persistence::{calculate_crc32, format_event_with_crc32, parse_and_validate_event};
 use super::{EngineError, EngineResult};
 use serde::{Deserialize, Serialize};
@@ -40,7 +41,8 @@ pub struct SnapshotMetadata {
     pub aggregate_id: Option<String>,
     /// Number of events included in this snapshot
     pub event_count: usize,
-    /// ID of the last event included in snapshot
+    /// ID of the last event included in snapshot (the event hash when hash chaining
+    /// is enabled)
     pub last_event_id: Option<String>,
     /// Unix timestamp when snapshot was created
     pub timestamp: u64,
@@ -48,6 +50,34 @@ pub struct SnapshotMetadata {
     pub state_crc32: String,
 }
 
+impl SnapshotMetadata {
+    /// Number of leading events in `events` (JSON envelopes) already in this snapshot
+    ///
+    /// The recorded position is trusted when the event there still carries
+    /// `last_event_id` as its hash; otherwise that event is searched for. A log shorter
+    /// than the snapshot, or one without its last event, has been compacted since: every
+    /// event in it is newer than the snapshot.
+    pub fn covered_events(&self, events: &[String]) -> usize {
+        let count = self.event_count;
+        if count == 0 || count > events.len() {
+            return 0;
+        }
+        let Some(last_id) = self.last_event_id.as_deref() else {
+            return count;
+        };
+        let hash_of = |line: &String| {
+            serde_json::from_str::<EventEnvelope>(line).ok().and_then(|e| e.event_hash)
+        };
+        if hash_of(&events[count - 1]).as_deref() == Some(last_id) {
+            return count;
+        }
+        events
+            .iter()
+            .position(|line| hash_of(line).as_deref() == Some(last_id))
+            .map_or(0, |i| i + 1)
+    }
+}
+
 /// A complete snapshot with metadata and state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
@@ -160,6 +190,54 @@ impl SnapshotStore {
         }
     }
 
+    /// Get the directory holding the older snapshots of an aggregate
+    fn history_dir(&self, aggregate_id: Option<&str>) -> String {
+        format!("{}/{}/history", self.base_dir, aggregate_id.unwrap_or("global"))
+    }
+
+    /// Older snapshots of an aggregate with their sequence numbers, the oldest first
+    fn history_files(&self, aggregate_id: Option<&str>) -> Vec<(u64, std::path::PathBuf)> {
+        let Ok(entries) = fs::read_dir(self.history_dir(aggregate_id)) else {
+            return Vec::new();
+        };
+        let mut files: Vec<(u64, std::path::PathBuf)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "raftsnap"))
+            .filter_map(|path| {
+                let sequence = path.file_stem()?.to_str()?.parse().ok()?;
+                Some((sequence, path))
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Save a snapshot for an aggregate, keeping a copy of the one it replaces among the
+    /// `keep` most recent older snapshots (see `load_history`)
+    pub fn save_snapshot_keeping(&self, snapshot: &Snapshot, keep: usize) -> EngineResult<()> {
+        let aggregate_id = snapshot.metadata.aggregate_id.as_deref();
+        let path = self.snapshot_path(aggregate_id);
+        if keep > 0 && Path::new(&path).exists() {
+            let dir = self.history_dir(aggregate_id);
+            fs::create_dir_all(&dir).map_err(|e| {
+                EngineError::PersistenceError(format!("Failed to create snapshot dir: {}", e))
+            })?;
+            let mut kept = self.history_files(aggregate_id);
+            let sequence = kept.last().map_or(0, |(sequence, _)| sequence + 1);
+            // Copied rather than moved: the latest snapshot stays in place until replaced
+            let archived = Path::new(&dir).join(format!("{:010}.raftsnap", sequence));
+            fs::copy(&path, &archived).map_err(|e| {
+                EngineError::PersistenceError(format!("Failed to keep snapshot: {}", e))
+            })?;
+            kept.push((sequence, archived));
+            for (_, old) in &kept[..kept.len().saturating_sub(keep)] {
+                let _ = fs::remove_file(old);
+            }
+        }
+        self.save_snapshot(snapshot)
+    }
+
     /// Save a snapshot for an aggregate
     pub fn save_snapshot(&self, snapshot: &Snapshot) -> EngineResult<()> {
         let path = self.snapshot_path(snapshot.metadata.aggregate_id.as_deref());
@@ -176,9 +254,14 @@ impl SnapshotStore {
         // Write with CRC32 protection
         let protected_content = format_event_with_crc32(&json);
 
-        fs::write(&path, &protected_content).map_err(|e| {
+        // Write then rename so a crash never leaves a torn snapshot behind
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, &protected_content).map_err(|e| {
             EngineError::PersistenceError(format!("Failed to write snapshot: {}", e))
         })?;
+        fs::rename(&tmp_path, &path).map_err(|e| {
+            EngineError::PersistenceError(format!("Failed to replace snapshot: {}", e))
+        })?;
 
         if self.log_verbose {
             log::debug!(
@@ -203,16 +286,7 @@ impl SnapshotStore {
             return Ok(None);
         }
 
-        let content = fs::read_to_string(&path).map_err(|e| {
-            EngineError::PersistenceError(format!("Failed to read snapshot: {}", e))
-        })?;
-
-        // Validate CRC32 and extract JSON
-        let json = parse_and_validate_event(&content).map_err(|e| {
-            EngineError::PersistenceError(format!("Snapshot file corrupted: {}", e))
-        })?;
-
-        let snapshot = Snapshot::from_json(&json)?;
+        let snapshot = Self::read_snapshot_file(Path::new(&path))?;
 
         if self.log_verbose {
             log::debug!(
@@ -225,6 +299,48 @@ impl SnapshotStore {
         Ok(Some(snapshot))
     }
 
+    /// Read and validate one snapshot file
+    fn read_snapshot_file(path: &Path) -> EngineResult<Snapshot> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            EngineError::PersistenceError(format!("Failed to read snapshot: {}", e))
+        })?;
+
+        // Validate CRC32 and extract JSON
+        let json = parse_and_validate_event(&content).map_err(|e| {
+            EngineError::PersistenceError(format!("Snapshot file corrupted: {}", e))
+        })?;
+
+        Snapshot::from_json(&json)
+    }
+
+    /// Load the older snapshots `save_snapshot_keeping` kept for an aggregate, the most
+    /// recent first; unreadable ones are skipped (logged)
+    pub fn load_history(&self, aggregate_id: Option<&str>) -> Vec<Snapshot> {
+        let mut files = self.history_files(aggregate_id);
+        files.reverse();
+        files
+            .into_iter()
+            .filter_map(|(_, path)| match Self::read_snapshot_file(&path) {
+                Ok(snapshot) => Some(snapshot),
+                Err(e) => {
+                    log::warn!("Ignoring snapshot {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Delete the older snapshots of an aggregate, keeping the latest one
+    pub fn delete_history(&self, aggregate_id: Option<&str>) -> EngineResult<()> {
+        let dir = self.history_dir(aggregate_id);
+        if Path::new(&dir).exists() {
+            fs::remove_dir_all(&dir).map_err(|e| {
+                EngineError::PersistenceError(format!("Failed to delete snapshot history: {}", e))
+            })?;
+        }
+        Ok(())
+    }
+
     /// Delete a snapshot
     pub fn delete_snapshot(&self, aggregate_id: Option<&str>) -> EngineResult<()> {
         let path = self.snapshot_path(aggregate_id);
@@ -463,6 +579,27 @@ mod tests {
         assert_eq!(ctx.get_initial_state(), Some(r#"{"data": "test"}"#));
     }
 
+    #[test]
+    fn test_snapshot_history_keeps_most_recent() {
+        let temp_dir = tempdir().unwrap();
+        let store = SnapshotStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+        for count in 1..=4 {
+            let snapshot = Snapshot::new(None, count * 10, None, "{}".to_string());
+            store.save_snapshot_keeping(&snapshot, 2).unwrap();
+        }
+
+        assert_eq!(store.load_snapshot(None).unwrap().unwrap().metadata.event_count, 40);
+        let history: Vec<usize> =
+            store.load_history(None).iter().map(|s| s.metadata.event_count).collect();
+        assert_eq!(history, vec![30, 20]);
+        assert_eq!(store.list_snapshots().unwrap(), vec![None]);
+
+        store.delete_history(None).unwrap();
+        assert!(store.load_history(None).is_empty());
+        assert!(store.load_snapshot(None).unwrap().is_some());
+    }
+
     #[test]
     fn test_list_snapshots() {
         let temp_dir = tempdir().unwrap();
@@ -486,6 +623,38 @@ mod tests {
         assert!(list.contains(&Some("products".to_string())));
     }
 
+    #[test]
+    fn test_covered_events() {
+        let line = |hash: &str| {
+            serde_json::to_string(&EventEnvelope {
+                event_type: "ItemCreated".to_string(),
+                event_id: "item:1".to_string(),
+                timestamp: 0,
+                payload: "{}".to_string(),
+                aggregate_id: None,
+                event_hash: Some(hash.to_string()),
+                previous_hash: None,
+                schema_version: None,
+                aggregate_version: None,
+            })
+            .unwrap()
+        };
+        let log: Vec<String> = ["h1", "h2", "h3", "h4"].iter().map(|h| line(h)).collect();
+        let metadata = |count: usize, last: Option<&str>| {
+            Snapshot::new(None, count, last.map(str::to_string), "{}".to_string()).metadata
+        };
+
+        // Position confirmed by the hash, or found by searching
+        assert_eq!(metadata(2, Some("h2")).covered_events(&log), 2);
+        assert_eq!(metadata(1, Some("h3")).covered_events(&log), 3);
+        // Positional only without a hash
+        assert_eq!(metadata(3, None).covered_events(&log), 3);
+        // Compacted log: shorter than the snapshot, or its last event is gone
+        assert_eq!(metadata(10, Some("h9")).covered_events(&log), 0);
+        assert_eq!(metadata(2, Some("gone")).covered_events(&log), 0);
+        assert_eq!(metadata(0, None).covered_events(&log), 0);
+    }
+
     #[test]
     fn test_snapshot_json_roundtrip() {
         let snapshot = Snapshot::new(
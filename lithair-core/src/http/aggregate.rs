@@ -0,0 +1,285 @@
+//! Aggregation queries for collection endpoints
+//!
+//! `GET /api/{model}/_aggregate?group_by=category&sum=price&avg=price` computes
+//! per-group metrics over the in-memory store instead of shipping the collection to
+//! the client:
+//!
+//! - `group_by=a,b`: group key fields (dotted paths allowed); without it the whole
+//!   collection is one group
+//! - `sum=`, `avg=`, `min=`, `max=`: comma-separated fields per metric
+//! - `count` is always reported
+//!
+//! Every other parameter uses the list filter syntax (see `http::query`), so the same
+//! `status=in(a,b)` or `or=(...)` expressions restrict the aggregated items. List
+//! parameters aggregations do not honour (paging, `sort`, `fields`, `explain`, `expand`,
+//! `include_deleted`, `as_of`) are rejected rather than ignored.
+//! `sum`/`avg` only consider numeric values; `min`/`max` compare like `sort=`.
+
+use crate::http::query::{
+    compare_json_values, get_path, parse_query_params, QueryError, QueryParams,
+};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Aggregate function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateOp {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateOp {
+    const ALL: [AggregateOp; 4] =
+        [AggregateOp::Sum, AggregateOp::Avg, AggregateOp::Min, AggregateOp::Max];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregateOp::Sum => "sum",
+            AggregateOp::Avg => "avg",
+            AggregateOp::Min => "min",
+            AggregateOp::Max => "max",
+        }
+    }
+}
+
+/// List parameters that do not apply to aggregations: aggregates always cover every
+/// matching live item as it is now
+const LIST_ONLY_PARAMS: &[&str] = &[
+    "skip",
+    "take",
+    "sort",
+    "explain",
+    "limit",
+    "after",
+    "before",
+    "fields",
+    "expand",
+    "include_deleted",
+    "as_of",
+];
+
+/// Parsed `_aggregate` request
+#[derive(Debug, Clone, Default)]
+pub struct AggregateQuery {
+    pub group_by: Vec<String>,
+    /// (function, field) pairs in request order
+    pub metrics: Vec<(AggregateOp, String)>,
+    /// Filters restricting the aggregated items
+    pub params: QueryParams,
+}
+
+impl AggregateQuery {
+    /// Parse the query string; non-aggregate parameters are parsed as list filters
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let mut group_by = Vec::new();
+        let mut metrics = Vec::new();
+        let mut rest: Vec<&str> = Vec::new();
+
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (raw_key, raw) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = urlencoding::decode(raw_key).map(|k| k.into_owned()).unwrap_or_default();
+            let value = urlencoding::decode(raw).map(|v| v.into_owned()).unwrap_or_default();
+            if LIST_ONLY_PARAMS.contains(&key.as_str()) {
+                return Err(QueryError {
+                    param: key,
+                    message: "does not apply to aggregations".to_string(),
+                });
+            }
+            let key = key.as_str();
+            let op = AggregateOp::ALL.into_iter().find(|op| op.as_str() == key);
+            match (key, op) {
+                ("group_by", _) => group_by.extend(field_list(key, &value)?),
+                (_, Some(op)) => {
+                    metrics.extend(field_list(key, &value)?.into_iter().map(|f| (op, f)));
+                }
+                // Always reported; accepted for readability
+                ("count", _) => {}
+                _ => rest.push(pair),
+            }
+        }
+
+        let params = parse_query_params(&rest.join("&"))?;
+        Ok(Self { group_by, metrics, params })
+    }
+
+    /// Aggregate serialized items (already permission-checked); filters are applied here
+    pub fn run<'a>(&self, items: impl IntoIterator<Item = &'a Value>) -> Vec<Value> {
+        let mut slots: HashMap<String, usize> = HashMap::new();
+        let mut groups: Vec<Group> = Vec::new();
+
+        for item in items {
+            if !self.params.matches(item) {
+                continue;
+            }
+            let key: Vec<Value> = self
+                .group_by
+                .iter()
+                .map(|f| get_path(item, f).cloned().unwrap_or(Value::Null))
+                .collect();
+            let slot = *slots.entry(Value::Array(key.clone()).to_string()).or_insert_with(|| {
+                groups.push(Group::new(key, self.metrics.len()));
+                groups.len() - 1
+            });
+            groups[slot].add(item, &self.metrics);
+        }
+
+        groups.sort_by(|a, b| {
+            a.key
+                .iter()
+                .zip(&b.key)
+                .map(|(x, y)| compare_json_values(x, y))
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        groups.into_iter().map(|g| g.to_json(&self.group_by, &self.metrics)).collect()
+    }
+}
+
+fn field_list(param: &str, value: &str) -> Result<Vec<String>, QueryError> {
+    let fields: Vec<String> = value
+        .split(',')
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+    if fields.is_empty() {
+        return Err(QueryError {
+            param: param.to_string(),
+            message: "expected a comma-separated field list".to_string(),
+        });
+    }
+    Ok(fields)
+}
+
+/// Running totals for one metric
+#[derive(Debug, Clone, Default)]
+struct Accumulator {
+    sum: f64,
+    numeric: u64,
+    min: Option<Value>,
+    max: Option<Value>,
+}
+
+#[derive(Debug)]
+struct Group {
+    key: Vec<Value>,
+    count: u64,
+    metrics: Vec<Accumulator>,
+}
+
+impl Group {
+    fn new(key: Vec<Value>, metrics: usize) -> Self {
+        Self { key, count: 0, metrics: vec![Accumulator::default(); metrics] }
+    }
+
+    fn add(&mut self, item: &Value, metrics: &[(AggregateOp, String)]) {
+        self.count += 1;
+        for ((_, field), acc) in metrics.iter().zip(&mut self.metrics) {
+            let Some(value) = get_path(item, field).filter(|v| !v.is_null()) else { continue };
+            if let Some(n) = value.as_f64() {
+                acc.sum += n;
+                acc.numeric += 1;
+            }
+            if acc.min.as_ref().is_none_or(|m| compare_json_values(value, m).is_lt()) {
+                acc.min = Some(value.clone());
+            }
+            if acc.max.as_ref().is_none_or(|m| compare_json_values(value, m).is_gt()) {
+                acc.max = Some(value.clone());
+            }
+        }
+    }
+
+    fn to_json(&self, group_by: &[String], metrics: &[(AggregateOp, String)]) -> Value {
+        let key: Map<String, Value> = group_by.iter().cloned().zip(self.key.clone()).collect();
+        let mut out = serde_json::json!({ "key": key, "count": self.count });
+        for ((op, field), acc) in metrics.iter().zip(&self.metrics) {
+            let value = match op {
+                AggregateOp::Sum => serde_json::json!(acc.sum),
+                AggregateOp::Avg if acc.numeric > 0 => {
+                    serde_json::json!(acc.sum / acc.numeric as f64)
+                }
+                AggregateOp::Avg => Value::Null,
+                AggregateOp::Min => acc.min.clone().unwrap_or(Value::Null),
+                AggregateOp::Max => acc.max.clone().unwrap_or(Value::Null),
+            };
+            let section = out
+                .as_object_mut()
+                .expect("group is an object")
+                .entry(op.as_str())
+                .or_insert_with(|| Value::Object(Map::new()));
+            section[field.as_str()] = value;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn items() -> Vec<Value> {
+        vec![
+            json!({"category": "books", "price": 10, "status": "open"}),
+            json!({"category": "books", "price": 30, "status": "open"}),
+            json!({"category": "games", "price": 5.5, "status": "closed"}),
+            json!({"category": "books", "status": "open"}),
+        ]
+    }
+
+    #[test]
+    fn test_group_by_with_metrics() {
+        let query =
+            AggregateQuery::parse("group_by=category&sum=price&avg=price&max=price").unwrap();
+        let groups = query.run(&items());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0]["key"], json!({"category": "books"}));
+        assert_eq!(groups[0]["count"], 3);
+        assert_eq!(groups[0]["sum"]["price"], 40.0);
+        assert_eq!(groups[0]["avg"]["price"], 20.0);
+        assert_eq!(groups[0]["max"]["price"], 30);
+        assert_eq!(groups[1]["key"]["category"], "games");
+    }
+
+    #[test]
+    fn test_filters_and_single_group() {
+        let query = AggregateQuery::parse("status=open&count&min=price").unwrap();
+        let groups = query.run(&items());
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0]["key"], json!({}));
+        assert_eq!(groups[0]["count"], 3);
+        assert_eq!(groups[0]["min"]["price"], 10);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(AggregateQuery::parse("sum=").unwrap_err().param, "sum");
+        assert_eq!(AggregateQuery::parse("take=x").unwrap_err().param, "take");
+        assert!(AggregateQuery::parse("limit=10").is_err());
+    }
+
+    #[test]
+    fn test_list_only_params_are_rejected() {
+        for query in [
+            "sort=-price",
+            "skip=5",
+            "take=10",
+            "explain=true",
+            "fields=id",
+            "after=abc",
+            "expand=owner",
+            "include_deleted=true",
+            "include_deleted",
+            "as_of=42",
+            "as%5Fof=42",
+        ] {
+            let error = AggregateQuery::parse(&format!("sum=price&{}", query)).unwrap_err();
+            assert_eq!(error.message, "does not apply to aggregations", "{}", query);
+        }
+        assert_eq!(AggregateQuery::parse("as_of=42").unwrap_err().param, "as_of");
+        // Filters and the read consistency still apply
+        assert!(AggregateQuery::parse("status=open&or=(a=1,b=2)&consistency=leader").is_ok());
+    }
+}
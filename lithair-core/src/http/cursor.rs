@@ -0,0 +1,187 @@
+//! Opaque, signed cursors for keyset pagination
+//!
+//! `GET /api/{model}?limit=N` pages by position instead of offset: each page returns
+//! `next_cursor`/`prev_cursor`, to be passed back as `after=` / `before=`. A cursor
+//! encodes the sort field and direction, the sort value of the boundary item and its
+//! primary key (the tie-breaker), so pages stay stable while writes happen and deep
+//! pages cost the same as the first one.
+//!
+//! Cursors are `base64url(payload).base64url(hmac_sha256(payload))`. The key comes
+//! from `LT_CURSOR_SECRET`; without it a random per-process key is used, so cursors
+//! do not survive a restart and are not portable between cluster nodes.
+
+use crate::http::index::IndexKey;
+use crate::http::query::{get_path, SortSpec};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::cmp::Ordering;
+use std::sync::OnceLock;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Page size when `limit` is not given
+pub const DEFAULT_CURSOR_LIMIT: u64 = 100;
+
+/// Position of an item in a sorted collection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Sort field the cursor was issued for (None = primary key order)
+    #[serde(rename = "s")]
+    pub sort_field: Option<String>,
+    #[serde(rename = "d")]
+    pub descending: bool,
+    /// Sort value of the boundary item
+    #[serde(rename = "v")]
+    pub value: Value,
+    /// Primary key of the boundary item
+    #[serde(rename = "k")]
+    pub key: String,
+}
+
+/// Why a cursor was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CursorError {
+    Malformed,
+    BadSignature,
+    /// Cursor was issued for another `sort`
+    SortMismatch,
+}
+
+impl std::fmt::Display for CursorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CursorError::Malformed => write!(f, "malformed cursor"),
+            CursorError::BadSignature => write!(f, "cursor signature mismatch"),
+            CursorError::SortMismatch => write!(f, "cursor was issued for a different sort"),
+        }
+    }
+}
+
+impl std::error::Error for CursorError {}
+
+impl Cursor {
+    /// Cursor for the item stored under `key`, whose serialized form is `item`
+    pub fn for_item(sort: Option<&SortSpec>, key: &str, item: &Value) -> Self {
+        Self {
+            sort_field: sort.map(|s| s.field.clone()),
+            descending: sort.map(|s| s.descending).unwrap_or(false),
+            value: sort.and_then(|s| get_path(item, &s.field)).cloned().unwrap_or(Value::Null),
+            key: key.to_string(),
+        }
+    }
+
+    /// Signed, URL-safe token
+    pub fn encode(&self) -> String {
+        self.encode_with(cursor_secret())
+    }
+
+    pub fn encode_with(&self, secret: &[u8]) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default());
+        let signature = URL_SAFE_NO_PAD.encode(sign(secret, payload.as_bytes()));
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Verify and decode a token produced by `encode`
+    pub fn decode(token: &str) -> Result<Self, CursorError> {
+        Self::decode_with(token, cursor_secret())
+    }
+
+    pub fn decode_with(token: &str, secret: &[u8]) -> Result<Self, CursorError> {
+        let (payload, signature) = token.split_once('.').ok_or(CursorError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| CursorError::Malformed)?;
+        let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        // Constant-time comparison
+        mac.verify_slice(&signature).map_err(|_| CursorError::BadSignature)?;
+        let bytes = URL_SAFE_NO_PAD.decode(payload).map_err(|_| CursorError::Malformed)?;
+        serde_json::from_slice(&bytes).map_err(|_| CursorError::Malformed)
+    }
+
+    /// Reject a cursor used with a different `sort` than it was issued for
+    pub fn check_sort(&self, sort: Option<&SortSpec>) -> Result<(), CursorError> {
+        let field = sort.map(|s| s.field.as_str());
+        let descending = sort.map(|s| s.descending).unwrap_or(false);
+        if self.sort_field.as_deref() == field && self.descending == descending {
+            Ok(())
+        } else {
+            Err(CursorError::SortMismatch)
+        }
+    }
+
+    /// Sort position as an index key and primary key
+    pub fn position(&self) -> (IndexKey, String) {
+        (IndexKey::from_value(Some(&self.value)), self.key.clone())
+    }
+}
+
+/// Order two positions by sort value, then primary key
+pub fn compare_positions(a: (&IndexKey, &str), b: (&IndexKey, &str), descending: bool) -> Ordering {
+    let ord = a.0.cmp(b.0).then_with(|| a.1.cmp(b.1));
+    if descending {
+        ord.reverse()
+    } else {
+        ord
+    }
+}
+
+fn sign(secret: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn cursor_secret() -> &'static [u8] {
+    static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+    SECRET.get_or_init(|| match std::env::var("LT_CURSOR_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ => {
+            use rand::RngCore;
+            let mut bytes = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            bytes
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sort(field: &str, descending: bool) -> SortSpec {
+        SortSpec { field: field.to_string(), descending }
+    }
+
+    #[test]
+    fn test_roundtrip_and_tamper() {
+        let secret = b"unit-secret";
+        let s = sort("price", true);
+        let cursor = Cursor::for_item(Some(&s), "42", &json!({"price": 9.5}));
+        let token = cursor.encode_with(secret);
+        assert_eq!(Cursor::decode_with(&token, secret).unwrap(), cursor);
+
+        assert_eq!(Cursor::decode_with(&token, b"other").unwrap_err(), CursorError::BadSignature);
+        let forged = format!("{}x", token);
+        assert!(Cursor::decode_with(&forged, secret).is_err());
+        assert_eq!(Cursor::decode_with("garbage", secret).unwrap_err(), CursorError::Malformed);
+    }
+
+    #[test]
+    fn test_check_sort() {
+        let s = sort("price", false);
+        let cursor = Cursor::for_item(Some(&s), "1", &json!({"price": 1}));
+        assert!(cursor.check_sort(Some(&s)).is_ok());
+        assert_eq!(cursor.check_sort(None).unwrap_err(), CursorError::SortMismatch);
+        assert!(cursor.check_sort(Some(&sort("price", true))).is_err());
+    }
+
+    #[test]
+    fn test_positions_break_ties_by_key() {
+        let one = IndexKey::Number(1.0);
+        assert_eq!(compare_positions((&one, "a"), (&one, "b"), false), Ordering::Less);
+        assert_eq!(compare_positions((&one, "a"), (&one, "b"), true), Ordering::Greater);
+    }
+}
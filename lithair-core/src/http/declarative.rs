@@ -3,6 +3,11 @@
 //! This module provides the bridge between Lithair's DeclarativeModel system
 //! and the Hyper HTTP server, automatically generating REST endpoints from model definitions.
 
+use crate::http::etag;
+use crate::http::index::SecondaryIndexes;
+use crate::http::patch::{self, Patch, PatchError};
+use crate::http::unique::{UniqueIndex, UniqueViolation};
+use crate::http::validation::ValidationErrors;
 use crate::http::FirewallConfig;
 use bytes::Bytes;
 use chrono;
@@ -10,14 +15,19 @@ use http_body_util::combinators::BoxBody;
 use http_body_util::{BodyExt, Full};
 use hyper::body::Incoming;
 use hyper::{Method, Request, Response, StatusCode};
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use crate::consensus::{ConsensusConfig, DeclarativeConsensus, ReplicatedModel};
 use crate::engine::events::{EventEnvelope, EventStore};
-use crate::lifecycle::LifecycleAware;
+use crate::engine::snapshot::{Snapshot, SnapshotMetadata, SnapshotStore};
+use crate::http::query::AsOf;
+use crate::lifecycle::retention::{self, PurgeReason, RecordPurge};
+use crate::lifecycle::{LifecycleAware, RetentionPolicy, RetentionReport};
 
 type RespBody = BoxBody<Bytes, Infallible>;
 type Req = Request<Incoming>;
@@ -46,6 +56,48 @@ pub trait HttpExposable: Serialize + DeserializeOwned + Clone + Send + Sync + 's
     /// Validate the model according to #[http(validate)] attributes
     fn validate(&self) -> Result<(), String>;
 
+    /// Validate the model and report every failing field
+    ///
+    /// The derive macro generates per-field checks; the default wraps `validate()`
+    /// for hand-written implementations.
+    fn validate_fields(&self) -> Result<(), ValidationErrors> {
+        self.validate().map_err(ValidationErrors::from)
+    }
+
+    /// Fields with `#[db(indexed)]`; each keeps an ordered index used to answer
+    /// list filters and sorts without a full scan. Defaults to none.
+    fn indexed_fields() -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Unique constraints enforced on writes
+    ///
+    /// One entry per `#[db(unique)]` field and per struct-level
+    /// `#[db(unique_together = "a, b")]` group. Defaults to none.
+    fn unique_constraints() -> Vec<Vec<String>> {
+        Vec::new()
+    }
+
+    /// Soft delete (`#[db(soft_delete)]`): DELETE keeps a tombstone that lists hide
+    /// unless `include_deleted=true`, and `POST /{id}/_restore` brings back. Defaults
+    /// to hard delete.
+    fn soft_delete() -> bool {
+        false
+    }
+
+    /// Foreign keys, from `#[db(fk = "...")]` and `#[relation(foreign_key | belongs_to)]`
+    ///
+    /// `?expand=` follows them to the referenced item. Defaults to none.
+    fn foreign_keys() -> Vec<crate::schema::RelationForeignKeySpec> {
+        Vec::new()
+    }
+
+    /// Inverse relations from `#[relation(has_many | has_one = "...")]`, each named
+    /// after its field. Defaults to none.
+    fn relations() -> Vec<crate::schema::RelationSpec> {
+        Vec::new()
+    }
+
     /// Optional declarative firewall configuration attached to the model type.
     /// Defaults to None; can be overridden by the derive macro via #[firewall(...)]
     fn firewall_config() -> Option<FirewallConfig> {
@@ -64,6 +116,13 @@ pub trait HttpExposable: Serialize + DeserializeOwned + Clone + Send + Sync + 's
         true // Default: allow all
     }
 
+    /// Permission needed to change `field`, from its `#[permission(write = "...")]`
+    ///
+    /// PATCH checks it for every field the patch changes. Defaults to none.
+    fn field_write_permission(_field: &str) -> Option<&'static str> {
+        None
+    }
+
     /// Apply lifecycle rules before persisting
     /// Based on `#[lifecycle]` attributes
     fn apply_lifecycle(&mut self) -> Result<(), String> {
@@ -71,6 +130,202 @@ pub trait HttpExposable: Serialize + DeserializeOwned + Clone + Send + Sync + 's
     }
 }
 
+/// Looks foreign keys up in the models they reference
+///
+/// `LithairServer` installs one on every model it serves, resolving model names against
+/// its registered models (see `DeclarativeHttpHandler::set_reference_resolver`).
+#[async_trait::async_trait]
+pub trait ReferenceResolver: Send + Sync {
+    /// Those of `values` that no item of `model` holds in `field`; None when `model` is
+    /// not served here
+    async fn missing(&self, model: &str, field: &str, values: &[String]) -> Option<Vec<String>>;
+}
+
+/// Snapshots kept besides the latest one, for `?as_of=` reads of compacted history
+const SNAPSHOT_HISTORY: usize = 4;
+
+/// Attempts of a replicated PATCH without `If-Match` whose item changed while it was
+/// proposed, before it answers `409`
+const PATCH_ATTEMPTS: usize = 3;
+
+/// Snapshot policy of a handler and the progress of its snapshots
+#[derive(Debug)]
+struct SnapshotSchedule {
+    /// Directory holding the snapshots (`<event store path>/snapshots`)
+    dir: String,
+    /// Events between automatic snapshots; 0 disables them
+    interval: AtomicUsize,
+    /// Truncate the event log once a snapshot is written
+    compaction: AtomicBool,
+    /// Event-store count already covered by the latest snapshot
+    covered_events: AtomicUsize,
+    /// Events discarded by compaction since the model's first event
+    compacted_events: AtomicUsize,
+    /// Last write of live records, for those whose events were compacted away
+    written_at: std::sync::Mutex<HashMap<String, u64>>,
+    running: AtomicBool,
+}
+
+impl SnapshotSchedule {
+    /// Directory a compacting snapshot is staged in until the log is truncated
+    fn pending_dir(&self) -> String {
+        format!("{}.compacting", self.dir)
+    }
+}
+
+/// Model state as written into a snapshot
+#[derive(Serialize)]
+struct SnapshotState<'a, T> {
+    schema_version: Option<u32>,
+    compacted_events: usize,
+    items: &'a HashMap<String, T>,
+    tombstones: &'a HashMap<String, T>,
+    written_at: &'a HashMap<String, u64>,
+    versions: &'a HashMap<String, u64>,
+}
+
+/// Model state read back from a snapshot, before upcasting
+#[derive(Deserialize)]
+struct StoredSnapshotState {
+    #[serde(default)]
+    schema_version: Option<u32>,
+    #[serde(default)]
+    compacted_events: usize,
+    items: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    tombstones: HashMap<String, serde_json::Value>,
+    /// None in snapshots written before retention tracked it
+    #[serde(default)]
+    written_at: Option<HashMap<String, u64>>,
+    /// None in snapshots written before items carried versions
+    #[serde(default)]
+    versions: Option<HashMap<String, u64>>,
+}
+
+/// How replay applies an event, from the operation suffix of its `event_type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventOp {
+    /// `Created`, `Updated`, `AdminEdit`, `Replicated`, `Restored`: store the payload
+    Put,
+    /// `Patched`: merge the payload, a merge patch, into the stored item
+    Patch,
+    /// `Deleted`: remove the item (and any tombstone)
+    Delete,
+    /// `Tombstoned`: soft delete, keep the payload as a tombstone
+    Tombstone,
+    /// `Purged`: retention audit record; removes the item when it expired
+    Purge,
+}
+
+impl EventOp {
+    fn of(event_type: &str) -> Self {
+        if event_type.ends_with("Deleted") {
+            EventOp::Delete
+        } else if event_type.ends_with("Tombstoned") {
+            EventOp::Tombstone
+        } else if event_type.ends_with("Purged") {
+            EventOp::Purge
+        } else if event_type.ends_with("Patched") {
+            EventOp::Patch
+        } else {
+            EventOp::Put
+        }
+    }
+}
+
+/// Event types bracketing the events of a `POST /_batch` in a model's log (see
+/// `BatchLock`)
+struct BatchMarker;
+
+impl BatchMarker {
+    const BEGUN: &'static str = "BatchBegun";
+    const COMMITTED: &'static str = "BatchCommitted";
+
+    /// `events` without batch markers, and without the events of batches whose commit
+    /// marker is missing: a crash or a failed write cut them short
+    ///
+    /// The events of a batch follow its `BatchBegun` marker and carry its id as
+    /// `event_id` suffix, as the markers do; any other event ends a batch left open.
+    fn committed(events: impl IntoIterator<Item = EventEnvelope>) -> Vec<EventEnvelope> {
+        let mut committed = Vec::new();
+        let mut batch: Option<(String, Vec<EventEnvelope>)> = None;
+        for event in events {
+            let batch_id = event.event_id.rsplit(':').next().unwrap_or_default().to_string();
+            if event.event_type.ends_with(Self::BEGUN) {
+                Self::skip(batch.replace((batch_id, Vec::new())));
+            } else if event.event_type.ends_with(Self::COMMITTED) {
+                match batch.take() {
+                    Some((id, events)) if id == batch_id => committed.extend(events),
+                    open => Self::skip(open),
+                }
+            } else {
+                match batch.as_mut() {
+                    Some((id, events)) if *id == batch_id => events.push(event),
+                    _ => {
+                        Self::skip(batch.take());
+                        committed.push(event);
+                    }
+                }
+            }
+        }
+        Self::skip(batch);
+        committed
+    }
+
+    fn skip(uncommitted: Option<(String, Vec<EventEnvelope>)>) {
+        if let Some((batch_id, events)) = uncommitted {
+            log::warn!(
+                "Skipping {} events of batch {}, which was never committed",
+                events.len(),
+                batch_id
+            );
+        }
+    }
+}
+
+/// Model state rebuilt for a point-in-time (`as_of`) read
+#[derive(Debug, Clone)]
+pub struct HistoricalState<T> {
+    pub items: HashMap<String, T>,
+    /// Soft-deleted items at that point
+    pub tombstones: HashMap<String, T>,
+}
+
+/// Why a point-in-time read cannot be answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsOfError {
+    /// The requested point lies in history discarded by compaction
+    Compacted {
+        /// Earliest event index that can still be read
+        earliest_event_index: usize,
+    },
+    /// The event log could not be read
+    Storage(String),
+}
+
+impl std::fmt::Display for AsOfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsOfError::Compacted { earliest_event_index } => write!(
+                f,
+                "history before event {} was compacted into a snapshot",
+                earliest_event_index
+            ),
+            AsOfError::Storage(e) => write!(f, "failed to read the event log: {}", e),
+        }
+    }
+}
+
+/// Write rights of a caller, resolved before the request body is consumed
+enum WriteAccess {
+    /// Permissions from the permission extractor
+    Permissions(Vec<String>),
+    /// Role checked against the permission checker (None = unauthenticated)
+    Role(Option<String>),
+    /// No access control configured
+    Open,
+}
+
 /// HTTP handler for DeclarativeModel CRUD operations
 pub struct DeclarativeHttpHandler<T>
 where
@@ -87,6 +342,180 @@ where
     pub(crate) session_store: Option<Arc<dyn std::any::Any + Send + Sync>>,
     /// Optional SSE broadcaster for real-time change notifications
     pub(crate) sse_broadcaster: Option<Arc<crate::http::sse::SseEventBroadcaster>>,
+    /// Owners of unique keys, kept in sync with `storage`
+    unique_index: Arc<UniqueIndex>,
+    /// Ordered indexes on `#[db(indexed)]` fields, used by the list query planner
+    secondary_indexes: Arc<SecondaryIndexes>,
+    /// Current model spec; new events record its version and replay upcasts older ones
+    schema_spec: Option<Arc<crate::schema::ModelSpec>>,
+    /// Upcasters for events written under older schema versions
+    migrations: Arc<crate::schema::SchemaMigrations>,
+    /// Snapshot policy, shared with background snapshot tasks
+    snapshots: Arc<SnapshotSchedule>,
+    /// Soft-deleted items (`#[db(soft_delete)]`), kept out of `storage`
+    tombstones: Arc<std::sync::Mutex<HashMap<String, T>>>,
+    /// Version of every live or soft-deleted item, bumped under the storage lock
+    versions: Arc<std::sync::Mutex<HashMap<String, u64>>>,
+    /// Last version of deleted items: an id created again counts on from it, so no
+    /// `If-Match` tag of the deleted item matches the new one
+    retired_versions: Arc<std::sync::Mutex<HashMap<String, u64>>>,
+    /// Checks `T::foreign_keys()` on writes, once the server installs it
+    references: std::sync::OnceLock<Arc<dyn ReferenceResolver>>,
+}
+
+/// One model's share of a `POST /_batch`
+///
+/// Holds the event log and storage write locks, taken in the handler's lock order, until
+/// finished or rolled back: no reader sees part of the batch, and versions read through
+/// the lock hold until its writes apply.
+///
+/// Writes apply to memory first, remembering what they replaced. `prepare` then logs
+/// their events between a `BatchBegun` and a `BatchCommitted` marker, the latter written
+/// by `finish` once every model of the batch prepared; replay skips the events of a batch
+/// whose commit marker is missing. The events share the batch id as `event_id` suffix.
+pub struct BatchLock<'a, T>
+where
+    T: HttpExposable + LifecycleAware + ReplicatedModel,
+{
+    handler: &'a DeclarativeHttpHandler<T>,
+    event_store: tokio::sync::RwLockWriteGuard<'a, EventStore>,
+    storage: tokio::sync::RwLockWriteGuard<'a, HashMap<String, T>>,
+    batch_id: String,
+    /// Events of the writes applied so far, logged by `prepare`
+    events: Vec<EventEnvelope>,
+    /// Stored item, tombstone and version of each key before the batch first wrote it
+    undo: Vec<(String, Option<T>, Option<T>, Option<u64>)>,
+}
+
+impl<T> BatchLock<'_, T>
+where
+    T: HttpExposable + LifecycleAware + ReplicatedModel,
+{
+    pub fn version(&self, id: &str) -> Option<u64> {
+        self.handler.item_version(id)
+    }
+
+    /// Stored item `id`, as the batch left it so far
+    pub fn item(&self, id: &str) -> Option<&T> {
+        self.storage.get(id)
+    }
+
+    /// Stored items whose `field` equals one of `values`, whoever may read them
+    pub fn items_where(&self, field: &str, values: &[String]) -> Vec<serde_json::Value> {
+        self.handler.items_in(&self.storage, field, values, None)
+    }
+
+    /// Store `item` under `key` and queue its `operation` event
+    ///
+    /// Unique keys are recorded as decided: the batch reserved them when it was checked.
+    pub fn put(&mut self, key: &str, item: T, operation: &str) -> Result<(), String> {
+        let mut envelope = self.envelope(operation, &item)?;
+        self.remember(key);
+        let previous = self.handler.put_item(&mut self.storage, key.to_string(), item.clone());
+        self.handler.apply_unique(key, previous.as_ref(), Some(&item));
+        envelope.aggregate_version = self.handler.item_version(key);
+        self.events.push(envelope);
+        Ok(())
+    }
+
+    /// Remove item `id` as DELETE does and queue its event; false when it does not exist
+    pub fn delete(&mut self, id: &str) -> Result<bool, String> {
+        let Some(item) = self.storage.get(id) else {
+            return Ok(false);
+        };
+        let mut envelope = self.envelope(DeclarativeHttpHandler::<T>::delete_operation(), item)?;
+        self.remember(id);
+        self.handler.tombstone_item(&mut self.storage, id);
+        envelope.aggregate_version = self.handler.item_version(id);
+        self.events.push(envelope);
+        Ok(true)
+    }
+
+    /// Log the events of the batch after a `BatchBegun` marker and flush them
+    pub fn prepare(&mut self) -> Result<(), String> {
+        if self.events.is_empty() {
+            return Ok(());
+        }
+        let begun = self.marker(BatchMarker::BEGUN);
+        self.event_store.append_envelope(&begun).map_err(|e| e.to_string())?;
+        for envelope in &self.events {
+            self.event_store.append_envelope(envelope).map_err(|e| e.to_string())?;
+        }
+        self.event_store.flush_events().map_err(|e| e.to_string())
+    }
+
+    /// Log the `BatchCommitted` marker, flush it and release the locks
+    pub fn finish(mut self) -> Result<(), String> {
+        let committed = if self.events.is_empty() {
+            Ok(())
+        } else {
+            let marker = self.marker(BatchMarker::COMMITTED);
+            self.event_store
+                .append_envelope(&marker)
+                .and_then(|_| self.event_store.flush_events())
+                .map_err(|e| e.to_string())
+        };
+        let event_count = self.event_store.event_count();
+        let handler = self.handler;
+        drop(self);
+        handler.maybe_snapshot(event_count);
+        committed
+    }
+
+    /// Put back what the batch replaced in memory and release the locks
+    ///
+    /// Events already logged by `prepare` stay uncommitted, so replay skips them.
+    pub fn rollback(mut self) {
+        let handler = self.handler;
+        for (key, stored, tombstone, version) in std::mem::take(&mut self.undo).into_iter().rev() {
+            let written = handler.take_item(&mut self.storage, &key);
+            if let Some(item) = stored.clone() {
+                handler.put_item(&mut self.storage, key.clone(), item);
+            }
+            handler.apply_unique(&key, written.as_ref(), stored.as_ref());
+            match tombstone {
+                Some(item) => handler.tombstones().insert(key.clone(), item),
+                None => handler.tombstones().remove(&key),
+            };
+            match version {
+                Some(version) => handler.versions().insert(key, version),
+                None => handler.versions().remove(&key),
+            };
+        }
+    }
+
+    /// Record the state of `key` before the batch first writes it
+    fn remember(&mut self, key: &str) {
+        if self.undo.iter().any(|(k, ..)| k == key) {
+            return;
+        }
+        let stored = self.storage.get(key).cloned();
+        let tombstone = self.handler.tombstones().get(key).cloned();
+        let version = self.handler.item_version(key);
+        self.undo.push((key.to_string(), stored, tombstone, version));
+    }
+
+    fn envelope(&self, operation: &str, item: &T) -> Result<EventEnvelope, String> {
+        let mut envelope = self.handler.envelope(operation, item).map_err(|e| e.to_string())?;
+        envelope.event_id = format!("{}:{}", envelope.event_id, self.batch_id);
+        Ok(envelope)
+    }
+
+    fn marker(&self, marker: &str) -> EventEnvelope {
+        let model = std::any::type_name::<T>();
+        EventEnvelope {
+            event_type: format!("{}{}", model, marker),
+            event_id: format!("{}:{}:{}", model, marker, self.batch_id),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            payload: serde_json::json!({ "batch_id": self.batch_id, "events": self.events.len() })
+                .to_string(),
+            aggregate_id: None,
+            event_hash: None,
+            previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
+        }
+    }
 }
 
 impl<T> DeclarativeHttpHandler<T>
@@ -139,52 +568,988 @@ where
             permission_extractor: None,
             session_store: None,
             sse_broadcaster: None,
+            unique_index: Arc::new(UniqueIndex::new(T::unique_constraints())),
+            secondary_indexes: Arc::new(SecondaryIndexes::new(T::indexed_fields())),
+            schema_spec: None,
+            migrations: Arc::new(crate::schema::SchemaMigrations::new()),
+            snapshots: Arc::new(SnapshotSchedule {
+                dir: format!("{}/snapshots", event_store_path),
+                interval: AtomicUsize::new(0),
+                compaction: AtomicBool::new(false),
+                covered_events: AtomicUsize::new(0),
+                compacted_events: AtomicUsize::new(0),
+                written_at: std::sync::Mutex::new(HashMap::new()),
+                running: AtomicBool::new(false),
+            }),
+            tombstones: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            versions: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            retired_versions: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            references: std::sync::OnceLock::new(),
+        };
+
+        Ok(handler)
+    }
+
+    /// Get a reference to the event store for chain verification
+    pub fn get_event_store(&self) -> &Arc<tokio::sync::RwLock<EventStore>> {
+        &self.event_store
+    }
+
+    /// Create a new DeclarativeHttpHandler with automatic event replay
+    ///
+    /// This is a convenience method that creates the handler and automatically
+    /// replays all persisted events to restore state from the event log.
+    pub async fn new_with_replay(
+        event_store_path: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let handler = Self::new(event_store_path)?;
+        handler.replay_events().await?;
+        Ok(handler)
+    }
+
+    /// Set the current model spec
+    ///
+    /// New events record `spec.version`, and replay brings events written under older
+    /// versions up to it with the upcasters from `with_migrations` before deserializing
+    /// them. Fields with `#[db(default = X)]` are backfilled when an event lacks them.
+    pub fn with_schema_spec(mut self, spec: crate::schema::ModelSpec) -> Self {
+        self.schema_spec = Some(Arc::new(spec));
+        self
+    }
+
+    /// Register the upcasters applied to events written under older schema versions
+    pub fn with_migrations(mut self, migrations: Arc<crate::schema::SchemaMigrations>) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    /// Snapshot every `interval` events (0 disables), truncating the event log after
+    /// each snapshot when `compaction` is set
+    ///
+    /// Compaction discards the history behind the snapshot: entity history and
+    /// hash-chain verification only cover events written since.
+    pub fn set_snapshot_policy(&self, interval: usize, compaction: bool) {
+        self.snapshots.interval.store(interval, Ordering::Relaxed);
+        self.snapshots.compaction.store(compaction, Ordering::Relaxed);
+    }
+
+    /// Restore state from the latest snapshot, then replay the events written after it
+    pub async fn replay_events(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let events = {
+            let mut store = self.event_store.write().await;
+            Self::settle_compaction(&mut store, &self.snapshots)?;
+            store.get_all_events()?
+        };
+
+        let mut storage = self.storage.write().await;
+        self.tombstones().clear();
+        self.versions().clear();
+        self.retired_versions().clear();
+        let mut failed = Vec::new();
+        let events = self.restore_snapshot(&mut storage, events, &mut failed);
+        let (replayed_count, replay_failed) = self.replay_into(&mut storage, events);
+        failed.extend(replay_failed);
+
+        if Self::is_verbose() || replayed_count > 0 {
+            log::info!("Replayed {} events into memory", replayed_count);
+        }
+        if let Some(first) = failed.first() {
+            log::warn!(
+                "Skipped {} event(s) that do not deserialize into {} (first: {})",
+                failed.len(),
+                std::any::type_name::<T>(),
+                first
+            );
+        }
+
+        Ok(replayed_count)
+    }
+
+    /// Rebuild memory from the event log, upcasting every event to the current schema
+    ///
+    /// Used when a schema change is approved; returns the replayed event count and one
+    /// message per event that could not be migrated.
+    pub async fn reload_from_events(
+        &self,
+    ) -> Result<(usize, Vec<String>), Box<dyn std::error::Error + Send + Sync>> {
+        let events = {
+            let mut store = self.event_store.write().await;
+            Self::settle_compaction(&mut store, &self.snapshots)?;
+            store.get_all_events()?
+        };
+
+        let mut storage = self.storage.write().await;
+        storage.clear();
+        self.tombstones().clear();
+        self.versions().clear();
+        self.retired_versions().clear();
+        self.unique_index.clear();
+        self.secondary_indexes.clear();
+        let mut failed = Vec::new();
+        let events = self.restore_snapshot(&mut storage, events, &mut failed);
+        let (replayed_count, replay_failed) = self.replay_into(&mut storage, events);
+        failed.extend(replay_failed);
+        Ok((replayed_count, failed))
+    }
+
+    /// Load the latest snapshot into `storage` and return the events it does not cover
+    ///
+    /// Snapshot items are upcast like events; those that fail are reported in `failed`.
+    /// A missing or unreadable snapshot leaves `events` to be replayed in full.
+    fn restore_snapshot(
+        &self,
+        storage: &mut HashMap<String, T>,
+        mut events: Vec<String>,
+        failed: &mut Vec<String>,
+    ) -> Vec<String> {
+        self.snapshots.covered_events.store(0, Ordering::Relaxed);
+        self.snapshots.compacted_events.store(0, Ordering::Relaxed);
+        let Some((metadata, state)) = self.read_snapshot() else {
+            self.written_at().clear();
+            return events;
+        };
+
+        let covered = metadata.covered_events(&events);
+        let restored = state.items.len();
+        // Records of older snapshots count as written when the snapshot was
+        *self.written_at() = state.written_at.unwrap_or_else(|| {
+            let keys = state.items.keys().chain(state.tombstones.keys());
+            keys.map(|key| (key.clone(), metadata.timestamp)).collect()
+        });
+        for (key, value) in state.items {
+            match self.decode_value(value, state.schema_version) {
+                Ok(item) => {
+                    self.apply_unique(&key, None, Some(&item));
+                    self.put_item(storage, key, item);
+                }
+                Err(e) => failed.push(format!("snapshot item {}: {}", key, e)),
+            }
+        }
+        if T::soft_delete() {
+            for (key, value) in state.tombstones {
+                match self.decode_value(value, state.schema_version) {
+                    Ok(item) => {
+                        self.tombstones().insert(key, item);
+                    }
+                    Err(e) => failed.push(format!("snapshot tombstone {}: {}", key, e)),
+                }
+            }
+        }
+        // Items of older snapshots start at version 1, set by `put_item`; the versions
+        // of items no longer stored are those of deleted items
+        if let Some(versions) = state.versions {
+            let tombstones = self.tombstones();
+            let known = |key: &String| storage.contains_key(key) || tombstones.contains_key(key);
+            let (current, retired): (HashMap<_, _>, HashMap<_, _>) =
+                versions.into_iter().partition(|(key, _)| known(key));
+            self.versions().extend(current);
+            self.retired_versions().extend(retired);
+        }
+        self.snapshots.covered_events.store(covered, Ordering::Relaxed);
+        self.snapshots.compacted_events.store(state.compacted_events, Ordering::Relaxed);
+        log::info!(
+            "Restored {} item(s) from snapshot, replaying {} newer event(s)",
+            restored,
+            events.len() - covered
+        );
+        events.split_off(covered)
+    }
+
+    /// Latest snapshot, or None when there is none or it cannot be read (logged)
+    fn read_snapshot(&self) -> Option<(SnapshotMetadata, StoredSnapshotState)> {
+        let snapshot =
+            SnapshotStore::new(&self.snapshots.dir).and_then(|store| store.load_snapshot(None));
+        match snapshot {
+            Ok(snapshot) => self.decode_snapshot(snapshot?),
+            Err(e) => {
+                log::warn!("Ignoring snapshot in {}: {}", self.snapshots.dir, e);
+                None
+            }
+        }
+    }
+
+    /// Latest snapshot followed by the older ones kept for `?as_of=` reads, the most
+    /// recent first
+    fn read_snapshots(&self) -> Vec<(SnapshotMetadata, StoredSnapshotState)> {
+        let Some(latest) = self.read_snapshot() else {
+            return Vec::new();
+        };
+        let history = SnapshotStore::new(&self.snapshots.dir)
+            .map(|store| store.load_history(None))
+            .unwrap_or_default();
+        std::iter::once(latest)
+            .chain(history.into_iter().filter_map(|snapshot| self.decode_snapshot(snapshot)))
+            .collect()
+    }
+
+    fn decode_snapshot(
+        &self,
+        snapshot: Snapshot,
+    ) -> Option<(SnapshotMetadata, StoredSnapshotState)> {
+        let state = snapshot.validate().and_then(|_| {
+            serde_json::from_str::<StoredSnapshotState>(&snapshot.state).map_err(|e| e.to_string())
+        });
+        match state {
+            Ok(state) => Some((snapshot.metadata, state)),
+            Err(e) => {
+                log::warn!("Ignoring snapshot in {}: {}", self.snapshots.dir, e);
+                None
+            }
+        }
+    }
+
+    /// Rebuild the model state at `as_of` without touching the live state
+    ///
+    /// Starts from the nearest snapshot at or before `as_of` and replays the events
+    /// between it and `as_of`; without one, replays the log from its first event, which
+    /// is only possible while nothing was compacted. Writers only wait for the log to be
+    /// flushed and read, not for the replay.
+    pub async fn state_as_of(&self, as_of: AsOf) -> Result<HistoricalState<T>, AsOfError> {
+        let events = {
+            let mut store = self.event_store.write().await;
+            let _ = store.flush_events();
+            let store = store.downgrade();
+            store.get_all_events().map_err(|e| AsOfError::Storage(e.to_string()))?
+        };
+        let snapshots = self.read_snapshots();
+        // The latest snapshot shares the current log: the first event it holds is
+        // event `compacted` of the model's history
+        let compacted = snapshots.first().map_or(0, |(_, state)| state.compacted_events);
+        // Each snapshot with the number of events it covers and, when its events are
+        // still in the log, how many of those it covers
+        let positioned: Vec<_> = snapshots
+            .into_iter()
+            .map(|(metadata, state)| {
+                if state.compacted_events == compacted {
+                    let covered = metadata.covered_events(&events);
+                    (compacted + covered, Some(covered), metadata, state)
+                } else {
+                    (state.compacted_events + metadata.event_count, None, metadata, state)
+                }
+            })
+            .collect();
+        let earliest_event_index = positioned
+            .iter()
+            .filter(|(_, covered, ..)| covered.is_some())
+            .map(|(position, ..)| *position)
+            .min()
+            .unwrap_or(compacted);
+        let nearest = positioned
+            .into_iter()
+            .filter(|(position, _, metadata, _)| match as_of {
+                AsOf::EventIndex(index) => *position <= index,
+                AsOf::Timestamp(secs) => metadata.timestamp <= secs,
+            })
+            .max_by_key(|(position, ..)| *position);
+
+        let (start, from_snapshot) = match nearest {
+            Some((_, Some(covered), _, state)) => (covered, Some(state)),
+            // The events after an older snapshot were compacted: it only tells the
+            // state at the exact point it was written
+            Some((position, None, _, state)) if as_of == AsOf::EventIndex(position) => {
+                (events.len(), Some(state))
+            }
+            None if compacted == 0 => (0, None),
+            _ => return Err(AsOfError::Compacted { earliest_event_index }),
+        };
+
+        let mut state = HistoricalState { items: HashMap::new(), tombstones: HashMap::new() };
+        if let Some(stored) = from_snapshot {
+            let decoded = |values: HashMap<String, serde_json::Value>| {
+                values
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        self.decode_value(value, stored.schema_version).ok().map(|i| (key, i))
+                    })
+                    .collect::<HashMap<_, _>>()
+            };
+            state.items = decoded(stored.items);
+            state.tombstones = decoded(stored.tombstones);
+        }
+        let end = match as_of {
+            AsOf::EventIndex(index) => index.saturating_sub(compacted).min(events.len()),
+            AsOf::Timestamp(_) => events.len(),
+        };
+
+        // A batch committed after the cut-off is left out whole
+        let envelopes = events
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|event_json| serde_json::from_str::<EventEnvelope>(event_json).ok())
+            .filter(
+                |envelope| !matches!(as_of, AsOf::Timestamp(secs) if envelope.timestamp > secs),
+            );
+        for envelope in BatchMarker::committed(envelopes) {
+            let op = EventOp::of(&envelope.event_type);
+            if op == EventOp::Purge {
+                if let Some(key) = RecordPurge::expired_id(&envelope.payload) {
+                    state.items.remove(&key);
+                    state.tombstones.remove(&key);
+                }
+                continue;
+            }
+            let decoded = match op {
+                EventOp::Patch => {
+                    let current =
+                        envelope.aggregate_id.as_deref().and_then(|id| state.items.get(id));
+                    self.decode_patch(current, &envelope)
+                }
+                _ => self.decode_payload(&envelope),
+            };
+            let Ok(item) = decoded else { continue };
+            let key = item.get_primary_key();
+            match op {
+                EventOp::Put | EventOp::Patch => {
+                    state.tombstones.remove(&key);
+                    state.items.insert(key, item);
+                }
+                EventOp::Tombstone if T::soft_delete() => {
+                    state.items.remove(&key);
+                    state.tombstones.insert(key, item);
+                }
+                EventOp::Delete | EventOp::Tombstone | EventOp::Purge => {
+                    state.items.remove(&key);
+                    state.tombstones.remove(&key);
+                }
+            }
+        }
+        Ok(state)
+    }
+
+    /// Write a snapshot of the current state now
+    ///
+    /// Returns the number of events it covers. With compaction enabled the event log
+    /// is truncated afterwards.
+    pub async fn snapshot(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let schema_version = self.schema_spec.as_ref().map(|s| s.version);
+        Self::write_snapshot(
+            &self.event_store,
+            &self.storage,
+            &self.tombstones,
+            &self.versions,
+            &self.retired_versions,
+            &self.snapshots,
+            schema_version,
+        )
+        .await
+    }
+
+    async fn write_snapshot(
+        event_store: &tokio::sync::RwLock<EventStore>,
+        storage: &tokio::sync::RwLock<HashMap<String, T>>,
+        tombstones: &std::sync::Mutex<HashMap<String, T>>,
+        versions: &std::sync::Mutex<HashMap<String, u64>>,
+        retired_versions: &std::sync::Mutex<HashMap<String, u64>>,
+        schedule: &SnapshotSchedule,
+        schema_version: Option<u32>,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        // Writers update storage before appending their event, so holding the event
+        // store while reading storage captures every event up to `event_count`
+        // (and possibly a few newer ones, which replay re-applies harmlessly)
+        let mut store = event_store.write().await;
+        store.flush_events()?;
+        let event_count = store.event_count();
+        let compact = schedule.compaction.load(Ordering::Relaxed) && event_count > 0;
+        let compacted_events = schedule.compacted_events.load(Ordering::Relaxed)
+            + if compact { event_count } else { 0 };
+        // Compaction drops the events retention reads write times from
+        let logged = if compact {
+            retention::last_writes(&store.get_all_envelopes()?)
+        } else {
+            HashMap::new()
+        };
+        let state = {
+            let storage = storage.read().await;
+            let tombstones = tombstones.lock().unwrap_or_else(|e| e.into_inner());
+            let mut written_at = schedule.written_at.lock().unwrap_or_else(|e| e.into_inner());
+            for (key, at) in logged {
+                let entry = written_at.entry(key).or_default();
+                *entry = (*entry).max(at);
+            }
+            written_at.retain(|key, _| storage.contains_key(key) || tombstones.contains_key(key));
+            let versions = versions.lock().unwrap_or_else(|e| e.into_inner());
+            let retired = retired_versions.lock().unwrap_or_else(|e| e.into_inner());
+            // Deleted items keep their last version, under the ids no longer stored
+            let versions: HashMap<String, u64> = retired
+                .iter()
+                .chain(versions.iter())
+                .map(|(key, v)| (key.clone(), *v))
+                .collect();
+            serde_json::to_string(&SnapshotState {
+                schema_version,
+                compacted_events,
+                items: &*storage,
+                tombstones: &*tombstones,
+                written_at: &*written_at,
+                versions: &versions,
+            })?
+        };
+
+        let covered = if compact {
+            // Staged until the log is truncated, then put in place by `settle_compaction`
+            let snapshot = Snapshot::new(None, event_count, None, state);
+            SnapshotStore::new(&schedule.pending_dir())?.save_snapshot(&snapshot)?;
+            let truncated = store.truncate_events();
+            if !Self::settle_compaction(&mut store, schedule)? {
+                truncated?;
+            }
+            log::info!("Snapshot written, {} event(s) compacted", event_count);
+            0
+        } else {
+            let last_event_id = store.get_last_event_hash().cloned();
+            let snapshot = Snapshot::new(None, event_count, last_event_id, state);
+            SnapshotStore::new(&schedule.dir)?
+                .save_snapshot_keeping(&snapshot, SNAPSHOT_HISTORY)?;
+            log::info!("Snapshot written at event {}", event_count);
+            event_count
+        };
+        schedule.covered_events.store(covered, Ordering::Relaxed);
+        schedule.compacted_events.store(compacted_events, Ordering::Relaxed);
+        Ok(event_count)
+    }
+
+    /// Finish or drop a compacting snapshot staged by `write_snapshot`
+    ///
+    /// The staged snapshot records how many events it compacts. While the log still
+    /// holds them, the truncation never happened: the snapshot is dropped and the log
+    /// stays as it was. Otherwise the truncation started, so the staged snapshot becomes
+    /// the latest one before the rest of the log is discarded; until then a restart
+    /// settles it the same way. Returns whether a staged snapshot was put in place.
+    fn settle_compaction(
+        store: &mut EventStore,
+        schedule: &SnapshotSchedule,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let pending_dir = schedule.pending_dir();
+        if !std::path::Path::new(&pending_dir).exists() {
+            return Ok(false);
+        }
+        let pending = SnapshotStore::new(&pending_dir)?;
+        let staged = match pending.load_snapshot(None) {
+            Ok(staged) => staged,
+            Err(e) => {
+                log::warn!("Dropping staged snapshot in {}: {}", pending_dir, e);
+                None
+            }
+        };
+        let logged = store.get_all_events()?.len();
+        let settled = match staged {
+            Some(mut snapshot) if logged < snapshot.metadata.event_count => {
+                // It covers no event of the truncated log
+                snapshot.metadata.event_count = 0;
+                SnapshotStore::new(&schedule.dir)?
+                    .save_snapshot_keeping(&snapshot, SNAPSHOT_HISTORY)?;
+                store.truncate_events()?;
+                true
+            }
+            _ => false,
+        };
+        std::fs::remove_dir_all(&pending_dir)?;
+        Ok(settled)
+    }
+
+    /// Start a background snapshot when `interval` events accumulated since the last one
+    fn maybe_snapshot(&self, event_count: usize) {
+        let interval = self.snapshots.interval.load(Ordering::Relaxed);
+        let covered = self.snapshots.covered_events.load(Ordering::Relaxed);
+        if interval == 0 || event_count < covered.saturating_add(interval) {
+            return;
+        }
+        if self.snapshots.running.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let event_store = Arc::clone(&self.event_store);
+        let storage = Arc::clone(&self.storage);
+        let tombstones = Arc::clone(&self.tombstones);
+        let versions = Arc::clone(&self.versions);
+        let retired_versions = Arc::clone(&self.retired_versions);
+        let schedule = Arc::clone(&self.snapshots);
+        let schema_version = self.schema_spec.as_ref().map(|s| s.version);
+        tokio::spawn(async move {
+            let written = Self::write_snapshot(
+                &event_store,
+                &storage,
+                &tombstones,
+                &versions,
+                &retired_versions,
+                &schedule,
+                schema_version,
+            )
+            .await;
+            if let Err(e) = written {
+                log::error!("Snapshot failed: {}", e);
+            }
+            schedule.running.store(false, Ordering::Release);
+        });
+    }
+
+    /// Expire records and trim field history as the model's `#[lifecycle]` policy asks
+    ///
+    /// The event log is rewritten without the purged events (hash chain re-linked) and
+    /// gains one `Purged` audit event per affected record, naming it and the trimmed
+    /// fields but none of their values. The snapshot, if any, is rewritten so it no
+    /// longer holds expired records.
+    ///
+    /// The purge is local to this node, so clustered servers do not run it (see
+    /// `StorageConfig::retention_interval_secs`).
+    pub async fn enforce_retention(
+        &self,
+    ) -> Result<RetentionReport, Box<dyn std::error::Error + Send + Sync>> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        let mut report = RetentionReport::default();
+        let policy = self.retention_policy().await?;
+        if policy.is_empty() {
+            return Ok(report);
+        }
+        {
+            let mut store = self.event_store.write().await;
+            store.flush_events()?;
+            let events = store.get_all_envelopes()?;
+            let mut storage = self.storage.write().await;
+            let purges = {
+                let tombstones = self.tombstones();
+                let mut written_at = self.written_at();
+                written_at
+                    .retain(|key, _| storage.contains_key(key) || tombstones.contains_key(key));
+                retention::plan_purge(&policy, &events, &written_at, now)
+            };
+            if purges.is_empty() {
+                return Ok(report);
+            }
+
+            let mut removed = std::collections::HashSet::new();
+            for purge in &purges {
+                match purge.reason {
+                    PurgeReason::Expired => {
+                        self.remove_item(&mut storage, &purge.id);
+                        self.written_at().remove(&purge.id);
+                        report.expired_records += 1;
+                    }
+                    PurgeReason::VersionLimit { .. } => report.trimmed_records += 1,
+                }
+                removed.extend(purge.events.iter().copied());
+            }
+            report.events_removed = removed.len();
+
+            let rebased: HashMap<usize, String> = purges
+                .iter()
+                .filter_map(|purge| purge.rebase.as_ref())
+                .map(|(i, state)| (*i, state.to_string()))
+                .collect();
+            let mut kept: Vec<EventEnvelope> = events
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| !removed.contains(i))
+                .map(|(i, mut event)| {
+                    if let Some(state) = rebased.get(&i) {
+                        event.payload = state.clone();
+                    }
+                    event
+                })
+                .collect();
+            for purge in &purges {
+                kept.push(EventEnvelope {
+                    event_type: format!("{}Purged", std::any::type_name::<T>()),
+                    event_id: format!(
+                        "{}:Purged:{}:{}",
+                        std::any::type_name::<T>(),
+                        purge.id,
+                        chrono::Utc::now().timestamp_millis()
+                    ),
+                    timestamp: now,
+                    payload: purge.audit_payload().to_string(),
+                    aggregate_id: Some(purge.id.clone()),
+                    event_hash: None,
+                    previous_hash: None,
+                    schema_version: None,
+                    aggregate_version: None,
+                });
+            }
+            store.rewrite_events(kept)?;
+        }
+
+        log::info!(
+            "Retention: {} record(s) expired, {} trimmed, {} event(s) removed",
+            report.expired_records,
+            report.trimmed_records,
+            report.events_removed
+        );
+        // The snapshots still hold expired records and no longer match the log
+        if self.read_snapshot().is_some() {
+            self.snapshot().await?;
+            SnapshotStore::new(&self.snapshots.dir)?.delete_history(None)?;
+        }
+        Ok(report)
+    }
+
+    /// Retention policy of `T`, read from any stored, soft-deleted or logged item
+    async fn retention_policy(
+        &self,
+    ) -> Result<RetentionPolicy, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(item) = self.storage.read().await.values().next() {
+            return Ok(RetentionPolicy::of(item));
+        }
+        if let Some(item) = self.tombstones().values().next() {
+            return Ok(RetentionPolicy::of(item));
+        }
+        // Only deleted records left, whose events may still have to go
+        let events = self.event_store.read().await.get_all_envelopes()?;
+        Ok(events
+            .iter()
+            .rev()
+            .find_map(|event| self.decode_payload(event).ok())
+            .map(|item| RetentionPolicy::of(&item))
+            .unwrap_or_default())
+    }
+
+    fn replay_into(
+        &self,
+        storage: &mut std::collections::HashMap<String, T>,
+        events: Vec<String>,
+    ) -> (usize, Vec<String>) {
+        let mut replayed_count = 0;
+        let mut failed = Vec::new();
+
+        let envelopes = events
+            .iter()
+            .filter_map(|event_json| serde_json::from_str::<EventEnvelope>(event_json).ok());
+        for envelope in BatchMarker::committed(envelopes) {
+            let op = EventOp::of(&envelope.event_type);
+            if op == EventOp::Purge {
+                if let Some(key) = RecordPurge::expired_id(&envelope.payload) {
+                    self.remove_item(storage, &key);
+                }
+                replayed_count += 1;
+                continue;
+            }
+            let decoded = match op {
+                EventOp::Patch => {
+                    let current = envelope.aggregate_id.as_deref().and_then(|id| storage.get(id));
+                    self.decode_patch(current, &envelope)
+                }
+                _ => self.decode_payload(&envelope),
+            };
+            match decoded {
+                Ok(item) => {
+                    self.replay_event(storage, op, item, envelope.aggregate_version);
+                    replayed_count += 1;
+                }
+                Err(e) => failed.push(format!("{}: {}", envelope.event_id, e)),
+            }
+        }
+
+        (replayed_count, failed)
+    }
+
+    /// Apply one decoded event; `version` is the item version it recorded, if any
+    fn replay_event(
+        &self,
+        storage: &mut HashMap<String, T>,
+        op: EventOp,
+        item: T,
+        version: Option<u64>,
+    ) {
+        let key = item.get_primary_key();
+        match op {
+            EventOp::Put | EventOp::Patch => {
+                let previous = storage.get(&key).cloned();
+                self.apply_unique(&key, previous.as_ref(), Some(&item));
+                self.put_item(storage, key.clone(), item);
+            }
+            // Models that dropped `#[db(soft_delete)]` treat old tombstones as deletes
+            EventOp::Tombstone if T::soft_delete() => {
+                self.tombstone_item(storage, &key);
+                self.tombstones().insert(key.clone(), item);
+            }
+            EventOp::Delete | EventOp::Tombstone | EventOp::Purge => {
+                self.remove_item(storage, &key);
+                return;
+            }
+        }
+        if let Some(version) = version {
+            self.versions().insert(key, version);
+        }
+    }
+
+    /// Remove `key` for good: storage, unique keys and any tombstone
+    fn remove_item(&self, storage: &mut HashMap<String, T>, key: &str) {
+        let removed = self.take_item(storage, key);
+        self.apply_unique(key, removed.as_ref(), None);
+        self.tombstones().remove(key);
+        self.retire_version(key);
+    }
+
+    /// Deserialize an event payload, upcasting it from the schema version it was written at
+    fn decode_payload(&self, envelope: &EventEnvelope) -> Result<T, String> {
+        if self.schema_spec.is_none() {
+            return serde_json::from_str(&envelope.payload).map_err(|e| e.to_string());
+        }
+        let payload: serde_json::Value =
+            serde_json::from_str(&envelope.payload).map_err(|e| e.to_string())?;
+        self.decode_value(payload, envelope.schema_version)
+    }
+
+    /// Apply a `Patched` event to `current`, upcasting its diff first
+    ///
+    /// Retention rewrites the oldest kept `Patched` event of a trimmed record with the
+    /// record's full state, which applies without a `current` item.
+    fn decode_patch(&self, current: Option<&T>, envelope: &EventEnvelope) -> Result<T, String> {
+        let mut diff: serde_json::Value =
+            serde_json::from_str(&envelope.payload).map_err(|e| e.to_string())?;
+        if let Some(spec) = self.schema_spec.as_deref() {
+            let from = envelope.schema_version.unwrap_or(crate::schema::LEGACY_SCHEMA_VERSION);
+            diff = self.migrations.apply(diff, from, spec.version).map_err(|e| e.to_string())?;
+        }
+        let mut value = match current {
+            Some(item) => serde_json::to_value(item).map_err(|e| e.to_string())?,
+            None => serde_json::Value::Object(Default::default()),
         };
+        patch::merge_patch(&mut value, &diff);
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    /// Deserialize an item written at `schema_version`, upcasting it first
+    fn decode_value(
+        &self,
+        payload: serde_json::Value,
+        schema_version: Option<u32>,
+    ) -> Result<T, String> {
+        let Some(spec) = self.schema_spec.as_deref() else {
+            return serde_json::from_value(payload).map_err(|e| e.to_string());
+        };
+        let from = schema_version.unwrap_or(crate::schema::LEGACY_SCHEMA_VERSION);
+        let mut payload =
+            self.migrations.apply(payload, from, spec.version).map_err(|e| e.to_string())?;
+        crate::schema::backfill_spec_defaults(&mut payload, spec);
+        serde_json::from_value(payload).map_err(|e| e.to_string())
+    }
+
+    /// Insert `item` under `key`, keeping the secondary indexes in sync and bumping the
+    /// item's version
+    fn put_item(
+        &self,
+        storage: &mut std::collections::HashMap<String, T>,
+        key: String,
+        item: T,
+    ) -> Option<T> {
+        if !self.secondary_indexes.is_empty() {
+            let old = storage.get(&key).and_then(|o| serde_json::to_value(o).ok());
+            let new = serde_json::to_value(&item).ok();
+            self.secondary_indexes.apply(&key, old.as_ref(), new.as_ref());
+        }
+        if T::soft_delete() {
+            self.tombstones().remove(&key);
+        }
+        {
+            let mut versions = self.versions();
+            let version = versions
+                .entry(key.clone())
+                .or_insert_with(|| self.retired_versions().remove(&key).unwrap_or_default());
+            *version += 1;
+        }
+        storage.insert(key, item)
+    }
+
+    fn tombstones(&self) -> std::sync::MutexGuard<'_, HashMap<String, T>> {
+        self.tombstones.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn written_at(&self) -> std::sync::MutexGuard<'_, HashMap<String, u64>> {
+        self.snapshots.written_at.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn versions(&self) -> std::sync::MutexGuard<'_, HashMap<String, u64>> {
+        self.versions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn retired_versions(&self) -> std::sync::MutexGuard<'_, HashMap<String, u64>> {
+        self.retired_versions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Drop the version of deleted item `key`, keeping it for an item created again
+    fn retire_version(&self, key: &str) {
+        let retired = self.versions().remove(key);
+        if let Some(version) = retired {
+            self.retired_versions().insert(key.to_string(), version);
+        }
+    }
+
+    /// Current version of item `id`, live or soft-deleted, sent as its `ETag`
+    ///
+    /// Versions change under the storage lock: read while holding it, the version
+    /// matches the stored item.
+    pub fn item_version(&self, id: &str) -> Option<u64> {
+        self.versions().get(id).copied()
+    }
+
+    /// Remove `key` from storage and release its unique keys; soft-delete models keep
+    /// the removed item as a tombstone, with a new version
+    fn tombstone_item(&self, storage: &mut HashMap<String, T>, key: &str) -> Option<T> {
+        let removed = self.take_item(storage, key);
+        self.apply_unique(key, removed.as_ref(), None);
+        if let Some(item) = removed.as_ref().filter(|_| T::soft_delete()) {
+            self.tombstones().insert(key.to_string(), item.clone());
+            *self.versions().entry(key.to_string()).or_default() += 1;
+        } else if removed.is_some() {
+            self.retire_version(key);
+        }
+        removed
+    }
+
+    /// Event operation recorded by DELETE
+    fn delete_operation() -> &'static str {
+        if T::soft_delete() {
+            "Tombstoned"
+        } else {
+            "Deleted"
+        }
+    }
+
+    /// Soft-deleted items readable with `user_perms`, marked with `"_deleted": true`
+    fn deleted_json<'a>(
+        tombstones: impl Iterator<Item = &'a T>,
+        user_perms: &[String],
+    ) -> Vec<serde_json::Value> {
+        tombstones
+            .filter(|item| item.can_read(user_perms))
+            .filter_map(|item| serde_json::to_value(item).ok())
+            .map(|mut value| {
+                if let Some(object) = value.as_object_mut() {
+                    object.insert("_deleted".to_string(), serde_json::Value::Bool(true));
+                }
+                value
+            })
+            .collect()
+    }
+
+    /// Remove `key`, keeping the secondary indexes in sync
+    fn take_item(
+        &self,
+        storage: &mut std::collections::HashMap<String, T>,
+        key: &str,
+    ) -> Option<T> {
+        let removed = storage.remove(key);
+        if let Some(old) = removed.as_ref().filter(|_| !self.secondary_indexes.is_empty()) {
+            let old = serde_json::to_value(old).ok();
+            self.secondary_indexes.apply(key, old.as_ref(), None);
+        }
+        removed
+    }
+
+    /// Claim the unique keys of `item` stored under `key`, releasing those of `old`
+    fn claim_unique(&self, key: &str, old: Option<&T>, item: &T) -> Result<(), UniqueViolation> {
+        if self.unique_index.is_empty() {
+            return Ok(());
+        }
+        let old = old.and_then(|o| serde_json::to_value(o).ok());
+        let new = serde_json::to_value(item).unwrap_or(serde_json::Value::Null);
+        self.unique_index.claim(key, old.as_ref(), &new)
+    }
+
+    /// Record a write decided elsewhere (replay, replication, reconciliation)
+    fn apply_unique(&self, key: &str, old: Option<&T>, new: Option<&T>) {
+        if self.unique_index.is_empty() {
+            return;
+        }
+        let old = old.and_then(|o| serde_json::to_value(o).ok());
+        match new.and_then(|n| serde_json::to_value(n).ok()) {
+            Some(new) => self.unique_index.apply(key, old.as_ref(), &new),
+            None => {
+                if let Some(old) = old {
+                    self.unique_index.release(key, &old);
+                }
+            }
+        }
+    }
+
+    /// Reserve the unique keys of `item` before a clustered write enters the
+    /// consensus log; the leader applies it later through `apply_replicated_*`
+    ///
+    /// The keys of the stored version stay claimed until the write applies, so that
+    /// no other write takes them while it may still fail.
+    pub async fn reserve_unique(&self, key: &str, item: &T) -> Result<(), UniqueViolation> {
+        if self.unique_index.is_empty() {
+            return Ok(());
+        }
+        let new = serde_json::to_value(item).unwrap_or(serde_json::Value::Null);
+        self.unique_index.reserve(key, &new)
+    }
 
-        Ok(handler)
+    /// Release a reservation taken by `reserve_unique` for a write that did not commit
+    pub async fn release_unique(&self, key: &str, item: &T) {
+        let storage = self.storage.read().await;
+        self.unreserve_unique(key, item, storage.get(key));
     }
 
-    /// Get a reference to the event store for chain verification
-    pub fn get_event_store(&self) -> &Arc<tokio::sync::RwLock<EventStore>> {
-        &self.event_store
+    /// Undo `reserve_unique`: drop the keys of `item` that `current` (the version still
+    /// in storage, if any) does not hold
+    fn unreserve_unique(&self, key: &str, item: &T, current: Option<&T>) {
+        if self.unique_index.is_empty() {
+            return;
+        }
+        let new = serde_json::to_value(item).unwrap_or(serde_json::Value::Null);
+        let current = current.and_then(|c| serde_json::to_value(c).ok());
+        self.unique_index.unreserve(key, &new, current.as_ref());
     }
 
-    /// Create a new DeclarativeHttpHandler with automatic event replay
-    ///
-    /// This is a convenience method that creates the handler and automatically
-    /// replays all persisted events to restore state from the event log.
-    pub async fn new_with_replay(
-        event_store_path: &str,
-    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let handler = Self::new(event_store_path)?;
-        handler.replay_events().await?;
-        Ok(handler)
+    /// Lock the model for its share of the `POST /_batch` identified by `batch_id`
+    pub async fn lock_batch(&self, batch_id: &str) -> BatchLock<'_, T> {
+        let event_store = self.event_store.write().await;
+        let storage = self.storage.write().await;
+        BatchLock {
+            handler: self,
+            event_store,
+            storage,
+            batch_id: batch_id.to_string(),
+            events: Vec::new(),
+            undo: Vec::new(),
+        }
     }
 
-    pub async fn replay_events(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
-        let events = {
-            let store = self.event_store.read().await;
-            store.get_all_events()?
+    /// Check a `POST /_batch` update setting the fields of `changes` on item `id` as
+    /// PATCH checks it (permissions, immutable fields, primary key, validation); returns
+    /// the updated item, or the response rejecting it
+    pub async fn prepare_batch_update(
+        &self,
+        req: &Req,
+        id: &str,
+        changes: &serde_json::Value,
+    ) -> Result<T, Resp> {
+        let access = self.write_access(req).await;
+        let Some(current) = self.storage.read().await.get(id).cloned() else {
+            return Err(self.not_found_response());
         };
+        self.batch_update(&current, changes, &access)
+    }
 
-        let mut replayed_count = 0;
-        let mut storage = self.storage.write().await;
-
-        for event_json in events {
-            if let Ok(envelope) = serde_json::from_str::<EventEnvelope>(&event_json) {
-                if let Ok(item) = serde_json::from_str::<T>(&envelope.payload) {
-                    let key = item.get_primary_key();
-                    storage.insert(key, item);
-                    replayed_count += 1;
-                }
-            }
+    /// `current` with the fields of `changes` set, checked as a PATCH of those fields
+    fn batch_update(
+        &self,
+        current: &T,
+        changes: &serde_json::Value,
+        access: &WriteAccess,
+    ) -> Result<T, Resp> {
+        let stored = serde_json::to_value(current).map_err(|_| self.internal_error_response())?;
+        let mut updated = stored.clone();
+        if let (Some(fields), Some(changes)) = (updated.as_object_mut(), changes.as_object()) {
+            fields.extend(changes.clone());
         }
+        let patch = Patch::Merge(patch::merge_diff(&stored, &updated));
+        self.patch_item(current, &patch, access).map(|(item, _)| item)
+    }
 
-        if Self::is_verbose() || replayed_count > 0 {
-            log::info!("Replayed {} events into memory", replayed_count);
+    /// Permission check of a `POST /_batch` operation on `item`: that of PATCH for
+    /// creates and updates (`changed` = the fields set), that of DELETE for deletes (None)
+    pub async fn authorize_batch_write(
+        &self,
+        req: &Req,
+        item: &T,
+        changed: Option<&[String]>,
+    ) -> Option<Resp> {
+        match changed {
+            Some(changed) => self.authorize_patch(&self.write_access(req).await, item, changed),
+            None => self.authorize_delete(req, item).await,
         }
-
-        Ok(replayed_count)
     }
 
     /// Returns true if consensus is enabled for this handler
@@ -270,6 +1635,18 @@ where
         storage.values().cloned().collect()
     }
 
+    /// All items with their versions, read under the same lock
+    pub async fn items_with_versions(&self) -> (Vec<T>, HashMap<String, u64>) {
+        let storage = self.storage.read().await;
+        let versions = self.versions();
+        let items = storage.values().cloned().collect();
+        let versions = storage
+            .keys()
+            .filter_map(|key| Some((key.clone(), *versions.get(key)?)))
+            .collect();
+        (items, versions)
+    }
+
     /// Return all items matching a predicate (cloned)
     /// Useful for relational queries like "orders for consumer X"
     pub async fn query<F>(&self, predicate: F) -> Vec<T>
@@ -286,16 +1663,135 @@ where
         storage.get(id).cloned()
     }
 
+    /// Permissions of `req` passed to `T::can_read`, from the permission extractor
+    pub fn read_permissions(&self, req: &Req) -> Vec<String> {
+        self.permission_extractor.as_ref().map(|f| f(req)).unwrap_or_default()
+    }
+
+    /// Check foreign keys on writes against the models they reference; only the first
+    /// resolver installed is kept
+    pub fn set_reference_resolver(&self, resolver: Arc<dyn ReferenceResolver>) {
+        let _ = self.references.set(resolver);
+    }
+
+    /// Foreign keys of `item` that reference no item, as `foreign_key` validation errors
+    ///
+    /// Null keys reference nothing and pass, as do keys into models the resolver does not
+    /// serve. The check reads the referenced models: callers must not hold this model's
+    /// storage lock, which a self reference would read. The server keeps deletes of the
+    /// referenced items from applying between the check and the write.
+    pub async fn check_references(&self, item: &T) -> Result<(), ValidationErrors> {
+        let Some(resolver) = self.references.get() else { return Ok(()) };
+        let foreign_keys = T::foreign_keys();
+        if foreign_keys.is_empty() {
+            return Ok(());
+        }
+        let value = serde_json::to_value(item).unwrap_or_default();
+        let mut errors = ValidationErrors::new();
+        for fk in &foreign_keys {
+            let Some(key) = value.get(&fk.field_name).and_then(crate::http::expand::key_text)
+            else {
+                continue;
+            };
+            let keys = std::slice::from_ref(&key);
+            let missing = resolver.missing(&fk.referenced_model, &fk.referenced_field, keys);
+            if missing.await.is_some_and(|missing| !missing.is_empty()) {
+                let message = format!("references no {} '{}'", fk.referenced_model, key);
+                errors.add(&fk.field_name, "foreign_key", message);
+            }
+        }
+        errors.into_result()
+    }
+
+    /// `check_references` as the 422 response rejecting the write
+    async fn reference_error(&self, item: &T) -> Option<Resp> {
+        let errors = self.check_references(item).await.err()?;
+        Some(self.validation_error_response(&errors.to_json()))
+    }
+
+    /// Items whose `field` equals one of `values`, as JSON; with `user_perms`, only those
+    /// they can read
+    ///
+    /// Relations are resolved with it: the primary key is looked up directly, other
+    /// fields through their index when they have one.
+    pub async fn items_where(
+        &self,
+        field: &str,
+        values: &[String],
+        user_perms: Option<&[String]>,
+    ) -> Vec<serde_json::Value> {
+        let storage = self.storage.read().await;
+        self.items_in(&storage, field, values, user_perms)
+    }
+
+    /// Items of `storage` whose `field` equals one of `values`, among those `user_perms`
+    /// may read (all when None)
+    fn items_in(
+        &self,
+        storage: &HashMap<String, T>,
+        field: &str,
+        values: &[String],
+        user_perms: Option<&[String]>,
+    ) -> Vec<serde_json::Value> {
+        use crate::http::query::{FilterOp, FilterSpec, QueryParams};
+
+        let readable = |item: &&T| user_perms.is_none_or(|perms| item.can_read(perms));
+        if field == T::primary_key_field() {
+            return values
+                .iter()
+                .filter_map(|id| storage.get(id))
+                .filter(readable)
+                .filter_map(|item| serde_json::to_value(item).ok())
+                .collect();
+        }
+        let filter = FilterSpec {
+            field: field.to_string(),
+            op: FilterOp::In(values.to_vec()),
+            value: format!("in({})", values.join(",")),
+        };
+        let params = QueryParams { filters: vec![filter], ..Default::default() };
+        let plan = self.secondary_indexes.plan(&params);
+        let items: Vec<&T> = match &plan.candidates {
+            Some(ids) => ids.iter().filter_map(|id| storage.get(id)).collect(),
+            None => storage.values().collect(),
+        };
+        items
+            .into_iter()
+            .filter(readable)
+            .filter_map(|item| serde_json::to_value(item).ok())
+            .filter(|value| params.matches(value))
+            .collect()
+    }
+
     /// Replace local in-memory storage with authoritative items from leader (no persistence writes)
-    pub async fn reconcile_replace_all(&self, items: Vec<T>) {
+    ///
+    /// Items listed in `versions` get the version recorded there, as the leader numbered
+    /// them, so `If-Match` tags keep meaning the same item state on every node.
+    pub async fn reconcile_replace_all(&self, items: Vec<T>, versions: &HashMap<String, u64>) {
         let mut storage = self.storage.write().await;
         storage.clear();
+        {
+            let tombstones = self.tombstones();
+            let mut versions = self.versions();
+            let removed: Vec<String> =
+                versions.keys().filter(|key| !tombstones.contains_key(*key)).cloned().collect();
+            let mut retired = self.retired_versions();
+            for key in removed {
+                retired.extend(versions.remove_entry(&key));
+            }
+        }
+        self.unique_index.clear();
+        self.secondary_indexes.clear();
         for item in items.into_iter() {
             let actual_key = serde_json::to_value(&item)
                 .ok()
                 .and_then(|v| v.get("id").and_then(|id| id.as_str().map(|s| s.to_string())))
                 .unwrap_or_else(|| item.get_primary_key());
-            storage.insert(actual_key, item);
+            self.apply_unique(&actual_key, None, Some(&item));
+            self.put_item(&mut storage, actual_key.clone(), item);
+            if let Some(version) = versions.get(&actual_key) {
+                self.versions().insert(actual_key, *version);
+            }
         }
         if Self::is_verbose() {
             log::debug!(
@@ -305,6 +1801,22 @@ where
         }
     }
 
+    /// Install a cluster snapshot from the leader: replace all items with `items` at
+    /// `versions` (see `reconcile_replace_all`), then snapshot them locally so a restart
+    /// comes back to the same state
+    pub async fn install_snapshot(
+        &self,
+        items: Vec<T>,
+        versions: &HashMap<String, u64>,
+    ) -> Result<usize, String> {
+        let count = items.len();
+        self.reconcile_replace_all(items, versions).await;
+        self.snapshot()
+            .await
+            .map_err(|e| format!("Failed to snapshot installed state: {}", e))?;
+        Ok(count)
+    }
+
     /// Apply a single replicated item from leader (for followers to receive replication)
     /// This adds to storage AND persists to event store (idempotent via key-based storage)
     pub async fn apply_replicated_item(&self, item: T) -> Result<(), String> {
@@ -313,15 +1825,19 @@ where
             .and_then(|v| v.get("id").and_then(|id| id.as_str().map(|s| s.to_string())))
             .unwrap_or_else(|| item.get_primary_key());
 
+        // Locked in the order of PUT: events are logged in the order the writes applied
+        let store = self.event_store.write().await;
+
         // Insert into storage FIRST (this is the critical operation)
         {
             let mut storage = self.storage.write().await;
-            storage.insert(actual_key.clone(), item.clone());
+            let previous = self.put_item(&mut storage, actual_key.clone(), item.clone());
+            self.apply_unique(&actual_key, previous.as_ref(), Some(&item));
         }
 
         // Persist to event store (best-effort - don't fail the operation)
         // IMPORTANT: Storage is already updated, so operation must succeed for consistency
-        if let Err(e) = self.persist_to_event_store("Replicated", &item).await {
+        if let Err(e) = self.commit_event(store, "Replicated", &item) {
             log::warn!(
                 "Failed to persist replicated item event for {}: {:?} (storage already updated)",
                 actual_key,
@@ -351,9 +1867,12 @@ where
     /// Apply a replicated UPDATE from leader (for followers to receive UPDATE replication)
     /// This updates storage AND persists to event store
     pub async fn apply_replicated_update(&self, id: &str, item: T) -> Result<(), String> {
-        // Check if item exists
+        // Locked in the order of PUT: events are logged in the order the writes applied
+        let store = self.event_store.write().await;
+
+        // Check if item exists, and update it in storage
         {
-            let storage = self.storage.read().await;
+            let mut storage = self.storage.write().await;
             let has_key = storage.contains_key(id);
             log::debug!(
                 "APPLY UPDATE: id={}, exists_in_storage={}, storage_len={}",
@@ -364,20 +1883,17 @@ where
             if !has_key {
                 // If item doesn't exist, treat as create (eventual consistency)
                 drop(storage);
+                drop(store);
                 log::debug!("APPLY UPDATE: item doesn't exist, creating instead");
                 return self.apply_replicated_item(item).await;
             }
-        }
-
-        // Update in storage
-        {
-            let mut storage = self.storage.write().await;
-            storage.insert(id.to_string(), item.clone());
+            let previous = self.put_item(&mut storage, id.to_string(), item.clone());
+            self.apply_unique(id, previous.as_ref(), Some(&item));
         }
 
         // Persist to event store (best-effort - don't fail the operation)
         // IMPORTANT: Storage is already updated, so we must succeed for consistency
-        if let Err(e) = self.persist_to_event_store("Updated", &item).await {
+        if let Err(e) = self.commit_event(store, "Updated", &item) {
             log::warn!(
                 "Failed to persist update event for {}: {:?} (storage already updated)",
                 id,
@@ -396,6 +1912,9 @@ where
     /// This removes from storage AND persists deletion event to event store
     /// IMPORTANT: This must be fully idempotent and never fail once storage is modified
     pub async fn apply_replicated_delete(&self, id: &str) -> Result<bool, String> {
+        // Locked in the order of DELETE: events are logged in the order the writes applied
+        let store = self.event_store.write().await;
+
         // Remove from storage
         let removed_item = {
             let mut storage = self.storage.write().await;
@@ -406,13 +1925,13 @@ where
                 has_key,
                 storage.len()
             );
-            storage.remove(id)
+            self.tombstone_item(&mut storage, id)
         };
 
         if let Some(item) = removed_item {
             // Persist deletion to event store (best-effort - don't fail the operation)
             // This ensures idempotency: once item is removed from storage, operation succeeds
-            if let Err(e) = self.persist_to_event_store("Deleted", &item).await {
+            if let Err(e) = self.commit_event(store, Self::delete_operation(), &item) {
                 log::warn!(
                     "Failed to persist delete event for {}: {:?} (storage already updated)",
                     id,
@@ -432,6 +1951,112 @@ where
         }
     }
 
+    /// Check a clustered PATCH of `id` on the leader, before it enters the consensus log,
+    /// as PATCH checks it: write permissions, immutable and primary-key fields, validation
+    ///
+    /// Returns the patched item and the merge patch of the fields it changes, None when
+    /// it changes nothing, or the response rejecting it.
+    pub async fn prepare_patch(
+        &self,
+        req: &Req,
+        id: &str,
+        body: &[u8],
+    ) -> Result<Option<(T, serde_json::Value)>, Resp> {
+        let content_type = req
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if body.len() > Self::max_body_bytes_single() {
+            return Err(self.entity_too_large_response(Self::max_body_bytes_single()));
+        }
+        let patch = match Patch::parse(content_type, body) {
+            Some(Ok(patch)) => patch,
+            Some(Err(e)) => return Err(self.patch_error_response(&e)),
+            None => return Err(self.unsupported_patch_type_response()),
+        };
+        let access = self.write_access(req).await;
+        let Some(current) = self.storage.read().await.get(id).cloned() else {
+            return Err(self.not_found_response());
+        };
+        let (item, diff) = self.patch_item(&current, &patch, &access)?;
+        Ok((!Self::is_empty_diff(&diff)).then_some((item, diff)))
+    }
+
+    /// Apply a replicated PATCH from leader: `diff` is merged into the stored item and
+    /// persisted as a `Patched` event
+    ///
+    /// Returns None when `id` is not stored (any more).
+    pub async fn apply_replicated_patch(
+        &self,
+        id: &str,
+        diff: &serde_json::Value,
+    ) -> Result<Option<T>, String> {
+        // Locked in the order of PATCH: diffs replay in the order they applied
+        let mut store = self.event_store.write().await;
+        let item = {
+            let mut storage = self.storage.write().await;
+            let Some(current) = storage.get(id) else {
+                return Ok(None);
+            };
+            let mut value = serde_json::to_value(current).map_err(|e| e.to_string())?;
+            patch::merge_patch(&mut value, diff);
+            let item: T = serde_json::from_value(value)
+                .map_err(|e| format!("Failed to apply replicated patch: {}", e))?;
+            let previous = self.put_item(&mut storage, id.to_string(), item.clone());
+            self.apply_unique(id, previous.as_ref(), Some(&item));
+            item
+        };
+
+        match self.append_patch(&mut store, id, diff) {
+            Ok(event_count) => {
+                drop(store);
+                self.maybe_snapshot(event_count);
+            }
+            Err(e) => log::warn!(
+                "Failed to persist patch event for {}: {:?} (storage already updated)",
+                id,
+                e
+            ),
+        }
+
+        Ok(Some(item))
+    }
+
+    /// Apply a replicated restore from leader: the soft-deleted item `id` goes back to
+    /// storage and a `Restored` event is persisted
+    ///
+    /// Returns None when `id` is not soft-deleted, e.g. restored by an earlier entry.
+    pub async fn apply_replicated_restore(&self, id: &str) -> Result<Option<T>, String> {
+        // Locked in the order of PUT: events are logged in the order the writes applied
+        let store = self.event_store.write().await;
+        let restored = {
+            let mut storage = self.storage.write().await;
+            let Some(item) = self.tombstones().get(id).cloned() else {
+                return Ok(None);
+            };
+            // The leader reserved its unique keys before proposing the restore
+            self.apply_unique(id, None, Some(&item));
+            self.put_item(&mut storage, id.to_string(), item.clone());
+            item
+        };
+
+        if let Err(e) = self.commit_event(store, "Restored", &restored) {
+            log::warn!(
+                "Failed to persist restore event for {}: {:?} (storage already updated)",
+                id,
+                e
+            );
+        }
+
+        Ok(Some(restored))
+    }
+
+    /// Soft-deleted item `id`
+    pub fn deleted_item(&self, id: &str) -> Option<T> {
+        self.tombstones().get(id).cloned()
+    }
+
     /// GET /api/{model}/count - Return item count only (lightweight read)
     async fn handle_count(&self) -> Result<Resp, Infallible> {
         let count = self.storage_count().await as u64;
@@ -443,6 +2068,45 @@ where
             .unwrap())
     }
 
+    /// GET /api/{model}/_aggregate - Grouped metrics over readable items
+    ///
+    /// Accepts the list filter syntax; see `http::aggregate` for the parameters.
+    async fn handle_aggregate(&self, req: &Req) -> Result<Resp, Infallible> {
+        use crate::http::aggregate::AggregateQuery;
+
+        let query = match AggregateQuery::parse(req.uri().query().unwrap_or("")) {
+            Ok(query) => query,
+            Err(e) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header("content-type", "application/json")
+                    .body(body_from(e.to_json().to_string()))
+                    .unwrap());
+            }
+        };
+        let user_perms: Vec<String> =
+            self.permission_extractor.as_ref().map(|f| f(req)).unwrap_or_default();
+
+        let items: Vec<serde_json::Value> = {
+            let storage = self.storage.read().await;
+            storage
+                .values()
+                .filter(|item| item.can_read(&user_perms))
+                .filter_map(|item| serde_json::to_value(item).ok())
+                .collect()
+        };
+        let groups = query.run(&items);
+        let body = serde_json::json!({
+            "group_by": query.group_by,
+            "groups": groups,
+        });
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "application/json")
+            .body(body_from(body.to_string()))
+            .unwrap())
+    }
+
     /// GET /api/{model}/random-id - Return any existing id to help UPDATE workloads
     async fn handle_random_id(&self) -> Result<Resp, Infallible> {
         let storage = self.storage.read().await;
@@ -506,6 +2170,11 @@ where
             // GET /api/products/count - Count items (lightweight read)
             (&Method::GET, 1) if path_segments[0] == "count" => self.handle_count().await,
 
+            // GET /api/products/_aggregate - Grouped count/sum/avg/min/max
+            (&Method::GET, 1) if path_segments[0] == "_aggregate" => {
+                self.handle_aggregate(&req).await
+            }
+
             // GET /api/products/random-id - Return a single existing id (lightweight)
             (&Method::GET, 1) if path_segments[0] == "random-id" => self.handle_random_id().await,
 
@@ -527,12 +2196,24 @@ where
                 self.handle_update(id, req).await
             }
 
+            // PATCH /api/products/{id} - Partial update (merge patch / JSON Patch)
+            (&Method::PATCH, 1) => {
+                let id = path_segments[0];
+                self.handle_patch(id, req).await
+            }
+
             // DELETE /api/products/{id} - Delete
             (&Method::DELETE, 1) => {
                 let id = path_segments[0];
                 self.handle_delete(id, req).await
             }
 
+            // POST /api/products/{id}/_restore - Restore a soft-deleted item
+            (&Method::POST, 2) if path_segments[1] == "_restore" && T::soft_delete() => {
+                let id = path_segments[0];
+                self.handle_restore(id, req).await
+            }
+
             _ => {
                 // Provide 405 Method Not Allowed for known resources with wrong methods
                 let resp = if path_segments.is_empty() {
@@ -540,15 +2221,15 @@ where
                     self.method_not_allowed_response("GET, POST")
                 } else if path_segments.len() == 1 {
                     let seg = path_segments[0];
-                    if seg == "count" || seg == "random-id" || seg == "stream" {
+                    if matches!(seg, "count" | "random-id" | "stream" | "_aggregate") {
                         // Only GET allowed
                         self.method_not_allowed_response("GET")
                     } else if seg == "_bulk" {
                         // Only POST allowed
                         self.method_not_allowed_response("POST")
                     } else {
-                        // Item resource: GET, PUT, DELETE allowed
-                        self.method_not_allowed_response("GET, PUT, DELETE")
+                        // Item resource: GET, PUT, PATCH, DELETE allowed
+                        self.method_not_allowed_response("GET, PUT, PATCH, DELETE")
                     }
                 } else {
                     // Unknown nested path → 404
@@ -561,9 +2242,7 @@ where
 
     /// GET /api/{model} - List all items with filtering, sorting, and pagination
     async fn handle_list(&self, req: &Req) -> Result<Resp, Infallible> {
-        use crate::http::query::{
-            compare_json_values, matches_filter, parse_query_params, DEFAULT_MAX_TAKE,
-        };
+        use crate::http::query::{parse_query_params, DEFAULT_MAX_TAKE};
 
         // Extract permissions from request if extractor is provided
         let user_perms: Vec<String> =
@@ -571,64 +2250,154 @@ where
 
         // Parse query parameters
         let query_str = req.uri().query().unwrap_or("");
-        let params = parse_query_params(query_str);
-
-        // Clone readable items while holding the lock, then release before expensive transforms
-        let json_items: Vec<serde_json::Value> = {
-            let storage = self.storage.read().await;
-            storage
-                .values()
-                .filter(|item| item.can_read(&user_perms))
-                .filter_map(|item| serde_json::to_value(item).ok())
-                .collect()
+        let mut params = match parse_query_params(query_str) {
+            Ok(params) => params,
+            Err(e) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header("content-type", "application/json")
+                    .body(body_from(e.to_json().to_string()))
+                    .unwrap());
+            }
         };
+        // `?expand=` projects the items once their relations are embedded
+        if req.extensions().get::<crate::http::expand::Unprojected>().is_some() {
+            params.fields = None;
+        }
 
-        let mut json_items = json_items;
-
-        // Apply filters
-        if !params.filters.is_empty() {
-            json_items.retain(|item| params.filters.iter().all(|f| matches_filter(item, f)));
+        if params.uses_cursor() {
+            if params.include_deleted || params.as_of.is_some() {
+                return Ok(self.bad_request_response(
+                    "include_deleted and as_of are not supported with cursor pagination",
+                ));
+            }
+            return self.handle_list_cursor(req, &params, &user_perms).await;
         }
 
-        let total = json_items.len() as u64;
+        let historical = match params.as_of {
+            Some(as_of) => match self.state_as_of(as_of).await {
+                Ok(state) => Some(state),
+                Err(e) => return Ok(self.as_of_error_response(&e)),
+            },
+            None => None,
+        };
+
+        // Tombstones and past states are not indexed
+        let plan = if params.include_deleted || historical.is_some() {
+            crate::http::index::QueryPlan::full_scan()
+        } else {
+            self.secondary_indexes.plan(&params)
+        };
+        let skip = params.skip as usize;
+        // Apply take limit (use default max if not specified to prevent unbounded responses)
+        let effective_take = params.take.unwrap_or(DEFAULT_MAX_TAKE) as usize;
+        let mut scanned = 0usize;
 
-        // Apply sorting
-        if let Some(ref sort) = params.sort {
-            let field = sort.field.clone();
-            let desc = sort.descending;
-            json_items.sort_by(|a, b| {
-                let va = a.get(&field).unwrap_or(&serde_json::Value::Null);
-                let vb = b.get(&field).unwrap_or(&serde_json::Value::Null);
-                let ord = compare_json_values(va, vb);
-                if desc {
-                    ord.reverse()
+        let (json_items, total, has_more) = if plan.sorted {
+            // Candidates are already ordered: count every match but only serialize the page
+            let storage = self.storage.read().await;
+            let mut page: Vec<serde_json::Value> = Vec::new();
+            let mut total = 0usize;
+            for item in plan.candidates.iter().flatten().filter_map(|id| storage.get(id)) {
+                scanned += 1;
+                if !item.can_read(&user_perms) {
+                    continue;
+                }
+                let in_page = total >= skip && total - skip < effective_take;
+                if !params.has_filters() {
+                    if in_page {
+                        if let Ok(value) = serde_json::to_value(item) {
+                            page.push(value);
+                        }
+                    }
                 } else {
-                    ord
+                    let Ok(value) = serde_json::to_value(item) else { continue };
+                    if !params.matches(&value) {
+                        continue;
+                    }
+                    if in_page {
+                        page.push(value);
+                    }
                 }
-            });
-        }
+                total += 1;
+            }
+            let has_more = total > skip + page.len();
+            (page, total, has_more)
+        } else {
+            // Clone readable items while holding the lock, then release before expensive
+            // transforms
+            let mut json_items: Vec<serde_json::Value> = if let Some(state) = &historical {
+                scanned = state.items.len();
+                state
+                    .items
+                    .values()
+                    .filter(|item| item.can_read(&user_perms))
+                    .filter_map(|item| serde_json::to_value(item).ok())
+                    .collect()
+            } else {
+                let storage = self.storage.read().await;
+                let readable = |item: &&T| item.can_read(&user_perms);
+                let items: Vec<&T> = match &plan.candidates {
+                    Some(ids) => ids.iter().filter_map(|id| storage.get(id)).collect(),
+                    None => storage.values().collect(),
+                };
+                scanned = items.len();
+                items
+                    .into_iter()
+                    .filter(readable)
+                    .filter_map(|item| serde_json::to_value(item).ok())
+                    .collect()
+            };
+            if params.include_deleted {
+                let deleted = match &historical {
+                    Some(state) => Self::deleted_json(state.tombstones.values(), &user_perms),
+                    None => Self::deleted_json(self.tombstones().values(), &user_perms),
+                };
+                scanned += deleted.len();
+                json_items.extend(deleted);
+            }
 
-        // Apply pagination
-        let skip = params.skip as usize;
-        if skip > 0 && skip < json_items.len() {
-            json_items = json_items.into_iter().skip(skip).collect();
-        } else if skip >= json_items.len() && !json_items.is_empty() {
-            json_items.clear();
-        }
+            // Apply filters
+            if params.has_filters() {
+                json_items.retain(|item| params.matches(item));
+            }
 
-        // Apply take limit (use default max if not specified to prevent unbounded responses)
-        let effective_take = params.take.unwrap_or(DEFAULT_MAX_TAKE) as usize;
-        let has_more = json_items.len() > effective_take;
-        json_items.truncate(effective_take);
+            let total = json_items.len();
+
+            // Apply sorting
+            if !params.sort.is_empty() {
+                json_items.sort_by(|a, b| params.compare(a, b));
+            }
+
+            // Apply pagination
+            if skip > 0 && skip < json_items.len() {
+                json_items = json_items.into_iter().skip(skip).collect();
+            } else if skip >= json_items.len() && !json_items.is_empty() {
+                json_items.clear();
+            }
+
+            let has_more = json_items.len() > effective_take;
+            json_items.truncate(effective_take);
+            (json_items, total, has_more)
+        };
+        // Projection runs last: permissions and filters see the whole item
+        let json_items: Vec<serde_json::Value> =
+            json_items.into_iter().map(|item| params.project(item)).collect();
 
         // Build wrapper response
-        let response = serde_json::json!({
+        let mut response = serde_json::json!({
             "data": json_items,
-            "total": total,
+            "total": total as u64,
             "skip": params.skip,
             "take": effective_take,
             "has_more": has_more,
         });
+        if params.explain {
+            response["explain"] = plan.explain_json(scanned, total);
+        }
+        if let Some(as_of) = params.as_of {
+            response["as_of"] = as_of.to_json();
+        }
 
         match serde_json::to_string(&response) {
             Ok(json) => Ok(Response::builder()
@@ -640,6 +2409,205 @@ where
         }
     }
 
+    /// GET /api/{model}?limit=N[&after=|&before=cursor] - Keyset pagination
+    ///
+    /// Items are ordered by the sort field (or the primary key), with the primary key
+    /// as tie-breaker. Only the page is serialized when the sort field is indexed.
+    async fn handle_list_cursor(
+        &self,
+        req: &Req,
+        params: &crate::http::query::QueryParams,
+        user_perms: &[String],
+    ) -> Result<Resp, Infallible> {
+        use crate::http::cursor::{compare_positions, Cursor, DEFAULT_CURSOR_LIMIT};
+        use crate::http::index::{IndexKey, PlanStrategy};
+        use crate::http::query::{get_path, DEFAULT_MAX_TAKE};
+
+        if params.after.is_some() && params.before.is_some() {
+            return Ok(self.bad_request_response("after and before are mutually exclusive"));
+        }
+        if params.sort.len() > 1 {
+            return Ok(self.bad_request_response("cursor pagination supports a single sort field"));
+        }
+        let sort = params.sort.first();
+        let backwards = params.before.is_some();
+        let cursor = match params.after.as_ref().or(params.before.as_ref()) {
+            Some(token) => {
+                match Cursor::decode(token).and_then(|c| c.check_sort(sort).map(|_| c)) {
+                    Ok(cursor) => Some(cursor),
+                    Err(e) => {
+                        let message = e.to_string();
+                        let body =
+                            serde_json::json!({ "error": "invalid_cursor", "message": message });
+                        return Ok(Response::builder()
+                            .status(StatusCode::BAD_REQUEST)
+                            .header("content-type", "application/json")
+                            .body(body_from(body.to_string()))
+                            .unwrap());
+                    }
+                }
+            }
+            None => None,
+        };
+        let limit =
+            params.limit.unwrap_or(DEFAULT_CURSOR_LIMIT).clamp(1, DEFAULT_MAX_TAKE) as usize;
+        // Walk direction: `before` walks backwards from the cursor, then flips the page
+        let descending = sort.map(|s| s.descending).unwrap_or(false) != backwards;
+        let start = cursor.as_ref().map(|c| c.position());
+        let start_ref = start.as_ref().map(|(k, id)| (k, id.as_str()));
+
+        let storage = self.storage.read().await;
+
+        // Ids in walk order, strictly after the cursor position
+        let (ids, strategy, index): (Vec<String>, PlanStrategy, Option<String>) = match sort {
+            Some(s) => match self.secondary_indexes.ordered_ids(&s.field, start_ref, descending) {
+                Some(ids) => (ids, PlanStrategy::IndexSort, Some(s.field.clone())),
+                None => {
+                    let mut positioned: Vec<(IndexKey, &String)> = storage
+                        .iter()
+                        .map(|(key, item)| {
+                            let value = serde_json::to_value(item).ok();
+                            let field_value = value.as_ref().and_then(|v| get_path(v, &s.field));
+                            (IndexKey::from_value(field_value), key)
+                        })
+                        .collect();
+                    positioned.sort_by(|a, b| {
+                        compare_positions((&a.0, a.1.as_str()), (&b.0, b.1.as_str()), descending)
+                    });
+                    let ids = positioned
+                        .into_iter()
+                        .filter(|(k, id)| {
+                            start_ref.is_none_or(|pos| {
+                                compare_positions((k, id.as_str()), pos, descending).is_gt()
+                            })
+                        })
+                        .map(|(_, id)| id.clone())
+                        .collect();
+                    (ids, PlanStrategy::FullScan, None)
+                }
+            },
+            None => {
+                let mut keys: Vec<&String> = storage
+                    .keys()
+                    .filter(|key| {
+                        start_ref.is_none_or(|(_, id)| {
+                            if descending {
+                                key.as_str() < id
+                            } else {
+                                key.as_str() > id
+                            }
+                        })
+                    })
+                    .collect();
+                keys.sort();
+                if descending {
+                    keys.reverse();
+                }
+                (keys.into_iter().cloned().collect(), PlanStrategy::FullScan, None)
+            }
+        };
+
+        // Collect one extra item to know whether another page exists
+        let mut scanned = 0usize;
+        let mut page: Vec<(String, serde_json::Value)> = Vec::with_capacity(limit + 1);
+        for id in &ids {
+            let Some(item) = storage.get(id) else { continue };
+            scanned += 1;
+            if !item.can_read(user_perms) {
+                continue;
+            }
+            let Ok(value) = serde_json::to_value(item) else { continue };
+            if !params.matches(&value) {
+                continue;
+            }
+            page.push((id.clone(), value));
+            if page.len() > limit {
+                break;
+            }
+        }
+        drop(storage);
+
+        let has_more = page.len() > limit;
+        page.truncate(limit);
+        if backwards {
+            page.reverse();
+        }
+
+        let cursor_at = |entry: Option<&(String, serde_json::Value)>| {
+            entry.map(|(key, value)| Cursor::for_item(sort, key, value).encode())
+        };
+        // Forward: more items after the page means a next page; an `after` cursor means
+        // something precedes it. Backwards is the mirror image.
+        let (next_cursor, prev_cursor) = if backwards {
+            (cursor_at(page.last()), if has_more { cursor_at(page.first()) } else { None })
+        } else {
+            (
+                if has_more { cursor_at(page.last()) } else { None },
+                if cursor.is_some() { cursor_at(page.first()) } else { None },
+            )
+        };
+
+        let link =
+            Self::pagination_link_header(req, next_cursor.as_deref(), prev_cursor.as_deref());
+        let data: Vec<serde_json::Value> =
+            page.into_iter().map(|(_, value)| params.project(value)).collect();
+        let matched = data.len();
+        let mut response = serde_json::json!({
+            "data": data,
+            "limit": limit,
+            "has_more": has_more,
+            "next_cursor": next_cursor,
+            "prev_cursor": prev_cursor,
+        });
+        if params.explain {
+            response["explain"] = serde_json::json!({
+                "strategy": strategy.as_str(),
+                "index": index,
+                "candidates": ids.len(),
+                "sorted_by_index": strategy == PlanStrategy::IndexSort,
+                "scanned": scanned,
+                "matched": matched,
+            });
+        }
+
+        let mut builder = Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "application/json");
+        if let Some(link) = link {
+            builder = builder.header("link", link);
+        }
+        Ok(builder.body(body_from(response.to_string())).unwrap())
+    }
+
+    /// RFC 8288 `Link` header with `next`/`prev` relations for cursor pagination
+    fn pagination_link_header(req: &Req, next: Option<&str>, prev: Option<&str>) -> Option<String> {
+        let base: Vec<&str> = req
+            .uri()
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| {
+                let key = pair.split_once('=').map(|(k, _)| k).unwrap_or(pair);
+                !pair.is_empty() && key != "after" && key != "before"
+            })
+            .collect();
+        let path = req.uri().path();
+        let link = |param: &str, cursor: &str, rel: &str| {
+            let mut pairs: Vec<String> = base.iter().map(|p| p.to_string()).collect();
+            pairs.push(format!("{}={}", param, urlencoding::encode(cursor)));
+            format!("<{}?{}>; rel=\"{}\"", path, pairs.join("&"), rel)
+        };
+        let links: Vec<String> = [("after", next, "next"), ("before", prev, "prev")]
+            .into_iter()
+            .filter_map(|(param, cursor, rel)| cursor.map(|c| link(param, c, rel)))
+            .collect();
+        if links.is_empty() {
+            None
+        } else {
+            Some(links.join(", "))
+        }
+    }
+
     /// GET /api/{model}/stream - SSE real-time change subscription
     async fn handle_sse_stream(&self) -> Result<Resp, Infallible> {
         match &self.sse_broadcaster {
@@ -739,8 +2707,11 @@ where
         }
 
         // Validate the model
-        if let Err(validation_error) = item.validate() {
-            return Ok(self.bad_request_response(&validation_error));
+        if let Err(errors) = item.validate_fields() {
+            return Ok(self.validation_error_response(&errors.to_json()));
+        }
+        if let Some(failed) = self.reference_error(&item).await {
+            return Ok(failed);
         }
 
         // Apply lifecycle rules
@@ -751,9 +2722,20 @@ where
         let primary_key = item.get_primary_key();
 
         // RAFT INTEGRATION: Check if consensus is required
-        if let Some(consensus_arc) = &self.consensus {
+        let version = if let Some(consensus_arc) = &self.consensus {
             log::debug!("Raft: Proposing create operation for item {}", primary_key);
 
+            // Use the item's actual ID as key, not the placeholder
+            let actual_key = serde_json::to_value(&item)
+                .ok()
+                .and_then(|v| v.get("id").and_then(|id| id.as_str().map(|s| s.to_string())))
+                .unwrap_or_else(|| primary_key.clone());
+
+            // Reserve unique keys before proposing so concurrent duplicates lose here
+            if let Err(violation) = self.reserve_unique(&actual_key, &item).await {
+                return Ok(self.conflict_response(&violation));
+            }
+
             // Real Raft consensus proposal
             match consensus_arc
                 .read()
@@ -765,12 +2747,6 @@ where
                     log::info!("Raft: Consensus achieved, applying operation locally");
 
                     // Apply to local storage after successful consensus
-                    // Use the item's actual ID as key, not the placeholder
-                    let actual_key = serde_json::to_value(&item)
-                        .ok()
-                        .and_then(|v| v.get("id").and_then(|id| id.as_str().map(|s| s.to_string())))
-                        .unwrap_or_else(|| primary_key.clone());
-
                     log::debug!(
                         "DEBUG: primary_key = {}, actual_key = {}",
                         primary_key,
@@ -781,19 +2757,25 @@ where
                         serde_json::to_string(&item).unwrap_or_default()
                     );
 
-                    {
+                    // Locked in the order of PUT: events are logged in the order the
+                    // writes applied
+                    let store = self.event_store.write().await;
+                    let version = {
                         let mut storage = self.storage.write().await;
-                        storage.insert(actual_key.clone(), item.clone());
+                        self.put_item(&mut storage, actual_key.clone(), item.clone());
                         log::debug!("DEBUG: Storage now has {} items", storage.len());
-                    }
+                        self.item_version(&actual_key)
+                    };
 
-                    if (self.persist_to_event_store("Created", &item).await).is_err() {
+                    if self.commit_event(store, "Created", &item).is_err() {
                         return Ok(self.internal_error_response());
                     }
 
                     log::info!("Raft: Successfully replicated item {} across cluster", primary_key);
+                    version
                 }
                 Err(e) => {
+                    self.release_unique(&actual_key, &item).await;
                     return Ok(Response::builder()
                         .status(StatusCode::SERVICE_UNAVAILABLE)
                         .header("content-type", "application/json")
@@ -803,28 +2785,29 @@ where
             }
         } else {
             // Local-only mode (no replication)
-            {
+            let store = self.event_store.write().await;
+            let version = {
                 let mut storage = self.storage.write().await;
-                storage.insert(primary_key.clone(), item.clone());
-            }
+                if let Err(violation) =
+                    self.claim_unique(&primary_key, storage.get(&primary_key), &item)
+                {
+                    return Ok(self.conflict_response(&violation));
+                }
+                self.put_item(&mut storage, primary_key.clone(), item.clone());
+                self.item_version(&primary_key)
+            };
 
-            if (self.persist_to_event_store("Created", &item).await).is_err() {
+            if self.commit_event(store, "Created", &item).is_err() {
                 return Ok(self.internal_error_response());
             }
 
             log::debug!("Local: Item {} stored locally only", primary_key);
-        }
+            version
+        };
 
         self.broadcast_sse("create", &item).await;
 
-        match serde_json::to_string(&item) {
-            Ok(json) => Ok(Response::builder()
-                .status(StatusCode::CREATED)
-                .header("content-type", "application/json")
-                .body(body_from(json))
-                .unwrap()),
-            Err(_) => Ok(self.internal_error_response()),
-        }
+        Ok(self.item_response(StatusCode::CREATED, &item, version))
     }
 
     /// POST /api/{model}/_bulk - Create multiple items
@@ -853,78 +2836,125 @@ where
             Err(_) => return Ok(self.bad_request_response("Invalid JSON array")),
         };
 
+        // Validate every item up front so an invalid entry rejects the whole batch
+        let invalid: Vec<serde_json::Value> = items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                item.validate_fields()
+                    .err()
+                    .map(|errors| serde_json::json!({ "index": index, "fields": errors.fields }))
+            })
+            .collect();
+        if !invalid.is_empty() {
+            let body = serde_json::json!({
+                "error": "validation_failed",
+                "message": format!("{} item(s) failed validation", invalid.len()),
+                "items": invalid,
+            });
+            return Ok(self.validation_error_response(&body));
+        }
+        let mut unresolved = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            if let Err(errors) = self.check_references(item).await {
+                unresolved.push(serde_json::json!({ "index": index, "fields": errors.fields }));
+            }
+        }
+        if !unresolved.is_empty() {
+            let body = serde_json::json!({
+                "error": "validation_failed",
+                "message": format!("{} item(s) reference missing items", unresolved.len()),
+                "items": unresolved,
+            });
+            return Ok(self.validation_error_response(&body));
+        }
+
+        for item in items.iter_mut() {
+            if let Err(e) = item.apply_lifecycle() {
+                return Ok(self.bad_request_response(&e));
+            }
+        }
+
         let mut created: Vec<T> = Vec::with_capacity(items.len());
         let disable_consensus: bool = std::env::var("LT_DISABLE_CONSENSUS")
             .ok()
             .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
             .unwrap_or(false);
-
-        // Process sequentially for simplicity and determinism
-        for mut item in items.drain(..) {
-            if let Err(e) = item.validate() {
-                return Ok(self.bad_request_response(&e));
-            }
-            if let Err(e) = item.apply_lifecycle() {
-                return Ok(self.bad_request_response(&e));
+        let use_consensus = self.consensus.is_some() && !disable_consensus;
+
+        // Storage key of each item (the consensus path keys by the item's own "id")
+        let keys: Vec<String> = items
+            .iter()
+            .map(|item| {
+                let primary_key = item.get_primary_key();
+                if !use_consensus {
+                    return primary_key;
+                }
+                serde_json::to_value(item)
+                    .ok()
+                    .and_then(|v| v.get("id").and_then(|id| id.as_str().map(|s| s.to_string())))
+                    .unwrap_or(primary_key)
+            })
+            .collect();
+
+        // Reserve unique keys for the whole batch (including duplicates within it)
+        // before anything is persisted
+        for (index, (key, item)) in keys.iter().zip(items.iter()).enumerate() {
+            if let Err(violation) = self.reserve_unique(key, item).await {
+                for (k, reserved) in keys.iter().zip(items.iter()).take(index) {
+                    self.release_unique(k, reserved).await;
+                }
+                let mut body = violation.to_json();
+                body["index"] = serde_json::json!(index);
+                return Ok(Response::builder()
+                    .status(StatusCode::CONFLICT)
+                    .header("content-type", "application/json")
+                    .body(body_from(body.to_string()))
+                    .unwrap());
             }
+        }
 
+        // Process sequentially for simplicity and determinism
+        let mut pending = items.into_iter().zip(keys.iter());
+        while let Some((item, key)) = pending.next() {
             let primary_key = item.get_primary_key();
 
-            if let Some(consensus_arc) = &self.consensus {
-                if !disable_consensus {
-                    // Consensus path
-                    match consensus_arc
-                        .read()
-                        .await
-                        .propose_create(item.clone(), primary_key.clone())
-                        .await
-                    {
-                        Ok(_) => {
-                            // Apply to local storage after successful consensus
-                            let actual_key = serde_json::to_value(&item)
-                                .ok()
-                                .and_then(|v| {
-                                    v.get("id").and_then(|id| id.as_str().map(|s| s.to_string()))
-                                })
-                                .unwrap_or_else(|| primary_key.clone());
-                            {
-                                let mut storage = self.storage.write().await;
-                                storage.insert(actual_key, item.clone());
-                            }
-                            if (self.persist_to_event_store("Created", &item).await).is_err() {
-                                return Ok(self.internal_error_response());
-                            }
-                            created.push(item);
+            if let Some(consensus_arc) = self.consensus.as_ref().filter(|_| use_consensus) {
+                // Consensus path
+                match consensus_arc.read().await.propose_create(item.clone(), primary_key).await {
+                    Ok(_) => {
+                        // Apply to local storage after successful consensus
+                        let store = self.event_store.write().await;
+                        {
+                            let mut storage = self.storage.write().await;
+                            self.put_item(&mut storage, key.clone(), item.clone());
                         }
-                        Err(e) => {
-                            return Ok(Response::builder()
-                                .status(StatusCode::SERVICE_UNAVAILABLE)
-                                .header("content-type", "application/json")
-                                .body(body_from(format!(
-                                    r#"{{"error":"Consensus failed: {}"}}"#,
-                                    e
-                                )))
-                                .unwrap());
+                        if self.commit_event(store, "Created", &item).is_err() {
+                            return Ok(self.internal_error_response());
                         }
+                        created.push(item);
                     }
-                } else {
-                    // Consensus disabled -> local path
-                    {
-                        let mut storage = self.storage.write().await;
-                        storage.insert(primary_key.clone(), item.clone());
-                    }
-                    if (self.persist_to_event_store("Created", &item).await).is_err() {
-                        return Ok(self.internal_error_response());
+                    Err(e) => {
+                        // Release the claims of this item and of the ones never proposed
+                        self.release_unique(key, &item).await;
+                        for (rest, rest_key) in pending.by_ref() {
+                            self.release_unique(rest_key, &rest).await;
+                        }
+                        return Ok(Response::builder()
+                            .status(StatusCode::SERVICE_UNAVAILABLE)
+                            .header("content-type", "application/json")
+                            .body(body_from(format!(r#"{{"error":"Consensus failed: {}"}}"#, e)))
+                            .unwrap());
                     }
-                    created.push(item);
                 }
             } else {
-                // No consensus configured -> local path
+                // No consensus configured (or disabled) -> local path
+                let store = self.event_store.write().await;
                 {
                     let mut storage = self.storage.write().await;
-                    storage.insert(primary_key.clone(), item.clone());
+                    self.put_item(&mut storage, key.clone(), item.clone());
                 }
-                if (self.persist_to_event_store("Created", &item).await).is_err() {
+                if self.commit_event(store, "Created", &item).is_err() {
                     return Ok(self.internal_error_response());
                 }
                 created.push(item);
@@ -946,10 +2976,44 @@ where
         // Extract permissions from request if extractor is provided
         let user_perms: Vec<String> =
             self.permission_extractor.as_ref().map(|f| f(req)).unwrap_or_default();
+        let if_none_match = req.headers().get("if-none-match").and_then(|v| v.to_str().ok());
 
-        let storage = self.storage.read().await;
+        let query = req.uri().query().unwrap_or("");
+        let params = match crate::http::query::parse_item_query_params(query) {
+            Ok(params) => params,
+            Err(e) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header("content-type", "application/json")
+                    .body(body_from(e.to_json().to_string()))
+                    .unwrap());
+            }
+        };
+        let include_deleted = T::soft_delete() && params.include_deleted;
+
+        // Point-in-time read: look the item up in the rebuilt state instead, without ETag
+        let (live, deleted, version) = match params.as_of {
+            Some(as_of) => match self.state_as_of(as_of).await {
+                Ok(mut state) => match state.items.remove(id) {
+                    Some(item) => (Some(item), None, None),
+                    None => (None, state.tombstones.remove(id).filter(|_| include_deleted), None),
+                },
+                Err(e) => return Ok(self.as_of_error_response(&e)),
+            },
+            None => {
+                let storage = self.storage.read().await;
+                let version = self.item_version(id);
+                match storage.get(id) {
+                    Some(item) => (Some(item.clone()), None, version),
+                    None => {
+                        let deleted = self.tombstones().get(id).cloned();
+                        (None, deleted.filter(|_| include_deleted), version)
+                    }
+                }
+            }
+        };
 
-        match storage.get(id) {
+        match live.as_ref().or(deleted.as_ref()) {
             Some(item) => {
                 if !item.can_read(&user_perms) {
                     return Ok(Response::builder()
@@ -958,12 +3022,28 @@ where
                         .body(body_from(r#"{"error":"Insufficient permissions"}"#))
                         .unwrap());
                 }
-                match serde_json::to_string(item) {
-                    Ok(json) => Ok(Response::builder()
-                        .status(StatusCode::OK)
-                        .header("content-type", "application/json")
-                        .body(body_from(json))
-                        .unwrap()),
+                let mut response = Response::builder();
+                if let Some(version) = version {
+                    if if_none_match.is_some_and(|tags| etag::if_none_match(tags, Some(version))) {
+                        return Ok(response
+                            .status(StatusCode::NOT_MODIFIED)
+                            .header("etag", etag::etag(version))
+                            .body(body_from(Bytes::new()))
+                            .unwrap());
+                    }
+                    response = response.header("etag", etag::etag(version));
+                }
+                match serde_json::to_value(item) {
+                    Ok(mut value) => {
+                        if deleted.is_some() {
+                            value["_deleted"] = serde_json::Value::Bool(true);
+                        }
+                        Ok(response
+                            .status(StatusCode::OK)
+                            .header("content-type", "application/json")
+                            .body(body_from(value.to_string()))
+                            .unwrap())
+                    }
                     Err(_) => Ok(self.internal_error_response()),
                 }
             }
@@ -972,10 +3052,13 @@ where
     }
 
     /// PUT /api/{model}/{id} - Update item
+    ///
+    /// With `If-Match`, the update only applies to the version the client last read.
     async fn handle_update(&self, id: &str, req: Req) -> Result<Resp, Infallible> {
         // Agnostic write enforcement using permission_extractor + can_write()
         let extracted_perms: Option<Vec<String>> =
             self.permission_extractor.as_ref().map(|f| f(&req));
+        let if_match = Self::if_match_header(&req);
 
         // Extract role BEFORE consuming body (if needed for legacy fallback)
         let extracted_role = if extracted_perms.is_none() {
@@ -1045,8 +3128,11 @@ where
         }
 
         // Validate
-        if let Err(validation_error) = updated_item.validate() {
-            return Ok(self.bad_request_response(&validation_error));
+        if let Err(errors) = updated_item.validate_fields() {
+            return Ok(self.validation_error_response(&errors.to_json()));
+        }
+        if let Some(failed) = self.reference_error(&updated_item).await {
+            return Ok(failed);
         }
 
         // Apply lifecycle
@@ -1054,173 +3140,575 @@ where
             return Ok(self.bad_request_response(&lifecycle_error));
         }
 
-        // RAFT INTEGRATION: Check if consensus is required for UPDATE
+        // RAFT INTEGRATION: Check if consensus is required for UPDATE. The event log is
+        // locked before storage and until the event is appended, so events are logged in
+        // the order the writes applied and each carries the version it was served under
+        let (version, mut store) = if let Some(consensus_arc) = &self.consensus {
+            log::debug!("Raft: Proposing UPDATE operation for item {}", id);
+            let expected_version = match self.expected_version(if_match.as_deref(), id) {
+                Ok(version) => version,
+                Err(failed) => return Ok(failed),
+            };
+            if let Err(violation) = self.reserve_unique(id, &updated_item).await {
+                return Ok(self.conflict_response(&violation));
+            }
+            match consensus_arc
+                .read()
+                .await
+                .propose_update(updated_item.clone(), id.to_string(), expected_version)
+                .await
+            {
+                Ok(_) => {
+                    // Apply to local storage after successful consensus
+                    let store = self.event_store.write().await;
+                    let mut storage = self.storage.write().await;
+                    if !storage.contains_key(id) {
+                        self.unreserve_unique(id, &updated_item, None);
+                        return Ok(self.not_found_response());
+                    }
+                    if let Some(failed) = self.stale_write(id, expected_version) {
+                        self.unreserve_unique(id, &updated_item, storage.get(id));
+                        return Ok(failed);
+                    }
+                    self.put_item(&mut storage, id.to_string(), updated_item.clone());
+                    (self.item_version(id), store)
+                }
+                Err(e) => {
+                    self.release_unique(id, &updated_item).await;
+                    return Ok(Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .header("content-type", "application/json")
+                        .body(body_from(format!(r#"{{"error": "Consensus failed: {}"}}"#, e)))
+                        .unwrap());
+                }
+            }
+        } else {
+            // No consensus - update storage directly (single-node mode)
+            let store = self.event_store.write().await;
+            let mut storage = self.storage.write().await;
+            if let Some(failed) = self.check_if_match(if_match.as_deref(), id) {
+                return Ok(failed);
+            }
+            let Some(current) = storage.get(id) else {
+                return Ok(self.not_found_response());
+            };
+            if let Err(violation) = self.claim_unique(id, Some(current), &updated_item) {
+                return Ok(self.conflict_response(&violation));
+            }
+            self.put_item(&mut storage, id.to_string(), updated_item.clone());
+            (self.item_version(id), store)
+        };
+
+        // Persist to EventStore
+        let event_count = match self.append_event(&mut store, "Updated", &updated_item) {
+            Ok(event_count) => event_count,
+            Err(_) => return Ok(self.internal_error_response()),
+        };
+        drop(store);
+        self.maybe_snapshot(event_count);
+
+        self.broadcast_sse("update", &updated_item).await;
+
+        Ok(self.item_response(StatusCode::OK, &updated_item, version))
+    }
+
+    /// PATCH /api/{model}/{id} - Partial update with a merge patch or a JSON Patch
+    ///
+    /// The patch applies to the stored item under the storage lock, so concurrent
+    /// patches of different fields do not overwrite each other. In cluster mode it
+    /// applies before it is proposed, and the write is pinned to the version it applied
+    /// to: when another write lands first, the patch applies again to the new item. Only the changed
+    /// fields are persisted, as a `Patched` event; a patch changing nothing writes no
+    /// event. `If-Match` is honoured as for PUT.
+    async fn handle_patch(&self, id: &str, req: Req) -> Result<Resp, Infallible> {
+        let access = self.write_access(&req).await;
+        let if_match = Self::if_match_header(&req);
+        let content_type = req
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if let Some(cl) = Self::content_length(&req) {
+            if cl > Self::max_body_bytes_single() {
+                return Ok(self.entity_too_large_response(Self::max_body_bytes_single()));
+            }
+        }
+        let body_bytes = match req.into_body().collect().await.map(|c| c.to_bytes()) {
+            Ok(bytes) => bytes,
+            Err(_) => return Ok(self.bad_request_response("Invalid body")),
+        };
+        if body_bytes.len() > Self::max_body_bytes_single() {
+            return Ok(self.entity_too_large_response(Self::max_body_bytes_single()));
+        }
+        let patch = match Patch::parse(&content_type, &body_bytes) {
+            Some(Ok(patch)) => patch,
+            Some(Err(e)) => return Ok(self.patch_error_response(&e)),
+            None => return Ok(self.unsupported_patch_type_response()),
+        };
+
+        let (item, diff, version, mut store) = if let Some(consensus_arc) = &self.consensus {
+            // RAFT INTEGRATION: followers receive the patched item as an update. The
+            // patch applies to an item read before proposing, so the write is pinned to
+            // the version read with it: a concurrent write makes every node skip it,
+            // and it applies again to the new item
+            let mut attempt = 1;
+            loop {
+                let (current, version) = {
+                    let storage = self.storage.read().await;
+                    (storage.get(id).cloned(), self.item_version(id))
+                };
+                let expected_version = match self.pinned_version(if_match.as_deref(), id, version) {
+                    Ok(version) => version,
+                    Err(failed) => return Ok(failed),
+                };
+                let Some(current) = current else {
+                    return Ok(self.not_found_response());
+                };
+                let (item, diff) = match self.patch_item(&current, &patch, &access) {
+                    Ok(patched) => patched,
+                    Err(resp) => return Ok(resp),
+                };
+                if Self::is_empty_diff(&diff) {
+                    return Ok(self.item_response(StatusCode::OK, &current, version));
+                }
+                if let Some(failed) = self.reference_error(&item).await {
+                    return Ok(failed);
+                }
+                log::debug!("Raft: Proposing PATCH operation for item {}", id);
+                if let Err(violation) = self.reserve_unique(id, &item).await {
+                    return Ok(self.conflict_response(&violation));
+                }
+                let proposed = consensus_arc
+                    .read()
+                    .await
+                    .propose_update(item.clone(), id.to_string(), expected_version)
+                    .await;
+                if let Err(e) = proposed {
+                    self.release_unique(id, &item).await;
+                    return Ok(Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .header("content-type", "application/json")
+                        .body(body_from(format!(r#"{{"error": "Consensus failed: {}"}}"#, e)))
+                        .unwrap());
+                }
+                let store = self.event_store.write().await;
+                let mut storage = self.storage.write().await;
+                if !storage.contains_key(id) {
+                    self.unreserve_unique(id, &item, None);
+                    return Ok(self.not_found_response());
+                }
+                if let Some(failed) = self.stale_write(id, expected_version) {
+                    self.unreserve_unique(id, &item, storage.get(id));
+                    if if_match.is_some() {
+                        return Ok(failed);
+                    }
+                    if attempt == PATCH_ATTEMPTS {
+                        return Ok(self.patch_conflict_response(self.item_version(id)));
+                    }
+                    attempt += 1;
+                    continue;
+                }
+                self.put_item(&mut storage, id.to_string(), item.clone());
+                break (item, diff, self.item_version(id), store);
+            }
+        } else {
+            // Foreign keys are checked on a first application of the patch, before the
+            // locks: the check reads the referenced models, this one for self references
+            let current = self.storage.read().await.get(id).cloned();
+            if let Some(Ok((item, _))) = current.map(|c| self.patch_item(&c, &patch, &access)) {
+                if let Some(failed) = self.reference_error(&item).await {
+                    return Ok(failed);
+                }
+            }
+            // The event log stays locked until the event is appended: replay of diffs
+            // depends on the log recording patches in the order they were applied
+            let store = self.event_store.write().await;
+            let mut storage = self.storage.write().await;
+            if let Some(failed) = self.check_if_match(if_match.as_deref(), id) {
+                return Ok(failed);
+            }
+            let Some(current) = storage.get(id) else {
+                return Ok(self.not_found_response());
+            };
+            let (item, diff) = match self.patch_item(current, &patch, &access) {
+                Ok(patched) => patched,
+                Err(resp) => return Ok(resp),
+            };
+            if Self::is_empty_diff(&diff) {
+                return Ok(self.item_response(StatusCode::OK, current, self.item_version(id)));
+            }
+            if let Err(violation) = self.claim_unique(id, Some(current), &item) {
+                return Ok(self.conflict_response(&violation));
+            }
+            self.put_item(&mut storage, id.to_string(), item.clone());
+            let version = self.item_version(id);
+            (item, diff, version, store)
+        };
+        let event_count = match self.append_patch(&mut store, id, &diff) {
+            Ok(event_count) => event_count,
+            Err(_) => return Ok(self.internal_error_response()),
+        };
+        drop(store);
+        self.maybe_snapshot(event_count);
+
+        self.broadcast_sse("update", &item).await;
+        Ok(self.item_response(StatusCode::OK, &item, version))
+    }
+
+    /// Apply `patch` to `current` and check the result like an update
+    ///
+    /// Returns the patched item and the merge patch to persist, or the response
+    /// rejecting the patch.
+    fn patch_item(
+        &self,
+        current: &T,
+        patch: &Patch,
+        access: &WriteAccess,
+    ) -> Result<(T, serde_json::Value), Resp> {
+        let stored = serde_json::to_value(current).map_err(|_| self.internal_error_response())?;
+        let patched = patch.apply(&stored).map_err(|e| self.patch_error_response(&e))?;
+        let mut item: T = serde_json::from_value(patched).map_err(|e| {
+            self.validation_error_response(&serde_json::json!({
+                "error": "patched item does not match the model",
+                "message": e.to_string()
+            }))
+        })?;
+        let to_value =
+            |item: &T| serde_json::to_value(item).map_err(|_| self.internal_error_response());
+
+        let changed: Vec<String> = match patch::merge_diff(&stored, &to_value(&item)?) {
+            serde_json::Value::Object(diff) => diff.into_iter().map(|(field, _)| field).collect(),
+            _ => Vec::new(),
+        };
+        if let Some(denied) = self.authorize_patch(access, &item, &changed) {
+            return Err(denied);
+        }
+        let mut errors = ValidationErrors::new();
+        for field in &changed {
+            if field == T::primary_key_field() || current.is_field_immutable(field) {
+                errors.add(field, "immutable", "cannot be changed");
+            }
+        }
+        if let Err(errors) = errors.into_result().and_then(|_| item.validate_fields()) {
+            return Err(self.validation_error_response(&errors.to_json()));
+        }
+        item.apply_lifecycle().map_err(|e| self.bad_request_response(&e))?;
+
+        let diff = patch::merge_diff(&stored, &to_value(&item)?);
+        Ok((item, diff))
+    }
+
+    /// Write rights of the caller of `req`
+    async fn write_access(&self, req: &Req) -> WriteAccess {
+        if let Some(extractor) = &self.permission_extractor {
+            WriteAccess::Permissions(extractor(req))
+        } else if self.permission_checker.is_some() {
+            WriteAccess::Role(self.extract_role_from_request(req).await)
+        } else {
+            WriteAccess::Open
+        }
+    }
+
+    /// Write permission check of PATCH: the model-wide check of PUT, plus the
+    /// `#[permission(write)]` of every changed field
+    fn authorize_patch(&self, access: &WriteAccess, item: &T, changed: &[String]) -> Option<Resp> {
+        let forbidden = |message: String| {
+            Some(
+                Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .header("content-type", "application/json")
+                    .body(body_from(serde_json::json!({ "error": message }).to_string()))
+                    .unwrap(),
+            )
+        };
+        let field_denied = |has: &dyn Fn(&str) -> bool| {
+            changed.iter().find_map(|field| {
+                let permission = T::field_write_permission(field)?;
+                (!has(permission))
+                    .then(|| format!("Permission '{}' required to change '{}'", permission, field))
+            })
+        };
+        match access {
+            WriteAccess::Permissions(perms) => {
+                if !item.can_write(perms) {
+                    return forbidden("Insufficient permissions".to_string());
+                }
+                if let Some(message) = field_denied(&|p| perms.iter().any(|held| held == p)) {
+                    return forbidden(message);
+                }
+            }
+            WriteAccess::Role(None) => {
+                return Some(
+                    Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .header("content-type", "application/json")
+                        .body(body_from(r#"{"error":"Authentication required"}"#))
+                        .unwrap(),
+                );
+            }
+            WriteAccess::Role(Some(role)) => {
+                let checker = self.permission_checker.as_ref()?;
+                let model_name = std::any::type_name::<T>().split("::").last().unwrap_or("Item");
+                let specific_perm = format!("{}Write", model_name);
+                if !checker.has_permission(role, &specific_perm)
+                    && !checker.has_permission(role, "Write")
+                {
+                    return forbidden("Insufficient permissions".to_string());
+                }
+                if let Some(message) = field_denied(&|p| checker.has_permission(role, p)) {
+                    return forbidden(message);
+                }
+            }
+            WriteAccess::Open => {}
+        }
+        None
+    }
+
+    /// Append the `Patched` event of `id` and return the new event count
+    fn append_patch(
+        &self,
+        store: &mut EventStore,
+        id: &str,
+        diff: &serde_json::Value,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let envelope = EventEnvelope {
+            event_type: format!("{}Patched", std::any::type_name::<T>()),
+            event_id: format!(
+                "{}:Patched:{}:{}",
+                std::any::type_name::<T>(),
+                id,
+                chrono::Utc::now().timestamp_millis()
+            ),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            payload: diff.to_string(),
+            aggregate_id: Some(id.to_string()),
+            // Hash chain fields - computed automatically by EventStore when enabled
+            event_hash: None,
+            previous_hash: None,
+            schema_version: self.schema_spec.as_ref().map(|s| s.version),
+            aggregate_version: self.item_version(id),
+        };
+        store.append_envelope(&envelope)?;
+        Ok(store.event_count())
+    }
+
+    fn is_empty_diff(diff: &serde_json::Value) -> bool {
+        diff.as_object().is_some_and(|fields| fields.is_empty())
+    }
+
+    /// Delete permission check shared by DELETE and `_restore`: the response to return
+    /// when the caller may not delete `item`
+    async fn authorize_delete(&self, req: &Req, item: &T) -> Option<Resp> {
+        // Agnostic write/delete enforcement using permission_extractor + can_write()
+        if let Some(extractor) = &self.permission_extractor {
+            if !item.can_write(&extractor(req)) {
+                return Some(
+                    Response::builder()
+                        .status(StatusCode::FORBIDDEN)
+                        .header("content-type", "application/json")
+                        .body(body_from(r#"{"error":"Insufficient permissions"}"#))
+                        .unwrap(),
+                );
+            }
+        } else if let Some(checker) = &self.permission_checker {
+            // Permission checker configured - authentication REQUIRED
+            let Some(role) = self.extract_role_from_request(req).await else {
+                // No token provided - REJECT
+                return Some(
+                    Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .header("content-type", "application/json")
+                        .body(body_from(r#"{"error":"Authentication required"}"#))
+                        .unwrap(),
+                );
+            };
+
+            // Check permissions
+            let model_name = std::any::type_name::<T>().split("::").last().unwrap_or("Item");
+            let specific_perm = format!("{}Delete", model_name);
+            if !checker.has_permission(&role, &specific_perm)
+                && !checker.has_permission(&role, "Delete")
+            {
+                return Some(
+                    Response::builder()
+                        .status(StatusCode::FORBIDDEN)
+                        .header("content-type", "application/json")
+                        .body(body_from(r#"{"error":"Insufficient permissions"}"#))
+                        .unwrap(),
+                );
+            }
+        }
+        None
+    }
+
+    /// POST /api/{model}/{id}/_restore - Bring back a soft-deleted item
+    ///
+    /// Requires the delete permission. Answers 409 when a live item took one of its
+    /// unique keys in the meantime.
+    async fn handle_restore(&self, id: &str, req: Req) -> Result<Resp, Infallible> {
+        // Read under the storage lock, which tombstones and versions change under
+        let (item, deleted_version) = {
+            let _storage = self.storage.read().await;
+            (self.tombstones().get(id).cloned(), self.item_version(id))
+        };
+        let Some(item) = item else {
+            return Ok(self.not_found_response());
+        };
+        if let Some(denied) = self.authorize_delete(&req, &item).await {
+            return Ok(denied);
+        }
+        if let Err(violation) = self.reserve_unique(id, &item).await {
+            return Ok(self.conflict_response(&violation));
+        }
+
+        // RAFT INTEGRATION: followers apply the restore as an update
         if let Some(consensus_arc) = &self.consensus {
-            log::debug!("Raft: Proposing UPDATE operation for item {}", id);
-            match consensus_arc
+            log::debug!("Raft: Proposing RESTORE operation for item {}", id);
+            let proposed = consensus_arc
                 .read()
                 .await
-                .propose_update(updated_item.clone(), id.to_string())
-                .await
-            {
-                Ok(_) => {
-                    // Apply to local storage after successful consensus
-                    let mut storage = self.storage.write().await;
-                    if !storage.contains_key(id) {
-                        return Ok(self.not_found_response());
-                    }
-                    storage.insert(id.to_string(), updated_item.clone());
-                }
-                Err(e) => {
-                    return Ok(Response::builder()
-                        .status(StatusCode::SERVICE_UNAVAILABLE)
-                        .header("content-type", "application/json")
-                        .body(body_from(format!(r#"{{"error": "Consensus failed: {}"}}"#, e)))
-                        .unwrap());
-                }
+                .propose_update(item.clone(), id.to_string(), None)
+                .await;
+            if let Err(e) = proposed {
+                self.release_unique(id, &item).await;
+                return Ok(Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .header("content-type", "application/json")
+                    .body(body_from(format!(r#"{{"error": "Consensus failed: {}"}}"#, e)))
+                    .unwrap());
             }
-        } else {
-            // No consensus - update storage directly (single-node mode)
+        }
+
+        // Locked in the order of PUT: events are logged in the order the writes applied
+        let store = self.event_store.write().await;
+        let version = {
             let mut storage = self.storage.write().await;
-            if !storage.contains_key(id) {
+            // A concurrent restore, or a restore and a new delete, got in first
+            let unchanged = !storage.contains_key(id)
+                && self.tombstones().contains_key(id)
+                && self.item_version(id) == deleted_version;
+            if !unchanged {
+                self.unreserve_unique(id, &item, storage.get(id));
                 return Ok(self.not_found_response());
             }
-            storage.insert(id.to_string(), updated_item.clone());
-        }
+            self.put_item(&mut storage, id.to_string(), item.clone());
+            self.item_version(id)
+        };
 
-        // Persist to EventStore
-        if (self.persist_to_event_store("Updated", &updated_item).await).is_err() {
+        if self.commit_event(store, "Restored", &item).is_err() {
             return Ok(self.internal_error_response());
         }
 
-        self.broadcast_sse("update", &updated_item).await;
+        self.broadcast_sse("restore", &item).await;
 
-        match serde_json::to_string(&updated_item) {
-            Ok(json) => Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header("content-type", "application/json")
-                .body(body_from(json))
-                .unwrap()),
-            Err(_) => Ok(self.internal_error_response()),
-        }
+        Ok(self.item_response(StatusCode::OK, &item, version))
     }
 
     /// DELETE /api/{model}/{id} - Delete item
+    ///
+    /// With `If-Match`, only the version the client last read is deleted.
     async fn handle_delete(&self, id: &str, req: Req) -> Result<Resp, Infallible> {
-        // Agnostic write/delete enforcement using permission_extractor + can_write()
-        let extracted_perms: Option<Vec<String>> =
-            self.permission_extractor.as_ref().map(|f| f(&req));
-
+        let if_match = Self::if_match_header(&req);
         // First, fetch the item if present to evaluate permissions against it
         let existing_item_opt = {
             let storage = self.storage.read().await;
             storage.get(id).cloned()
         };
         if let Some(ref item) = existing_item_opt {
-            if let Some(ref perms) = extracted_perms {
-                if !item.can_write(perms) {
-                    return Ok(Response::builder()
-                        .status(StatusCode::FORBIDDEN)
-                        .header("content-type", "application/json")
-                        .body(body_from(r#"{"error":"Insufficient permissions"}"#))
-                        .unwrap());
-                }
-            } else if let Some(checker) = &self.permission_checker {
-                // Permission checker configured - authentication REQUIRED
-                let role = match self.extract_role_from_request(&req).await {
-                    Some(r) => r,
-                    None => {
-                        // No token provided - REJECT
-                        return Ok(Response::builder()
-                            .status(StatusCode::UNAUTHORIZED)
-                            .header("content-type", "application/json")
-                            .body(body_from(r#"{"error":"Authentication required"}"#))
-                            .unwrap());
-                    }
-                };
-
-                // Check permissions
-                let model_name = std::any::type_name::<T>().split("::").last().unwrap_or("Item");
-                let specific_perm = format!("{}Delete", model_name);
-                if !checker.has_permission(&role, &specific_perm)
-                    && !checker.has_permission(&role, "Delete")
-                {
-                    return Ok(Response::builder()
-                        .status(StatusCode::FORBIDDEN)
-                        .header("content-type", "application/json")
-                        .body(body_from(r#"{"error":"Insufficient permissions"}"#))
-                        .unwrap());
-                }
+            if let Some(denied) = self.authorize_delete(&req, item).await {
+                return Ok(denied);
             }
         }
 
-        // RAFT INTEGRATION: Check if consensus is required for DELETE
-        if let Some(consensus_arc) = &self.consensus {
+        // RAFT INTEGRATION: Check if consensus is required for DELETE. As for PUT, the
+        // event log stays locked from before the removal until the event is appended
+        let (removed_item, mut store) = if let Some(consensus_arc) = &self.consensus {
             log::debug!("Raft: Proposing DELETE operation for item {}", id);
-            match consensus_arc.read().await.propose_delete(id.to_string()).await {
-                Ok(_) => {
-                    // Apply to local storage after successful consensus
-                    let removed_item = {
-                        let mut storage = self.storage.write().await;
-                        storage.remove(id)
-                    };
-
-                    match removed_item {
-                        Some(item) => {
-                            // Persist deletion to EventStore
-                            if (self.persist_to_event_store("Deleted", &item).await).is_err() {
-                                return Ok(self.internal_error_response());
-                            }
-
-                            self.broadcast_sse("delete", &item).await;
-
-                            Ok(Response::builder()
-                                .status(StatusCode::NO_CONTENT)
-                                .body(body_from(Bytes::new()))
-                                .unwrap())
-                        }
-                        None => Ok(self.not_found_response()),
-                    }
-                }
-                Err(e) => Ok(Response::builder()
+            let expected_version = match self.expected_version(if_match.as_deref(), id) {
+                Ok(version) => version,
+                Err(failed) => return Ok(failed),
+            };
+            if let Err(e) = consensus_arc
+                .read()
+                .await
+                .propose_delete(id.to_string(), expected_version)
+                .await
+            {
+                return Ok(Response::builder()
                     .status(StatusCode::SERVICE_UNAVAILABLE)
                     .header("content-type", "application/json")
                     .body(body_from(format!(r#"{{"error": "Consensus failed: {}"}}"#, e)))
-                    .unwrap()),
+                    .unwrap());
+            }
+            // Apply to local storage after successful consensus
+            let store = self.event_store.write().await;
+            let mut storage = self.storage.write().await;
+            if let Some(failed) = self.stale_write(id, expected_version) {
+                return Ok(failed);
             }
+            (self.tombstone_item(&mut storage, id), store)
         } else {
             // No consensus - delete directly (single-node mode)
-            let removed_item = {
-                let mut storage = self.storage.write().await;
-                storage.remove(id)
-            };
+            let store = self.event_store.write().await;
+            let mut storage = self.storage.write().await;
+            if let Some(failed) = self.check_if_match(if_match.as_deref(), id) {
+                return Ok(failed);
+            }
+            (self.tombstone_item(&mut storage, id), store)
+        };
 
-            match removed_item {
-                Some(item) => {
-                    // Persist deletion to EventStore
-                    if (self.persist_to_event_store("Deleted", &item).await).is_err() {
-                        return Ok(self.internal_error_response());
-                    }
+        let Some(item) = removed_item else {
+            return Ok(self.not_found_response());
+        };
+        // Persist deletion to EventStore
+        let event_count = match self.append_event(&mut store, Self::delete_operation(), &item) {
+            Ok(event_count) => event_count,
+            Err(_) => return Ok(self.internal_error_response()),
+        };
+        drop(store);
+        self.maybe_snapshot(event_count);
 
-                    self.broadcast_sse("delete", &item).await;
+        self.broadcast_sse("delete", &item).await;
 
-                    Ok(Response::builder()
-                        .status(StatusCode::NO_CONTENT)
-                        .body(body_from(Bytes::new()))
-                        .unwrap())
-                }
-                None => Ok(self.not_found_response()),
-            }
-        }
+        Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(body_from(Bytes::new()))
+            .unwrap())
     }
 
-    /// Persist operation to EventStore
-    async fn persist_to_event_store(
+    /// Append the event recording `operation` on `item` to `store`, which the caller
+    /// locked before applying the write, then release the log
+    fn commit_event(
         &self,
+        mut store: tokio::sync::RwLockWriteGuard<'_, EventStore>,
         operation: &str,
         item: &T,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let envelope = EventEnvelope {
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let event_count = self.append_event(&mut store, operation, item)?;
+        // Flush is handled by the background flusher for high throughput
+        drop(store);
+        self.maybe_snapshot(event_count);
+        Ok(())
+    }
+
+    /// Append the event recording `operation` on `item` to `store`, which the caller
+    /// locked before applying the write; returns the number of events logged
+    fn append_event(
+        &self,
+        store: &mut EventStore,
+        operation: &str,
+        item: &T,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let mut envelope = self.envelope(operation, item)?;
+        envelope.aggregate_version = self.item_version(&item.get_primary_key());
+        store.append_envelope(&envelope)?;
+        Ok(store.event_count())
+    }
+
+    /// Event recording `operation` on `item`; the caller sets its `aggregate_version`
+    fn envelope(&self, operation: &str, item: &T) -> serde_json::Result<EventEnvelope> {
+        Ok(EventEnvelope {
             event_type: format!("{}{}", std::any::type_name::<T>(), operation),
             event_id: format!(
                 "{}:{}:{}",
@@ -1234,13 +3722,9 @@ where
             // Hash chain fields - computed automatically by EventStore when enabled
             event_hash: None,
             previous_hash: None,
-        };
-
-        let mut event_store = self.event_store.write().await;
-        event_store.append_envelope(&envelope)?;
-        // Flush is handled by the background flusher for high throughput
-
-        Ok(())
+            schema_version: self.schema_spec.as_ref().map(|s| s.version),
+            aggregate_version: None,
+        })
     }
 
     // Helper methods for responses
@@ -1261,6 +3745,38 @@ where
             .unwrap()
     }
 
+    fn validation_error_response(&self, body: &serde_json::Value) -> Resp {
+        Response::builder()
+            .status(StatusCode::UNPROCESSABLE_ENTITY)
+            .header("content-type", "application/json")
+            .body(body_from(body.to_string()))
+            .unwrap()
+    }
+
+    fn as_of_error_response(&self, error: &AsOfError) -> Resp {
+        let (status, code) = match error {
+            AsOfError::Compacted { .. } => (StatusCode::GONE, "history_compacted"),
+            AsOfError::Storage(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        };
+        let mut body = serde_json::json!({ "error": code, "message": error.to_string() });
+        if let AsOfError::Compacted { earliest_event_index } = error {
+            body["earliest_event_index"] = serde_json::json!(earliest_event_index);
+        }
+        Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(body_from(body.to_string()))
+            .unwrap()
+    }
+
+    fn conflict_response(&self, violation: &UniqueViolation) -> Resp {
+        Response::builder()
+            .status(StatusCode::CONFLICT)
+            .header("content-type", "application/json")
+            .body(body_from(violation.to_json().to_string()))
+            .unwrap()
+    }
+
     fn internal_error_response(&self) -> Resp {
         Response::builder()
             .status(StatusCode::INTERNAL_SERVER_ERROR)
@@ -1277,6 +3793,127 @@ where
             .unwrap()
     }
 
+    fn unsupported_patch_type_response(&self) -> Resp {
+        let message = format!("unsupported media type, expected {}", patch::ACCEPT_PATCH);
+        Response::builder()
+            .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            .header("content-type", "application/json")
+            .header("accept-patch", patch::ACCEPT_PATCH)
+            .body(body_from(serde_json::json!({ "error": message }).to_string()))
+            .unwrap()
+    }
+
+    fn patch_error_response(&self, error: &PatchError) -> Resp {
+        let status = match error {
+            PatchError::Malformed(_) => StatusCode::BAD_REQUEST,
+            // The patch does not apply to the item as it is now
+            PatchError::Failed { .. } => StatusCode::CONFLICT,
+        };
+        Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(body_from(serde_json::json!({ "error": error.to_string() }).to_string()))
+            .unwrap()
+    }
+
+    fn item_response(&self, status: StatusCode, item: &T, version: Option<u64>) -> Resp {
+        match serde_json::to_string(item) {
+            Ok(json) => {
+                let mut response =
+                    Response::builder().status(status).header("content-type", "application/json");
+                if let Some(version) = version {
+                    response = response.header("etag", etag::etag(version));
+                }
+                response.body(body_from(json)).unwrap()
+            }
+            Err(_) => self.internal_error_response(),
+        }
+    }
+
+    fn precondition_failed_response(&self, version: Option<u64>) -> Resp {
+        let current = version.map(etag::etag);
+        let body = serde_json::json!({
+            "error": "precondition_failed",
+            "message": "If-Match does not name the current version of the item",
+            "etag": current
+        });
+        let mut response = Response::builder()
+            .status(StatusCode::PRECONDITION_FAILED)
+            .header("content-type", "application/json");
+        if let Some(current) = &current {
+            response = response.header("etag", current);
+        }
+        response.body(body_from(body.to_string())).unwrap()
+    }
+
+    /// The 409 for a replicated PATCH without `If-Match` whose item kept changing while
+    /// it was proposed
+    fn patch_conflict_response(&self, version: Option<u64>) -> Resp {
+        let body = serde_json::json!({
+            "error": "conflict",
+            "message": "The item changed while the patch was replicated, retry it",
+            "etag": version.map(etag::etag)
+        });
+        Response::builder()
+            .status(StatusCode::CONFLICT)
+            .header("content-type", "application/json")
+            .body(body_from(body.to_string()))
+            .unwrap()
+    }
+
+    /// The 412 to answer when the `If-Match` of a write does not name the current
+    /// version of `id`
+    ///
+    /// Callers hold the storage lock, so the check and their write see the same version.
+    fn check_if_match(&self, if_match: Option<&str>, id: &str) -> Option<Resp> {
+        let header = if_match?;
+        let version = self.item_version(id);
+        (!etag::if_match(header, version)).then(|| self.precondition_failed_response(version))
+    }
+
+    /// Version a replicated write of `id` must still find when applied, from its
+    /// `If-Match` header (None without one, or for `*`), or the 412 to answer now
+    fn expected_version(&self, if_match: Option<&str>, id: &str) -> Result<Option<u64>, Resp> {
+        let Some(header) = if_match else {
+            return Ok(None);
+        };
+        let version = self.item_version(id);
+        if !etag::if_match(header, version) {
+            return Err(self.precondition_failed_response(version));
+        }
+        // `*` only asks for the item to exist
+        Ok(version.filter(|_| header.trim() != "*"))
+    }
+
+    /// Version a replicated PATCH of `id` must still find when applied: the one its
+    /// `If-Match` names, else `read`, the version of the item the patch applied to
+    fn pinned_version(
+        &self,
+        if_match: Option<&str>,
+        id: &str,
+        read: Option<u64>,
+    ) -> Result<Option<u64>, Resp> {
+        match if_match {
+            Some(_) => self.expected_version(if_match, id),
+            None => Ok(read),
+        }
+    }
+
+    /// The 412 for a replicated write of `id` that is no longer at `expected_version`
+    ///
+    /// Every node runs this check when applying the write, so they all skip it alike.
+    /// Callers hold the storage lock.
+    fn stale_write(&self, id: &str, expected_version: Option<u64>) -> Option<Resp> {
+        let expected = expected_version?;
+        let version = self.item_version(id);
+        (version != Some(expected)).then(|| self.precondition_failed_response(version))
+    }
+
+    /// `If-Match` of `req`, read before the body is consumed
+    fn if_match_header(req: &Req) -> Option<String> {
+        req.headers().get("if-match").and_then(|v| v.to_str().ok()).map(str::to_string)
+    }
+
     fn entity_too_large_response(&self, max: usize) -> Resp {
         let msg = format!("request body too large (max {} bytes)", max);
         Response::builder()
@@ -1386,8 +4023,8 @@ where
             .map_err(|e| format!("Failed to apply changes: {}", e))?;
 
         // Validate the updated item
-        if let Err(validation_error) = item.validate() {
-            return Err(format!("Validation failed: {}", validation_error));
+        if let Err(errors) = item.validate_fields() {
+            return Err(format!("Validation failed: {}", errors));
         }
 
         // Apply lifecycle rules
@@ -1395,14 +4032,16 @@ where
             return Err(format!("Lifecycle error: {}", lifecycle_error));
         }
 
-        // Update in-memory storage
+        // Update in-memory storage, with the event log locked first as for PUT
+        let mut event_store = self.event_store.write().await;
         {
             let mut storage = self.storage.write().await;
-            storage.insert(id.to_string(), item.clone());
+            self.claim_unique(id, storage.get(id), &item).map_err(|v| v.to_string())?;
+            self.put_item(&mut storage, id.to_string(), item.clone());
         }
 
         // Persist as AdminEdit event (different from regular Updated)
-        let envelope = EventEnvelope {
+        let mut envelope = EventEnvelope {
             event_type: format!("{}AdminEdit", std::any::type_name::<T>()),
             event_id: format!(
                 "{}:AdminEdit:{}:{}",
@@ -1417,15 +4056,526 @@ where
             // Hash chain fields - computed automatically by EventStore when enabled
             event_hash: None,
             previous_hash: None,
+            schema_version: self.schema_spec.as_ref().map(|s| s.version),
+            aggregate_version: None,
         };
 
-        {
-            let mut event_store = self.event_store.write().await;
-            event_store
-                .append_envelope(&envelope)
-                .map_err(|e| format!("Failed to persist event: {}", e))?;
-        }
+        envelope.aggregate_version = self.item_version(id);
+        event_store
+            .append_envelope(&envelope)
+            .map_err(|e| format!("Failed to persist event: {}", e))?;
+        let event_count = event_store.event_count();
+        drop(event_store);
+        self.maybe_snapshot(event_count);
 
         Ok(item)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+    struct Note {
+        id: String,
+        title: String,
+    }
+
+    impl HttpExposable for Note {
+        fn http_base_path() -> &'static str {
+            "notes"
+        }
+        fn primary_key_field() -> &'static str {
+            "id"
+        }
+        fn get_primary_key(&self) -> String {
+            self.id.clone()
+        }
+        fn validate(&self) -> Result<(), String> {
+            Ok(())
+        }
+        fn soft_delete() -> bool {
+            true
+        }
+    }
+
+    impl ReplicatedModel for Note {
+        fn needs_replication() -> bool {
+            true
+        }
+        fn replicated_fields() -> Vec<&'static str> {
+            vec!["id", "title"]
+        }
+    }
+
+    impl crate::lifecycle::LifecycleAware for Note {
+        fn lifecycle_policy_for_field(
+            &self,
+            _field_name: &str,
+        ) -> Option<crate::lifecycle::FieldPolicy> {
+            None
+        }
+        fn all_field_names(&self) -> Vec<&'static str> {
+            vec!["id", "title"]
+        }
+        fn model_name(&self) -> &'static str {
+            "Note"
+        }
+    }
+
+    fn note(id: &str) -> Note {
+        Note { id: id.to_string(), title: format!("note {}", id) }
+    }
+
+    /// A handler rebuilt from what `handler` wrote to `path`, as after a restart
+    async fn restart(
+        handler: &DeclarativeHttpHandler<Note>,
+        path: &str,
+    ) -> DeclarativeHttpHandler<Note> {
+        handler.get_event_store().write().await.flush_events().unwrap();
+        DeclarativeHttpHandler::new_with_replay(path).await.unwrap()
+    }
+
+    async fn ids(handler: &DeclarativeHttpHandler<Note>) -> Vec<String> {
+        let mut ids: Vec<String> =
+            handler.get_all_items().await.into_iter().map(|n| n.id).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Log `operation` on `item` as written at `timestamp`, leaving the live state alone
+    async fn log_event(
+        handler: &DeclarativeHttpHandler<Note>,
+        operation: &str,
+        item: &Note,
+        timestamp: u64,
+    ) {
+        let mut envelope = handler.envelope(operation, item).unwrap();
+        envelope.timestamp = timestamp;
+        handler.get_event_store().write().await.append_envelope(&envelope).unwrap();
+    }
+
+    fn sorted(items: &HashMap<String, Note>) -> Vec<String> {
+        let mut ids: Vec<String> = items.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_state_as_of_event_index_and_timestamp_cut_offs() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(dir.path().to_str().unwrap()).unwrap();
+        log_event(&handler, "Created", &note("a"), 100).await;
+        log_event(&handler, "Created", &note("b"), 200).await;
+        log_event(&handler, "Tombstoned", &note("a"), 300).await;
+
+        let at = |as_of| handler.state_as_of(as_of);
+        assert!(at(AsOf::EventIndex(0)).await.unwrap().items.is_empty());
+        assert_eq!(sorted(&at(AsOf::EventIndex(2)).await.unwrap().items), vec!["a", "b"]);
+        assert_eq!(sorted(&at(AsOf::Timestamp(150)).await.unwrap().items), vec!["a"]);
+        // A timestamp includes every event stamped at that second
+        let state = at(AsOf::Timestamp(300)).await.unwrap();
+        assert_eq!(sorted(&state.items), vec!["b"]);
+        assert_eq!(sorted(&state.tombstones), vec!["a"]);
+        assert_eq!(sorted(&at(AsOf::Timestamp(99)).await.unwrap().items), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn test_state_as_of_before_first_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(dir.path().to_str().unwrap()).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_item(note("b")).await.unwrap();
+        handler.snapshot().await.unwrap();
+        handler.apply_replicated_item(note("c")).await.unwrap();
+
+        // Before the snapshot: replayed from the first event
+        let at = |as_of| handler.state_as_of(as_of);
+        assert_eq!(sorted(&at(AsOf::EventIndex(1)).await.unwrap().items), vec!["a"]);
+        assert!(at(AsOf::Timestamp(0)).await.unwrap().items.is_empty());
+        assert_eq!(sorted(&at(AsOf::EventIndex(3)).await.unwrap().items), vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_state_as_of_reads_older_compacting_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(dir.path().to_str().unwrap()).unwrap();
+        handler.set_snapshot_policy(0, true);
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_item(note("b")).await.unwrap();
+        handler.snapshot().await.unwrap();
+
+        let at = |as_of| handler.state_as_of(as_of);
+        assert_eq!(
+            at(AsOf::EventIndex(1)).await.unwrap_err(),
+            AsOfError::Compacted { earliest_event_index: 2 }
+        );
+        assert!(at(AsOf::Timestamp(0)).await.is_err());
+
+        handler.apply_replicated_item(note("c")).await.unwrap();
+        handler.apply_replicated_delete("a").await.unwrap();
+        handler.snapshot().await.unwrap();
+        handler.apply_replicated_item(note("d")).await.unwrap();
+
+        // The first snapshot is kept: its point in history can still be read
+        assert_eq!(sorted(&at(AsOf::EventIndex(2)).await.unwrap().items), vec!["a", "b"]);
+        assert!(at(AsOf::EventIndex(3)).await.is_err());
+        let state = at(AsOf::EventIndex(4)).await.unwrap();
+        assert_eq!(sorted(&state.items), vec!["b", "c"]);
+        assert_eq!(sorted(&state.tombstones), vec!["a"]);
+        assert_eq!(sorted(&at(AsOf::EventIndex(5)).await.unwrap().items), vec!["b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_replicated_patch_persists_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+
+        let diff = serde_json::json!({ "title": "patched" });
+        let patched = handler.apply_replicated_patch("a", &diff).await.unwrap().unwrap();
+        assert_eq!(patched.title, "patched");
+        assert_eq!(handler.item_version("a"), Some(2));
+        assert_eq!(handler.apply_replicated_patch("missing", &diff).await.unwrap(), None);
+
+        let restarted = restart(&handler, path).await;
+        assert_eq!(restarted.get_all_items().await, vec![patched]);
+        assert_eq!(restarted.item_version("a"), Some(2));
+        let history = restarted.get_entity_history("a").await;
+        assert!(history.last().unwrap().event_type.ends_with("Patched"));
+        assert_eq!(history.last().unwrap().payload, diff.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_item_stays_deleted_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_item(note("b")).await.unwrap();
+        assert!(handler.apply_replicated_delete("a").await.unwrap());
+
+        let restarted = restart(&handler, path).await;
+        assert_eq!(ids(&restarted).await, vec!["b"]);
+        assert_eq!(restarted.deleted_item("a"), Some(note("a")));
+        assert_eq!(restarted.item_version("a"), handler.item_version("a"));
+    }
+
+    #[tokio::test]
+    async fn test_restore_brings_back_soft_deleted_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_delete("a").await.unwrap();
+        let deleted_version = handler.item_version("a").unwrap();
+
+        assert_eq!(handler.apply_replicated_restore("a").await.unwrap(), Some(note("a")));
+        assert_eq!(handler.deleted_item("a"), None);
+        assert!(handler.item_version("a").unwrap() > deleted_version);
+        // A restore applied twice finds nothing left to restore
+        assert_eq!(handler.apply_replicated_restore("a").await.unwrap(), None);
+
+        let restarted = restart(&handler, path).await;
+        assert_eq!(ids(&restarted).await, vec!["a"]);
+        assert_eq!(restarted.deleted_item("a"), None);
+        assert_eq!(restarted.item_version("a"), handler.item_version("a"));
+    }
+
+    #[tokio::test]
+    async fn test_tombstones_replay_after_compacting_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.set_snapshot_policy(0, true);
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_item(note("b")).await.unwrap();
+        handler.apply_replicated_delete("a").await.unwrap();
+        handler.snapshot().await.unwrap();
+        // One tombstone in the snapshot, one in the events written after it
+        handler.apply_replicated_item(note("c")).await.unwrap();
+        handler.apply_replicated_delete("b").await.unwrap();
+
+        let restarted = restart(&handler, path).await;
+        assert_eq!(ids(&restarted).await, vec!["c"]);
+        assert_eq!(restarted.deleted_item("a"), Some(note("a")));
+        assert_eq!(restarted.deleted_item("b"), Some(note("b")));
+        assert_eq!(restarted.item_version("a"), handler.item_version("a"));
+        assert_eq!(restarted.apply_replicated_restore("a").await.unwrap(), Some(note("a")));
+        assert_eq!(ids(&restarted).await, vec!["a", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_truncation_keeps_compaction_consistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let staged = format!("{}/snapshots.compacting", path);
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.set_snapshot_policy(0, true);
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_item(note("b")).await.unwrap();
+
+        // Staged, then a crash before truncating: the log still holds what it compacts
+        let snapshot = Snapshot::new(None, 2, None, r#"{"items": {}}"#.to_string());
+        SnapshotStore::new(&staged).unwrap().save_snapshot(&snapshot).unwrap();
+        let restarted = restart(&handler, path).await;
+        assert_eq!(ids(&restarted).await, vec!["a", "b"]);
+        assert!(!std::path::Path::new(&staged).exists());
+
+        // The log is emptied but its offset index cannot be
+        restarted.set_snapshot_policy(0, true);
+        let index = dir.path().join("events.raftidx");
+        let _ = std::fs::remove_file(&index);
+        std::fs::create_dir(&index).unwrap();
+        assert!(restarted.snapshot().await.is_err());
+
+        std::fs::remove_dir(&index).unwrap();
+        let recovered = restart(&restarted, path).await;
+        assert_eq!(ids(&recovered).await, vec!["a", "b"]);
+        assert!(!std::path::Path::new(&staged).exists());
+        // Restored from the compacting snapshot, not by replaying half a log
+        assert_eq!(
+            recovered.state_as_of(AsOf::EventIndex(1)).await.unwrap_err(),
+            AsOfError::Compacted { earliest_event_index: 2 }
+        );
+        recovered.apply_replicated_item(note("c")).await.unwrap();
+        let state = recovered.state_as_of(AsOf::EventIndex(3)).await.unwrap();
+        assert_eq!(sorted(&state.items), vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_replay_keeps_item_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_update("a", note("a")).await.unwrap();
+        handler.snapshot().await.unwrap();
+        handler.apply_replicated_update("a", note("a")).await.unwrap();
+        handler.apply_replicated_item(note("b")).await.unwrap();
+
+        let restarted = restart(&handler, path).await;
+        assert_eq!(restarted.item_version("a"), Some(3));
+        assert_eq!(restarted.item_version("b"), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_recreated_item_counts_on_from_deleted_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_update("a", note("a")).await.unwrap();
+        // A hard delete replays from the log
+        log_event(&handler, "Deleted", &note("a"), 0).await;
+        let deleted = restart(&handler, path).await;
+        assert_eq!(deleted.item_version("a"), None);
+
+        // The deleted version survives a compacting snapshot
+        deleted.set_snapshot_policy(0, true);
+        deleted.snapshot().await.unwrap();
+        let restarted = restart(&deleted, path).await;
+        restarted.apply_replicated_item(note("a")).await.unwrap();
+        assert_eq!(restarted.item_version("a"), Some(3));
+        assert_eq!(restart(&restarted, path).await.item_version("a"), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_install_snapshot_restores_versions() {
+        let leader_dir = tempfile::tempdir().unwrap();
+        let leader =
+            DeclarativeHttpHandler::<Note>::new(leader_dir.path().to_str().unwrap()).unwrap();
+        leader.apply_replicated_item(note("a")).await.unwrap();
+        leader.apply_replicated_update("a", note("a")).await.unwrap();
+        leader.apply_replicated_update("a", note("a")).await.unwrap();
+        leader.apply_replicated_item(note("b")).await.unwrap();
+
+        // A follower that missed those writes and holds an item the leader no longer has
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let follower = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        follower.apply_replicated_item(note("a")).await.unwrap();
+        follower.apply_replicated_item(note("c")).await.unwrap();
+
+        let (items, versions) = leader.items_with_versions().await;
+        assert_eq!(follower.install_snapshot(items, &versions).await.unwrap(), 2);
+        assert_eq!(ids(&follower).await, vec!["a", "b"]);
+        assert_eq!(follower.item_version("a"), Some(3));
+        assert_eq!(follower.item_version("b"), Some(1));
+        assert_eq!(follower.item_version("c"), None);
+
+        let restarted = restart(&follower, path).await;
+        assert_eq!(ids(&restarted).await, vec!["a", "b"]);
+        assert_eq!(restarted.item_version("a"), Some(3));
+        assert_eq!(restarted.item_version("b"), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_stale_write_is_skipped_on_every_node() {
+        let mut nodes = Vec::new();
+        let mut dirs = Vec::new();
+        for _ in 0..2 {
+            let dir = tempfile::tempdir().unwrap();
+            let node = DeclarativeHttpHandler::<Note>::new(dir.path().to_str().unwrap()).unwrap();
+            node.apply_replicated_item(note("a")).await.unwrap();
+            nodes.push(node);
+            dirs.push(dir);
+        }
+
+        // Two writes both read version 1 and were proposed with `If-Match: "1"`
+        let expected = nodes[0].expected_version(Some("\"1\""), "a").unwrap();
+        assert_eq!(expected, Some(1));
+        assert_eq!(nodes[0].expected_version(Some("*"), "a").unwrap(), None);
+        assert_eq!(
+            nodes[0].expected_version(Some("\"2\""), "a").unwrap_err().status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+
+        for node in &nodes {
+            let first = Note { id: "a".to_string(), title: "first".to_string() };
+            assert!(node.stale_write("a", expected).is_none());
+            node.apply_replicated_update("a", first).await.unwrap();
+            // The second finds the item moved on, wherever it is applied
+            let failed = node.stale_write("a", expected).unwrap();
+            assert_eq!(failed.status(), StatusCode::PRECONDITION_FAILED);
+            assert_eq!(node.item_version("a"), Some(2));
+            assert_eq!(node.get_all_items().await[0].title, "first");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replicated_patch_is_pinned_to_the_version_it_applied_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(dir.path().to_str().unwrap()).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        let access = WriteAccess::Open;
+
+        // Two patches without If-Match both apply to version 1
+        let read = handler.item_version("a");
+        let first = handler.pinned_version(None, "a", read).unwrap();
+        let second = handler.pinned_version(None, "a", read).unwrap();
+        assert_eq!((first, second), (Some(1), Some(1)));
+        let stale = handler.storage.read().await["a"].clone();
+
+        let patch = Patch::Merge(serde_json::json!({ "title": "first" }));
+        let (item, _) = handler.patch_item(&stale, &patch, &access).unwrap();
+        assert!(handler.stale_write("a", first).is_none());
+        handler.apply_replicated_update("a", item).await.unwrap();
+
+        // The second finds the item moved on and applies again to the new one
+        assert!(handler.stale_write("a", second).is_some());
+        let current = handler.storage.read().await["a"].clone();
+        let retry = handler.pinned_version(None, "a", handler.item_version("a")).unwrap();
+        assert_eq!(retry, Some(2));
+        assert_eq!(current.title, "first");
+        let patch = Patch::Merge(serde_json::json!({ "title": "second" }));
+        let (item, diff) = handler.patch_item(&current, &patch, &access).unwrap();
+        assert_eq!(diff, serde_json::json!({ "title": "second" }));
+        assert!(handler.stale_write("a", retry).is_none());
+        handler.apply_replicated_patch("a", &diff).await.unwrap();
+        assert_eq!(handler.get_all_items().await, vec![item]);
+
+        // If-Match still decides on its own
+        let failed = handler.pinned_version(Some("\"1\""), "a", Some(2)).unwrap_err();
+        assert_eq!(failed.status(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[tokio::test]
+    async fn test_batch_rollback_restores_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(dir.path().to_str().unwrap()).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+        handler.apply_replicated_item(note("b")).await.unwrap();
+
+        let mut batch = handler.lock_batch("batch-1").await;
+        batch.put("c", note("c"), "Created").unwrap();
+        let renamed = Note { id: "a".to_string(), title: "renamed".to_string() };
+        batch.put("a", renamed.clone(), "Updated").unwrap();
+        batch.put("a", renamed, "Updated").unwrap();
+        assert!(batch.delete("b").unwrap());
+        batch.rollback();
+
+        assert_eq!(ids(&handler).await, vec!["a", "b"]);
+        assert_eq!(handler.get_all_items().await.iter().find(|n| n.id == "a"), Some(&note("a")));
+        assert_eq!(handler.item_version("a"), Some(1));
+        assert_eq!(handler.item_version("b"), Some(1));
+        assert_eq!(handler.item_version("c"), None);
+        assert!(handler.tombstones().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replay_skips_uncommitted_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(path).unwrap();
+        handler.apply_replicated_item(note("a")).await.unwrap();
+
+        // Logged, but the crash came before the commit marker
+        let mut batch = handler.lock_batch("batch-1").await;
+        batch.put("b", note("b"), "Created").unwrap();
+        batch.prepare().unwrap();
+        drop(batch);
+
+        let mut batch = handler.lock_batch("batch-2").await;
+        batch.put("c", note("c"), "Created").unwrap();
+        assert!(batch.delete("a").unwrap());
+        batch.prepare().unwrap();
+        batch.finish().unwrap();
+        handler.apply_replicated_item(note("d")).await.unwrap();
+
+        let restarted = restart(&handler, path).await;
+        assert_eq!(ids(&restarted).await, vec!["c", "d"]);
+        assert_eq!(restarted.item_version("a"), Some(2));
+        assert_eq!(restarted.item_version("b"), None);
+    }
+
+    #[test]
+    fn test_batch_markers_drop_unfinished_batches() {
+        let event = |event_type: &str, event_id: &str| EventEnvelope {
+            event_type: event_type.to_string(),
+            event_id: event_id.to_string(),
+            timestamp: 0,
+            payload: "{}".to_string(),
+            aggregate_id: None,
+            event_hash: None,
+            previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
+        };
+        let log = vec![
+            event("NoteCreated", "Note:Created:a"),
+            event("NoteBatchBegun", "Note:BatchBegun:b1"),
+            event("NoteCreated", "Note:Created:b:b1"),
+            // Written after a crash cut batch b1 short
+            event("NoteCreated", "Note:Created:c"),
+            event("NoteBatchBegun", "Note:BatchBegun:b2"),
+            event("NoteCreated", "Note:Created:d:b2"),
+            event("NoteBatchCommitted", "Note:BatchCommitted:b2"),
+            event("NoteBatchBegun", "Note:BatchBegun:b3"),
+            event("NoteCreated", "Note:Created:e:b3"),
+        ];
+        let kept: Vec<String> =
+            BatchMarker::committed(log).into_iter().map(|e| e.event_id).collect();
+        assert_eq!(kept, vec!["Note:Created:a", "Note:Created:c", "Note:Created:d:b2"]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_update_is_checked_like_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = DeclarativeHttpHandler::<Note>::new(dir.path().to_str().unwrap()).unwrap();
+        let current = note("a");
+
+        let updated = handler
+            .batch_update(&current, &serde_json::json!({"title": "new"}), &WriteAccess::Open)
+            .unwrap();
+        assert_eq!(updated, Note { id: "a".to_string(), title: "new".to_string() });
+
+        let denied = handler
+            .batch_update(&current, &serde_json::json!({"id": "b"}), &WriteAccess::Open)
+            .unwrap_err();
+        assert_eq!(denied.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -668,6 +668,11 @@ where
             T::http_base_path(),
             ""
         );
+        log::info!(
+            "   GET    /api/{}/_aggregate{:<5} - Grouped count/sum/avg/min/max",
+            T::http_base_path(),
+            ""
+        );
         log::info!(
             "   GET    /api/{}/random-id{:<6} - Lightweight random existing id",
             T::http_base_path(),
@@ -0,0 +1,60 @@
+//! Item versions as HTTP entity tags
+//!
+//! Every declarative model item carries a version, bumped by each write and recorded
+//! in its events (`EventEnvelope::aggregate_version`). Item responses expose it as a
+//! strong `ETag` (`"3"`):
+//!
+//! - `If-Match` on PUT, PATCH and DELETE makes the write conditional; a tag that does
+//!   not name the current version answers `412 Precondition Failed`. `*` matches any
+//!   existing item.
+//! - `If-None-Match` on GET answers `304 Not Modified` while the tag still names the
+//!   current version.
+
+/// Header value naming `version`
+pub fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Whether an `If-Match` header accepts an item at `version` (None = no such item)
+///
+/// Uses the strong comparison of RFC 9110: weak tags never match.
+pub fn if_match(header: &str, version: Option<u64>) -> bool {
+    let Some(version) = version else { return false };
+    let current = etag(version);
+    tags(header).any(|tag| tag == "*" || tag == current)
+}
+
+/// Whether an `If-None-Match` header already names the item at `version`
+///
+/// Uses the weak comparison of RFC 9110: `W/"3"` matches version 3.
+pub fn if_none_match(header: &str, version: Option<u64>) -> bool {
+    let Some(version) = version else { return false };
+    let current = etag(version);
+    tags(header).any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == current)
+}
+
+fn tags(header: &str) -> impl Iterator<Item = &str> {
+    header.split(',').map(str::trim).filter(|tag| !tag.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preconditions() {
+        assert_eq!(etag(3), "\"3\"");
+
+        assert!(if_match("\"3\"", Some(3)));
+        assert!(if_match("\"1\", \"3\"", Some(3)));
+        assert!(if_match("*", Some(1)));
+        assert!(!if_match("\"2\"", Some(3)));
+        assert!(!if_match("W/\"3\"", Some(3)));
+        assert!(!if_match("*", None));
+
+        assert!(if_none_match("W/\"3\"", Some(3)));
+        assert!(if_none_match("*", Some(3)));
+        assert!(!if_none_match("\"2\"", Some(3)));
+        assert!(!if_none_match("\"3\"", None));
+    }
+}
@@ -0,0 +1,178 @@
+//! Relation expansion on model reads
+//!
+//! `GET /api/articles?expand=author,comments.author` adds the related items to each item
+//! returned, so a client gets a page and what it references in one call:
+//!
+//! - A foreign key (`#[db(fk = "Author")] author_id`) expands under its relation name
+//!   (`author`, see `ForeignKeySpec::relation_name`) into the referenced item, or `null`.
+//! - A `#[relation(has_many = "Comment")] comments` field expands into the `Comment`
+//!   items whose foreign key points back at the item; `has_one` into the first of them.
+//!
+//! Dotted paths expand the related items in turn, up to `MAX_EXPAND_DEPTH` levels. A
+//! related item only appears when the caller can read it under its own model's
+//! `can_read`. The server resolves relations against its registered models; this module
+//! parses the parameter and attaches what was fetched. With `fields=`, list items are
+//! projected once expanded and keep the relations they embed.
+
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// Deepest relation path `expand` accepts (`a.b.c`)
+pub const MAX_EXPAND_DEPTH: usize = 3;
+
+/// Relations to expand, each with the relations to expand on its own items
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expand {
+    pub relations: BTreeMap<String, Expand>,
+}
+
+impl Expand {
+    /// Parse a comma-separated list of relation paths (`author,comments.author`)
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut expand = Self::default();
+        for path in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let names: Vec<&str> = path.split('.').map(str::trim).collect();
+            if names.iter().any(|name| name.is_empty()) {
+                return Err(format!("`{}` is not a relation path", path));
+            }
+            if names.len() > MAX_EXPAND_DEPTH {
+                return Err(format!(
+                    "`{}` is nested deeper than {} relations",
+                    path, MAX_EXPAND_DEPTH
+                ));
+            }
+            let mut node = &mut expand;
+            for name in names {
+                node = node.relations.entry(name.to_string()).or_default();
+            }
+        }
+        if expand.is_empty() {
+            return Err("expected a comma-separated list of relations".to_string());
+        }
+        Ok(expand)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.relations.is_empty()
+    }
+}
+
+/// Request extension asking the model handler for whole list items: the server applies
+/// `fields=` itself once relations are expanded, as the projection could drop the keys
+/// they are resolved by
+#[derive(Debug, Clone, Copy)]
+pub struct Unprojected;
+
+/// Fields to project expanded items on: `fields`, plus every expanded relation that no
+/// field selects a part of (`author.name`)
+pub fn projected_fields(fields: &[String], expand: &Expand) -> Vec<String> {
+    let mut projected = fields.to_vec();
+    for relation in expand.relations.keys() {
+        let prefix = format!("{}.", relation);
+        if !fields.iter().any(|f| f == relation || f.starts_with(&prefix)) {
+            projected.push(relation.clone());
+        }
+    }
+    projected
+}
+
+/// Text a key is matched by: strings as is, numbers and booleans printed; None otherwise
+pub fn key_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Distinct keys held by `field` across `items`, in first-seen order
+pub fn keys_of(items: &[Value], field: &str) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for key in items.iter().filter_map(|item| item.get(field).and_then(key_text)) {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// Set `relation` on every item to the `related` items whose `remote` field equals the
+/// item's `local` field: all of them when `many`, else the first one or `null`
+pub fn attach(
+    items: &mut [Value],
+    relation: &str,
+    local: &str,
+    related: Vec<Value>,
+    remote: &str,
+    many: bool,
+) {
+    let mut by_key: HashMap<String, Vec<Value>> = HashMap::new();
+    for item in related {
+        if let Some(key) = item.get(remote).and_then(key_text) {
+            by_key.entry(key).or_default().push(item);
+        }
+    }
+    for item in items.iter_mut().filter(|item| item.is_object()) {
+        let matches = item.get(local).and_then(key_text).and_then(|key| by_key.get(&key));
+        item[relation] = match (matches, many) {
+            (Some(matches), true) => Value::Array(matches.clone()),
+            (None, true) => Value::Array(Vec::new()),
+            (matches, false) => matches.and_then(|m| m.first()).cloned().unwrap_or(Value::Null),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_expand() {
+        let expand = Expand::parse("author, comments.author,comments").unwrap();
+        assert_eq!(expand.relations.keys().collect::<Vec<_>>(), ["author", "comments"]);
+        assert!(expand.relations["author"].is_empty());
+        assert!(expand.relations["comments"].relations.contains_key("author"));
+
+        assert!(Expand::parse("a.b.c").is_ok());
+        assert!(Expand::parse("a.b.c.d").is_err());
+        assert!(Expand::parse("author..name").is_err());
+        assert!(Expand::parse(",").is_err());
+    }
+
+    #[test]
+    fn test_attach() {
+        let mut articles = vec![
+            json!({"id": "a1", "author_id": "u1"}),
+            json!({"id": "a2", "author_id": "u9"}),
+            json!({"id": "a3", "author_id": "u1"}),
+        ];
+        assert_eq!(keys_of(&articles, "author_id"), ["u1", "u9"]);
+
+        let authors = vec![json!({"id": "u1", "name": "Ada"})];
+        attach(&mut articles, "author", "author_id", authors, "id", false);
+        assert_eq!(articles[0]["author"]["name"], "Ada");
+        assert_eq!(articles[1]["author"], Value::Null);
+
+        let comments = vec![
+            json!({"id": 1, "article_id": "a1"}),
+            json!({"id": 2, "article_id": "a3"}),
+            json!({"id": 3, "article_id": "a1"}),
+        ];
+        attach(&mut articles, "comments", "id", comments, "article_id", true);
+        assert_eq!(
+            articles[0]["comments"],
+            json!([{"id": 1, "article_id": "a1"}, {"id": 3, "article_id": "a1"}])
+        );
+        assert_eq!(articles[1]["comments"], json!([]));
+        assert_eq!(key_text(&json!(7)).as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn test_projected_fields() {
+        let expand = Expand::parse("author,comments.author").unwrap();
+        let fields = ["title".to_string(), "comments.id".to_string()];
+        assert_eq!(projected_fields(&fields, &expand), ["title", "comments.id", "author"]);
+    }
+}
@@ -0,0 +1,475 @@
+//! Ordered secondary indexes and query planning for collection endpoints
+//!
+//! Every `#[db(indexed)]` field keeps a `BTreeMap` from its value to the ids holding
+//! it, maintained by `DeclarativeHttpHandler` on each write. `GET /api/{model}` asks
+//! the planner for the cheapest way to answer a query:
+//!
+//! - `index_eq`: equality, `in(...)` or `null` filter on an indexed field
+//! - `index_range`: `>`, `<`, `>=`, `<=` filter on an indexed field
+//! - `index_sort`: no usable filter, but a single-field `sort=` is on an indexed
+//!   field, so items are walked in index order and only the requested page is
+//!   serialized
+//! - `full_scan`: everything else
+//!
+//! Index lookups only narrow the candidate set; every filter is still evaluated on
+//! the candidates, so results are identical to a full scan. The plan is reported in
+//! the response with `?explain=true`.
+
+use crate::http::query::{FilterOp, FilterSpec, QueryParams};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::RwLock;
+
+/// Index key: JSON values ordered by type (null < bool < number < string < other),
+/// then by value, matching `compare_json_values` within a type
+#[derive(Debug, Clone)]
+pub enum IndexKey {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    /// Arrays and objects: never matched by lookups, kept so index walks see every id
+    Other(String),
+}
+
+impl IndexKey {
+    /// Key for a field value; a missing field indexes as `Null` (it sorts like one)
+    pub fn from_value(value: Option<&Value>) -> Self {
+        match value {
+            None | Some(Value::Null) => IndexKey::Null,
+            Some(Value::Bool(b)) => IndexKey::Bool(*b),
+            Some(Value::Number(n)) => match n.as_f64() {
+                // Normalize -0.0 so it shares a key with 0.0
+                Some(f) => IndexKey::Number(if f == 0.0 { 0.0 } else { f }),
+                None => IndexKey::Other(n.to_string()),
+            },
+            Some(Value::String(s)) => IndexKey::String(s.clone()),
+            Some(other) => IndexKey::Other(other.to_string()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            IndexKey::Null => 0,
+            IndexKey::Bool(_) => 1,
+            IndexKey::Number(_) => 2,
+            IndexKey::String(_) => 3,
+            IndexKey::Other(_) => 4,
+        }
+    }
+
+    /// Keys a query-string value can be compared with (see `query::value_equals`)
+    fn targets(raw: &str) -> Vec<IndexKey> {
+        let mut keys = Vec::new();
+        match raw {
+            "" | "null" => keys.push(IndexKey::Null),
+            "true" => keys.push(IndexKey::Bool(true)),
+            "false" => keys.push(IndexKey::Bool(false)),
+            _ => {}
+        }
+        if let Ok(n) = raw.parse::<f64>() {
+            if n.is_finite() {
+                keys.push(IndexKey::Number(if n == 0.0 { 0.0 } else { n }));
+            }
+        }
+        keys.push(IndexKey::String(raw.to_string()));
+        keys
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (IndexKey::Bool(a), IndexKey::Bool(b)) => a.cmp(b),
+            (IndexKey::Number(a), IndexKey::Number(b)) => a.total_cmp(b),
+            (IndexKey::String(a), IndexKey::String(b)) => a.cmp(b),
+            (IndexKey::Other(a), IndexKey::Other(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+type FieldIndex = BTreeMap<IndexKey, BTreeSet<String>>;
+
+/// How a list query is answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanStrategy {
+    IndexEq,
+    IndexRange,
+    IndexSort,
+    FullScan,
+}
+
+impl PlanStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlanStrategy::IndexEq => "index_eq",
+            PlanStrategy::IndexRange => "index_range",
+            PlanStrategy::IndexSort => "index_sort",
+            PlanStrategy::FullScan => "full_scan",
+        }
+    }
+}
+
+/// Result of planning a list query
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    pub strategy: PlanStrategy,
+    /// Index used, if any
+    pub index: Option<String>,
+    /// Candidate ids; `None` means every stored item
+    pub candidates: Option<Vec<String>>,
+    /// Candidates are already in the requested sort order
+    pub sorted: bool,
+}
+
+impl QueryPlan {
+    pub(crate) fn full_scan() -> Self {
+        Self { strategy: PlanStrategy::FullScan, index: None, candidates: None, sorted: false }
+    }
+
+    /// `explain` object returned with `?explain=true`
+    pub fn explain_json(&self, scanned: usize, matched: usize) -> Value {
+        serde_json::json!({
+            "strategy": self.strategy.as_str(),
+            "index": self.index,
+            "candidates": self.candidates.as_ref().map(|c| c.len()),
+            "sorted_by_index": self.sorted,
+            "scanned": scanned,
+            "matched": matched,
+        })
+    }
+}
+
+/// Ordered indexes for the `#[db(indexed)]` fields of one model
+pub struct SecondaryIndexes {
+    indexes: RwLock<HashMap<String, FieldIndex>>,
+}
+
+impl SecondaryIndexes {
+    pub fn new(fields: Vec<String>) -> Self {
+        let indexes = fields.into_iter().map(|f| (f, FieldIndex::new())).collect();
+        Self { indexes: RwLock::new(indexes) }
+    }
+
+    /// True when the model has no indexed field (all operations are no-ops)
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Names of the indexed fields
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields: Vec<String> = self.read().keys().cloned().collect();
+        fields.sort();
+        fields
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, FieldIndex>> {
+        self.indexes.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, FieldIndex>> {
+        self.indexes.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move `id` from the keys of `old` to the keys of `new` (None = removed)
+    pub fn apply(&self, id: &str, old: Option<&Value>, new: Option<&Value>) {
+        let mut indexes = self.write();
+        for (field, index) in indexes.iter_mut() {
+            if let Some(old) = old {
+                let key = IndexKey::from_value(old.get(field));
+                if let Some(ids) = index.get_mut(&key) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        index.remove(&key);
+                    }
+                }
+            }
+            if let Some(new) = new {
+                let key = IndexKey::from_value(new.get(field));
+                index.entry(key).or_default().insert(id.to_string());
+            }
+        }
+    }
+
+    /// Drop every entry (used before a full rebuild)
+    pub fn clear(&self) {
+        for index in self.write().values_mut() {
+            index.clear();
+        }
+    }
+
+    /// Choose how to answer `params`
+    pub fn plan(&self, params: &QueryParams) -> QueryPlan {
+        let indexes = self.read();
+        if indexes.is_empty() {
+            return QueryPlan::full_scan();
+        }
+
+        // Index order only matches the requested order when there is a single sort key
+        let sort = match params.sort.as_slice() {
+            [only] if indexes.contains_key(&only.field) => Some(only),
+            _ => None,
+        };
+        let eq_like =
+            |op: &FilterOp| matches!(op, FilterOp::Eq | FilterOp::In(_) | FilterOp::IsNull);
+        let range = |op: &FilterOp| {
+            matches!(op, FilterOp::Gt | FilterOp::Lt | FilterOp::Gte | FilterOp::Lte)
+        };
+        // Equality is the most selective, then ranges. `or` groups are left to the
+        // residual evaluation.
+        let filter = params
+            .filters
+            .iter()
+            .find(|f| eq_like(&f.op) && indexes.contains_key(&f.field))
+            .or_else(|| {
+                params.filters.iter().find(|f| range(&f.op) && indexes.contains_key(&f.field))
+            });
+
+        match (filter, sort) {
+            (Some(filter), _) => {
+                let index = &indexes[&filter.field];
+                let mut ids = lookup(index, filter);
+                // Lookups return ids in key order, which is the sort order on the same field
+                let sorted = sort.map(|s| s.field == filter.field).unwrap_or(false);
+                if sorted && sort.map(|s| s.descending).unwrap_or(false) {
+                    ids.reverse();
+                }
+                QueryPlan {
+                    strategy: if eq_like(&filter.op) {
+                        PlanStrategy::IndexEq
+                    } else {
+                        PlanStrategy::IndexRange
+                    },
+                    index: Some(filter.field.clone()),
+                    candidates: Some(ids),
+                    sorted,
+                }
+            }
+            (None, Some(sort)) => {
+                let index = &indexes[&sort.field];
+                let mut ids: Vec<String> = index.values().flatten().cloned().collect();
+                if sort.descending {
+                    ids.reverse();
+                }
+                QueryPlan {
+                    strategy: PlanStrategy::IndexSort,
+                    index: Some(sort.field.clone()),
+                    candidates: Some(ids),
+                    sorted: true,
+                }
+            }
+            (None, None) => QueryPlan::full_scan(),
+        }
+    }
+}
+
+impl SecondaryIndexes {
+    /// Ids in `field` order strictly after `start` (sort key, then id), or None if
+    /// `field` is not indexed. Used by cursor pagination.
+    pub fn ordered_ids(
+        &self,
+        field: &str,
+        start: Option<(&IndexKey, &str)>,
+        descending: bool,
+    ) -> Option<Vec<String>> {
+        let indexes = self.read();
+        let index = indexes.get(field)?;
+        let mut ids = Vec::new();
+        match (start, descending) {
+            (None, false) => ids.extend(index.values().flatten().cloned()),
+            (None, true) => {
+                ids.extend(index.values().rev().flat_map(|set| set.iter().rev()).cloned())
+            }
+            (Some((key, id)), false) => {
+                for (k, set) in index.range((Bound::Included(key), Bound::Unbounded)) {
+                    if k == key {
+                        ids.extend(
+                            set.range::<str, _>((Bound::Excluded(id), Bound::Unbounded)).cloned(),
+                        );
+                    } else {
+                        ids.extend(set.iter().cloned());
+                    }
+                }
+            }
+            (Some((key, id)), true) => {
+                for (k, set) in index.range((Bound::Unbounded, Bound::Included(key))).rev() {
+                    if k == key {
+                        ids.extend(
+                            set.range::<str, _>((Bound::Unbounded, Bound::Excluded(id)))
+                                .rev()
+                                .cloned(),
+                        );
+                    } else {
+                        ids.extend(set.iter().rev().cloned());
+                    }
+                }
+            }
+        }
+        Some(ids)
+    }
+}
+
+impl std::fmt::Debug for SecondaryIndexes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let summary: Vec<(String, usize)> =
+            self.read().iter().map(|(field, index)| (field.clone(), index.len())).collect();
+        f.debug_struct("SecondaryIndexes").field("indexes", &summary).finish()
+    }
+}
+
+/// Ids whose indexed value may satisfy `filter`, in key order
+fn lookup(index: &FieldIndex, filter: &FilterSpec) -> Vec<String> {
+    match &filter.op {
+        FilterOp::IsNull => {
+            return index.get(&IndexKey::Null).into_iter().flatten().cloned().collect();
+        }
+        FilterOp::In(values) => {
+            // Collected by key so the ids come out in key order
+            let matched: BTreeMap<&IndexKey, &BTreeSet<String>> = values
+                .iter()
+                .flat_map(|v| IndexKey::targets(v))
+                .filter_map(|target| index.get_key_value(&target))
+                .collect();
+            return matched.into_values().flatten().cloned().collect();
+        }
+        _ => {}
+    }
+    let mut ids: Vec<String> = Vec::new();
+    for target in IndexKey::targets(&filter.value) {
+        // Ranges never match null (see `query::value_compare`)
+        if filter.op != FilterOp::Eq && target.rank() == 0 {
+            continue;
+        }
+        let rank = target.rank();
+        let same_type = |(key, _): &(&IndexKey, &BTreeSet<String>)| key.rank() == rank;
+        let matched: Vec<&BTreeSet<String>> = match filter.op {
+            FilterOp::Eq => index.get(&target).into_iter().collect(),
+            FilterOp::Gt => index
+                .range((Bound::Excluded(&target), Bound::Unbounded))
+                .take_while(same_type)
+                .map(|(_, ids)| ids)
+                .collect(),
+            FilterOp::Gte => index
+                .range((Bound::Included(&target), Bound::Unbounded))
+                .take_while(same_type)
+                .map(|(_, ids)| ids)
+                .collect(),
+            FilterOp::Lt | FilterOp::Lte => {
+                let upper = if filter.op == FilterOp::Lt {
+                    Bound::Excluded(&target)
+                } else {
+                    Bound::Included(&target)
+                };
+                let mut below: Vec<&BTreeSet<String>> = index
+                    .range((Bound::Unbounded, upper))
+                    .rev()
+                    .take_while(same_type)
+                    .map(|(_, ids)| ids)
+                    .collect();
+                below.reverse();
+                below
+            }
+            _ => Vec::new(),
+        };
+        ids.extend(matched.into_iter().flatten().cloned());
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::query::parse_query_params;
+    use serde_json::json;
+
+    fn indexes() -> SecondaryIndexes {
+        let idx = SecondaryIndexes::new(vec!["price".to_string(), "status".to_string()]);
+        idx.apply("a", None, Some(&json!({"price": 5, "status": "open"})));
+        idx.apply("b", None, Some(&json!({"price": 50, "status": "closed"})));
+        idx.apply("c", None, Some(&json!({"price": 500, "status": "open"})));
+        idx.apply("d", None, Some(&json!({"status": "open"})));
+        idx
+    }
+
+    #[test]
+    fn test_eq_lookup() {
+        let plan = indexes().plan(&parse_query_params("status=open").unwrap());
+        assert_eq!(plan.strategy, PlanStrategy::IndexEq);
+        assert_eq!(plan.index.as_deref(), Some("status"));
+        assert_eq!(plan.candidates.unwrap(), vec!["a", "c", "d"]);
+    }
+
+    #[test]
+    fn test_range_lookup_is_numeric_and_ordered() {
+        let plan = indexes().plan(&parse_query_params("price=>=50&sort=-price").unwrap());
+        assert_eq!(plan.strategy, PlanStrategy::IndexRange);
+        assert!(plan.sorted);
+        assert_eq!(plan.candidates.unwrap(), vec!["c", "b"]);
+
+        let plan = indexes().plan(&parse_query_params("price=<50").unwrap());
+        assert_eq!(plan.candidates.unwrap(), vec!["a"]);
+    }
+
+    #[test]
+    fn test_index_sort_includes_missing_values_first() {
+        let plan = indexes().plan(&parse_query_params("sort=price").unwrap());
+        assert_eq!(plan.strategy, PlanStrategy::IndexSort);
+        assert_eq!(plan.candidates.unwrap(), vec!["d", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_ordered_ids_resume_after_position() {
+        let idx = indexes();
+        idx.apply("e", None, Some(&json!({"price": 50})));
+        let fifty = IndexKey::Number(50.0);
+        let after = idx.ordered_ids("price", Some((&fifty, "b")), false).unwrap();
+        assert_eq!(after, vec!["e", "c"]);
+        let before = idx.ordered_ids("price", Some((&fifty, "e")), true).unwrap();
+        assert_eq!(before, vec!["b", "a", "d"]);
+        assert!(idx.ordered_ids("name", None, false).is_none());
+    }
+
+    #[test]
+    fn test_update_moves_id_and_unindexed_queries_scan() {
+        let idx = indexes();
+        idx.apply("a", Some(&json!({"price": 5, "status": "open"})), Some(&json!({"price": 7})));
+        let plan = idx.plan(&parse_query_params("status=open").unwrap());
+        assert_eq!(plan.candidates.unwrap(), vec!["c", "d"]);
+
+        let plan = idx.plan(&parse_query_params("name=~foo&sort=name").unwrap());
+        assert_eq!(plan.strategy, PlanStrategy::FullScan);
+        assert!(plan.candidates.is_none());
+    }
+
+    #[test]
+    fn test_in_and_null_lookups_and_multi_sort() {
+        let params = parse_query_params("status=in(closed,open)&sort=status").unwrap();
+        let plan = indexes().plan(&params);
+        assert_eq!(plan.strategy, PlanStrategy::IndexEq);
+        assert!(plan.sorted);
+        assert_eq!(plan.candidates.unwrap(), vec!["b", "a", "c", "d"]);
+
+        let plan = indexes().plan(&parse_query_params("price=null").unwrap());
+        assert_eq!(plan.candidates.unwrap(), vec!["d"]);
+
+        let plan = indexes().plan(&parse_query_params("sort=price,status").unwrap());
+        assert_eq!(plan.strategy, PlanStrategy::FullScan);
+    }
+}
@@ -28,16 +28,22 @@
 //! - **Concurrency**: Efficient handling of thousands of connections
 
 pub mod admin;
+pub mod aggregate;
 pub mod api_router; // Generic API routing for DeclarativeHttpHandler
 pub mod async_server; // Async HTTP server with Hyper
 pub mod backend;
+pub mod cursor;
 pub mod declarative;
 pub mod declarative_handlers; // Revolutionary Data-First routing system
 pub mod declarative_server;
 pub mod error;
+pub mod etag;
+pub mod expand;
 pub mod firewall;
+pub mod index;
 pub mod openapi;
 pub mod optimized_declarative; // T021 bincode optimization
+pub mod patch;
 pub mod query;
 pub mod request;
 pub mod response;
@@ -47,8 +53,10 @@ pub mod server;
 pub mod sse;
 pub mod three_tier;
 pub mod ultra_performance;
+pub mod unique;
 pub mod url_handlers; // Direct URL-to-Function mapping system
 pub mod utils;
+pub mod validation;
 
 // Re-export main types for convenience
 pub use admin::{
@@ -58,12 +66,14 @@ pub use admin::{
     handle_auto_reload_endpoint, handle_complete_admin_management, AutoAdminConfig,
     ReloadableServer, ServerMetrics,
 };
+pub use aggregate::{AggregateOp, AggregateQuery};
 pub use api_router::{ApiRouter, ApiRouterStats};
 pub use async_server::AsyncHttpServer;
 pub use backend::{
     handle_with_segments, proxy_to_declarative_handler, BackendHandler, BackendRoute, BackendRouter,
 };
-pub use declarative::{DeclarativeHttpHandler, HttpExposable};
+pub use cursor::{Cursor, CursorError};
+pub use declarative::{DeclarativeHttpHandler, HttpExposable, ReferenceResolver};
 pub use declarative_handlers::{
     AdminHandlerConfig, ApiProxyConfig, CustomHandlerCallback, CustomHandlerConfig,
     CustomHandlerRegistry, DeclarativeHandlerConfig, DeclarativeHandlerSystem,
@@ -75,8 +85,10 @@ pub use declarative_server::{
 };
 pub use error as http_error;
 pub use firewall::{Firewall, FirewallConfig};
+pub use index::{PlanStrategy, QueryPlan, SecondaryIndexes};
 pub use openapi::{generate_openapi_spec, OpenApiModelInfo};
 pub use optimized_declarative::{OptimizedDeclarativeHttpHandler, OptimizedHttpExposable};
+pub use patch::{Patch, PatchError, PatchOperation};
 pub use request::{HttpMethod, HttpRequest, HttpVersion};
 pub use response::{HttpResponse, StatusCode};
 pub use route_guard::{GuardResult, RouteGuard, RouteGuardMatcher};
@@ -87,6 +99,7 @@ pub use router::{
 pub use server::HttpServer;
 pub use sse::{create_broadcaster, ModelChangeEvent, SseEventBroadcaster};
 pub use three_tier::{ThreeTierHandler, ThreeTierResult, ThreeTierRouter, ThreeTierRouterBuilder};
+pub use unique::{UniqueIndex, UniqueViolation};
 pub use url_handlers::{UrlHandler, UrlHandlerRegistry, UrlHandlerStats};
 pub use utils::{
     access_log_buffer, body_from, extract_client_ip, extract_method_str, extract_path,
@@ -96,6 +109,7 @@ pub use utils::{
     serve_dev_asset, AccessLogBuffer, AccessLogEntry, Req, Resp, RespBody,
     DEFAULT_ACCESS_LOG_CAPACITY,
 };
+pub use validation::{FieldError, ValidationErrors, ValidationRule};
 
 /// Result type for HTTP operations
 pub type HttpResult<T> = std::result::Result<T, HttpError>;
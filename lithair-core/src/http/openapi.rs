@@ -224,11 +224,27 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
                 "parameters": [
                     { "name": "skip", "in": "query", "schema": { "type": "integer", "default": 0 }, "description": "Number of items to skip" },
                     { "name": "take", "in": "query", "schema": { "type": "integer" }, "description": "Maximum number of items to return" },
-                    { "name": "sort", "in": "query", "schema": { "type": "string" }, "description": "Sort field (prefix with - for descending)" }
+                    { "name": "sort", "in": "query", "schema": { "type": "string" }, "description": "Comma-separated sort fields, most significant first (prefix with - for descending)" },
+                    { "name": "fields", "in": "query", "schema": { "type": "string" }, "description": "Comma-separated fields to return (dotted paths allowed)" },
+                    { "name": "or", "in": "query", "schema": { "type": "string" }, "description": "Filter group where at least one filter must match, e.g. `(status=open,priority=>3)`" },
+                    { "name": "limit", "in": "query", "schema": { "type": "integer", "minimum": 1 }, "description": "Page size for cursor pagination (replaces skip/take)" },
+                    { "name": "after", "in": "query", "schema": { "type": "string" }, "description": "Opaque cursor: return the page after it (`next_cursor`)" },
+                    { "name": "before", "in": "query", "schema": { "type": "string" }, "description": "Opaque cursor: return the page before it (`prev_cursor`)" },
+                    { "name": "explain", "in": "query", "schema": { "type": "boolean" }, "description": "Include the query plan in the response" },
+                    { "name": "include_deleted", "in": "query", "schema": { "type": "boolean" }, "description": "Also return soft-deleted items, marked `_deleted` (`#[db(soft_delete)]` models)" },
+                    { "name": "as_of", "in": "query", "schema": { "type": "string" }, "description": "Read the collection as it was at an RFC 3339 timestamp or event index (offset pagination only)" },
+                    { "name": "expand", "in": "query", "schema": { "type": "string" }, "description": "Embed related items, e.g. `author,comments.author` (up to 3 levels)" }
                 ],
                 "responses": {
+                    "400": { "description": "Malformed query parameter" },
                     "200": {
                         "description": "Paginated list",
+                        "headers": {
+                            "Link": {
+                                "description": "RFC 8288 `next`/`prev` links (cursor pagination only)",
+                                "schema": { "type": "string" }
+                            }
+                        },
                         "content": {
                             "application/json": {
                                 "schema": {
@@ -238,7 +254,11 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
                                         "total": { "type": "integer" },
                                         "skip": { "type": "integer" },
                                         "take": { "type": ["integer", "null"] },
-                                        "has_more": { "type": "boolean" }
+                                        "limit": { "type": "integer" },
+                                        "has_more": { "type": "boolean" },
+                                        "next_cursor": { "type": ["string", "null"] },
+                                        "prev_cursor": { "type": ["string", "null"] },
+                                        "explain": { "type": "object" }
                                     }
                                 }
                             }
@@ -274,8 +294,75 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
         }),
     ));
 
-    // Item endpoint: GET + PUT + DELETE
+    // Aggregation endpoint
+    let metric_param = |name: &str| {
+        json!({
+            "name": name,
+            "in": "query",
+            "schema": { "type": "string" },
+            "description": format!("Comma-separated fields to {}", name)
+        })
+    };
+    let metric_section = json!({ "type": "object", "additionalProperties": true });
+    paths.push((
+        format!("{}/_aggregate", base),
+        json!({
+            "get": {
+                "tags": [&tag],
+                "summary": format!("Aggregate {} items", model_name),
+                "description": format!(
+                    "Grouped count/sum/avg/min/max over readable items. Other query \
+                     parameters filter the items like the list endpoint.{}",
+                    perm_note
+                ),
+                "operationId": format!("aggregate{}", model_name),
+                "parameters": [
+                    { "name": "group_by", "in": "query", "schema": { "type": "string" }, "description": "Comma-separated group key fields" },
+                    metric_param("sum"),
+                    metric_param("avg"),
+                    metric_param("min"),
+                    metric_param("max")
+                ],
+                "responses": {
+                    "200": {
+                        "description": "One entry per group",
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "type": "object",
+                                    "properties": {
+                                        "group_by": { "type": "array", "items": { "type": "string" } },
+                                        "groups": {
+                                            "type": "array",
+                                            "items": {
+                                                "type": "object",
+                                                "properties": {
+                                                    "key": { "type": "object", "additionalProperties": true },
+                                                    "count": { "type": "integer" },
+                                                    "sum": &metric_section,
+                                                    "avg": &metric_section,
+                                                    "min": &metric_section,
+                                                    "max": &metric_section
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "400": { "description": "Malformed query parameter" }
+                }
+            }
+        }),
+    ));
+
+    // Item endpoint: GET + PUT + PATCH + DELETE, conditional on the item's ETag
     let item_path = format!("{}/{{id}}", base);
+    let etag_header = json!({
+        "ETag": { "description": "Current version of the item", "schema": { "type": "string" } }
+    });
+    let if_match = json!({ "name": "If-Match", "in": "header", "schema": { "type": "string" }, "description": "Only write if the item is still at one of these ETags" });
     paths.push((
         item_path,
         json!({
@@ -284,17 +371,23 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
                 "summary": format!("Get {} by ID", model_name),
                 "operationId": format!("get{}", model_name),
                 "parameters": [
-                    { "name": "id", "in": "path", "required": true, "schema": id_schema.clone() }
+                    { "name": "id", "in": "path", "required": true, "schema": id_schema.clone() },
+                    { "name": "include_deleted", "in": "query", "schema": { "type": "boolean" }, "description": "Return the item even if soft-deleted, marked `_deleted`" },
+                    { "name": "as_of", "in": "query", "schema": { "type": "string" }, "description": "Read the item as it was at an RFC 3339 timestamp or event index" },
+                    { "name": "expand", "in": "query", "schema": { "type": "string" }, "description": "Embed related items, e.g. `author,comments.author` (up to 3 levels)" },
+                    { "name": "If-None-Match", "in": "header", "schema": { "type": "string" }, "description": "Answer 304 while the item is still at one of these ETags" }
                 ],
                 "responses": {
                     "200": {
                         "description": "Found",
+                        "headers": &etag_header,
                         "content": {
                             "application/json": {
                                 "schema": { "$ref": &schema_ref }
                             }
                         }
                     },
+                    "304": { "description": "Not modified since the given ETag", "headers": &etag_header },
                     "404": { "description": "Not found" }
                 }
             },
@@ -303,7 +396,8 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
                 "summary": format!("Update {}", model_name),
                 "operationId": format!("update{}", model_name),
                 "parameters": [
-                    { "name": "id", "in": "path", "required": true, "schema": id_schema.clone() }
+                    { "name": "id", "in": "path", "required": true, "schema": id_schema.clone() },
+                    &if_match
                 ],
                 "requestBody": {
                     "required": true,
@@ -316,6 +410,7 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
                 "responses": {
                     "200": {
                         "description": "Updated",
+                        "headers": &etag_header,
                         "content": {
                             "application/json": {
                                 "schema": { "$ref": &schema_ref }
@@ -323,7 +418,62 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
                         }
                     },
                     "404": { "description": "Not found" },
-                    "400": { "description": "Invalid input" }
+                    "400": { "description": "Invalid input" },
+                    "412": { "description": "The item is no longer at the If-Match ETag" }
+                }
+            },
+            "patch": {
+                "tags": [&tag],
+                "summary": format!("Patch {}", model_name),
+                "description": "Partial update with a JSON Merge Patch (RFC 7396) or a JSON Patch (RFC 6902).",
+                "operationId": format!("patch{}", model_name),
+                "parameters": [
+                    { "name": "id", "in": "path", "required": true, "schema": id_schema.clone() },
+                    &if_match
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/merge-patch+json": {
+                            "schema": { "type": "object" }
+                        },
+                        "application/json-patch+json": {
+                            "schema": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "required": ["op", "path"],
+                                    "properties": {
+                                        "op": {
+                                            "type": "string",
+                                            "enum": ["add", "remove", "replace", "move", "copy", "test"]
+                                        },
+                                        "path": { "type": "string" },
+                                        "from": { "type": "string" },
+                                        "value": {}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                "responses": {
+                    "200": {
+                        "description": "Patched",
+                        "headers": &etag_header,
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": &schema_ref }
+                            }
+                        }
+                    },
+                    "400": { "description": "Invalid patch document" },
+                    "403": { "description": "A changed field needs a write permission the caller lacks" },
+                    "404": { "description": "Not found" },
+                    "409": { "description": "The patch does not apply to the item" },
+                    "412": { "description": "The item is no longer at the If-Match ETag" },
+                    "415": { "description": "Unsupported patch media type" },
+                    "422": { "description": "Patched item is invalid or changes an immutable field" }
                 }
             },
             "delete": {
@@ -331,11 +481,13 @@ fn model_to_paths(info: &OpenApiModelInfo) -> Vec<(String, Value)> {
                 "summary": format!("Delete {}", model_name),
                 "operationId": format!("delete{}", model_name),
                 "parameters": [
-                    { "name": "id", "in": "path", "required": true, "schema": id_schema.clone() }
+                    { "name": "id", "in": "path", "required": true, "schema": id_schema.clone() },
+                    &if_match
                 ],
                 "responses": {
                     "200": { "description": "Deleted" },
-                    "404": { "description": "Not found" }
+                    "404": { "description": "Not found" },
+                    "412": { "description": "The item is no longer at the If-Match ETag" }
                 }
             }
         }),
@@ -472,8 +624,50 @@ mod tests {
         assert!(spec["paths"]["/api/todos"]["post"].is_object());
         assert!(spec["paths"]["/api/todos/{id}"]["get"].is_object());
         assert!(spec["paths"]["/api/todos/{id}"]["put"].is_object());
+        assert!(spec["paths"]["/api/todos/{id}"]["patch"].is_object());
         assert!(spec["paths"]["/api/todos/{id}"]["delete"].is_object());
         assert!(spec["components"]["schemas"]["Todo"].is_object());
+
+        let item = &spec["paths"]["/api/todos/{id}"];
+        assert!(item["get"]["responses"]["200"]["headers"]["ETag"].is_object());
+        assert!(item["get"]["responses"]["304"].is_object());
+        assert_eq!(item["put"]["parameters"][1]["name"], "If-Match");
+        assert!(item["patch"]["responses"]["412"].is_object());
+    }
+
+    #[test]
+    fn test_list_documents_cursor_pagination() {
+        let spec = generate_openapi_spec(&[sample_model()]);
+        let list = &spec["paths"]["/api/todos"]["get"];
+        let params: Vec<&str> = list["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert!(
+            params.contains(&"after") && params.contains(&"before") && params.contains(&"limit")
+        );
+        assert!(params.contains(&"fields") && params.contains(&"or"));
+        assert!(list["responses"]["400"].is_object());
+        let ok = &list["responses"]["200"];
+        assert!(ok["headers"]["Link"].is_object());
+        let props = &ok["content"]["application/json"]["schema"]["properties"];
+        assert!(props["next_cursor"].is_object());
+    }
+
+    #[test]
+    fn test_aggregate_endpoint_documented() {
+        let spec = generate_openapi_spec(&[sample_model()]);
+        let op = &spec["paths"]["/api/todos/_aggregate"]["get"];
+        assert_eq!(op["operationId"], "aggregateTodo");
+        let params: Vec<&str> = op["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert_eq!(params, vec!["group_by", "sum", "avg", "min", "max"]);
     }
 
     #[test]
@@ -0,0 +1,288 @@
+//! Partial updates of declarative model items
+//!
+//! `PATCH /api/{model}/{id}` takes one of two body formats, chosen by `Content-Type`:
+//!
+//! - `application/merge-patch+json` (RFC 7396): an object whose members replace the
+//!   item's, `null` removing them; nested objects merge recursively.
+//! - `application/json-patch+json` (RFC 6902): a list of `add`, `remove`, `replace`,
+//!   `move`, `copy` and `test` operations addressed by JSON Pointer. The list applies
+//!   atomically: one failing operation rejects the whole patch.
+//!
+//! Whatever the format, the handler persists `merge_diff(stored, patched)` as a
+//! `Patched` event, so the log only holds the fields that changed. A merge patch does
+//! not tell a `null` member from a missing one; both deserialize to `None`.
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+/// Value of the `Accept-Patch` header of item resources
+pub const ACCEPT_PATCH: &str = "application/merge-patch+json, application/json-patch+json";
+
+/// One RFC 6902 operation
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/// A parsed PATCH body
+#[derive(Debug, Clone, PartialEq)]
+pub enum Patch {
+    Merge(Value),
+    Json(Vec<PatchOperation>),
+}
+
+/// Why a patch was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The body is not a valid patch document
+    Malformed(String),
+    /// Operation `index` does not apply to the item (including a failed `test`)
+    Failed { index: usize, message: String },
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::Malformed(e) => write!(f, "invalid patch document: {}", e),
+            PatchError::Failed { index, message } => {
+                write!(f, "patch operation {} failed: {}", index, message)
+            }
+        }
+    }
+}
+
+impl Patch {
+    /// Parse `body` according to `content_type`; None when the media type is not a
+    /// patch format
+    pub fn parse(content_type: &str, body: &[u8]) -> Option<Result<Self, PatchError>> {
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+        let malformed = |e: serde_json::Error| PatchError::Malformed(e.to_string());
+        if media_type.eq_ignore_ascii_case(MERGE_PATCH_CONTENT_TYPE) {
+            Some(serde_json::from_slice::<Value>(body).map_err(malformed).and_then(|patch| {
+                match patch {
+                    Value::Object(_) => Ok(Patch::Merge(patch)),
+                    _ => Err(PatchError::Malformed("merge patch must be an object".to_string())),
+                }
+            }))
+        } else if media_type.eq_ignore_ascii_case(JSON_PATCH_CONTENT_TYPE) {
+            Some(serde_json::from_slice(body).map(Patch::Json).map_err(malformed))
+        } else {
+            None
+        }
+    }
+
+    /// Apply the patch to a copy of `document`
+    pub fn apply(&self, document: &Value) -> Result<Value, PatchError> {
+        let mut patched = document.clone();
+        match self {
+            Patch::Merge(patch) => merge_patch(&mut patched, patch),
+            Patch::Json(operations) => {
+                for (index, operation) in operations.iter().enumerate() {
+                    apply_operation(&mut patched, operation)
+                        .map_err(|message| PatchError::Failed { index, message })?;
+                }
+            }
+        }
+        Ok(patched)
+    }
+}
+
+/// Apply an RFC 7396 merge patch to `target`
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(members) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(object) = target {
+        for (key, value) in members {
+            if value.is_null() {
+                object.remove(key);
+            } else {
+                merge_patch(object.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Merge patch taking `old` to `new`; empty when they are equal
+pub fn merge_diff(old: &Value, new: &Value) -> Value {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        return new.clone();
+    };
+    let mut diff = Map::new();
+    for key in old.keys().filter(|key| !new.contains_key(*key)) {
+        diff.insert(key.clone(), Value::Null);
+    }
+    for (key, value) in new {
+        match old.get(key) {
+            Some(previous) if previous == value => {}
+            Some(previous @ Value::Object(_)) if value.is_object() => {
+                diff.insert(key.clone(), merge_diff(previous, value));
+            }
+            _ => {
+                diff.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Value::Object(diff)
+}
+
+fn apply_operation(document: &mut Value, operation: &PatchOperation) -> Result<(), String> {
+    match operation {
+        PatchOperation::Add { path, value } => add(document, path, value.clone()),
+        PatchOperation::Remove { path } => remove(document, path).map(|_| ()),
+        PatchOperation::Replace { path, value } => {
+            let slot = document.pointer_mut(path).ok_or_else(|| missing(path))?;
+            *slot = value.clone();
+            Ok(())
+        }
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(format!("cannot move '{}' into itself", from));
+            }
+            let value = remove(document, from)?;
+            add(document, path, value)
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = document.pointer(from).cloned().ok_or_else(|| missing(from))?;
+            add(document, path, value)
+        }
+        PatchOperation::Test { path, value } => match document.pointer(path) {
+            Some(current) if current == value => Ok(()),
+            Some(_) => Err(format!("test failed: '{}' does not hold the expected value", path)),
+            None => Err(missing(path)),
+        },
+    }
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let (parent, key) = split_pointer(path)?;
+    match document.pointer_mut(parent) {
+        Some(Value::Object(object)) => {
+            object.insert(key, value);
+            Ok(())
+        }
+        Some(Value::Array(array)) => {
+            let index = match key.as_str() {
+                "-" => array.len(),
+                _ => {
+                    array_index(&key).filter(|&i| i <= array.len()).ok_or_else(|| missing(path))?
+                }
+            };
+            array.insert(index, value);
+            Ok(())
+        }
+        Some(_) => Err(format!("parent of '{}' is not an object or array", path)),
+        None => Err(missing(parent)),
+    }
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() {
+        return Err("cannot remove the whole document".to_string());
+    }
+    let (parent, key) = split_pointer(path)?;
+    let removed = match document.pointer_mut(parent) {
+        Some(Value::Object(object)) => object.remove(&key),
+        Some(Value::Array(array)) => {
+            array_index(&key).filter(|&i| i < array.len()).map(|i| array.remove(i))
+        }
+        _ => None,
+    };
+    removed.ok_or_else(|| missing(path))
+}
+
+/// Parent pointer and unescaped last token of a non-empty JSON Pointer
+fn split_pointer(pointer: &str) -> Result<(&str, String), String> {
+    if !pointer.starts_with('/') {
+        return Err(format!("invalid JSON pointer '{}'", pointer));
+    }
+    let at = pointer.rfind('/').unwrap_or(0);
+    Ok((&pointer[..at], pointer[at + 1..].replace("~1", "/").replace("~0", "~")))
+}
+
+fn array_index(token: &str) -> Option<usize> {
+    let digits = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit());
+    if !digits || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn missing(path: &str) -> String {
+    format!("path '{}' does not exist", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_patch() {
+        let item = json!({"id": "p1", "name": "Lamp", "tags": ["a"], "dims": {"w": 1, "h": 2}});
+        let body = br#"{"name": "Desk lamp", "tags": null, "dims": {"h": 3}}"#;
+        let patch = Patch::parse("application/merge-patch+json; charset=utf-8", body);
+        let patched = patch.unwrap().unwrap().apply(&item).unwrap();
+        assert_eq!(patched, json!({"id": "p1", "name": "Desk lamp", "dims": {"w": 1, "h": 3}}));
+
+        assert_eq!(
+            merge_diff(&item, &patched),
+            json!({"name": "Desk lamp", "tags": null, "dims": {"h": 3}})
+        );
+        assert_eq!(merge_diff(&item, &item), json!({}));
+        assert!(matches!(
+            Patch::parse(MERGE_PATCH_CONTENT_TYPE, b"[1]"),
+            Some(Err(PatchError::Malformed(_)))
+        ));
+        assert!(Patch::parse("application/json", b"{}").is_none());
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let item = json!({"id": "p1", "name": "Lamp", "tags": ["a", "c"], "price": 10});
+        let body = br#"[
+            {"op": "test", "path": "/price", "value": 10},
+            {"op": "replace", "path": "/price", "value": 12},
+            {"op": "add", "path": "/tags/1", "value": "b"},
+            {"op": "add", "path": "/tags/-", "value": "d"},
+            {"op": "copy", "from": "/name", "path": "/label"},
+            {"op": "move", "from": "/label", "path": "/title"},
+            {"op": "remove", "path": "/name"}
+        ]"#;
+        let patch = Patch::parse(JSON_PATCH_CONTENT_TYPE, body).unwrap().unwrap();
+        assert_eq!(
+            patch.apply(&item).unwrap(),
+            json!({"id": "p1", "title": "Lamp", "tags": ["a", "b", "c", "d"], "price": 12})
+        );
+
+        // All or nothing: a failed test rejects the patch
+        let body = br#"[
+            {"op": "replace", "path": "/price", "value": 12},
+            {"op": "test", "path": "/name", "value": "Desk"}
+        ]"#;
+        let patch = Patch::parse(JSON_PATCH_CONTENT_TYPE, body).unwrap().unwrap();
+        assert!(matches!(patch.apply(&item), Err(PatchError::Failed { index: 1, .. })));
+
+        let body = br#"[{"op": "remove", "path": "/missing"}]"#;
+        let patch = Patch::parse(JSON_PATCH_CONTENT_TYPE, body).unwrap().unwrap();
+        assert!(matches!(patch.apply(&item), Err(PatchError::Failed { index: 0, .. })));
+        assert!(Patch::parse(JSON_PATCH_CONTENT_TYPE, br#"[{"op": "jump"}]"#).unwrap().is_err());
+    }
+}
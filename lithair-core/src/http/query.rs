@@ -1,19 +1,101 @@
 //! Query parameter parsing for collection endpoints
 //!
-//! Supports pagination (skip/take), sorting (sort=-price), and filtering
-//! (field=value, field=>value, field=<value, field=~value).
+//! Supports pagination (skip/take), multi-field sorting (`sort=-created_at,name`),
+//! projection (`fields=id,name`) and filtering:
+//!
+//! | Syntax                   | Meaning                                   |
+//! |--------------------------|-------------------------------------------|
+//! | `field=value`            | equality                                  |
+//! | `field=!value`           | not equal                                 |
+//! | `field=>v`, `<v`, `>=v`, `<=v` | comparisons                         |
+//! | `field=~value`           | case-insensitive contains                 |
+//! | `field=in(a,b)`          | one of the listed values (`!in(...)`: none) |
+//! | `field=null`             | null or missing (`field=!null`: present)  |
+//! | `or=(a=1,b=>2)`          | at least one of the filters in the group  |
+//!
+//! Field names may be dotted paths into nested objects (`address.city`). Filters and
+//! `or` groups are ANDed together. Malformed parameters are reported as a
+//! `QueryError` (400) rather than ignored.
+//!
+//! `explain=true` adds the query plan (see `http::index`) to the response. `limit`,
+//! `after` and `before` switch to keyset pagination with signed cursors (see
+//! `http::cursor`). `include_deleted=true` also lists soft-deleted items. `as_of`
+//! (an RFC 3339 timestamp or an event index) reads the collection as it was then.
+//! `expand` names relations to embed (see `http::expand`); the server resolves them.
+
+use serde_json::Value;
 
 /// Default maximum number of items returned per page when `take` is not specified.
 /// Prevents unbounded collection responses.
 pub const DEFAULT_MAX_TAKE: u64 = 1000;
 
 /// Parsed query parameters for collection list endpoints
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct QueryParams {
     pub skip: u64,
     pub take: Option<u64>,
-    pub sort: Option<SortSpec>,
+    /// Sort keys, most significant first
+    pub sort: Vec<SortSpec>,
+    /// Filters that must all match
     pub filters: Vec<FilterSpec>,
+    /// `or=(...)` groups: each needs at least one matching filter
+    pub or_groups: Vec<Vec<FilterSpec>>,
+    /// `fields=` projection (None = whole item)
+    pub fields: Option<Vec<String>>,
+    /// Include the query plan in the response
+    pub explain: bool,
+    /// Cursor pagination: page size
+    pub limit: Option<u64>,
+    /// Cursor pagination: return items after this cursor
+    pub after: Option<String>,
+    /// Cursor pagination: return items before this cursor
+    pub before: Option<String>,
+    /// Also return soft-deleted (tombstoned) items
+    pub include_deleted: bool,
+    /// Point-in-time read
+    pub as_of: Option<AsOf>,
+    /// Relations to embed in the returned items
+    pub expand: Option<crate::http::expand::Expand>,
+}
+
+impl QueryParams {
+    /// True when the request asks for keyset (cursor) pagination
+    pub fn uses_cursor(&self) -> bool {
+        self.limit.is_some() || self.after.is_some() || self.before.is_some()
+    }
+
+    /// True when any filter or `or` group is present
+    pub fn has_filters(&self) -> bool {
+        !self.filters.is_empty() || !self.or_groups.is_empty()
+    }
+
+    /// Evaluate every filter and `or` group against a serialized item
+    pub fn matches(&self, item: &Value) -> bool {
+        self.filters.iter().all(|f| matches_filter(item, f))
+            && self.or_groups.iter().all(|group| group.iter().any(|f| matches_filter(item, f)))
+    }
+
+    /// Compare two serialized items by the sort keys
+    pub fn compare(&self, a: &Value, b: &Value) -> std::cmp::Ordering {
+        for sort in &self.sort {
+            let va = get_path(a, &sort.field).unwrap_or(&Value::Null);
+            let vb = get_path(b, &sort.field).unwrap_or(&Value::Null);
+            let ord = compare_json_values(va, vb);
+            let ord = if sort.descending { ord.reverse() } else { ord };
+            if ord != std::cmp::Ordering::Equal {
+                return ord;
+            }
+        }
+        std::cmp::Ordering::Equal
+    }
+
+    /// Apply the `fields=` projection (no-op without one)
+    pub fn project(&self, item: Value) -> Value {
+        match &self.fields {
+            Some(fields) => project_fields(&item, fields),
+            None => item,
+        }
+    }
 }
 
 /// Sort specification
@@ -23,6 +105,39 @@ pub struct SortSpec {
     pub descending: bool,
 }
 
+/// Point in a model's history requested with `?as_of=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    /// State after the first N events (numbered from 1, compacted events included)
+    EventIndex(usize),
+    /// State after every event stamped at or before this Unix time (seconds)
+    Timestamp(u64),
+}
+
+impl AsOf {
+    /// Parse an event index (`42`) or an RFC 3339 timestamp (`2025-03-01T12:00:00Z`)
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Ok(index) = value.parse::<usize>() {
+            return Ok(AsOf::EventIndex(index));
+        }
+        // An unencoded `+01:00` offset arrives as a space
+        chrono::DateTime::parse_from_rfc3339(&value.replace(' ', "+"))
+            .map(|t| AsOf::Timestamp(t.timestamp().max(0) as u64))
+            .map_err(|_| "expected an RFC 3339 timestamp or an event index".to_string())
+    }
+
+    /// Echo of the point in time, returned with list responses
+    pub fn to_json(&self) -> Value {
+        match self {
+            AsOf::EventIndex(index) => serde_json::json!({ "event_index": index }),
+            AsOf::Timestamp(secs) => {
+                let time = chrono::DateTime::from_timestamp(*secs as i64, 0).unwrap_or_default();
+                serde_json::json!({ "timestamp": time.to_rfc3339() })
+            }
+        }
+    }
+}
+
 /// Filter specification
 #[derive(Debug, Clone)]
 pub struct FilterSpec {
@@ -48,58 +163,214 @@ pub enum FilterOp {
     Lte,
     /// String contains (case-insensitive)
     Contains,
+    /// Equal to one of the values
+    In(Vec<String>),
+    /// Present and equal to none of the values
+    NotIn(Vec<String>),
+    /// Null or missing
+    IsNull,
+    /// Present and not null
+    NotNull,
 }
 
-/// Reserved query parameter names that are not treated as filters
-const RESERVED_PARAMS: &[&str] = &["skip", "take", "sort"];
+/// A malformed query parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    pub param: String,
+    pub message: String,
+}
 
-/// Parse query string into structured QueryParams
-pub fn parse_query_params(query: &str) -> QueryParams {
-    let mut skip = 0u64;
-    let mut take = None;
-    let mut sort = None;
-    let mut filters = Vec::new();
+impl QueryError {
+    fn new(param: &str, message: impl Into<String>) -> Self {
+        Self { param: param.to_string(), message: message.into() }
+    }
 
-    if query.is_empty() {
-        return QueryParams { skip, take, sort, filters };
+    /// JSON body used for 400 responses
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "error": "invalid_query",
+            "param": self.param,
+            "message": self.message,
+        })
     }
+}
 
-    for pair in query.split('&') {
-        let (key, value) = match pair.split_once('=') {
-            Some((k, v)) => (k, v),
-            None => continue,
-        };
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid query parameter `{}`: {}", self.param, self.message)
+    }
+}
 
-        match key {
-            "skip" => {
-                skip = value.parse().unwrap_or(0);
+impl std::error::Error for QueryError {}
+
+/// Reserved query parameter names that are not treated as filters
+const RESERVED_PARAMS: &[&str] = &[
+    "skip",
+    "take",
+    "sort",
+    "explain",
+    "limit",
+    "after",
+    "before",
+    "fields",
+    "or",
+    "include_deleted",
+    "as_of",
+    "expand",
+    "consistency",
+];
+
+/// Boolean flag value; a bare flag (`?explain`) counts as true
+pub(crate) fn parse_flag(value: &str) -> bool {
+    value.is_empty() || value == "1" || value.eq_ignore_ascii_case("true")
+}
+
+/// Parse the parameters of a single-item read (`include_deleted`, `as_of`, `expand`), ignoring
+/// the rest
+pub fn parse_item_query_params(query: &str) -> Result<QueryParams, QueryError> {
+    let mut params = QueryParams::default();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (raw_key, raw_value) = pair.split_once('=').unwrap_or((pair, ""));
+        match decode_component(raw_key)?.as_str() {
+            "include_deleted" => params.include_deleted = parse_flag(&decode_component(raw_value)?),
+            "as_of" => {
+                let value = decode_component(raw_value)?;
+                params.as_of = Some(AsOf::parse(&value).map_err(|e| QueryError::new("as_of", e))?)
             }
-            "take" => {
-                take = value.parse().ok();
+            "expand" => params.expand = Some(parse_expand(&decode_component(raw_value)?)?),
+            _ => {}
+        }
+    }
+    Ok(params)
+}
+
+/// Parse query string into structured QueryParams
+pub fn parse_query_params(query: &str) -> Result<QueryParams, QueryError> {
+    let mut params = QueryParams::default();
+
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (raw_key, raw_value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = decode_component(raw_key)?;
+        let value = decode_component(raw_value)?;
+        if key.is_empty() {
+            return Err(QueryError::new(pair, "missing parameter name"));
+        }
+
+        match key.as_str() {
+            "skip" => params.skip = parse_number(&key, &value)?,
+            "take" => params.take = Some(parse_number(&key, &value)?),
+            "limit" => params.limit = Some(parse_number(&key, &value)?),
+            "explain" => params.explain = parse_flag(&value),
+            "include_deleted" => params.include_deleted = parse_flag(&value),
+            "as_of" => {
+                params.as_of = Some(AsOf::parse(&value).map_err(|e| QueryError::new("as_of", e))?)
             }
+            "expand" => params.expand = Some(parse_expand(&value)?),
+            "after" => params.after = Some(value).filter(|v| !v.is_empty()),
+            "before" => params.before = Some(value).filter(|v| !v.is_empty()),
             "sort" => {
-                if let Some(field) = value.strip_prefix('-') {
-                    sort = Some(SortSpec { field: field.to_string(), descending: true });
-                } else {
-                    sort = Some(SortSpec { field: value.to_string(), descending: false });
+                for part in value.split(',') {
+                    let part = part.trim();
+                    let (field, descending) = match part.strip_prefix('-') {
+                        Some(field) => (field, true),
+                        None => (part.strip_prefix('+').unwrap_or(part), false),
+                    };
+                    if field.is_empty() {
+                        return Err(QueryError::new("sort", "empty sort field"));
+                    }
+                    params.sort.push(SortSpec { field: field.to_string(), descending });
                 }
             }
+            "fields" => {
+                let fields: Vec<String> = value
+                    .split(',')
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty())
+                    .collect();
+                if fields.is_empty() {
+                    return Err(QueryError::new("fields", "expected a comma-separated field list"));
+                }
+                params.fields = Some(fields);
+            }
+            "or" => {
+                let inner = value
+                    .strip_prefix('(')
+                    .and_then(|v| v.strip_suffix(')'))
+                    .ok_or_else(|| QueryError::new("or", "expected or=(field=value,...)"))?;
+                let mut group = Vec::new();
+                for member in split_top_level(inner) {
+                    let (field, filter) = member.split_once('=').ok_or_else(|| {
+                        QueryError::new("or", format!("`{}` is not a field=value filter", member))
+                    })?;
+                    group.push(parse_filter(field.trim(), filter)?);
+                }
+                if group.is_empty() {
+                    return Err(QueryError::new("or", "empty group"));
+                }
+                params.or_groups.push(group);
+            }
             field if !RESERVED_PARAMS.contains(&field) => {
-                let (op, val) = parse_filter_value(value);
-                filters.push(FilterSpec { field: field.to_string(), op, value: val.to_string() });
+                if !pair.contains('=') {
+                    return Err(QueryError::new(field, "expected field=value"));
+                }
+                params.filters.push(parse_filter(field, &value)?);
             }
             _ => {}
         }
     }
 
-    QueryParams { skip, take, sort, filters }
+    Ok(params)
+}
+
+fn parse_expand(value: &str) -> Result<crate::http::expand::Expand, QueryError> {
+    crate::http::expand::Expand::parse(value).map_err(|e| QueryError::new("expand", e))
+}
+
+fn decode_component(raw: &str) -> Result<String, QueryError> {
+    let raw = raw.replace('+', " ");
+    urlencoding::decode(&raw)
+        .map(|s| s.into_owned())
+        .map_err(|_| QueryError::new(&raw, "invalid percent-encoding"))
+}
+
+fn parse_number(param: &str, value: &str) -> Result<u64, QueryError> {
+    value
+        .parse()
+        .map_err(|_| QueryError::new(param, format!("`{}` is not a number", value)))
+}
+
+/// Parse one `field=value` filter (value carries the operator prefix)
+fn parse_filter(field: &str, value: &str) -> Result<FilterSpec, QueryError> {
+    if field.is_empty() || field.split('.').any(|segment| segment.is_empty()) {
+        return Err(QueryError::new(field, "invalid field path"));
+    }
+    let (op, val) = parse_filter_value(value).map_err(|message| QueryError::new(field, message))?;
+    Ok(FilterSpec { field: field.to_string(), op, value: val.to_string() })
 }
 
 /// Parse a filter value to extract the operation
 /// Syntax: `value` (eq), `!value` (ne), `>value` (gt), `<value` (lt),
-/// `>=value` (gte), `<=value` (lte), `~value` (contains)
-fn parse_filter_value(value: &str) -> (FilterOp, &str) {
-    if let Some(v) = value.strip_prefix(">=") {
+/// `>=value` (gte), `<=value` (lte), `~value` (contains), `in(a,b)`, `!in(a,b)`,
+/// `null`, `!null`
+fn parse_filter_value(value: &str) -> Result<(FilterOp, &str), String> {
+    if value == "null" {
+        return Ok((FilterOp::IsNull, value));
+    }
+    if value == "!null" {
+        return Ok((FilterOp::NotNull, value));
+    }
+    if let Some(list) = value.strip_prefix("!in(").or_else(|| value.strip_prefix("in(")) {
+        let list = list.strip_suffix(')').ok_or("unterminated in(...) list")?;
+        let values: Vec<String> =
+            split_top_level(list).into_iter().map(|v| v.trim().to_string()).collect();
+        if values.is_empty() {
+            return Err("in(...) needs at least one value".to_string());
+        }
+        let op =
+            if value.starts_with('!') { FilterOp::NotIn(values) } else { FilterOp::In(values) };
+        return Ok((op, value));
+    }
+    Ok(if let Some(v) = value.strip_prefix(">=") {
         (FilterOp::Gte, v)
     } else if let Some(v) = value.strip_prefix("<=") {
         (FilterOp::Lte, v)
@@ -113,29 +384,92 @@ fn parse_filter_value(value: &str) -> (FilterOp, &str) {
         (FilterOp::Ne, v)
     } else {
         (FilterOp::Eq, value)
+    })
+}
+
+/// Split on commas outside parentheses
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts.retain(|p| !p.trim().is_empty());
+    parts
+}
+
+/// Resolve a dotted path (`address.city`, `tags.0`); a top-level key containing dots
+/// wins over the nested interpretation
+pub fn get_path<'a>(item: &'a Value, path: &str) -> Option<&'a Value> {
+    if let Some(v) = item.get(path) {
+        return Some(v);
+    }
+    if !path.contains('.') {
+        return None;
+    }
+    path.split('.').try_fold(item, |current, segment| match current {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+/// Keep only `fields` (dotted paths rebuild the nested structure)
+pub fn project_fields(item: &Value, fields: &[String]) -> Value {
+    let mut out = Value::Object(serde_json::Map::new());
+    for field in fields {
+        let Some(value) = get_path(item, field) else { continue };
+        if item.get(field.as_str()).is_some() || !field.contains('.') {
+            out[field.as_str()] = value.clone();
+            continue;
+        }
+        let mut target = &mut out;
+        let segments: Vec<&str> = field.split('.').collect();
+        for segment in &segments[..segments.len() - 1] {
+            if !target.get(*segment).is_some_and(Value::is_object) {
+                target[*segment] = Value::Object(serde_json::Map::new());
+            }
+            target = &mut target[*segment];
+        }
+        target[segments[segments.len() - 1]] = value.clone();
     }
+    out
 }
 
 /// Apply a filter to a JSON value
-pub fn matches_filter(item: &serde_json::Value, filter: &FilterSpec) -> bool {
-    let field_value = match item.get(&filter.field) {
-        Some(v) => v,
-        None => return false,
-    };
-
-    match filter.op {
-        FilterOp::Eq => value_equals(field_value, &filter.value),
-        FilterOp::Ne => !value_equals(field_value, &filter.value),
-        FilterOp::Gt => {
-            value_compare(field_value, &filter.value) == Some(std::cmp::Ordering::Greater)
+pub fn matches_filter(item: &Value, filter: &FilterSpec) -> bool {
+    let field_value = get_path(item, &filter.field);
+    match (&filter.op, field_value) {
+        (FilterOp::IsNull, v) => v.is_none_or(Value::is_null),
+        (FilterOp::NotNull, v) => v.is_some_and(|v| !v.is_null()),
+        (_, None) => false,
+        (FilterOp::Eq, Some(v)) => value_equals(v, &filter.value),
+        (FilterOp::Ne, Some(v)) => !value_equals(v, &filter.value),
+        (FilterOp::Gt, Some(v)) => {
+            value_compare(v, &filter.value) == Some(std::cmp::Ordering::Greater)
+        }
+        (FilterOp::Lt, Some(v)) => {
+            value_compare(v, &filter.value) == Some(std::cmp::Ordering::Less)
         }
-        FilterOp::Lt => value_compare(field_value, &filter.value) == Some(std::cmp::Ordering::Less),
-        FilterOp::Gte => {
-            value_compare(field_value, &filter.value).is_some_and(|o| o != std::cmp::Ordering::Less)
+        (FilterOp::Gte, Some(v)) => {
+            value_compare(v, &filter.value).is_some_and(|o| o != std::cmp::Ordering::Less)
         }
-        FilterOp::Lte => value_compare(field_value, &filter.value)
-            .is_some_and(|o| o != std::cmp::Ordering::Greater),
-        FilterOp::Contains => value_contains(field_value, &filter.value),
+        (FilterOp::Lte, Some(v)) => {
+            value_compare(v, &filter.value).is_some_and(|o| o != std::cmp::Ordering::Greater)
+        }
+        (FilterOp::Contains, Some(v)) => value_contains(v, &filter.value),
+        (FilterOp::In(values), Some(v)) => values.iter().any(|t| value_equals(v, t)),
+        (FilterOp::NotIn(values), Some(v)) => !values.iter().any(|t| value_equals(v, t)),
     }
 }
 
@@ -212,39 +546,81 @@ mod tests {
 
     #[test]
     fn test_parse_empty_query() {
-        let params = parse_query_params("");
+        let params = parse_query_params("").unwrap();
         assert_eq!(params.skip, 0);
         assert!(params.take.is_none());
-        assert!(params.sort.is_none());
+        assert!(params.sort.is_empty());
         assert!(params.filters.is_empty());
     }
 
     #[test]
     fn test_parse_pagination() {
-        let params = parse_query_params("skip=10&take=20");
+        let params = parse_query_params("skip=10&take=20").unwrap();
         assert_eq!(params.skip, 10);
         assert_eq!(params.take, Some(20));
     }
 
     #[test]
     fn test_parse_sort_asc() {
-        let params = parse_query_params("sort=name");
-        let sort = params.sort.unwrap();
+        let params = parse_query_params("sort=name").unwrap();
+        let sort = &params.sort[0];
         assert_eq!(sort.field, "name");
         assert!(!sort.descending);
     }
 
     #[test]
     fn test_parse_sort_desc() {
-        let params = parse_query_params("sort=-price");
-        let sort = params.sort.unwrap();
+        let params = parse_query_params("sort=-price").unwrap();
+        let sort = &params.sort[0];
         assert_eq!(sort.field, "price");
         assert!(sort.descending);
     }
 
+    #[test]
+    fn test_parse_explain_is_not_a_filter() {
+        let params = parse_query_params("explain=true&include_deleted&status=open").unwrap();
+        assert!(params.explain);
+        assert!(params.include_deleted);
+        assert_eq!(params.filters.len(), 1);
+
+        let params = parse_query_params("expand=author%2Ccomments.author&status=open").unwrap();
+        assert_eq!(params.expand.unwrap().relations.len(), 2);
+        assert_eq!(params.filters.len(), 1);
+        assert_eq!(parse_item_query_params("expand=a.b.c.d").unwrap_err().param, "expand");
+    }
+
+    #[test]
+    fn test_parse_as_of() {
+        let params = parse_query_params("as_of=42").unwrap();
+        assert_eq!(params.as_of, Some(AsOf::EventIndex(42)));
+        let params = parse_query_params("as_of=2025-03-01T12:00:00%2B01:00").unwrap();
+        assert_eq!(params.as_of, Some(AsOf::Timestamp(1_740_826_800)));
+        let params = parse_query_params("as_of=2025-03-01T12:00:00+01:00").unwrap();
+        assert_eq!(params.as_of, Some(AsOf::Timestamp(1_740_826_800)));
+        assert_eq!(
+            AsOf::Timestamp(1_740_826_800).to_json(),
+            serde_json::json!({ "timestamp": "2025-03-01T11:00:00+00:00" })
+        );
+        assert!(parse_query_params("as_of=yesterday").is_err());
+
+        let params = parse_item_query_params("as_of=7&include_deleted&verbose").unwrap();
+        assert_eq!(params.as_of, Some(AsOf::EventIndex(7)));
+        assert!(params.include_deleted);
+    }
+
+    #[test]
+    fn test_parse_cursor_params() {
+        let params = parse_query_params("limit=20&after=abc.def&sort=-price").unwrap();
+        assert!(params.uses_cursor());
+        assert_eq!(params.limit, Some(20));
+        assert_eq!(params.after.as_deref(), Some("abc.def"));
+        assert!(params.filters.is_empty());
+        assert!(!parse_query_params("skip=5").unwrap().uses_cursor());
+    }
+
     #[test]
     fn test_parse_filters() {
-        let params = parse_query_params("status=active&price=>100&name=~foo");
+        let params = parse_query_params("status=active&price=>100&name=~foo").unwrap();
         assert_eq!(params.filters.len(), 3);
         assert_eq!(params.filters[0].field, "status");
         assert_eq!(params.filters[0].op, FilterOp::Eq);
@@ -329,11 +705,73 @@ mod tests {
 
     #[test]
     fn test_parse_gte_lte() {
-        let params = parse_query_params("price=>=100&age=<=30");
+        let params = parse_query_params("price=>=100&age=<=30").unwrap();
         assert_eq!(params.filters.len(), 2);
         assert_eq!(params.filters[0].op, FilterOp::Gte);
         assert_eq!(params.filters[0].value, "100");
         assert_eq!(params.filters[1].op, FilterOp::Lte);
         assert_eq!(params.filters[1].value, "30");
     }
+
+    #[test]
+    fn test_parse_multi_sort_and_fields() {
+        let params = parse_query_params("sort=-created_at,name&fields=id,address.city").unwrap();
+        assert_eq!(params.sort.len(), 2);
+        assert!(params.sort[0].descending);
+        assert_eq!(params.sort[1].field, "name");
+        assert!(!params.sort[1].descending);
+        assert_eq!(params.fields, Some(vec!["id".to_string(), "address.city".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_in_null_and_or() {
+        let params =
+            parse_query_params("status=in(a,b)&deleted_at=null&owner=!null&or=(a=1,b=>2)").unwrap();
+        assert_eq!(params.filters[0].op, FilterOp::In(vec!["a".into(), "b".into()]));
+        assert_eq!(params.filters[1].op, FilterOp::IsNull);
+        assert_eq!(params.filters[2].op, FilterOp::NotNull);
+        assert_eq!(params.or_groups.len(), 1);
+        assert_eq!(params.or_groups[0][1].op, FilterOp::Gt);
+        assert_eq!(params.or_groups[0][1].value, "2");
+
+        let params = parse_query_params("status=%21in%28a%2Cb%29").unwrap();
+        assert_eq!(params.filters[0].op, FilterOp::NotIn(vec!["a".into(), "b".into()]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_query_params("take=ten").unwrap_err().param, "take");
+        assert_eq!(parse_query_params("status=in(a,b").unwrap_err().param, "status");
+        assert_eq!(parse_query_params("or=a=1").unwrap_err().param, "or");
+        assert_eq!(parse_query_params("or=(a)").unwrap_err().param, "or");
+        assert_eq!(parse_query_params("sort=name,").unwrap_err().param, "sort");
+        assert_eq!(parse_query_params("fields=").unwrap_err().param, "fields");
+        assert_eq!(parse_query_params("status").unwrap_err().param, "status");
+        assert_eq!(parse_query_params("a..b=1").unwrap_err().to_json()["error"], "invalid_query");
+    }
+
+    #[test]
+    fn test_matches_query_nested_or_and_null() {
+        let item = json!({"address": {"city": "Paris"}, "tags": ["x"], "deleted_at": null});
+        let params = parse_query_params(
+            "address.city=in(Paris,Lyon)&deleted_at=null&or=(tags.0=y,address.city=~par)",
+        )
+        .unwrap();
+        assert!(params.matches(&item));
+        assert!(!parse_query_params("missing=!null").unwrap().matches(&item));
+        assert!(parse_query_params("missing=null").unwrap().matches(&item));
+        assert!(!parse_query_params("or=(tags.0=y,address.city=Lyon)").unwrap().matches(&item));
+    }
+
+    #[test]
+    fn test_multi_sort_and_projection() {
+        let params = parse_query_params("sort=-a,b&fields=b,address.city").unwrap();
+        let x = json!({"a": 1, "b": "x", "address": {"city": "Paris", "zip": "75"}});
+        let y = json!({"a": 1, "b": "y"});
+        let z = json!({"a": 2, "b": "a"});
+        assert_eq!(params.compare(&x, &y), std::cmp::Ordering::Less);
+        assert_eq!(params.compare(&z, &x), std::cmp::Ordering::Less);
+        assert_eq!(params.project(x), json!({"b": "x", "address": {"city": "Paris"}}));
+        assert_eq!(params.project(y), json!({"b": "y"}));
+    }
 }
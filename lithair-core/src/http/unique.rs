@@ -0,0 +1,304 @@
+//! In-memory unique indexes for declarative models
+//!
+//! Each `#[db(unique)]` field and each struct-level `#[db(unique_together = "a, b")]`
+//! group gets its own map from the (serialized) key to the primary key that owns it.
+//! Claims are atomic, so two concurrent creates with the same e-mail cannot both
+//! succeed: the first one reserves the key before any `await`, the second one sees
+//! the conflict and is rejected with 409.
+//!
+//! `null`/missing values never conflict (SQL semantics), so optional unique fields
+//! can be left empty by many records.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A write that would duplicate an existing unique key
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniqueViolation {
+    /// Fields of the violated constraint
+    pub fields: Vec<String>,
+    /// Conflicting value(s), as JSON
+    pub value: Value,
+    /// Primary key of the record that already owns the value
+    pub existing_id: String,
+}
+
+impl UniqueViolation {
+    /// JSON body used for 409 responses
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "error": "unique_violation",
+            "message": self.to_string(),
+            "fields": self.fields,
+            "value": self.value,
+            "existing_id": self.existing_id,
+        })
+    }
+}
+
+impl std::fmt::Display for UniqueViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "duplicate value {} for unique ({})", self.value, self.fields.join(", "))
+    }
+}
+
+impl std::error::Error for UniqueViolation {}
+
+struct Constraint {
+    fields: Vec<String>,
+    owners: HashMap<String, String>,
+}
+
+impl Constraint {
+    /// Serialized key for `item`, or None if any field is null/missing
+    fn key_for(&self, item: &Value) -> Option<(String, Value)> {
+        let mut values = Vec::with_capacity(self.fields.len());
+        for field in &self.fields {
+            match item.get(field) {
+                None | Some(Value::Null) => return None,
+                Some(v) => values.push(v.clone()),
+            }
+        }
+        let value = if values.len() == 1 { values.remove(0) } else { Value::Array(values) };
+        Some((value.to_string(), value))
+    }
+}
+
+/// Set of unique constraints for one model
+pub struct UniqueIndex {
+    constraints: Mutex<Vec<Constraint>>,
+}
+
+impl UniqueIndex {
+    /// Build an empty index for the given constraints (each entry is a field group)
+    pub fn new(constraints: Vec<Vec<String>>) -> Self {
+        let constraints = constraints
+            .into_iter()
+            .filter(|fields| !fields.is_empty())
+            .map(|fields| Constraint { fields, owners: HashMap::new() })
+            .collect();
+        Self { constraints: Mutex::new(constraints) }
+    }
+
+    /// True when the model declares no unique constraint (all operations are no-ops)
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Constraint>> {
+        self.constraints.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Check that `new` can be stored under `id` without claiming anything
+    pub fn check(&self, id: &str, new: &Value) -> Result<(), UniqueViolation> {
+        let constraints = self.lock();
+        Self::check_locked(&constraints, id, new)
+    }
+
+    fn check_locked(
+        constraints: &[Constraint],
+        id: &str,
+        new: &Value,
+    ) -> Result<(), UniqueViolation> {
+        for c in constraints {
+            if let Some((key, value)) = c.key_for(new) {
+                if let Some(owner) = c.owners.get(&key) {
+                    if owner != id {
+                        return Err(UniqueViolation {
+                            fields: c.fields.clone(),
+                            value,
+                            existing_id: owner.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Atomically check and claim the keys of `new` for `id`, releasing the keys of
+    /// `old` (the previous version of the same record, if any).
+    ///
+    /// Nothing is modified when a conflict is found.
+    pub fn claim(&self, id: &str, old: Option<&Value>, new: &Value) -> Result<(), UniqueViolation> {
+        let mut constraints = self.lock();
+        Self::check_locked(&constraints, id, new)?;
+        Self::replace_locked(&mut constraints, id, old, Some(new));
+        Ok(())
+    }
+
+    /// Atomically check and claim the keys of `new` for `id` on top of those it holds,
+    /// for a write that commits later: the keys of the stored version stay claimed until
+    /// the write applies (`apply`) or is dropped (`unreserve`).
+    ///
+    /// Nothing is modified when a conflict is found.
+    pub fn reserve(&self, id: &str, new: &Value) -> Result<(), UniqueViolation> {
+        let mut constraints = self.lock();
+        Self::check_locked(&constraints, id, new)?;
+        Self::replace_locked(&mut constraints, id, None, Some(new));
+        Ok(())
+    }
+
+    /// Undo `reserve` for a write that did not apply: release the keys of `new` that
+    /// `current` (the version still stored, if any) does not hold
+    ///
+    /// Only keys `id` still owns are released; nothing is claimed, so another record's
+    /// key is never taken over.
+    pub fn unreserve(&self, id: &str, new: &Value, current: Option<&Value>) {
+        let mut constraints = self.lock();
+        for c in constraints.iter_mut() {
+            let Some((key, _)) = c.key_for(new) else { continue };
+            let held = current.and_then(|current| c.key_for(current)).map(|(k, _)| k);
+            if held.as_ref() != Some(&key) && c.owners.get(&key).is_some_and(|o| o == id) {
+                c.owners.remove(&key);
+            }
+        }
+    }
+
+    /// Record `new` for `id` without checking (replay, followers, reconciliation).
+    ///
+    /// The leader already decided the write is valid; if a key is owned by another
+    /// record the new owner wins.
+    pub fn apply(&self, id: &str, old: Option<&Value>, new: &Value) {
+        let mut constraints = self.lock();
+        Self::replace_locked(&mut constraints, id, old, Some(new));
+    }
+
+    /// Release the keys held by `item` for `id`
+    pub fn release(&self, id: &str, item: &Value) {
+        let mut constraints = self.lock();
+        Self::replace_locked(&mut constraints, id, Some(item), None);
+    }
+
+    /// Drop every key (used before a full rebuild)
+    pub fn clear(&self) {
+        for c in self.lock().iter_mut() {
+            c.owners.clear();
+        }
+    }
+
+    fn replace_locked(
+        constraints: &mut [Constraint],
+        id: &str,
+        old: Option<&Value>,
+        new: Option<&Value>,
+    ) {
+        for c in constraints.iter_mut() {
+            if let Some((old_key, _)) = old.and_then(|o| c.key_for(o)) {
+                if c.owners.get(&old_key).map(|owner| owner == id).unwrap_or(false) {
+                    c.owners.remove(&old_key);
+                }
+            }
+            if let Some((new_key, _)) = new.and_then(|n| c.key_for(n)) {
+                c.owners.insert(new_key, id.to_string());
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for UniqueIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let constraints = self.lock();
+        let summary: Vec<(String, usize)> =
+            constraints.iter().map(|c| (c.fields.join(","), c.owners.len())).collect();
+        f.debug_struct("UniqueIndex").field("constraints", &summary).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn index() -> UniqueIndex {
+        UniqueIndex::new(vec![
+            vec!["email".to_string()],
+            vec!["tenant".to_string(), "slug".to_string()],
+        ])
+    }
+
+    #[test]
+    fn test_claim_rejects_duplicates() {
+        let idx = index();
+        idx.claim("1", None, &json!({"email": "a@x.io", "tenant": "t", "slug": "s"}))
+            .unwrap();
+        let err = idx.claim("2", None, &json!({"email": "a@x.io"})).unwrap_err();
+        assert_eq!(err.fields, vec!["email"]);
+        assert_eq!(err.existing_id, "1");
+
+        let err = idx.claim("3", None, &json!({"tenant": "t", "slug": "s"})).unwrap_err();
+        assert_eq!(err.fields, vec!["tenant", "slug"]);
+        assert!(idx.claim("4", None, &json!({"tenant": "t", "slug": "other"})).is_ok());
+    }
+
+    #[test]
+    fn test_update_releases_old_key() {
+        let idx = index();
+        let v1 = json!({"email": "old@x.io"});
+        let v2 = json!({"email": "new@x.io"});
+        idx.claim("1", None, &v1).unwrap();
+        // Same record keeps its own key
+        idx.claim("1", Some(&v1), &v1).unwrap();
+        idx.claim("1", Some(&v1), &v2).unwrap();
+        assert!(idx.claim("2", None, &v1).is_ok());
+        assert!(idx.check("3", &v2).is_err());
+    }
+
+    #[test]
+    fn test_reserved_update_keeps_old_key_until_applied() {
+        let idx = index();
+        let v1 = json!({"email": "old@x.io"});
+        let v2 = json!({"email": "new@x.io"});
+        idx.claim("1", None, &v1).unwrap();
+
+        // Both keys stay with the record while the update is pending
+        idx.reserve("1", &v2).unwrap();
+        assert!(idx.check("2", &v1).is_err());
+        assert!(idx.check("2", &v2).is_err());
+
+        // Dropped: the new key is free again, the stored one still held
+        idx.unreserve("1", &v2, Some(&v1));
+        assert!(idx.check("2", &v2).is_ok());
+        assert!(idx.check("2", &v1).is_err());
+
+        // Applied: the old key is released then
+        idx.reserve("1", &v2).unwrap();
+        idx.apply("1", Some(&v1), &v2);
+        assert!(idx.check("2", &v1).is_ok());
+        assert!(idx.check("2", &v2).is_err());
+
+        // Dropping never takes over a key another record claimed since
+        idx.release("1", &v2);
+        idx.claim("2", None, &v2).unwrap();
+        idx.unreserve("1", &v2, None);
+        assert_eq!(idx.check("3", &v2).unwrap_err().existing_id, "2");
+    }
+
+    #[test]
+    fn test_nulls_never_conflict_and_release() {
+        let idx = index();
+        idx.claim("1", None, &json!({"email": null})).unwrap();
+        idx.claim("2", None, &json!({})).unwrap();
+
+        let item = json!({"email": "a@x.io"});
+        idx.claim("3", None, &item).unwrap();
+        idx.release("3", &item);
+        assert!(idx.claim("4", None, &item).is_ok());
+    }
+
+    #[test]
+    fn test_concurrent_claims_single_winner() {
+        let idx = std::sync::Arc::new(index());
+        let handles: Vec<_> = (0..16)
+            .map(|i| {
+                let idx = idx.clone();
+                std::thread::spawn(move || {
+                    idx.claim(&i.to_string(), None, &json!({"email": "race@x.io"})).is_ok()
+                })
+            })
+            .collect();
+        let winners = handles.into_iter().map(|h| h.join().unwrap());
+        assert_eq!(winners.filter(|ok| *ok).count(), 1);
+    }
+}
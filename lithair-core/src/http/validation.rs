@@ -0,0 +1,348 @@
+//! Field-level validation for declarative models
+//!
+//! The `DeclarativeModel` derive parses `#[http(validate = "...")]` rules at compile
+//! time and generates calls into this module for every annotated field. Values are
+//! checked in their serialized JSON form so the same rules work for any field type
+//! that serializes to a string, number or array.
+//!
+//! Supported rules:
+//!
+//! | Rule                         | Applies to        |
+//! |------------------------------|-------------------|
+//! | `non_empty`                  | strings, arrays   |
+//! | `length(min, max)`           | strings, arrays   |
+//! | `min_length(n)` / `max_length(n)` | strings, arrays |
+//! | `range(min, max)`            | numbers           |
+//! | `min_value(x)` / `max_value(x)` | numbers        |
+//! | `regex(pattern)`             | strings           |
+//! | `email` / `url` / `uuid`     | strings           |
+//! | `one_of(a, b, c)`            | strings, numbers  |
+//! | `custom(path::to::fn)`       | any (typed hook)  |
+//!
+//! `null` values (e.g. `Option::None`) are only rejected by `non_empty`; every other
+//! rule treats them as absent and passes.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// A single validation rule applied to a serialized field value
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationRule {
+    /// String or array must not be empty (and must not be null)
+    NonEmpty,
+    /// String length (in chars) or array length bounds, inclusive
+    Length { min: Option<usize>, max: Option<usize> },
+    /// Numeric bounds, inclusive
+    Range { min: Option<f64>, max: Option<f64> },
+    /// String must match the regular expression
+    Regex(String),
+    /// String must look like an e-mail address
+    Email,
+    /// String must be an absolute http(s) URL
+    Url,
+    /// String must be a hyphenated UUID
+    Uuid,
+    /// Value must be one of the listed literals
+    OneOf(Vec<String>),
+}
+
+impl ValidationRule {
+    /// Short rule identifier reported in error payloads
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValidationRule::NonEmpty => "non_empty",
+            ValidationRule::Length { .. } => "length",
+            ValidationRule::Range { .. } => "range",
+            ValidationRule::Regex(_) => "regex",
+            ValidationRule::Email => "email",
+            ValidationRule::Url => "url",
+            ValidationRule::Uuid => "uuid",
+            ValidationRule::OneOf(_) => "one_of",
+        }
+    }
+
+    /// Check a serialized value against this rule
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        if value.is_null() {
+            return match self {
+                ValidationRule::NonEmpty => Err("must not be empty".to_string()),
+                _ => Ok(()),
+            };
+        }
+
+        match self {
+            ValidationRule::NonEmpty => match value {
+                Value::String(s) if s.trim().is_empty() => Err("must not be empty".to_string()),
+                Value::Array(a) if a.is_empty() => Err("must not be empty".to_string()),
+                Value::Object(o) if o.is_empty() => Err("must not be empty".to_string()),
+                _ => Ok(()),
+            },
+            ValidationRule::Length { min, max } => {
+                let len = match value {
+                    Value::String(s) => s.chars().count(),
+                    Value::Array(a) => a.len(),
+                    _ => return Err("must be a string or an array".to_string()),
+                };
+                if let Some(min) = min {
+                    if len < *min {
+                        return Err(format!("length must be at least {}", min));
+                    }
+                }
+                if let Some(max) = max {
+                    if len > *max {
+                        return Err(format!("length must be at most {}", max));
+                    }
+                }
+                Ok(())
+            }
+            ValidationRule::Range { min, max } => {
+                let n = value.as_f64().ok_or_else(|| "must be a number".to_string())?;
+                if let Some(min) = min {
+                    if n < *min {
+                        return Err(format!("must be greater than or equal to {}", min));
+                    }
+                }
+                if let Some(max) = max {
+                    if n > *max {
+                        return Err(format!("must be less than or equal to {}", max));
+                    }
+                }
+                Ok(())
+            }
+            ValidationRule::Regex(pattern) => {
+                let s = value.as_str().ok_or_else(|| "must be a string".to_string())?;
+                if regex_matches(pattern, s)? {
+                    Ok(())
+                } else {
+                    Err(format!("must match pattern {}", pattern))
+                }
+            }
+            ValidationRule::Email => {
+                let s = value.as_str().ok_or_else(|| "must be a string".to_string())?;
+                if is_valid_email(s) {
+                    Ok(())
+                } else {
+                    Err("must be a valid email address".to_string())
+                }
+            }
+            ValidationRule::Url => {
+                let s = value.as_str().ok_or_else(|| "must be a string".to_string())?;
+                if is_valid_url(s) {
+                    Ok(())
+                } else {
+                    Err("must be a valid http(s) URL".to_string())
+                }
+            }
+            ValidationRule::Uuid => {
+                let s = value.as_str().ok_or_else(|| "must be a string".to_string())?;
+                if uuid::Uuid::parse_str(s).is_ok() && s.len() == 36 {
+                    Ok(())
+                } else {
+                    Err("must be a valid UUID".to_string())
+                }
+            }
+            ValidationRule::OneOf(allowed) => {
+                let repr = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => return Err("must be a scalar value".to_string()),
+                };
+                if allowed.iter().any(|a| a == &repr) {
+                    Ok(())
+                } else {
+                    Err(format!("must be one of: {}", allowed.join(", ")))
+                }
+            }
+        }
+    }
+}
+
+/// A validation failure on a single field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub rule: String,
+    pub message: String,
+}
+
+/// Accumulated validation failures for one model instance
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ValidationErrors {
+    pub fields: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a failure for `field`
+    pub fn add(&mut self, field: &str, rule: &str, message: impl Into<String>) {
+        self.fields.push(FieldError {
+            field: field.to_string(),
+            rule: rule.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Check `value` against `rule` and record the failure, if any
+    pub fn check(&mut self, field: &str, rule: &ValidationRule, value: &Value) {
+        if let Err(message) = rule.check(value) {
+            self.add(field, rule.name(), message);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// `Ok(())` when no failures were recorded
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// JSON body used for 422 responses:
+    /// `{"error":"validation_failed","message":"...","fields":[{field, rule, message}]}`
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "error": "validation_failed",
+            "message": self.to_string(),
+            "fields": self.fields,
+        })
+    }
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> =
+            self.fields.iter().map(|e| format!("{}: {}", e.field, e.message)).collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl From<String> for ValidationErrors {
+    /// Wrap a free-form model-level error (e.g. from a hand-written `validate()`)
+    fn from(message: String) -> Self {
+        let mut errors = ValidationErrors::new();
+        errors.add("_model", "custom", message);
+        errors
+    }
+}
+
+/// Compiled regex cache shared by all models (patterns are static per field)
+fn regex_matches(pattern: &str, input: &str) -> Result<bool, String> {
+    static CACHE: OnceLock<Mutex<HashMap<String, regex::Regex>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = cache.lock().map_err(|_| "regex cache poisoned".to_string())?;
+    if let Some(re) = cache.get(pattern) {
+        return Ok(re.is_match(input));
+    }
+    let re = regex::Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?;
+    let matched = re.is_match(input);
+    cache.insert(pattern.to_string(), re);
+    Ok(matched)
+}
+
+fn is_valid_email(s: &str) -> bool {
+    let Some((local, domain)) = s.rsplit_once('@') else {
+        return false;
+    };
+    if local.is_empty() || local.len() > 64 || domain.len() > 255 {
+        return false;
+    }
+    if s.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|l| {
+            !l.is_empty()
+                && !l.starts_with('-')
+                && !l.ends_with('-')
+                && l.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+fn is_valid_url(s: &str) -> bool {
+    let rest = match s.strip_prefix("https://").or_else(|| s.strip_prefix("http://")) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once('@').map(|(_, h)| h).unwrap_or(host);
+    !host.is_empty() && !s.chars().any(|c| c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_non_empty() {
+        assert!(ValidationRule::NonEmpty.check(&json!("x")).is_ok());
+        assert!(ValidationRule::NonEmpty.check(&json!("  ")).is_err());
+        assert!(ValidationRule::NonEmpty.check(&json!([])).is_err());
+        assert!(ValidationRule::NonEmpty.check(&Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_length_and_range() {
+        let len = ValidationRule::Length { min: Some(2), max: Some(4) };
+        assert!(len.check(&json!("ab")).is_ok());
+        assert!(len.check(&json!("a")).is_err());
+        assert!(len.check(&json!("abcde")).is_err());
+
+        let range = ValidationRule::Range { min: Some(0.01), max: None };
+        assert!(range.check(&json!(1.5)).is_ok());
+        assert!(range.check(&json!(0)).is_err());
+        assert!(range.check(&json!("1")).is_err());
+        assert!(range.check(&Value::Null).is_ok());
+    }
+
+    #[test]
+    fn test_formats() {
+        assert!(ValidationRule::Email.check(&json!("a@example.com")).is_ok());
+        assert!(ValidationRule::Email.check(&json!("not-an-email")).is_err());
+        assert!(ValidationRule::Email.check(&json!("a@localhost")).is_err());
+        assert!(ValidationRule::Url.check(&json!("https://lithair.dev/docs")).is_ok());
+        assert!(ValidationRule::Url.check(&json!("ftp://lithair.dev")).is_err());
+        assert!(ValidationRule::Uuid
+            .check(&json!("67e55044-10b1-426f-9247-bb680e5fe0c8"))
+            .is_ok());
+        assert!(ValidationRule::Uuid.check(&json!("67e5504410b1426f9247bb680e5fe0c8")).is_err());
+    }
+
+    #[test]
+    fn test_regex_and_one_of() {
+        let re = ValidationRule::Regex("^[a-z]+-[0-9]+$".to_string());
+        assert!(re.check(&json!("sku-42")).is_ok());
+        assert!(re.check(&json!("SKU-42")).is_err());
+
+        let one_of = ValidationRule::OneOf(vec!["draft".into(), "published".into()]);
+        assert!(one_of.check(&json!("draft")).is_ok());
+        assert!(one_of.check(&json!("archived")).is_err());
+    }
+
+    #[test]
+    fn test_errors_json_shape() {
+        let mut errors = ValidationErrors::new();
+        errors.check("name", &ValidationRule::NonEmpty, &json!(""));
+        errors.check("price", &ValidationRule::Range { min: Some(0.0), max: None }, &json!(-1));
+        let body = errors.to_json();
+        assert_eq!(body["error"], "validation_failed");
+        assert_eq!(body["fields"][0]["field"], "name");
+        assert_eq!(body["fields"][0]["rule"], "non_empty");
+        assert_eq!(body["fields"][1]["rule"], "range");
+        assert!(errors.into_result().is_err());
+    }
+}
@@ -84,6 +84,7 @@ pub mod frontend; // Memory-first static file serving
 pub mod http;
 pub mod lifecycle;
 pub mod logging; // Declarative logging system with standard log crate integration
+pub mod metrics; // Prometheus metrics registry and text exposition
 pub mod mfa; // Multi-Factor Authentication (TOTP)
 pub mod model; // Declarative model specifications
 pub mod model_inspect; // Internal field inspection and optimization
@@ -1,6 +1,10 @@
 // Lithair Lifecycle Management - Core Engine Integration
 // Migrated from product_app patterns to provide declarative data lifecycle as a framework feature
 
+pub mod retention;
+
+pub use retention::{RetentionPolicy, RetentionReport};
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
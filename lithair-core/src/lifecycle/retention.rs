@@ -0,0 +1,340 @@
+//! Retention of declarative model data
+//!
+//! Two `#[lifecycle]` attributes bound what the event log keeps:
+//!
+//! - `retention = N` expires whole records N days after their last write. When several
+//!   fields declare one, the shortest applies to the record.
+//! - `versioned = K` keeps the K most recent values of a field. Older events of the
+//!   record that still carry a dropped value are removed; the latest event always stays,
+//!   so the current state is untouched. When the oldest event kept is a `Patched` diff,
+//!   its payload becomes the record's full state at that point (`RecordPurge::rebase`).
+//!
+//! `plan_purge` works out which events go. The caller rewrites the log without them and
+//! appends one `Purged` audit event per affected record (`RecordPurge::audit_payload`).
+
+use super::LifecycleAware;
+use crate::engine::events::EventEnvelope;
+use crate::http::patch::merge_patch;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+pub const SECS_PER_DAY: u64 = 86_400;
+
+/// Retention rules of one model, gathered from its field policies
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Days after its last write a record expires (None = kept forever)
+    pub record_days: Option<u32>,
+    /// Versions kept per field; fields without a limit are absent
+    pub version_limits: BTreeMap<String, u32>,
+}
+
+impl RetentionPolicy {
+    /// Policy declared by the fields of `model`
+    ///
+    /// A `retention_limit` of 0 or `u32::MAX` (the `audited` preset) means unlimited.
+    pub fn of<T: LifecycleAware>(model: &T) -> Self {
+        let mut policy = Self::default();
+        for field in model.all_field_names() {
+            let Some(field_policy) = model.lifecycle_policy_for_field(field) else { continue };
+            let days = field_policy.retention_limit;
+            if days > 0 && days < u32::MAX {
+                policy.record_days = Some(policy.record_days.map_or(days, |d| d.min(days)));
+            }
+            if field_policy.version_limit > 0 {
+                policy.version_limits.insert(field.to_string(), field_policy.version_limit);
+            }
+        }
+        policy
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.record_days.is_none() && self.version_limits.is_empty()
+    }
+}
+
+/// Why events of a record are purged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PurgeReason {
+    /// The record outlived its retention: it goes with all its events
+    Expired,
+    /// Older versions of these fields exceed their `versioned` limit
+    VersionLimit { fields: Vec<String> },
+}
+
+/// Events of one record removed by a purge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordPurge {
+    pub id: String,
+    pub reason: PurgeReason,
+    /// Positions of the removed events in the log
+    pub events: Vec<usize>,
+    /// Kept `Patched` event whose base state is removed, with the full state its
+    /// payload must carry instead
+    pub rebase: Option<(usize, Value)>,
+}
+
+impl RecordPurge {
+    /// Payload of the `Purged` audit event; it names the fields, never their values
+    pub fn audit_payload(&self) -> Value {
+        match &self.reason {
+            PurgeReason::Expired => json!({
+                "id": self.id,
+                "reason": "expired",
+                "events_removed": self.events.len()
+            }),
+            PurgeReason::VersionLimit { fields } => json!({
+                "id": self.id,
+                "reason": "version_limit",
+                "fields": fields,
+                "events_removed": self.events.len()
+            }),
+        }
+    }
+
+    /// Id of the record a `Purged` audit payload expired, None for history trims
+    pub fn expired_id(payload: &str) -> Option<String> {
+        let payload: Value = serde_json::from_str(payload).ok()?;
+        if payload.get("reason")?.as_str()? != "expired" {
+            return None;
+        }
+        payload.get("id")?.as_str().map(str::to_string)
+    }
+}
+
+/// Outcome of a retention run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RetentionReport {
+    pub expired_records: usize,
+    pub trimmed_records: usize,
+    pub events_removed: usize,
+}
+
+impl RetentionReport {
+    pub fn is_empty(&self) -> bool {
+        self.expired_records == 0 && self.trimmed_records == 0
+    }
+}
+
+/// Newest event timestamp of each record in `events`, audit events aside
+pub fn last_writes(events: &[EventEnvelope]) -> HashMap<String, u64> {
+    let mut writes: HashMap<String, u64> = HashMap::new();
+    for event in events.iter().filter(|e| !e.event_type.ends_with("Purged")) {
+        if let Some(id) = &event.aggregate_id {
+            let at = writes.entry(id.clone()).or_default();
+            *at = (*at).max(event.timestamp);
+        }
+    }
+    writes
+}
+
+/// Work out the purges `policy` calls for at `now` (unix seconds)
+///
+/// Events are grouped by `aggregate_id`. `last_written` supplies write times the log no
+/// longer holds (records restored from a compacted snapshot); a record expires once the
+/// later of that time and its newest event is older than the retention. Audit events
+/// (`...Purged`) are never removed.
+pub fn plan_purge(
+    policy: &RetentionPolicy,
+    events: &[EventEnvelope],
+    last_written: &HashMap<String, u64>,
+    now: u64,
+) -> Vec<RecordPurge> {
+    let mut records: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, event) in events.iter().enumerate() {
+        if event.event_type.ends_with("Purged") {
+            continue;
+        }
+        if let Some(id) = event.aggregate_id.as_deref() {
+            records.entry(id).or_default().push(index);
+        }
+    }
+    for id in last_written.keys() {
+        records.entry(id).or_default();
+    }
+
+    let cutoff = policy.record_days.map(|days| now.saturating_sub(days as u64 * SECS_PER_DAY));
+    let mut purges = Vec::new();
+    for (id, indices) in records {
+        let last_write = indices
+            .iter()
+            .map(|&i| events[i].timestamp)
+            .chain(last_written.get(id).copied())
+            .max()
+            .unwrap_or(0);
+        if cutoff.is_some_and(|cutoff| last_write < cutoff) {
+            purges.push(RecordPurge {
+                id: id.to_string(),
+                reason: PurgeReason::Expired,
+                events: indices,
+                rebase: None,
+            });
+            continue;
+        }
+        if let Some(purge) = trim_versions(policy, id, events, &indices) {
+            purges.push(purge);
+        }
+    }
+    purges
+}
+
+/// Events of a record carrying field values older than the `versioned` limits
+fn trim_versions(
+    policy: &RetentionPolicy,
+    id: &str,
+    events: &[EventEnvelope],
+    indices: &[usize],
+) -> Option<RecordPurge> {
+    if policy.version_limits.is_empty() {
+        return None;
+    }
+    // Deletes and tombstones are state transitions, not versions. Each put is paired
+    // with the record state after it: `Patched` events only carry a diff.
+    let mut state = Value::Null;
+    let puts: Vec<(usize, Value)> = indices
+        .iter()
+        .filter(|&&i| {
+            let event_type = &events[i].event_type;
+            !event_type.ends_with("Deleted") && !event_type.ends_with("Tombstoned")
+        })
+        .filter_map(|&i| {
+            let payload: Value = serde_json::from_str(&events[i].payload).ok()?;
+            if events[i].event_type.ends_with("Patched") {
+                merge_patch(&mut state, &payload);
+            } else {
+                state = payload;
+            }
+            Some((i, state.clone()))
+        })
+        .collect();
+
+    let mut removed = BTreeSet::new();
+    let mut fields = Vec::new();
+    for (field, &limit) in &policy.version_limits {
+        // Version number of each put: it changes whenever the field's value does
+        let mut versions = Vec::with_capacity(puts.len());
+        let mut previous: Option<&Value> = None;
+        let mut version = 0;
+        for (_, payload) in &puts {
+            let value = payload.get(field).unwrap_or(&Value::Null);
+            if previous != Some(value) {
+                version += 1;
+            }
+            previous = Some(value);
+            versions.push(version);
+        }
+        let dropped = version.saturating_sub(limit as usize);
+        if dropped == 0 {
+            continue;
+        }
+        fields.push(field.clone());
+        removed.extend(
+            puts.iter().zip(&versions).filter(|(_, &v)| v <= dropped).map(|((i, _), _)| *i),
+        );
+    }
+
+    if removed.is_empty() {
+        return None;
+    }
+    let rebase = puts
+        .iter()
+        .find(|(i, _)| !removed.contains(i))
+        .filter(|(i, _)| events[*i].event_type.ends_with("Patched"))
+        .cloned();
+    Some(RecordPurge {
+        id: id.to_string(),
+        reason: PurgeReason::VersionLimit { fields },
+        events: removed.into_iter().collect(),
+        rebase,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = SECS_PER_DAY;
+
+    fn event(op: &str, id: &str, timestamp: u64, payload: Value) -> EventEnvelope {
+        EventEnvelope {
+            event_type: format!("app::Customer{}", op),
+            event_id: format!("{}:{}:{}", op, id, timestamp),
+            timestamp,
+            payload: payload.to_string(),
+            aggregate_id: Some(id.to_string()),
+            event_hash: None,
+            previous_hash: None,
+            schema_version: None,
+            aggregate_version: None,
+        }
+    }
+
+    #[test]
+    fn test_expired_records() {
+        let policy = RetentionPolicy { record_days: Some(30), ..Default::default() };
+        let now = 100 * DAY;
+        let events = vec![
+            event("Created", "old", 10 * DAY, json!({"id": "old"})),
+            event("Created", "fresh", 10 * DAY, json!({"id": "fresh"})),
+            event("Updated", "fresh", 90 * DAY, json!({"id": "fresh"})),
+            event("Updated", "old", 20 * DAY, json!({"id": "old"})),
+        ];
+        // Restored from a snapshot, written 60 days ago
+        let last_written = HashMap::from([("snap".to_string(), 40 * DAY)]);
+
+        let purges = plan_purge(&policy, &events, &last_written, now);
+        assert_eq!(purges.len(), 2);
+        assert_eq!(purges[0].id, "old");
+        assert_eq!(purges[0].events, vec![0, 3]);
+        assert_eq!(purges[1].id, "snap");
+        assert!(purges[1].events.is_empty());
+        assert!(purges.iter().all(|p| p.reason == PurgeReason::Expired));
+
+        let payload = purges[0].audit_payload().to_string();
+        assert_eq!(RecordPurge::expired_id(&payload).as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn test_version_limit_keeps_latest_versions() {
+        let mut policy = RetentionPolicy::default();
+        policy.version_limits.insert("email".to_string(), 2);
+        let events = vec![
+            event("Created", "c1", 1, json!({"id": "c1", "email": "a@x", "name": "A"})),
+            event("Updated", "c1", 2, json!({"id": "c1", "email": "a@x", "name": "B"})),
+            event("Updated", "c1", 3, json!({"id": "c1", "email": "b@x", "name": "B"})),
+            event("Tombstoned", "c1", 4, json!({"id": "c1", "email": "b@x", "name": "B"})),
+            event("Restored", "c1", 5, json!({"id": "c1", "email": "b@x", "name": "B"})),
+            event("Updated", "c1", 6, json!({"id": "c1", "email": "c@x", "name": "B"})),
+        ];
+
+        let purges = plan_purge(&policy, &events, &HashMap::new(), 10);
+        assert_eq!(purges.len(), 1);
+        assert_eq!(purges[0].reason, PurgeReason::VersionLimit { fields: vec!["email".into()] });
+        assert_eq!(purges[0].events, vec![0, 1]);
+        assert_eq!(RecordPurge::expired_id(&purges[0].audit_payload().to_string()), None);
+
+        assert_eq!(purges[0].rebase, None);
+
+        // Within the limit nothing goes
+        policy.version_limits.insert("email".to_string(), 3);
+        assert!(plan_purge(&policy, &events, &HashMap::new(), 10).is_empty());
+    }
+
+    #[test]
+    fn test_version_limit_rebases_patches() {
+        let mut policy = RetentionPolicy::default();
+        policy.version_limits.insert("email".to_string(), 1);
+        let events = vec![
+            event("Created", "c1", 1, json!({"id": "c1", "email": "a@x", "name": "A"})),
+            event("Patched", "c1", 2, json!({"email": "b@x"})),
+            event("Patched", "c1", 3, json!({"name": "B"})),
+        ];
+
+        let purges = plan_purge(&policy, &events, &HashMap::new(), 10);
+        assert_eq!(purges[0].events, vec![0]);
+        // The kept diff now carries the whole record
+        let full = json!({"id": "c1", "email": "b@x", "name": "A"});
+        assert_eq!(purges[0].rebase, Some((1, full)));
+    }
+}
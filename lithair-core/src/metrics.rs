@@ -0,0 +1,361 @@
+//! Prometheus metrics
+//!
+//! A process-wide registry collects what is cheap to count where it happens: HTTP
+//! requests per route, event-store flush and fsync latencies, and rejected
+//! connections. `LithairServer` adds gauges it can read on demand (model sizes,
+//! sessions, anti-DDoS state, replication lag) and serves everything in the Prometheus
+//! text exposition format on `/metrics`.
+//!
+//! ```rust,ignore
+//! let mut out = PrometheusText::new();
+//! lithair_core::metrics::global().render(&mut out);
+//! out.header("my_queue_depth", "Jobs waiting", "gauge");
+//! out.sample("my_queue_depth", &[("queue", "emails")], 12);
+//! let body = out.finish();
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write as _};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Bucket bounds (seconds) for HTTP request latency
+pub const HTTP_LATENCY_BUCKETS: &[f64] =
+    &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Bucket bounds (seconds) for event-store flush and fsync latency
+pub const STORAGE_LATENCY_BUCKETS: &[f64] =
+    &[0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+/// Cumulative histogram with fixed bucket bounds
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self { bounds, counts: vec![0; bounds.len()], sum: 0.0, count: 0 }
+    }
+
+    pub fn observe(&mut self, seconds: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Write the `_bucket`, `_sum` and `_count` samples
+    pub fn write(&self, out: &mut PrometheusText, name: &str, labels: &[(&str, &str)]) {
+        let bucket = format!("{name}_bucket");
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let le = bound.to_string();
+            out.sample(&bucket, &with_label(labels, ("le", &le)), count);
+        }
+        out.sample(&bucket, &with_label(labels, ("le", "+Inf")), self.count);
+        out.sample(&format!("{name}_sum"), labels, self.sum);
+        out.sample(&format!("{name}_count"), labels, self.count);
+    }
+}
+
+fn with_label<'a>(
+    labels: &[(&'a str, &'a str)],
+    extra: (&'a str, &'a str),
+) -> Vec<(&'a str, &'a str)> {
+    let mut all = labels.to_vec();
+    all.push(extra);
+    all
+}
+
+/// Builder for a text exposition document
+#[derive(Debug, Default)]
+pub struct PrometheusText {
+    buf: String,
+}
+
+impl PrometheusText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `# HELP` and `# TYPE` lines; `kind` is `counter`, `gauge` or `histogram`
+    pub fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.buf, "# HELP {name} {help}");
+        let _ = writeln!(self.buf, "# TYPE {name} {kind}");
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.buf.push_str(name);
+        if !labels.is_empty() {
+            self.buf.push('{');
+            for (i, (key, value)) in labels.iter().enumerate() {
+                if i > 0 {
+                    self.buf.push(',');
+                }
+                let _ = write!(self.buf, "{key}=\"{}\"", escape_label(value));
+            }
+            self.buf.push('}');
+        }
+        let _ = writeln!(self.buf, " {value}");
+    }
+
+    pub fn finish(self) -> String {
+        self.buf
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Why a connection or request was turned away before reaching a handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Denied by the IP/method/path firewall
+    Firewall,
+    /// Connection refused by anti-DDoS connection limits
+    DdosConnection,
+    /// Request refused by anti-DDoS rate limiting
+    DdosRate,
+    /// Body larger than `max_body_size`
+    BodyTooLarge,
+}
+
+impl Rejection {
+    fn label(self) -> &'static str {
+        match self {
+            Rejection::Firewall => "firewall",
+            Rejection::DdosConnection => "ddos_connection",
+            Rejection::DdosRate => "ddos_rate",
+            Rejection::BodyTooLarge => "body_too_large",
+        }
+    }
+}
+
+/// Session counts of a `PersistentSessionStore`, served as the `lithair_sessions_*` metrics
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistentSessionMetrics {
+    /// Number of sessions currently stored (expired ones until cleanup)
+    pub active_sessions: usize,
+
+    /// Sessions created since the store was opened
+    pub total_created: u64,
+
+    /// Sessions deleted (logout, expiry cleanup, revocation) since the store was opened
+    pub total_deleted: u64,
+}
+
+#[derive(Debug)]
+struct RouteStats {
+    statuses: BTreeMap<u16, u64>,
+    latency: Histogram,
+}
+
+/// Process-wide metrics registry
+#[derive(Debug)]
+pub struct Metrics {
+    // (method, route pattern) -> stats
+    routes: Mutex<BTreeMap<(String, String), RouteStats>>,
+    event_store_flush: Mutex<Histogram>,
+    event_store_fsync: Mutex<Histogram>,
+    rejections: [AtomicU64; 4],
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// The global registry
+pub fn global() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            routes: Mutex::new(BTreeMap::new()),
+            event_store_flush: Mutex::new(Histogram::new(STORAGE_LATENCY_BUCKETS)),
+            event_store_fsync: Mutex::new(Histogram::new(STORAGE_LATENCY_BUCKETS)),
+            rejections: Default::default(),
+        }
+    }
+
+    /// Count a handled request; `route` must be a pattern, not the raw path, to keep
+    /// label cardinality bounded
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut routes = self.routes.lock().expect("metrics lock poisoned");
+        let stats =
+            routes
+                .entry((method.to_string(), route.to_string()))
+                .or_insert_with(|| RouteStats {
+                    statuses: BTreeMap::new(),
+                    latency: Histogram::new(HTTP_LATENCY_BUCKETS),
+                });
+        *stats.statuses.entry(status).or_default() += 1;
+        stats.latency.observe(elapsed.as_secs_f64());
+    }
+
+    /// Time spent flushing an event store to the OS
+    pub fn record_flush(&self, elapsed: Duration) {
+        let mut histogram = self.event_store_flush.lock().expect("metrics lock poisoned");
+        histogram.observe(elapsed.as_secs_f64());
+    }
+
+    /// Time spent in `fsync` for event-store files
+    pub fn record_fsync(&self, elapsed: Duration) {
+        let mut histogram = self.event_store_fsync.lock().expect("metrics lock poisoned");
+        histogram.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_rejection(&self, rejection: Rejection) {
+        self.rejections[rejection as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rejections(&self, rejection: Rejection) -> u64 {
+        self.rejections[rejection as usize].load(Ordering::Relaxed)
+    }
+
+    /// Append every registry metric to `out`
+    pub fn render(&self, out: &mut PrometheusText) {
+        {
+            let routes = self.routes.lock().expect("metrics lock poisoned");
+            out.header(
+                "lithair_http_requests_total",
+                "HTTP requests by route and status",
+                "counter",
+            );
+            for ((method, route), stats) in routes.iter() {
+                for (status, count) in &stats.statuses {
+                    let status = status.to_string();
+                    let labels = [
+                        ("method", method.as_str()),
+                        ("route", route.as_str()),
+                        ("status", status.as_str()),
+                    ];
+                    out.sample("lithair_http_requests_total", &labels, count);
+                }
+            }
+            out.header(
+                "lithair_http_request_duration_seconds",
+                "HTTP request latency by route",
+                "histogram",
+            );
+            for ((method, route), stats) in routes.iter() {
+                let labels = [("method", method.as_str()), ("route", route.as_str())];
+                stats.latency.write(out, "lithair_http_request_duration_seconds", &labels);
+            }
+        }
+
+        out.header(
+            "lithair_event_store_flush_seconds",
+            "Time to flush buffered events to the OS",
+            "histogram",
+        );
+        self.event_store_flush.lock().expect("metrics lock poisoned").write(
+            out,
+            "lithair_event_store_flush_seconds",
+            &[],
+        );
+        out.header("lithair_event_store_fsync_seconds", "Time spent in fsync", "histogram");
+        self.event_store_fsync.lock().expect("metrics lock poisoned").write(
+            out,
+            "lithair_event_store_fsync_seconds",
+            &[],
+        );
+
+        out.header(
+            "lithair_rejected_total",
+            "Connections and requests rejected before reaching a handler",
+            "counter",
+        );
+        for rejection in [
+            Rejection::Firewall,
+            Rejection::DdosConnection,
+            Rejection::DdosRate,
+            Rejection::BodyTooLarge,
+        ] {
+            out.sample(
+                "lithair_rejected_total",
+                &[("reason", rejection.label())],
+                self.rejections(rejection),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[0.25, 1.0]);
+        histogram.observe(0.25);
+        histogram.observe(0.5);
+        histogram.observe(2.0);
+
+        let mut out = PrometheusText::new();
+        histogram.write(&mut out, "latency", &[("route", "/api/items")]);
+        let text = out.finish();
+
+        assert!(text.contains("latency_bucket{route=\"/api/items\",le=\"0.25\"} 1\n"));
+        assert!(text.contains("latency_bucket{route=\"/api/items\",le=\"1\"} 2\n"));
+        assert!(text.contains("latency_bucket{route=\"/api/items\",le=\"+Inf\"} 3\n"));
+        assert!(text.contains("latency_sum{route=\"/api/items\"} 2.75\n"));
+        assert!(text.contains("latency_count{route=\"/api/items\"} 3\n"));
+    }
+
+    #[test]
+    fn test_render_registry() {
+        let metrics = Metrics::new();
+        metrics.record_request("GET", "/api/x/:id", 200, Duration::from_millis(3));
+        metrics.record_request("GET", "/api/x/:id", 404, Duration::from_millis(1));
+        metrics.record_rejection(Rejection::DdosRate);
+        metrics.record_fsync(Duration::from_micros(800));
+
+        let mut out = PrometheusText::new();
+        metrics.render(&mut out);
+        let text = out.finish();
+
+        assert!(text.contains("# TYPE lithair_http_requests_total counter\n"));
+        assert!(text.contains(
+            "lithair_http_requests_total{method=\"GET\",route=\"/api/x/:id\",status=\"404\"} 1\n"
+        ));
+        assert!(text.contains(
+            "lithair_http_request_duration_seconds_count{method=\"GET\",route=\"/api/x/:id\"} 2\n"
+        ));
+        assert!(text.contains("lithair_event_store_fsync_seconds_count 1\n"));
+        assert!(text.contains("lithair_rejected_total{reason=\"ddos_rate\"} 1\n"));
+        assert!(text.contains("lithair_rejected_total{reason=\"firewall\"} 0\n"));
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        let mut out = PrometheusText::new();
+        out.sample("m", &[("path", "a\"b\\c")], 1);
+        assert_eq!(out.finish(), "m{path=\"a\\\"b\\\\c\"} 1\n");
+    }
+}
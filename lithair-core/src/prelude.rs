@@ -22,7 +22,7 @@
 //! - **Security & RBAC**: [`AuthContext`], [`Permission`], [`Role`]
 //! - **Derive macros** (with `macros` feature): `DeclarativeModel`, `RbacRole`, `SchemaEvolution`, etc.
 //! - **Clustering**: [`ClusterArgs`]
-//! - **Schema**: [`SchemaMigrationMode`]
+//! - **Schema**: [`SchemaMigrationMode`], [`SchemaMigrations`], [`Upcaster`]
 
 // === Derive macros (from lithair-macros) ===
 #[cfg(feature = "macros")]
@@ -87,6 +87,8 @@ pub use crate::cluster::ClusterArgs;
 
 // === Schema ===
 pub use crate::config::SchemaMigrationMode;
+pub use crate::schema::SchemaMigrations;
+pub use crate::schema::Upcaster;
 
 // === Model inspection ===
 pub use crate::model_inspect::Inspectable;
@@ -7,6 +7,26 @@ use super::metadata::{BlockResult, FilterEntry};
 use std::collections::{HashMap, HashSet};
 use tokio::sync::RwLock;
 
+/// Category checked against the client IP
+pub const BLOCK_SOURCE_IPS: &str = "block_source_ips";
+/// Category checked against the requested host
+pub const BLOCK_TARGET_HOSTS: &str = "block_target_hosts";
+
+/// Host name as target host lists hold it: lowercase, without the trailing root dot
+/// (`Evil.com.` is `evil.com`)
+pub(crate) fn host_key(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Key of `value` in a list of `category`; target hosts are normalized with `host_key`
+fn list_key(category: &str, value: &str) -> String {
+    if category == BLOCK_TARGET_HOSTS {
+        host_key(value)
+    } else {
+        value.to_string()
+    }
+}
+
 /// Filter list manager with metadata support
 pub struct FilterListManager {
     /// Legacy filter lists (value-only, for compatibility)
@@ -34,7 +54,7 @@ impl FilterListManager {
         let mut lists = self.legacy_lists.write().await;
         let list = lists.entry(category.to_string()).or_insert_with(HashSet::new);
         for value in values {
-            list.insert(value);
+            list.insert(list_key(category, &value));
         }
     }
 
@@ -47,7 +67,7 @@ impl FilterListManager {
         let mut lists = self.enhanced_lists.write().await;
         let list = lists.entry(category.to_string()).or_insert_with(HashMap::new);
         for entry in entries {
-            list.insert(entry.value.clone(), entry);
+            list.insert(list_key(category, &entry.value), entry);
         }
     }
 
@@ -56,6 +76,8 @@ impl FilterListManager {
     /// Checks both enhanced lists (with metadata) and legacy lists (without metadata).
     /// Returns detailed block information if blocked.
     pub async fn check_block(&self, category: &str, value: &str) -> BlockResult {
+        let key = list_key(category, value);
+        let value = key.as_str();
         // First check enhanced filter lists with metadata
         let enhanced_lists = self.enhanced_lists.read().await;
         if let Some(category_entries) = enhanced_lists.get(category) {
@@ -104,6 +126,8 @@ impl FilterListManager {
 
     /// Remove a value from a category
     pub async fn remove_entry(&self, category: &str, value: &str) -> bool {
+        let key = list_key(category, value);
+        let value = key.as_str();
         let mut enhanced = self.enhanced_lists.write().await;
         let mut removed = false;
 
@@ -158,6 +182,7 @@ impl FilterListManager {
 
     /// Replace all entries in a legacy category
     pub async fn replace_category_legacy(&self, category: &str, entries: HashSet<String>) {
+        let entries = entries.iter().map(|value| list_key(category, value)).collect();
         let mut lists = self.legacy_lists.write().await;
         lists.insert(category.to_string(), entries);
     }
@@ -166,7 +191,7 @@ impl FilterListManager {
     pub async fn replace_category_enhanced(&self, category: &str, entries: Vec<FilterEntry>) {
         let mut lists = self.enhanced_lists.write().await;
         let map: HashMap<String, FilterEntry> =
-            entries.into_iter().map(|e| (e.value.clone(), e)).collect();
+            entries.into_iter().map(|e| (list_key(category, &e.value), e)).collect();
         lists.insert(category.to_string(), map);
     }
 }
@@ -1,28 +1,86 @@
 //! Forward proxy implementation (Squid-like)
 //!
-//! Handles client requests to external servers, with filtering and caching support.
+//! Handles client requests to external servers, with filter-list enforcement.
+//!
+//! Plain HTTP requests arrive in absolute form (`GET http://host/path`) and are relayed
+//! with streamed bodies. `CONNECT host:port` opens a TCP tunnel (used for HTTPS); it
+//! relies on the serving connection having upgrades enabled
+//! (`serve_connection(..).with_upgrades()`).
+//!
+//! Before connecting, the client IP is checked against `block_source_ips` and the
+//! target host (and its parent domains) against `block_target_hosts`. Blocked requests
+//! get a `403` page with `BlockInfo::create_message`.
 
+use super::filtering::{host_key, FilterListManager, BLOCK_SOURCE_IPS, BLOCK_TARGET_HOSTS};
+use super::metadata::BlockInfo;
 use super::traits::{ProxyHandler, ProxyResult};
+use super::upstream::{self, UpstreamFailure};
+use super::utils::{error_response, strip_hop_by_hop};
 use bytes::Bytes;
 use http_body_util::combinators::BoxBody;
-use hyper::{Request, Response};
+use http_body_util::{BodyExt, Full};
+use hyper::header::{HeaderValue, HOST};
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 type Body = BoxBody<Bytes, hyper::Error>;
 
 /// Forward proxy handler
 ///
 /// Implements a forward proxy that intercepts client requests to external servers.
-/// Supports filtering, caching, and authentication.
+/// Supports plain HTTP relaying, `CONNECT` tunnels and filter-list blocking.
+#[derive(Clone)]
 pub struct ForwardProxyHandler {
-    // Note: filter lists, cache, and auth state fields are not yet defined
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    filters: Option<Arc<FilterListManager>>,
+    /// Ports `CONNECT` may reach (None = any)
+    connect_ports: Option<Vec<u16>>,
+    connect_timeout: Duration,
+    response_timeout: Duration,
 }
 
 impl ForwardProxyHandler {
     /// Create a new forward proxy handler
     pub fn new() -> Self {
-        Self {}
+        Self {
+            inner: Arc::new(Inner {
+                filters: None,
+                connect_ports: Some(vec![443]),
+                connect_timeout: Duration::from_secs(10),
+                response_timeout: Duration::from_secs(60),
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("configure the proxy before cloning it")
+    }
+
+    /// Block clients and targets listed in `block_source_ips` / `block_target_hosts`
+    pub fn with_filters(mut self, filters: Arc<FilterListManager>) -> Self {
+        self.inner_mut().filters = Some(filters);
+        self
+    }
+
+    /// Ports reachable through `CONNECT` (default: 443 only); None allows any port
+    pub fn with_connect_ports(mut self, ports: Option<Vec<u16>>) -> Self {
+        self.inner_mut().connect_ports = ports;
+        self
+    }
+
+    pub fn with_timeouts(mut self, connect: Duration, response: Duration) -> Self {
+        let inner = self.inner_mut();
+        inner.connect_timeout = connect;
+        inner.response_timeout = response;
+        self
     }
 }
 
@@ -32,25 +90,187 @@ impl Default for ForwardProxyHandler {
     }
 }
 
+/// Target host and port of a proxy request (CONNECT authority or absolute URI)
+fn target_of(req: &Request<Body>) -> Option<(String, u16)> {
+    let authority = req.uri().authority()?;
+    let default_port = if req.uri().scheme_str() == Some("https") { 443 } else { 80 };
+    let port = authority.port_u16().unwrap_or(default_port);
+    // `evil.com.` names the same host as `evil.com`
+    Some((host_key(authority.host()), port))
+}
+
+/// `host`, then each parent domain (`a.b.example.com` -> `b.example.com` -> `example.com`)
+fn host_and_parents(host: &str) -> Vec<&str> {
+    let mut names = vec![host];
+    // IP literals have no parents
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return names;
+    }
+    let mut rest = host;
+    while let Some((_, parent)) = rest.split_once('.') {
+        if !parent.contains('.') {
+            break;
+        }
+        names.push(parent);
+        rest = parent;
+    }
+    names
+}
+
+impl Inner {
+    async fn block_info(&self, client: Option<SocketAddr>, host: &str) -> Option<BlockInfo> {
+        let filters = self.filters.as_ref()?;
+        if let Some(addr) = client {
+            let result = filters.check_block(BLOCK_SOURCE_IPS, &addr.ip().to_string()).await;
+            if result.blocked {
+                return result.block_info;
+            }
+        }
+        for name in host_and_parents(host) {
+            let result = filters.check_block(BLOCK_TARGET_HOSTS, name).await;
+            if result.blocked {
+                return result.block_info;
+            }
+        }
+        None
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let Some((host, port)) = target_of(&req) else {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "Forward proxy requests need an absolute URI or CONNECT authority",
+            );
+        };
+        let client = req.extensions().get::<SocketAddr>().copied();
+        if let Some(info) = self.block_info(client, &host).await {
+            log::info!("Forward proxy blocked {} {}: {}", req.method(), host, info.category);
+            return blocked_response(&info);
+        }
+
+        if req.method() == Method::CONNECT {
+            self.tunnel(req, &host, port).await
+        } else {
+            self.relay(req, &host, port).await
+        }
+    }
+
+    /// Plain HTTP: re-issue the request in origin form to the target
+    async fn relay(&self, req: Request<Body>, host: &str, port: u16) -> Response<Body> {
+        if req.uri().scheme_str() != Some("http") {
+            return error_response(StatusCode::BAD_REQUEST, "Use CONNECT for HTTPS targets");
+        }
+        let authority = req.uri().authority().map(|a| a.to_string()).unwrap_or_default();
+        let path = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        let Ok(origin) = path.parse::<Uri>() else {
+            return error_response(StatusCode::BAD_REQUEST, "Invalid request path");
+        };
+        let (mut parts, body) = req.into_parts();
+        parts.uri = origin;
+        strip_hop_by_hop(&mut parts.headers);
+        if let Ok(value) = HeaderValue::from_str(&authority) {
+            parts.headers.insert(HOST, value);
+        }
+        parts.headers.append("via", HeaderValue::from_static("1.1 lithair"));
+
+        let addr = format!("{}:{}", host, port);
+        let mut sender = match upstream::connect(&addr, self.connect_timeout).await {
+            Ok(sender) => sender,
+            Err(e) => return gateway_error(&addr, e),
+        };
+        let outgoing = Request::from_parts(parts, body);
+        match upstream::send(&mut sender, outgoing, self.response_timeout).await {
+            Ok(resp) => {
+                let (mut parts, body) = resp.into_parts();
+                strip_hop_by_hop(&mut parts.headers);
+                Response::from_parts(parts, body.boxed())
+            }
+            Err(e) => gateway_error(&addr, e),
+        }
+    }
+
+    /// CONNECT: open the TCP connection, answer 200, then splice both directions
+    async fn tunnel(&self, mut req: Request<Body>, host: &str, port: u16) -> Response<Body> {
+        if self.connect_ports.as_ref().is_some_and(|ports| !ports.contains(&port)) {
+            return error_response(StatusCode::FORBIDDEN, "CONNECT to this port is not allowed");
+        }
+        let addr = format!("{}:{}", host, port);
+        let connect = tokio::net::TcpStream::connect(addr.as_str());
+        let mut target = match tokio::time::timeout(self.connect_timeout, connect).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => return gateway_error(&addr, UpstreamFailure::Connect(e.to_string())),
+            Err(_) => return gateway_error(&addr, UpstreamFailure::Timeout),
+        };
+
+        let on_upgrade = hyper::upgrade::on(&mut req);
+        tokio::spawn(async move {
+            match on_upgrade.await {
+                Ok(upgraded) => {
+                    let mut client = TokioIo::new(upgraded);
+                    match tokio::io::copy_bidirectional(&mut client, &mut target).await {
+                        Ok((up, down)) => {
+                            log::debug!("Tunnel to {} closed ({} up, {} down)", addr, up, down)
+                        }
+                        Err(e) => log::debug!("Tunnel to {} failed: {}", addr, e),
+                    }
+                }
+                Err(e) => log::warn!("CONNECT upgrade failed: {}", e),
+            }
+        });
+        Response::new(upstream::empty_body())
+    }
+}
+
+fn gateway_error(addr: &str, failure: UpstreamFailure) -> Response<Body> {
+    log::warn!("Forward proxy to {} failed: {}", addr, failure);
+    match failure {
+        UpstreamFailure::Timeout => error_response(StatusCode::GATEWAY_TIMEOUT, "Target timed out"),
+        _ => error_response(StatusCode::BAD_GATEWAY, "Target unreachable"),
+    }
+}
+
+/// HTML block page built from `BlockInfo::create_message`
+fn blocked_response(info: &BlockInfo) -> Response<Body> {
+    let message = info
+        .create_message()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+    let page = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Access denied</title>\
+         </head><body><h1>Access denied</h1><p>{}</p></body></html>\n",
+        message
+    );
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("content-type", "text/html; charset=utf-8")
+        .header("x-lithair-block-category", info.category.as_str())
+        .body(Full::new(Bytes::from(page)).map_err(|never| match never {}).boxed())
+        .expect("valid HTTP response")
+}
+
 impl ProxyHandler for ForwardProxyHandler {
     fn handle_request(
         &self,
-        _req: Request<Body>,
+        req: Request<Body>,
     ) -> Pin<Box<dyn Future<Output = ProxyResult<Response<Body>>> + Send + 'static>> {
-        Box::pin(async move {
-            // Note: forward proxy logic (filter checking, authentication, request
-            // forwarding, response caching) is not yet implemented
-            unimplemented!("Forward proxy not yet implemented")
-        })
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move { Ok(inner.handle(req).await) })
     }
 
     fn should_block(
         &self,
-        _req: &Request<Body>,
+        req: &Request<Body>,
     ) -> Pin<Box<dyn Future<Output = ProxyResult<bool>> + Send + 'static>> {
+        let inner = Arc::clone(&self.inner);
+        let client = req.extensions().get::<SocketAddr>().copied();
+        let host = target_of(req).map(|(host, _)| host);
         Box::pin(async move {
-            // Note: filter list checking is not yet implemented; defaults to allow
-            Ok(false)
+            match host {
+                Some(host) => Ok(inner.block_info(client, &host).await.is_some()),
+                None => Ok(false),
+            }
         })
     }
 
@@ -58,3 +278,142 @@ impl ProxyHandler for ForwardProxyHandler {
         "forward"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::metadata::FilterEntry;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn request(method: Method, uri: &str) -> Request<Body> {
+        let mut req =
+            Request::builder().method(method).uri(uri).body(upstream::empty_body()).unwrap();
+        req.extensions_mut().insert::<SocketAddr>("192.0.2.7:40000".parse().unwrap());
+        req
+    }
+
+    /// Local upstream answering one request and returning what it received
+    async fn upstream_once(reply: &'static str) -> (SocketAddr, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let task = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket.write_all(reply.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+        (addr, task)
+    }
+
+    #[test]
+    fn test_host_and_parents() {
+        let names = host_and_parents("a.b.example.com");
+        assert_eq!(names, vec!["a.b.example.com", "b.example.com", "example.com"]);
+        assert_eq!(host_and_parents("10.0.0.1"), vec!["10.0.0.1"]);
+    }
+
+    #[tokio::test]
+    async fn test_relays_plain_http() {
+        let reply = "HTTP/1.1 200 OK\r\ncontent-length: 5\r\nconnection: close\r\n\r\nhello";
+        let (addr, upstream) = upstream_once(reply).await;
+        let proxy = ForwardProxyHandler::new();
+        let mut req = request(Method::GET, &format!("http://{}/greet?x=1", addr));
+        req.headers_mut()
+            .insert("proxy-connection", HeaderValue::from_static("keep-alive"));
+
+        let resp = proxy.handle_request(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(&resp.into_body().collect().await.unwrap().to_bytes()[..], b"hello");
+
+        let head = upstream.await.unwrap().to_ascii_lowercase();
+        assert!(head.starts_with("get /greet?x=1 http/1.1"));
+        assert!(head.contains(&format!("host: {}", addr)));
+        assert!(!head.contains("proxy-connection"));
+    }
+
+    #[tokio::test]
+    async fn test_blocks_listed_hosts_and_clients() {
+        let filters = Arc::new(FilterListManager::new());
+        let entry = FilterEntry::from_source(
+            "evil.test".into(),
+            "Test Feed".into(),
+            Some("phishing".into()),
+            "https://feed.test/list".into(),
+        );
+        filters.add_entries(BLOCK_TARGET_HOSTS, vec![entry]).await;
+        let proxy = ForwardProxyHandler::new().with_filters(filters.clone());
+
+        let req = request(Method::GET, "http://www.evil.test/");
+        let resp = proxy.handle_request(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(resp.headers()["x-lithair-block-category"], BLOCK_TARGET_HOSTS);
+        let page = resp.into_body().collect().await.unwrap().to_bytes();
+        let page = String::from_utf8_lossy(&page);
+        assert!(page.contains("Target Host blocked by Test Feed (phishing)"));
+
+        let connect = request(Method::CONNECT, "evil.test:443");
+        assert!(proxy.should_block(&connect).await.unwrap());
+        // A trailing root dot names the same host
+        for target in ["Evil.Test.:443", "www.evil.test.:443"] {
+            assert!(proxy.should_block(&request(Method::CONNECT, target)).await.unwrap());
+        }
+        assert!(proxy.should_block(&request(Method::GET, "http://evil.test./")).await.unwrap());
+        filters
+            .add_legacy_entries(BLOCK_TARGET_HOSTS, vec!["Dotted.Test.".into()])
+            .await;
+        let dotted = request(Method::CONNECT, "api.dotted.test:443");
+        assert!(proxy.should_block(&dotted).await.unwrap());
+        assert!(!proxy.should_block(&request(Method::GET, "http://good.test/")).await.unwrap());
+
+        filters.add_legacy_entries(BLOCK_SOURCE_IPS, vec!["192.0.2.7".into()]).await;
+        assert!(proxy.should_block(&request(Method::GET, "http://good.test/")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_connect_tunnel() {
+        // Echo server standing in for the TLS target
+        let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target_addr = target.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = target.accept().await.unwrap();
+            let mut buf = [0u8; 64];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket.write_all(&buf[..n]).await.unwrap();
+        });
+
+        let proxy = ForwardProxyHandler::new().with_connect_ports(None);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = hyper::service::service_fn(move |req: Request<hyper::body::Incoming>| {
+                let proxy = proxy.clone();
+                async move { proxy.handle_request(req.map(|b| b.boxed())).await }
+            });
+            let _ = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await;
+        });
+
+        let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+        let connect = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", target_addr);
+        client.write_all(connect.as_bytes()).await.unwrap();
+        let mut buf = [0u8; 256];
+        let n = client.read(&mut buf).await.unwrap();
+        assert!(String::from_utf8_lossy(&buf[..n]).starts_with("HTTP/1.1 200"));
+
+        client.write_all(b"ping").await.unwrap();
+        let n = client.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+
+        // Default policy only tunnels to 443
+        let resp = ForwardProxyHandler::new()
+            .handle_request(request(Method::CONNECT, &target_addr.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -6,11 +6,15 @@
 pub mod forward;
 pub mod reverse;
 pub mod traits;
+pub mod upstream;
 pub mod utils;
 
 pub use forward::ForwardProxyHandler;
-pub use reverse::ReverseProxyHandler;
+pub use reverse::{ProxyRoute, ReverseProxyHandler};
 pub use traits::{ProxyHandler, ProxyRequest, ProxyResponse};
+pub use upstream::{
+    HashKey, HealthCheckConfig, LoadBalancing, PassiveHealthConfig, Upstream, UpstreamGroup,
+};
 pub use utils::{ProxyError, ProxyResult};
 
 // Advanced filtering with metadata
@@ -18,6 +22,6 @@ pub mod filtering;
 pub mod metadata;
 pub mod tls;
 
-pub use filtering::FilterListManager;
+pub use filtering::{FilterListManager, BLOCK_SOURCE_IPS, BLOCK_TARGET_HOSTS};
 pub use metadata::{BlockInfo, BlockResult, EntryMetadata, FilterEntry};
 pub use tls::{CertificateFingerprint, TlsFingerprinter};
@@ -1,28 +1,188 @@
 //! Reverse proxy implementation (nginx-like)
 //!
 //! Handles incoming requests and forwards them to backend services.
+//!
+//! Requests are matched against `ProxyRoute`s (longest path prefix first, optionally
+//! restricted to a `Host`), rewritten, and sent to a server of the route's
+//! `UpstreamGroup`. Request and response bodies are streamed, hop-by-hop headers are
+//! dropped and `X-Forwarded-For`/`-Host`/`-Proto` are set. A server that refuses the
+//! connection is skipped and the next one is tried; the request itself is never
+//! replayed once sent.
+//!
+//! The client address is read from a `SocketAddr` request extension, which the
+//! accepting server is expected to insert.
 
+use super::filtering::{FilterListManager, BLOCK_SOURCE_IPS, BLOCK_TARGET_HOSTS};
 use super::traits::{ProxyHandler, ProxyResult};
+use super::upstream::{self, HashKey, LoadBalancing, UpstreamFailure, UpstreamGroup};
+use super::utils::{error_response, request_host, strip_hop_by_hop};
+use crate::http::firewall::Firewall;
 use bytes::Bytes;
 use http_body_util::combinators::BoxBody;
-use hyper::{Request, Response};
+use http_body_util::BodyExt;
+use hyper::header::{HeaderMap, HeaderValue, HOST};
+use hyper::{Request, Response, StatusCode, Uri};
+use std::collections::HashMap;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
 type Body = BoxBody<Bytes, hyper::Error>;
 
+/// Maps a path prefix to an upstream group
+#[derive(Debug, Clone)]
+pub struct ProxyRoute {
+    /// Path prefix, e.g. `/legacy`
+    pub prefix: String,
+    /// Only match requests for this `Host` (port ignored)
+    pub host: Option<String>,
+    /// Name of the `UpstreamGroup`
+    pub upstream: String,
+    /// Replacement for the matched prefix (`Some("")` strips it)
+    pub rewrite: Option<String>,
+}
+
+impl ProxyRoute {
+    pub fn new(prefix: impl Into<String>, upstream: impl Into<String>) -> Self {
+        Self { prefix: prefix.into(), host: None, upstream: upstream.into(), rewrite: None }
+    }
+
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Remove the prefix before forwarding (`/legacy/users` -> `/users`)
+    pub fn strip_prefix(self) -> Self {
+        self.rewrite_prefix("")
+    }
+
+    /// Replace the prefix before forwarding (`/legacy/users` -> `/v1/users`)
+    pub fn rewrite_prefix(mut self, replacement: impl Into<String>) -> Self {
+        self.rewrite = Some(replacement.into());
+        self
+    }
+
+    fn matches(&self, host: Option<&str>, path: &str) -> bool {
+        if let Some(expected) = &self.host {
+            if host.is_none_or(|h| !h.eq_ignore_ascii_case(expected)) {
+                return false;
+            }
+        }
+        let prefix = self.prefix.trim_end_matches('/');
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Upstream path for `path`, which must match this route
+    fn rewrite_path(&self, path: &str) -> String {
+        let Some(replacement) = &self.rewrite else { return path.to_string() };
+        let rest = &path[self.prefix.trim_end_matches('/').len()..];
+        let rewritten = format!("{}{}", replacement.trim_end_matches('/'), rest);
+        if rewritten.starts_with('/') {
+            rewritten
+        } else {
+            format!("/{}", rewritten)
+        }
+    }
+}
+
 /// Reverse proxy handler
 ///
 /// Implements a reverse proxy that forwards incoming requests to backend services.
-/// Supports load balancing, health checks, and SSL termination.
+/// Supports load balancing, health checks, and firewall/filter-list blocking.
+#[derive(Clone)]
 pub struct ReverseProxyHandler {
-    // Note: upstream config, load balancer, and health checker fields are not yet defined
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    routes: Vec<ProxyRoute>,
+    groups: HashMap<String, Arc<UpstreamGroup>>,
+    filters: Option<Arc<FilterListManager>>,
+    firewall: Option<Arc<Firewall>>,
+    connect_timeout: Duration,
+    response_timeout: Duration,
+    preserve_host: bool,
 }
 
 impl ReverseProxyHandler {
     /// Create a new reverse proxy handler
     pub fn new() -> Self {
-        Self {}
+        Self {
+            inner: Arc::new(Inner {
+                routes: Vec::new(),
+                groups: HashMap::new(),
+                filters: None,
+                firewall: None,
+                connect_timeout: Duration::from_secs(5),
+                response_timeout: Duration::from_secs(60),
+                preserve_host: false,
+            }),
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("configure the proxy before cloning it")
+    }
+
+    pub fn with_upstream_group(mut self, group: UpstreamGroup) -> Self {
+        let group = Arc::new(group);
+        self.inner_mut().groups.insert(group.name().to_string(), group);
+        self
+    }
+
+    pub fn with_route(mut self, route: ProxyRoute) -> Self {
+        let inner = self.inner_mut();
+        inner.routes.push(route);
+        // Longest prefix wins; host-specific routes before catch-all ones
+        inner.routes.sort_by(|a, b| {
+            b.prefix
+                .len()
+                .cmp(&a.prefix.len())
+                .then(b.host.is_some().cmp(&a.host.is_some()))
+        });
+        self
+    }
+
+    /// Block clients and hosts listed in `block_source_ips` / `block_target_hosts`
+    pub fn with_filters(mut self, filters: Arc<FilterListManager>) -> Self {
+        self.inner_mut().filters = Some(filters);
+        self
+    }
+
+    pub fn with_firewall(mut self, firewall: Arc<Firewall>) -> Self {
+        self.inner_mut().firewall = Some(firewall);
+        self
+    }
+
+    pub fn with_timeouts(mut self, connect: Duration, response: Duration) -> Self {
+        let inner = self.inner_mut();
+        inner.connect_timeout = connect;
+        inner.response_timeout = response;
+        self
+    }
+
+    /// Forward the client's `Host` header instead of the upstream address
+    pub fn with_preserve_host(mut self, preserve: bool) -> Self {
+        self.inner_mut().preserve_host = preserve;
+        self
+    }
+
+    pub fn upstream_group(&self, name: &str) -> Option<&Arc<UpstreamGroup>> {
+        self.inner.groups.get(name)
+    }
+
+    /// Start active health checks for every group that configures them
+    pub fn spawn_health_checks(&self) -> Vec<tokio::task::JoinHandle<()>> {
+        self.inner.groups.values().filter_map(|g| g.spawn_health_checks()).collect()
+    }
+
+    /// Matching route for a request
+    pub fn route_for(&self, host: Option<&str>, path: &str) -> Option<&ProxyRoute> {
+        self.inner.routes.iter().find(|r| r.matches(host, path))
     }
 }
 
@@ -32,29 +192,293 @@ impl Default for ReverseProxyHandler {
     }
 }
 
+/// What `should_block` needs from a request, detached from its lifetime
+struct RequestFacts {
+    remote: Option<SocketAddr>,
+    method: hyper::Method,
+    path: String,
+    host: Option<String>,
+}
+
+impl RequestFacts {
+    fn of(req: &Request<Body>) -> Self {
+        Self {
+            remote: req.extensions().get::<SocketAddr>().copied(),
+            method: req.method().clone(),
+            path: req.uri().path().to_string(),
+            host: request_host(req),
+        }
+    }
+}
+
+impl Inner {
+    /// Reason the request is blocked, if it is
+    async fn block_reason(&self, facts: &RequestFacts) -> Option<(StatusCode, String)> {
+        if let Some(firewall) = &self.firewall {
+            if let Err(resp) = firewall.check(facts.remote, &facts.method, &facts.path) {
+                return Some((resp.status(), "Blocked by firewall".to_string()));
+            }
+        }
+        let filters = self.filters.as_ref()?;
+        let ip = facts.remote.map(|addr| addr.ip().to_string());
+        let checks = [(BLOCK_SOURCE_IPS, ip), (BLOCK_TARGET_HOSTS, facts.host.clone())];
+        for (category, value) in checks {
+            let Some(value) = value else { continue };
+            let result = filters.check_block(category, &value).await;
+            if let Some(info) = result.block_info.filter(|_| result.blocked) {
+                return Some((StatusCode::FORBIDDEN, info.create_message()));
+            }
+        }
+        None
+    }
+
+    async fn forward(&self, req: Request<Body>) -> Response<Body> {
+        let facts = RequestFacts::of(&req);
+        if let Some((status, reason)) = self.block_reason(&facts).await {
+            log::info!("Reverse proxy blocked {} {}: {}", facts.method, facts.path, reason);
+            return error_response(status, &reason);
+        }
+
+        let route = self.routes.iter().find(|r| r.matches(facts.host.as_deref(), &facts.path));
+        let Some(route) = route else {
+            return error_response(StatusCode::NOT_FOUND, "No proxy route for this path");
+        };
+        let Some(group) = self.groups.get(&route.upstream) else {
+            log::error!("Proxy route {} uses unknown upstream {}", route.prefix, route.upstream);
+            return error_response(StatusCode::BAD_GATEWAY, "Upstream not configured");
+        };
+
+        let hash_input = match group.balancing() {
+            LoadBalancing::Hash(HashKey::ClientIp) => facts.remote.map(|a| a.ip().to_string()),
+            LoadBalancing::Hash(HashKey::Path) => Some(facts.path.clone()),
+            LoadBalancing::Hash(HashKey::Header(name)) => {
+                req.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+            }
+            _ => None,
+        };
+
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{}?{}", route.rewrite_path(&facts.path), query),
+            None => route.rewrite_path(&facts.path),
+        };
+        let (mut parts, body) = req.into_parts();
+        let Ok(uri) = path_and_query.parse::<Uri>() else {
+            return error_response(StatusCode::BAD_REQUEST, "Invalid request path");
+        };
+        parts.uri = uri;
+        strip_hop_by_hop(&mut parts.headers);
+        let tls = parts.extensions.get::<TlsConnection>().is_some();
+        add_forwarded_headers(&mut parts.headers, facts.remote, facts.host.as_deref(), tls);
+
+        // Connection failures are retried on the next server: nothing was sent yet
+        let mut tried: Vec<String> = Vec::new();
+        let (guard, mut sender) = loop {
+            let Some(guard) = group.select(hash_input.as_deref(), &tried) else {
+                return if tried.is_empty() {
+                    error_response(StatusCode::SERVICE_UNAVAILABLE, "No healthy upstream")
+                } else {
+                    error_response(StatusCode::BAD_GATEWAY, "Upstream unreachable")
+                };
+            };
+            match upstream::connect(guard.upstream().addr(), self.connect_timeout).await {
+                Ok(sender) => break (guard, sender),
+                Err(e) => {
+                    log::warn!("Upstream {} failed: {}", guard.upstream().addr(), e);
+                    group.report(guard.upstream(), false);
+                    tried.push(guard.upstream().addr().to_string());
+                }
+            }
+        };
+
+        if !self.preserve_host {
+            if let Ok(value) = HeaderValue::from_str(guard.upstream().addr()) {
+                parts.headers.insert(HOST, value);
+            }
+        }
+        let upstream_req = Request::from_parts(parts, body);
+
+        match upstream::send(&mut sender, upstream_req, self.response_timeout).await {
+            Ok(resp) => {
+                let server_error = matches!(
+                    resp.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                );
+                group.report(guard.upstream(), !server_error);
+                let (mut parts, body) = resp.into_parts();
+                strip_hop_by_hop(&mut parts.headers);
+                // The guard lives as long as the streamed body, so least-connections
+                // sees long downloads
+                let body = body.map_frame(move |frame| {
+                    let _in_flight = &guard;
+                    frame
+                });
+                Response::from_parts(parts, body.boxed())
+            }
+            Err(e) => {
+                log::warn!("Upstream {} failed: {}", guard.upstream().addr(), e);
+                group.report(guard.upstream(), false);
+                match e {
+                    UpstreamFailure::Timeout => {
+                        error_response(StatusCode::GATEWAY_TIMEOUT, "Upstream timed out")
+                    }
+                    _ => error_response(StatusCode::BAD_GATEWAY, "Upstream connection failed"),
+                }
+            }
+        }
+    }
+}
+
 impl ProxyHandler for ReverseProxyHandler {
     fn handle_request(
         &self,
-        _req: Request<Body>,
+        req: Request<Body>,
     ) -> Pin<Box<dyn Future<Output = ProxyResult<Response<Body>>> + Send + 'static>> {
-        Box::pin(async move {
-            // Note: reverse proxy logic (route matching, upstream selection, request
-            // forwarding, response relay) is not yet implemented
-            unimplemented!("Reverse proxy not yet implemented")
-        })
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move { Ok(inner.forward(req).await) })
     }
 
     fn should_block(
         &self,
-        _req: &Request<Body>,
+        req: &Request<Body>,
     ) -> Pin<Box<dyn Future<Output = ProxyResult<bool>> + Send + 'static>> {
-        Box::pin(async move {
-            // Note: firewall rule checking is not yet implemented; defaults to allow
-            Ok(false)
-        })
+        let inner = Arc::clone(&self.inner);
+        let facts = RequestFacts::of(req);
+        Box::pin(async move { Ok(inner.block_reason(&facts).await.is_some()) })
     }
 
     fn proxy_type(&self) -> &'static str {
         "reverse"
     }
 }
+
+fn add_forwarded_headers(
+    headers: &mut HeaderMap,
+    remote: Option<SocketAddr>,
+    host: Option<&str>,
+    tls: bool,
+) {
+    if let Some(addr) = remote {
+        let client = addr.ip().to_string();
+        let chain = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+            Some(existing) => format!("{}, {}", existing, client),
+            None => client,
+        };
+        if let Ok(value) = HeaderValue::from_str(&chain) {
+            headers.insert("x-forwarded-for", value);
+        }
+    }
+    if let Some(original) = headers.get(HOST).cloned() {
+        headers.insert("x-forwarded-host", original);
+    } else if let Some(value) = host.and_then(|h| HeaderValue::from_str(h).ok()) {
+        headers.insert("x-forwarded-host", value);
+    }
+    let proto = if tls { "https" } else { "http" };
+    headers.insert("x-forwarded-proto", HeaderValue::from_static(proto));
+}
+
+/// Request extension marking a request received over TLS (sets `X-Forwarded-Proto`)
+#[derive(Debug, Clone, Copy)]
+pub struct TlsConnection;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::metadata::FilterEntry;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn request(uri: &str, host: &str) -> Request<Body> {
+        let mut req = Request::get(uri).header(HOST, host).body(upstream::empty_body()).unwrap();
+        req.extensions_mut().insert::<SocketAddr>("10.1.2.3:5555".parse().unwrap());
+        req
+    }
+
+    #[test]
+    fn test_route_matching_and_rewrite() {
+        let proxy = ReverseProxyHandler::new()
+            .with_route(ProxyRoute::new("/", "default"))
+            .with_route(ProxyRoute::new("/legacy", "old").strip_prefix())
+            .with_route(ProxyRoute::new("/api", "v1").rewrite_prefix("/v1"));
+        let route = proxy.route_for(None, "/legacy/users").unwrap();
+        assert_eq!(route.upstream, "old");
+        assert_eq!(route.rewrite_path("/legacy/users"), "/users");
+        assert_eq!(route.rewrite_path("/legacy"), "/");
+        let route = proxy.route_for(None, "/api/items").unwrap();
+        assert_eq!(route.rewrite_path("/api/items"), "/v1/items");
+        assert_eq!(proxy.route_for(None, "/legacyx").unwrap().upstream, "default");
+    }
+
+    #[test]
+    fn test_headers_are_cleaned_and_forwarded() {
+        let mut headers = HeaderMap::new();
+        headers.insert("connection", HeaderValue::from_static("keep-alive, x-secret"));
+        headers.insert("x-secret", HeaderValue::from_static("1"));
+        headers.insert("upgrade", HeaderValue::from_static("h2c"));
+        headers.insert(HOST, HeaderValue::from_static("shop.example.com"));
+        headers.insert("x-forwarded-for", HeaderValue::from_static("1.1.1.1"));
+        strip_hop_by_hop(&mut headers);
+        let remote = Some("10.1.2.3:5555".parse().unwrap());
+        add_forwarded_headers(&mut headers, remote, None, false);
+        assert!(headers.get("x-secret").is_none() && headers.get("upgrade").is_none());
+        assert_eq!(headers["x-forwarded-for"], "1.1.1.1, 10.1.2.3");
+        assert_eq!(headers["x-forwarded-host"], "shop.example.com");
+        assert_eq!(headers["x-forwarded-proto"], "http");
+    }
+
+    #[tokio::test]
+    async fn test_filter_lists_block_requests() {
+        let filters = Arc::new(FilterListManager::new());
+        let entry = FilterEntry::manual("10.1.2.3".into(), None);
+        filters.add_entries(BLOCK_SOURCE_IPS, vec![entry]).await;
+        let proxy = ReverseProxyHandler::new().with_filters(filters.clone());
+        assert!(proxy.should_block(&request("/", "a.example.com")).await.unwrap());
+
+        filters.clear_category(BLOCK_SOURCE_IPS).await;
+        filters
+            .add_legacy_entries(BLOCK_TARGET_HOSTS, vec!["evil.example.com".into()])
+            .await;
+        assert!(proxy.should_block(&request("/", "evil.example.com:8080")).await.unwrap());
+        assert!(!proxy.should_block(&request("/", "a.example.com")).await.unwrap());
+        let resp = proxy.handle_request(request("/", "evil.example.com")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_forwards_to_upstream_and_fails_over() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let backend = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let head = String::from_utf8_lossy(&buf[..n]).to_string();
+            let reply = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok";
+            socket.write_all(reply.as_bytes()).await.unwrap();
+            head
+        });
+
+        // The first server refuses connections; the proxy moves on to the live one
+        let dead = {
+            let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            l.local_addr().unwrap().to_string()
+        };
+        let proxy = ReverseProxyHandler::new()
+            .with_upstream_group(UpstreamGroup::new("legacy", [dead, addr.to_string()]))
+            .with_route(ProxyRoute::new("/legacy", "legacy").strip_prefix());
+
+        let resp = proxy.handle_request(request("/legacy/users?x=1", "shop.test")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"ok");
+
+        let head = backend.await.unwrap().to_ascii_lowercase();
+        assert!(head.starts_with("get /users?x=1 http/1.1"));
+        assert!(head.contains("x-forwarded-for: 10.1.2.3"));
+        assert!(head.contains("x-forwarded-host: shop.test"));
+        assert!(head.contains(&format!("host: {}", addr)));
+
+        let resp = proxy.handle_request(request("/other", "shop.test")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,427 @@
+//! Upstream pools for the reverse proxy
+//!
+//! An `UpstreamGroup` is a named set of backend servers with a load-balancing policy.
+//! Servers are taken out of rotation by passive checks (consecutive failures on live
+//! traffic eject a server for a cooldown) and by optional active checks (a periodic
+//! `GET` on a health path). Upstreams are plain HTTP/1.1 `host:port` authorities.
+//!
+//! `connect`/`send` are the HTTP/1.1 client used by both proxies.
+
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Incoming;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type Body = BoxBody<Bytes, hyper::Error>;
+
+/// How requests are spread over the servers of a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadBalancing {
+    /// Each server in turn
+    RoundRobin,
+    /// Server with the fewest in-flight requests
+    LeastConnections,
+    /// Same key, same server (while the set of healthy servers is stable)
+    Hash(HashKey),
+}
+
+/// Request attribute used by `LoadBalancing::Hash`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashKey {
+    ClientIp,
+    Path,
+    /// Value of the named request header
+    Header(String),
+}
+
+/// Active health check: `GET path` on every server each `interval`
+#[derive(Debug, Clone)]
+pub struct HealthCheckConfig {
+    pub path: String,
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// Consecutive successes before a down server is used again
+    pub rise: u32,
+    /// Consecutive failures before a server is marked down
+    pub fall: u32,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            path: "/health".to_string(),
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(2),
+            rise: 2,
+            fall: 3,
+        }
+    }
+}
+
+/// Passive health check: eject a server after `max_failures` consecutive errors
+#[derive(Debug, Clone)]
+pub struct PassiveHealthConfig {
+    pub max_failures: u32,
+    /// How long an ejected server stays out of rotation
+    pub cooldown: Duration,
+}
+
+impl Default for PassiveHealthConfig {
+    fn default() -> Self {
+        Self { max_failures: 3, cooldown: Duration::from_secs(30) }
+    }
+}
+
+/// A backend server and its runtime state
+#[derive(Debug)]
+pub struct Upstream {
+    addr: String,
+    active: AtomicUsize,
+    /// Verdict of the active health check
+    healthy: AtomicBool,
+    check_streak: AtomicU32,
+    failures: AtomicU32,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl Upstream {
+    fn new(addr: String) -> Self {
+        Self {
+            addr,
+            active: AtomicUsize::new(0),
+            healthy: AtomicBool::new(true),
+            check_streak: AtomicU32::new(0),
+            failures: AtomicU32::new(0),
+            ejected_until: Mutex::new(None),
+        }
+    }
+
+    /// `host:port` of the server
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// In-flight requests
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Healthy and not ejected by passive checks
+    pub fn is_available(&self) -> bool {
+        if !self.healthy.load(Ordering::Relaxed) {
+            return false;
+        }
+        let ejected = self.ejected_until.lock().expect("upstream state lock poisoned");
+        ejected.is_none_or(|until| Instant::now() >= until)
+    }
+
+    fn record_success(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        *self.ejected_until.lock().expect("upstream state lock poisoned") = None;
+    }
+
+    fn record_failure(&self, passive: &PassiveHealthConfig) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= passive.max_failures {
+            log::warn!(
+                "Upstream {} ejected for {:?} after {} failures",
+                self.addr,
+                passive.cooldown,
+                failures
+            );
+            self.failures.store(0, Ordering::Relaxed);
+            *self.ejected_until.lock().expect("upstream state lock poisoned") =
+                Some(Instant::now() + passive.cooldown);
+        }
+    }
+
+    fn record_check(&self, ok: bool, cfg: &HealthCheckConfig) {
+        let healthy = self.healthy.load(Ordering::Relaxed);
+        if ok == healthy {
+            self.check_streak.store(0, Ordering::Relaxed);
+            return;
+        }
+        let streak = self.check_streak.fetch_add(1, Ordering::Relaxed) + 1;
+        if streak >= if ok { cfg.rise } else { cfg.fall } {
+            log::info!("Upstream {} is now {}", self.addr, if ok { "up" } else { "down" });
+            self.healthy.store(ok, Ordering::Relaxed);
+            self.check_streak.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Counts an in-flight request against its server until dropped
+#[derive(Debug)]
+pub struct UpstreamGuard {
+    upstream: Arc<Upstream>,
+}
+
+impl UpstreamGuard {
+    fn new(upstream: Arc<Upstream>) -> Self {
+        upstream.active.fetch_add(1, Ordering::Relaxed);
+        Self { upstream }
+    }
+
+    pub fn upstream(&self) -> &Arc<Upstream> {
+        &self.upstream
+    }
+}
+
+impl Drop for UpstreamGuard {
+    fn drop(&mut self) {
+        self.upstream.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Named pool of servers
+#[derive(Debug)]
+pub struct UpstreamGroup {
+    name: String,
+    servers: Vec<Arc<Upstream>>,
+    balancing: LoadBalancing,
+    health_check: Option<HealthCheckConfig>,
+    passive: PassiveHealthConfig,
+    next: AtomicUsize,
+}
+
+impl UpstreamGroup {
+    /// Round-robin group over `servers` (`host:port`)
+    pub fn new(
+        name: impl Into<String>,
+        servers: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            servers: servers.into_iter().map(|s| Arc::new(Upstream::new(s.into()))).collect(),
+            balancing: LoadBalancing::RoundRobin,
+            health_check: None,
+            passive: PassiveHealthConfig::default(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_load_balancing(mut self, balancing: LoadBalancing) -> Self {
+        self.balancing = balancing;
+        self
+    }
+
+    pub fn with_health_check(mut self, cfg: HealthCheckConfig) -> Self {
+        self.health_check = Some(cfg);
+        self
+    }
+
+    pub fn with_passive_health(mut self, cfg: PassiveHealthConfig) -> Self {
+        self.passive = cfg;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn servers(&self) -> &[Arc<Upstream>] {
+        &self.servers
+    }
+
+    pub fn balancing(&self) -> &LoadBalancing {
+        &self.balancing
+    }
+
+    /// Pick an available server, skipping `exclude` (servers already tried).
+    /// `hash_input` is the value of the configured `HashKey`.
+    pub fn select(&self, hash_input: Option<&str>, exclude: &[String]) -> Option<UpstreamGuard> {
+        let candidates: Vec<&Arc<Upstream>> = self
+            .servers
+            .iter()
+            .filter(|s| s.is_available() && !exclude.iter().any(|e| e == &s.addr))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let chosen = match (&self.balancing, hash_input) {
+            (LoadBalancing::LeastConnections, _) => {
+                // Rotate the starting point so ties do not always land on the first server
+                let offset = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..candidates.len())
+                    .map(|i| candidates[(offset + i) % candidates.len()])
+                    .min_by_key(|s| s.active_connections())
+                    .expect("candidates is not empty")
+            }
+            (LoadBalancing::Hash(_), Some(input)) => {
+                let mut hasher = DefaultHasher::new();
+                input.hash(&mut hasher);
+                candidates[(hasher.finish() % candidates.len() as u64) as usize]
+            }
+            // Round-robin, and hashing without a key
+            _ => candidates[self.next.fetch_add(1, Ordering::Relaxed) % candidates.len()],
+        };
+        Some(UpstreamGuard::new(chosen.clone()))
+    }
+
+    /// Feed the outcome of a proxied request into passive health checking
+    pub fn report(&self, upstream: &Upstream, ok: bool) {
+        if ok {
+            upstream.record_success();
+        } else {
+            upstream.record_failure(&self.passive);
+        }
+    }
+
+    /// Run the active health check loop until the task is aborted. Returns None when
+    /// the group has no active check configured.
+    pub fn spawn_health_checks(self: &Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        let cfg = self.health_check.clone()?;
+        let group = Arc::clone(self);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(cfg.interval);
+            loop {
+                ticker.tick().await;
+                for server in &group.servers {
+                    let ok = probe(server.addr(), &cfg).await;
+                    server.record_check(ok, &cfg);
+                }
+            }
+        }))
+    }
+}
+
+/// Why a request did not get a response from the upstream
+#[derive(Debug)]
+pub enum UpstreamFailure {
+    /// TCP connect or HTTP handshake failed; the request was not sent
+    Connect(String),
+    /// Request was sent but no response headers arrived in time
+    Timeout,
+    /// Connection broke after the request was sent
+    Io(String),
+}
+
+impl std::fmt::Display for UpstreamFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpstreamFailure::Connect(e) => write!(f, "connect failed: {}", e),
+            UpstreamFailure::Timeout => write!(f, "upstream timed out"),
+            UpstreamFailure::Io(e) => write!(f, "upstream connection error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UpstreamFailure {}
+
+/// Open an HTTP/1.1 connection to `addr`; the connection task runs in the background
+pub async fn connect(
+    addr: &str,
+    timeout: Duration,
+) -> Result<hyper::client::conn::http1::SendRequest<Body>, UpstreamFailure> {
+    let stream = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr))
+        .await
+        .map_err(|_| UpstreamFailure::Connect("connect timed out".to_string()))?
+        .map_err(|e| UpstreamFailure::Connect(e.to_string()))?;
+    let _ = stream.set_nodelay(true);
+    let (sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| UpstreamFailure::Connect(e.to_string()))?;
+    let addr = addr.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            log::debug!("Upstream connection to {} closed: {}", addr, e);
+        }
+    });
+    Ok(sender)
+}
+
+/// Send `req` on `sender`, waiting at most `timeout` for the response headers
+pub async fn send(
+    sender: &mut hyper::client::conn::http1::SendRequest<Body>,
+    req: Request<Body>,
+    timeout: Duration,
+) -> Result<Response<Incoming>, UpstreamFailure> {
+    match tokio::time::timeout(timeout, sender.send_request(req)).await {
+        Ok(Ok(resp)) => Ok(resp),
+        Ok(Err(e)) => Err(UpstreamFailure::Io(e.to_string())),
+        Err(_) => Err(UpstreamFailure::Timeout),
+    }
+}
+
+/// Empty request/response body
+pub fn empty_body() -> Body {
+    Empty::<Bytes>::new().map_err(|never| match never {}).boxed()
+}
+
+async fn probe(addr: &str, cfg: &HealthCheckConfig) -> bool {
+    let Ok(mut sender) = connect(addr, cfg.timeout).await else { return false };
+    let Ok(req) = Request::get(cfg.path.as_str()).header("host", addr).body(empty_body()) else {
+        return false;
+    };
+    match send(&mut sender, req, cfg.timeout).await {
+        Ok(resp) => resp.status().is_success() || resp.status().is_redirection(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(balancing: LoadBalancing) -> UpstreamGroup {
+        UpstreamGroup::new("api", ["a:80", "b:80", "c:80"]).with_load_balancing(balancing)
+    }
+
+    fn pick(group: &UpstreamGroup, key: Option<&str>) -> String {
+        group.select(key, &[]).unwrap().upstream().addr().to_string()
+    }
+
+    #[test]
+    fn test_round_robin_cycles_and_skips_excluded() {
+        let g = group(LoadBalancing::RoundRobin);
+        let picks: Vec<String> = (0..3).map(|_| pick(&g, None)).collect();
+        assert_eq!(picks, vec!["a:80", "b:80", "c:80"]);
+        let guard = g.select(None, &["a:80".to_string(), "b:80".to_string()]).unwrap();
+        assert_eq!(guard.upstream().addr(), "c:80");
+    }
+
+    #[test]
+    fn test_least_connections_prefers_idle_server() {
+        let g = group(LoadBalancing::LeastConnections);
+        let _a = g.select(None, &["b:80".to_string(), "c:80".to_string()]).unwrap();
+        let _b = g.select(None, &["a:80".to_string(), "c:80".to_string()]).unwrap();
+        assert_eq!(pick(&g, None), "c:80");
+    }
+
+    #[test]
+    fn test_hash_is_sticky() {
+        let g = group(LoadBalancing::Hash(HashKey::ClientIp));
+        let first = pick(&g, Some("10.0.0.7"));
+        assert!((0..10).all(|_| pick(&g, Some("10.0.0.7")) == first));
+    }
+
+    #[test]
+    fn test_passive_ejection_and_active_checks() {
+        let g = group(LoadBalancing::RoundRobin).with_passive_health(PassiveHealthConfig {
+            max_failures: 2,
+            cooldown: Duration::from_secs(60),
+        });
+        let a = g.servers()[0].clone();
+        g.report(&a, false);
+        assert!(a.is_available());
+        g.report(&a, false);
+        assert!(!a.is_available());
+        assert!((0..4).all(|_| pick(&g, None) != "a:80"));
+
+        let cfg = HealthCheckConfig { rise: 1, fall: 2, ..Default::default() };
+        let b = g.servers()[1].clone();
+        b.record_check(false, &cfg);
+        assert!(b.is_available());
+        b.record_check(false, &cfg);
+        assert!(!b.is_available());
+        b.record_check(true, &cfg);
+        assert!(b.is_available());
+    }
+}
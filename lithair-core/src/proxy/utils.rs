@@ -1,5 +1,10 @@
 //! Utility functions and types for proxy operations
 
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::header::{HeaderMap, HeaderName, HOST};
+use hyper::{Request, Response, StatusCode};
 use std::fmt;
 
 /// Error types for proxy operations
@@ -61,6 +66,66 @@ pub fn extract_client_ip<B>(req: &hyper::Request<B>) -> Option<String> {
     None
 }
 
+/// Headers that describe a single connection and must not be forwarded (RFC 9110 7.6.1)
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Remove hop-by-hop headers, including those named in `Connection`
+pub fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(hyper::header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in listed {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP {
+        headers.remove(*name);
+    }
+}
+
+/// Lowercased host of an authority (`host:port`), keeping IPv6 literals like `[::1]`
+pub fn host_without_port(authority: &str) -> String {
+    let host = match authority.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => authority,
+    };
+    host.to_ascii_lowercase()
+}
+
+/// `Host` header (or URI authority) without the port
+pub fn request_host<B>(req: &Request<B>) -> Option<String> {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| req.uri().authority().map(|a| a.as_str()))?;
+    Some(host_without_port(host))
+}
+
+/// JSON error response for proxy failures
+pub fn error_response(status: StatusCode, message: &str) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let body = serde_json::json!({ "error": status.canonical_reason(), "message": message });
+    let body = Full::new(Bytes::from(body.to_string())).map_err(|never| match never {}).boxed();
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(body)
+        .expect("valid HTTP response")
+}
+
 /// Helper to check if a URI matches a pattern
 pub fn matches_pattern(uri: &str, pattern: &str) -> bool {
     // Simple wildcard matching; complex patterns (regex, CIDR for IPs) are not yet supported
@@ -93,4 +158,12 @@ mod tests {
         assert!(matches_pattern("/api/users", "/api/*"));
         assert!(!matches_pattern("/admin/users", "/api/*"));
     }
+
+    #[test]
+    fn test_host_without_port() {
+        assert_eq!(host_without_port("Example.com:8080"), "example.com");
+        assert_eq!(host_without_port("example.com"), "example.com");
+        assert_eq!(host_without_port("[::1]:443"), "[::1]");
+        assert_eq!(host_without_port("[::1]"), "[::1]");
+    }
 }
@@ -182,7 +182,7 @@ impl<App: RaftstoneApplication> DistributedEngine<App> {
         
         // Register all peer nodes
         for peer in &cluster_config.peers {
-            network.add_node(peer.node_id, peer.address.to_string());
+            network.add_node(peer.node_id, &peer.address.to_string())?;
         }
 
         // Create Lithair storage with EventStore integration
@@ -14,11 +14,12 @@ use openraft::error::{RPCError, NetworkError, RaftError};
 use openraft::{raft, LogId, Vote};
 
 use super::distributed_engine::{NodeId, TypeConfig, LithairRequest, LithairResponse};
+use crate::cluster::NodeAddress;
 
 /// Lithair network factory using existing HTTP infrastructure
 #[derive(Clone, Debug)]
 pub struct LithairNetworkFactory {
-    cluster_nodes: Arc<std::sync::RwLock<BTreeMap<NodeId, String>>>,
+    cluster_nodes: Arc<std::sync::RwLock<BTreeMap<NodeId, NodeAddress>>>,
 }
 
 impl LithairNetworkFactory {
@@ -28,13 +29,16 @@ impl LithairNetworkFactory {
         }
     }
     
-    pub fn add_node(&self, node_id: NodeId, address: String) {
+    /// Register the address a node advertises (`host:port` or `scheme://host:port`)
+    pub fn add_node(&self, node_id: NodeId, address: &str) -> Result<(), String> {
+        let address = NodeAddress::parse(address)?;
         let mut nodes = self.cluster_nodes.write().expect("cluster nodes lock poisoned");
+        log::info!("Lithair: Registered node {} at {}", node_id, address);
         nodes.insert(node_id, address);
-        log::info!("Lithair: Registered node {} at {}", node_id, &nodes[&node_id]);
+        Ok(())
     }
     
-    fn get_node_address(&self, node_id: NodeId) -> Option<String> {
+    fn get_node_address(&self, node_id: NodeId) -> Option<NodeAddress> {
         let nodes = self.cluster_nodes.read().expect("cluster nodes lock poisoned");
         nodes.get(&node_id).cloned()
     }
@@ -45,10 +49,14 @@ impl RaftNetworkFactory<TypeConfig> for LithairNetworkFactory {
     type Network = LithairConnection;
 
     async fn new_client(&mut self, target: NodeId, _node: &()) -> Self::Network {
-        let address = self.get_node_address(target)
-            .unwrap_or_else(|| format!("127.0.0.1:808{}", target));
-        
-        log::debug!("Lithair: Creating network connection to node {} at {}", target, address);
+        // A node nobody registered is unreachable: its RPCs fail until it is added
+        let address = self.get_node_address(target);
+        match &address {
+            Some(address) => {
+                log::debug!("Lithair: Creating network connection to node {} at {}", target, address)
+            }
+            None => log::warn!("Lithair: No address registered for node {}", target),
+        }
         
         LithairConnection {
             target_id: target,
@@ -61,7 +69,7 @@ impl RaftNetworkFactory<TypeConfig> for LithairNetworkFactory {
 #[derive(Debug)]
 pub struct LithairConnection {
     target_id: NodeId,
-    target_addr: String,
+    target_addr: Option<NodeAddress>,
 }
 
 impl LithairConnection {
@@ -71,7 +79,15 @@ impl LithairConnection {
         T: Serialize,
         R: for<'de> Deserialize<'de>,
     {
-        let url = format!("http://{}{}", self.target_addr, endpoint);
+        let Some(ref target_addr) = self.target_addr else {
+            return Err(RPCError::Network(
+                NetworkError::new(&std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No address registered for node {}", self.target_id)
+                ))
+            ));
+        };
+        let url = target_addr.url(endpoint);
         
         log::debug!("Lithair HTTP: {} to node {} at {}", endpoint, self.target_id, url);
 
@@ -2,7 +2,7 @@
 //!
 //! This module provides automatically generated /auth/login and /auth/logout handlers
 
-use super::RbacUser;
+use super::{RbacUser, UserStore};
 use crate::session::{PersistentSessionStore, Session, SessionStore};
 use anyhow::Result;
 use bytes::Bytes;
@@ -53,22 +53,70 @@ pub async fn handle_rbac_login(
     };
 
     // Find user from in-memory list
-    let user = users
-        .iter()
-        .find(|u| u.username == login_req.username && u.verify_password(&login_req.password));
+    let user = users.iter().find(|u| {
+        u.active && u.username == login_req.username && u.verify_password(&login_req.password)
+    });
 
-    let user = match user {
-        Some(u) if u.active => u,
-        _ => {
+    match user {
+        Some(user) => {
+            start_session(user, &login_req, session_store, session_duration, mfa_storage).await
+        }
+        None => Ok(invalid_credentials()),
+    }
+}
+
+/// Login handler backed by the event-sourced `UserStore`
+pub async fn handle_user_store_login(
+    mut req: Request<hyper::body::Incoming>,
+    session_store: Arc<PersistentSessionStore>,
+    user_store: &UserStore,
+    session_duration: u64,
+    mfa_storage: Option<Arc<crate::mfa::MfaStorage>>,
+) -> Result<Response<Full<Bytes>>> {
+    use http_body_util::BodyExt;
+
+    let body = req.body_mut().collect().await?.to_bytes();
+    let login_req: LoginRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(_) => {
             return Ok(json_response(
-                StatusCode::UNAUTHORIZED,
+                StatusCode::BAD_REQUEST,
                 serde_json::json!({
-                    "error": "Invalid credentials"
+                    "error": "Invalid JSON"
                 }),
             ));
         }
     };
 
+    let (username, password) = (login_req.username.clone(), login_req.password.clone());
+    let authenticated = user_store
+        .blocking(move |store| store.authenticate(&username, &password))
+        .await?;
+    match authenticated {
+        Some(user) => {
+            start_session(&user, &login_req, session_store, session_duration, mfa_storage).await
+        }
+        None => Ok(invalid_credentials()),
+    }
+}
+
+fn invalid_credentials() -> Response<Full<Bytes>> {
+    json_response(
+        StatusCode::UNAUTHORIZED,
+        serde_json::json!({
+            "error": "Invalid credentials"
+        }),
+    )
+}
+
+/// Check MFA for an authenticated user, then open a session
+async fn start_session(
+    user: &RbacUser,
+    login_req: &LoginRequest,
+    session_store: Arc<PersistentSessionStore>,
+    session_duration: u64,
+    mfa_storage: Option<Arc<crate::mfa::MfaStorage>>,
+) -> Result<Response<Full<Bytes>>> {
     // Check if MFA is enabled for this user
     if let Some(mfa_store) = mfa_storage {
         if let Ok(Some(mfa_data)) = mfa_store.get(&user.username).await {
@@ -183,7 +231,7 @@ pub async fn handle_rbac_logout(
 }
 
 /// Helper to create JSON response
-fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Full<Bytes>> {
+pub(super) fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Full<Bytes>> {
     Response::builder()
         .status(status)
         .header("Content-Type", "application/json")
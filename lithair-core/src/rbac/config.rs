@@ -92,6 +92,15 @@ pub struct ServerRbacConfig {
 
     /// Session duration in seconds (default: 8 hours)
     pub session_duration: u64,
+
+    /// Directory of the event-sourced user store
+    ///
+    /// When set, accounts live in a `UserStore` seeded from `users`, and the
+    /// `/auth/password` and `/_admin/users` endpoints are enabled.
+    pub user_store_path: Option<String>,
+
+    /// Role given to self-registered users; `None` disables `/auth/register`
+    pub registration_role: Option<String>,
 }
 
 impl Default for ServerRbacConfig {
@@ -104,6 +113,8 @@ impl Default for ServerRbacConfig {
             users: vec![],
             session_store_path: None,
             session_duration: 28800, // 8 hours
+            user_store_path: None,
+            registration_role: None,
         }
     }
 }
@@ -150,6 +161,25 @@ impl ServerRbacConfig {
         self
     }
 
+    /// Manage users in an event-sourced store at `path`
+    pub fn with_user_store(mut self, path: impl Into<String>) -> Self {
+        self.user_store_path = Some(path.into());
+        self
+    }
+
+    /// Allow self-registration through `/auth/register` with the given role
+    ///
+    /// Only effective together with `with_user_store`.
+    pub fn with_registration(mut self, role: impl Into<String>) -> Self {
+        self.registration_role = Some(role.into());
+        self
+    }
+
+    /// Role names defined in this configuration
+    pub fn role_names(&self) -> Vec<String> {
+        self.roles.iter().map(|(name, _)| name.clone()).collect()
+    }
+
     /// Create Role objects from definitions
     pub fn create_roles(&self) -> Vec<Role> {
         self.roles
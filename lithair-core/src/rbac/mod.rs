@@ -9,6 +9,7 @@
 //! - Field-level access control
 //! - Role-based authorization
 //! - Automatic middleware integration with DeclarativeServer
+//! - Event-sourced user accounts with registration and admin endpoints (`UserStore`)
 //!
 //! # Example
 //! ```rust,ignore
@@ -34,9 +35,11 @@ mod permissions;
 mod providers;
 mod roles;
 mod traits;
+mod user_handlers;
+mod user_store;
 
 // Public exports
-pub use auth_handlers::{handle_rbac_login, handle_rbac_logout};
+pub use auth_handlers::{handle_rbac_login, handle_rbac_logout, handle_user_store_login};
 pub use config::{DeclarativePermissionChecker, RbacUser, ServerRbacConfig};
 pub use context::{AuthContext, RbacContext};
 pub use middleware::RbacMiddleware;
@@ -44,6 +47,10 @@ pub use permissions::{FieldPermission, Permission, PermissionLevel};
 pub use providers::{PasswordProvider, ProviderConfig};
 pub use roles::{Role, RoleDefinition};
 pub use traits::{AuthProvider, Authorizable, FieldFilter};
+pub use user_handlers::{
+    handle_admin_users, handle_change_password, handle_register, USER_ADMIN_PERMISSION,
+};
+pub use user_store::{UserEvent, UserStore, UserStoreError, MIN_PASSWORD_LENGTH};
 
 /// Trait for checking if a role has a specific permission
 ///
@@ -0,0 +1,316 @@
+//! HTTP handlers for user accounts managed by `UserStore`
+//!
+//! - `POST /auth/register` - self-registration (when enabled)
+//! - `POST /auth/password` - change own password
+//! - `/_admin/users/**` - user administration, requires the `UserAdmin` permission
+//!
+//! Password hashes are never returned.
+
+use super::auth_handlers::json_response;
+use super::{PermissionChecker, RbacUser, UserStore, UserStoreError};
+use crate::session::{PersistentSessionStore, Session, SessionStore};
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Permission required by the `/_admin/users` endpoints
+pub const USER_ADMIN_PERMISSION: &str = "UserAdmin";
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateUserRequest {
+    username: String,
+    password: String,
+    role: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssignRoleRequest {
+    role: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetPasswordRequest {
+    password: String,
+}
+
+/// Public view of a user
+fn user_json(user: &RbacUser) -> serde_json::Value {
+    json!({
+        "username": user.username,
+        "role": user.role,
+        "active": user.active
+    })
+}
+
+fn error_response(status: StatusCode, message: impl std::fmt::Display) -> Response<Full<Bytes>> {
+    json_response(status, json!({ "error": message.to_string() }))
+}
+
+fn store_error_response(err: UserStoreError) -> Response<Full<Bytes>> {
+    let status = match err {
+        UserStoreError::NotFound(_) => StatusCode::NOT_FOUND,
+        UserStoreError::AlreadyExists(_) => StatusCode::CONFLICT,
+        UserStoreError::Invalid(_) => StatusCode::BAD_REQUEST,
+        UserStoreError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, err)
+}
+
+async fn parse_body<T: DeserializeOwned>(
+    req: Request<hyper::body::Incoming>,
+) -> Result<Result<T, Response<Full<Bytes>>>> {
+    let body = req.into_body().collect().await?.to_bytes();
+    Ok(serde_json::from_slice(&body)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e))))
+}
+
+/// Session token from the Authorization header or the `session_token` cookie
+fn session_token<B>(req: &Request<B>) -> Option<String> {
+    req.headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or_else(|| {
+            req.headers().get(hyper::header::COOKIE).and_then(|h| h.to_str().ok()).and_then(
+                |cookies| {
+                    cookies
+                        .split(';')
+                        .find(|c| c.trim().starts_with("session_token="))
+                        .and_then(|c| c.split('=').nth(1))
+                },
+            )
+        })
+        .map(str::to_string)
+}
+
+/// Resolve the caller's session and account, or the 401 to return
+async fn current_user<B>(
+    req: &Request<B>,
+    session_store: &PersistentSessionStore,
+    user_store: &UserStore,
+) -> Result<Result<(Session, RbacUser), Response<Full<Bytes>>>> {
+    let Some(token) = session_token(req) else {
+        return Ok(Err(error_response(StatusCode::UNAUTHORIZED, "No session token provided")));
+    };
+    let session = match session_store.get(&token).await? {
+        Some(session) if !session.is_expired() => session,
+        _ => return Ok(Err(error_response(StatusCode::UNAUTHORIZED, "Invalid session"))),
+    };
+    let username: String = session.get("username").unwrap_or_default();
+    match user_store.get(&username) {
+        Some(user) if user.active => Ok(Ok((session, user))),
+        _ => Ok(Err(error_response(StatusCode::UNAUTHORIZED, "Invalid session"))),
+    }
+}
+
+/// `POST /auth/register` - create an account with the registration role
+pub async fn handle_register(
+    req: Request<hyper::body::Incoming>,
+    user_store: &UserStore,
+    registration_role: &str,
+) -> Result<Response<Full<Bytes>>> {
+    let register: RegisterRequest = match parse_body(req).await? {
+        Ok(body) => body,
+        Err(resp) => return Ok(resp),
+    };
+
+    let role = registration_role.to_string();
+    let created = user_store
+        .blocking(move |store| store.create(&register.username, &register.password, &role))
+        .await?;
+    match created {
+        Ok(user) => {
+            log::info!("User registered: {} as {}", user.username, user.role);
+            Ok(json_response(StatusCode::CREATED, user_json(&user)))
+        }
+        Err(e) => Ok(store_error_response(e)),
+    }
+}
+
+/// `POST /auth/password` - change the caller's password
+///
+/// Other sessions of the user are revoked; the current one stays valid.
+pub async fn handle_change_password(
+    req: Request<hyper::body::Incoming>,
+    session_store: Arc<PersistentSessionStore>,
+    user_store: &UserStore,
+) -> Result<Response<Full<Bytes>>> {
+    let (session, user) = match current_user(&req, &session_store, user_store).await? {
+        Ok(current) => current,
+        Err(resp) => return Ok(resp),
+    };
+    let change: ChangePasswordRequest = match parse_body(req).await? {
+        Ok(body) => body,
+        Err(resp) => return Ok(resp),
+    };
+
+    let account = user.clone();
+    let changed = user_store
+        .blocking(move |store| {
+            if !account.verify_password(&change.current_password) {
+                return Ok(false);
+            }
+            store.change_password(&account.username, &change.new_password).map(|_| true)
+        })
+        .await?;
+    match changed {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(error_response(StatusCode::UNAUTHORIZED, "Current password is incorrect"))
+        }
+        Err(e) => return Ok(store_error_response(e)),
+    }
+    let revoked = session_store.delete_user_sessions(&user.username, Some(&session.id))?;
+
+    log::info!("Password changed for {} ({} other sessions revoked)", user.username, revoked);
+    Ok(json_response(
+        StatusCode::OK,
+        json!({ "message": "Password changed", "revoked_sessions": revoked }),
+    ))
+}
+
+/// `/_admin/users/**` - user administration
+///
+/// | Method | Path | Action |
+/// |--------|------|--------|
+/// | GET | `/_admin/users` | List users |
+/// | POST | `/_admin/users` | Create `{username, password, role}` |
+/// | GET | `/_admin/users/{name}` | Get a user |
+/// | PUT | `/_admin/users/{name}/role` | Assign `{role}` |
+/// | PUT | `/_admin/users/{name}/password` | Reset `{password}` |
+/// | POST | `/_admin/users/{name}/disable` | Disable the account |
+/// | POST | `/_admin/users/{name}/enable` | Re-enable the account |
+/// | DELETE | `/_admin/users/{name}` | Delete the account |
+///
+/// Role changes, password resets, disabling and deletion revoke the user's sessions.
+pub async fn handle_admin_users(
+    req: Request<hyper::body::Incoming>,
+    session_store: Arc<PersistentSessionStore>,
+    user_store: &UserStore,
+    permission_checker: &dyn PermissionChecker,
+    roles: &[String],
+) -> Result<Response<Full<Bytes>>> {
+    let (_, admin) = match current_user(&req, &session_store, user_store).await? {
+        Ok(current) => current,
+        Err(resp) => return Ok(resp),
+    };
+    if !permission_checker.has_permission(&admin.role, USER_ADMIN_PERMISSION) {
+        return Ok(error_response(
+            StatusCode::FORBIDDEN,
+            format!("Permission '{}' required", USER_ADMIN_PERMISSION),
+        ));
+    }
+
+    let path = req.uri().path().to_string();
+    let Some(rest) = path.strip_prefix("/_admin/users") else {
+        return Ok(error_response(StatusCode::NOT_FOUND, "Not found"));
+    };
+    let segments: Vec<&str> = rest.split('/').filter(|s| !s.is_empty()).collect();
+    let method = req.method().clone();
+    let known_role = |role: &str| roles.iter().any(|r| r == role);
+
+    // Account changes run on the blocking pool: hashing and the durable append are slow
+    type Write = Box<dyn FnOnce(&UserStore, &str) -> Result<(), UserStoreError> + Send>;
+    let (username, write): (String, Write) = match (&method, segments.as_slice()) {
+        (&Method::GET, []) => {
+            let users: Vec<_> = user_store.list().iter().map(user_json).collect();
+            return Ok(json_response(StatusCode::OK, json!({ "users": users })));
+        }
+        (&Method::POST, []) => {
+            let create: CreateUserRequest = match parse_body(req).await? {
+                Ok(body) => body,
+                Err(resp) => return Ok(resp),
+            };
+            if !known_role(&create.role) {
+                return Ok(unknown_role(&create.role));
+            }
+            let created = user_store
+                .blocking(move |store| {
+                    store.create(&create.username, &create.password, &create.role)
+                })
+                .await?;
+            return match created {
+                Ok(user) => {
+                    log::info!("User {} created by {}", user.username, admin.username);
+                    Ok(json_response(StatusCode::CREATED, user_json(&user)))
+                }
+                Err(e) => Ok(store_error_response(e)),
+            };
+        }
+        (&Method::GET, [username]) => {
+            return Ok(match user_store.get(username) {
+                Some(user) => json_response(StatusCode::OK, user_json(&user)),
+                None => store_error_response(UserStoreError::NotFound(username.to_string())),
+            });
+        }
+        (&Method::PUT, [username, "role"]) => {
+            let assign: AssignRoleRequest = match parse_body(req).await? {
+                Ok(body) => body,
+                Err(resp) => return Ok(resp),
+            };
+            if !known_role(&assign.role) {
+                return Ok(unknown_role(&assign.role));
+            }
+            let write = move |store: &UserStore, name: &str| store.assign_role(name, &assign.role);
+            (username.to_string(), Box::new(write))
+        }
+        (&Method::PUT, [username, "password"]) => {
+            let reset: SetPasswordRequest = match parse_body(req).await? {
+                Ok(body) => body,
+                Err(resp) => return Ok(resp),
+            };
+            let write =
+                move |store: &UserStore, name: &str| store.change_password(name, &reset.password);
+            (username.to_string(), Box::new(write))
+        }
+        (&Method::POST, [username, action @ ("disable" | "enable")]) => {
+            let active = *action == "enable";
+            if !active && *username == admin.username {
+                return Ok(error_response(StatusCode::BAD_REQUEST, "Cannot disable yourself"));
+            }
+            let write = move |store: &UserStore, name: &str| store.set_active(name, active);
+            (username.to_string(), Box::new(write))
+        }
+        (&Method::DELETE, [username]) => {
+            if *username == admin.username {
+                return Ok(error_response(StatusCode::BAD_REQUEST, "Cannot delete yourself"));
+            }
+            (username.to_string(), Box::new(UserStore::delete))
+        }
+        _ => return Ok(error_response(StatusCode::NOT_FOUND, "Not found")),
+    };
+
+    let name = username.clone();
+    if let Err(e) = user_store.blocking(move |store| write(store, &name)).await? {
+        return Ok(store_error_response(e));
+    }
+    // Existing sessions carry the old role or belong to a locked-out account
+    let revoked = session_store.delete_user_sessions(&username, None)?;
+    log::info!("{} {} by {} ({} sessions revoked)", method, path, admin.username, revoked);
+
+    Ok(match user_store.get(&username) {
+        Some(user) => json_response(StatusCode::OK, user_json(&user)),
+        None => json_response(StatusCode::OK, json!({ "deleted": username })),
+    })
+}
+
+fn unknown_role(role: &str) -> Response<Full<Bytes>> {
+    error_response(StatusCode::BAD_REQUEST, format!("Unknown role '{}'", role))
+}
@@ -0,0 +1,438 @@
+//! Event-sourced user accounts
+//!
+//! `UserStore` keeps RBAC users in memory and persists every change as an event in a
+//! `.raftlog` file, like `PersistentSessionStore` does for sessions. Users can be
+//! registered, created, disabled, re-roled and deleted at runtime, and passwords are
+//! hashed with Argon2id (`security::password`).
+//!
+//! Hashing and the durable append block, so async code runs the store through
+//! `UserStore::blocking`. Writers are serialized by the event store lock; the state lock
+//! is only taken to read or apply, never across the write to disk.
+
+use super::RbacUser;
+use crate::engine::{Event, EventStore, FileStorage};
+use crate::security::password::hash_password;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Minimum password length accepted by the user store
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// User state for event sourcing: username -> user
+pub type UserState = HashMap<String, RbacUser>;
+
+/// Change to a user account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event_type")]
+pub enum UserEvent {
+    #[serde(rename = "UserCreated.v1")]
+    Created { username: String, password_hash: String, role: String, timestamp: DateTime<Utc> },
+    #[serde(rename = "UserPasswordChanged.v1")]
+    PasswordChanged { username: String, password_hash: String, timestamp: DateTime<Utc> },
+    #[serde(rename = "UserRoleAssigned.v1")]
+    RoleAssigned { username: String, role: String, timestamp: DateTime<Utc> },
+    #[serde(rename = "UserActiveChanged.v1")]
+    ActiveChanged { username: String, active: bool, timestamp: DateTime<Utc> },
+    #[serde(rename = "UserDeleted.v1")]
+    Deleted { username: String, timestamp: DateTime<Utc> },
+}
+
+impl UserEvent {
+    pub fn username(&self) -> &str {
+        match self {
+            UserEvent::Created { username, .. }
+            | UserEvent::PasswordChanged { username, .. }
+            | UserEvent::RoleAssigned { username, .. }
+            | UserEvent::ActiveChanged { username, .. }
+            | UserEvent::Deleted { username, .. } => username,
+        }
+    }
+}
+
+impl Event for UserEvent {
+    type State = UserState;
+
+    fn apply(&self, state: &mut Self::State) {
+        match self {
+            UserEvent::Created { username, password_hash, role, .. } => {
+                state.insert(
+                    username.clone(),
+                    RbacUser::new_with_hashed_password(username, password_hash, role),
+                );
+            }
+            UserEvent::PasswordChanged { username, password_hash, .. } => {
+                if let Some(user) = state.get_mut(username) {
+                    user.password_hash = password_hash.clone();
+                }
+            }
+            UserEvent::RoleAssigned { username, role, .. } => {
+                if let Some(user) = state.get_mut(username) {
+                    user.role = role.clone();
+                }
+            }
+            UserEvent::ActiveChanged { username, active, .. } => {
+                if let Some(user) = state.get_mut(username) {
+                    user.active = *active;
+                }
+            }
+            UserEvent::Deleted { username, .. } => {
+                state.remove(username);
+            }
+        }
+    }
+
+    fn aggregate_id(&self) -> Option<String> {
+        Some(self.username().to_string())
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    fn from_json(json: &str) -> crate::engine::EngineResult<Self>
+    where
+        Self: Sized,
+    {
+        serde_json::from_str(json).map_err(|e| {
+            crate::engine::EngineError::SerializationError(format!(
+                "Failed to deserialize UserEvent: {}",
+                e
+            ))
+        })
+    }
+}
+
+/// Error returned by `UserStore` operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserStoreError {
+    NotFound(String),
+    AlreadyExists(String),
+    Invalid(String),
+    Storage(String),
+}
+
+impl fmt::Display for UserStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserStoreError::NotFound(u) => write!(f, "user '{}' not found", u),
+            UserStoreError::AlreadyExists(u) => write!(f, "user '{}' already exists", u),
+            UserStoreError::Invalid(msg) => write!(f, "{}", msg),
+            UserStoreError::Storage(msg) => write!(f, "storage error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for UserStoreError {}
+
+/// Persistent, event-sourced user store
+#[derive(Clone)]
+pub struct UserStore {
+    event_store: Arc<std::sync::Mutex<EventStore>>,
+    state: Arc<std::sync::RwLock<UserState>>,
+}
+
+impl UserStore {
+    /// Open (or create) the store in `data_path` and replay its events
+    pub fn new(data_path: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&data_path)?;
+
+        let storage = FileStorage::new(
+            data_path
+                .to_str()
+                .ok_or_else(|| anyhow::anyhow!("user store path contains invalid UTF-8"))?,
+        )?;
+        let event_store = EventStore::with_storage(storage)?;
+
+        let mut state = UserState::new();
+        for event_json in event_store.get_all_events()? {
+            match serde_json::from_str::<UserEvent>(&event_json) {
+                Ok(event) => event.apply(&mut state),
+                Err(e) => log::warn!("Skipping unreadable user event: {}", e),
+            }
+        }
+        log::info!("Loaded {} users from event store", state.len());
+
+        Ok(Self {
+            event_store: Arc::new(std::sync::Mutex::new(event_store)),
+            state: Arc::new(std::sync::RwLock::new(state)),
+        })
+    }
+
+    /// Create the given users unless an account with the same name already exists
+    ///
+    /// Used to bootstrap the store from `ServerRbacConfig::users`; returns how many were
+    /// created. Later changes made through the store win over the static list.
+    pub fn seed(&self, users: &[RbacUser]) -> Result<usize, UserStoreError> {
+        let mut created = 0;
+        for user in users {
+            let mut store = self.writer();
+            if self.read_state().contains_key(&user.username) {
+                continue;
+            }
+            let mut events = vec![UserEvent::Created {
+                username: user.username.clone(),
+                password_hash: user.password_hash.clone(),
+                role: user.role.clone(),
+                timestamp: Utc::now(),
+            }];
+            if !user.active {
+                events.push(UserEvent::ActiveChanged {
+                    username: user.username.clone(),
+                    active: false,
+                    timestamp: Utc::now(),
+                });
+            }
+            self.append(&mut store, events)?;
+            created += 1;
+        }
+        Ok(created)
+    }
+
+    pub fn get(&self, username: &str) -> Option<RbacUser> {
+        self.read_state().get(username).cloned()
+    }
+
+    /// All users, sorted by username
+    pub fn list(&self) -> Vec<RbacUser> {
+        let mut users: Vec<RbacUser> = self.read_state().values().cloned().collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        users
+    }
+
+    pub fn len(&self) -> usize {
+        self.read_state().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Run `f` on the blocking thread pool
+    ///
+    /// Password hashing and verification are deliberately slow and writes wait for the
+    /// disk, so async handlers call the store through this instead of directly.
+    pub async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&UserStore) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|e| anyhow::anyhow!("user store task failed: {}", e))
+    }
+
+    /// Return the user if it exists, is active and `password` matches
+    pub fn authenticate(&self, username: &str, password: &str) -> Option<RbacUser> {
+        // Verify outside the lock: Argon2 is deliberately slow
+        self.get(username).filter(|u| u.active && u.verify_password(password))
+    }
+
+    /// Create a user with a new password
+    pub fn create(
+        &self,
+        username: &str,
+        password: &str,
+        role: &str,
+    ) -> Result<RbacUser, UserStoreError> {
+        validate_username(username)?;
+        if role.trim().is_empty() {
+            return Err(UserStoreError::Invalid("role must not be empty".to_string()));
+        }
+        let password_hash = hash(password)?;
+
+        let mut store = self.writer();
+        if self.read_state().contains_key(username) {
+            return Err(UserStoreError::AlreadyExists(username.to_string()));
+        }
+        self.append(
+            &mut store,
+            vec![UserEvent::Created {
+                username: username.to_string(),
+                password_hash,
+                role: role.to_string(),
+                timestamp: Utc::now(),
+            }],
+        )?;
+        Ok(self.read_state()[username].clone())
+    }
+
+    pub fn change_password(&self, username: &str, password: &str) -> Result<(), UserStoreError> {
+        let password_hash = hash(password)?;
+        self.update(
+            username,
+            UserEvent::PasswordChanged {
+                username: username.to_string(),
+                password_hash,
+                timestamp: Utc::now(),
+            },
+        )
+    }
+
+    pub fn assign_role(&self, username: &str, role: &str) -> Result<(), UserStoreError> {
+        if role.trim().is_empty() {
+            return Err(UserStoreError::Invalid("role must not be empty".to_string()));
+        }
+        self.update(
+            username,
+            UserEvent::RoleAssigned {
+                username: username.to_string(),
+                role: role.to_string(),
+                timestamp: Utc::now(),
+            },
+        )
+    }
+
+    /// Enable or disable a user; disabled users cannot log in
+    pub fn set_active(&self, username: &str, active: bool) -> Result<(), UserStoreError> {
+        self.update(
+            username,
+            UserEvent::ActiveChanged {
+                username: username.to_string(),
+                active,
+                timestamp: Utc::now(),
+            },
+        )
+    }
+
+    pub fn delete(&self, username: &str) -> Result<(), UserStoreError> {
+        self.update(
+            username,
+            UserEvent::Deleted { username: username.to_string(), timestamp: Utc::now() },
+        )
+    }
+
+    /// Append an event for an existing user
+    fn update(&self, username: &str, event: UserEvent) -> Result<(), UserStoreError> {
+        let mut store = self.writer();
+        if !self.read_state().contains_key(username) {
+            return Err(UserStoreError::NotFound(username.to_string()));
+        }
+        self.append(&mut store, vec![event])
+    }
+
+    /// The event store, locked: holding it makes the caller the only writer, so the
+    /// state it checked cannot change before its events apply
+    fn writer(&self) -> std::sync::MutexGuard<'_, EventStore> {
+        self.event_store.lock().expect("event store lock poisoned")
+    }
+
+    /// Persist `events`, then apply them; readers only wait for the apply
+    fn append(&self, store: &mut EventStore, events: Vec<UserEvent>) -> Result<(), UserStoreError> {
+        for event in &events {
+            store.append_event(event).map_err(|e| UserStoreError::Storage(e.to_string()))?;
+        }
+        store.flush_events().map_err(|e| UserStoreError::Storage(e.to_string()))?;
+        let mut state = self.write_state();
+        for event in events {
+            event.apply(&mut state);
+        }
+        Ok(())
+    }
+
+    fn read_state(&self) -> std::sync::RwLockReadGuard<'_, UserState> {
+        self.state.read().expect("user state lock poisoned")
+    }
+
+    fn write_state(&self) -> std::sync::RwLockWriteGuard<'_, UserState> {
+        self.state.write().expect("user state lock poisoned")
+    }
+}
+
+fn validate_username(username: &str) -> Result<(), UserStoreError> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '@');
+    if username.is_empty() || username.len() > 64 || !username.chars().all(valid_char) {
+        return Err(UserStoreError::Invalid(
+            "username must be 1-64 characters of letters, digits, '.', '_', '-' or '@'".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn hash(password: &str) -> Result<String, UserStoreError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(UserStoreError::Invalid(format!(
+            "password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    hash_password(password).map_err(|e| UserStoreError::Storage(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_user_lifecycle_survives_restart() {
+        let dir = TempDir::new().unwrap();
+        let store = UserStore::new(dir.path().to_path_buf()).unwrap();
+
+        store.create("alice", "correct horse", "User").unwrap();
+        assert!(matches!(
+            store.create("alice", "another pass", "User"),
+            Err(UserStoreError::AlreadyExists(_))
+        ));
+        assert!(store.authenticate("alice", "correct horse").is_some());
+
+        store.assign_role("alice", "Editor").unwrap();
+        store.change_password("alice", "battery staple").unwrap();
+        store.create("bob", "bob password", "User").unwrap();
+        store.set_active("bob", false).unwrap();
+        store.create("carol", "carol password", "User").unwrap();
+        store.delete("carol").unwrap();
+
+        let reopened = UserStore::new(dir.path().to_path_buf()).unwrap();
+        let alice = reopened.get("alice").unwrap();
+        assert_eq!(alice.role, "Editor");
+        assert!(reopened.authenticate("alice", "correct horse").is_none());
+        assert!(reopened.authenticate("alice", "battery staple").is_some());
+        assert!(reopened.authenticate("bob", "bob password").is_none());
+        assert!(reopened.get("carol").is_none());
+        assert_eq!(reopened.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_blocking_writes() {
+        let dir = TempDir::new().unwrap();
+        let store = UserStore::new(dir.path().to_path_buf()).unwrap();
+
+        // Same name from several tasks: exactly one create wins
+        let attempts =
+            (0..4).map(|_| store.blocking(|s| s.create("erin", "erin password", "User")));
+        let results = futures::future::join_all(attempts).await;
+        let created = results.into_iter().filter(|r| matches!(r, Ok(Ok(_)))).count();
+        assert_eq!(created, 1);
+
+        let user = store.blocking(|s| s.authenticate("erin", "erin password")).await.unwrap();
+        assert_eq!(user.map(|u| u.username), Some("erin".to_string()));
+    }
+
+    #[test]
+    fn test_seed_and_validation() {
+        let dir = TempDir::new().unwrap();
+        let store = UserStore::new(dir.path().to_path_buf()).unwrap();
+
+        let seed = vec![RbacUser::new("admin", "admin-password", "Admin")];
+        assert_eq!(store.seed(&seed).unwrap(), 1);
+        store.assign_role("admin", "Auditor").unwrap();
+        // Re-seeding does not overwrite runtime changes
+        assert_eq!(store.seed(&seed).unwrap(), 0);
+        assert_eq!(store.get("admin").unwrap().role, "Auditor");
+
+        let invalid =
+            |r: Result<RbacUser, UserStoreError>| matches!(r, Err(UserStoreError::Invalid(_)));
+        assert!(invalid(store.create("bad name", "password1", "User")));
+        assert!(invalid(store.create("dave", "short", "User")));
+        assert_eq!(
+            store.set_active("nobody", false),
+            Err(UserStoreError::NotFound("nobody".to_string()))
+        );
+    }
+}
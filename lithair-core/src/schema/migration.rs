@@ -0,0 +1,321 @@
+//! Data migrations for breaking schema changes (event upcasting)
+//!
+//! Events are never rewritten on disk. Instead each event records the schema
+//! version of the model that produced it (`EventEnvelope::schema_version`), and
+//! registered upcasters bring its JSON payload up to the current `ModelSpec.version`
+//! before it is deserialized. Upcasters run on replay and when a schema change is
+//! approved through `/_admin/schema/approve`.
+//!
+//! ```rust,ignore
+//! use lithair_core::schema::{SchemaMigrations, Upcaster};
+//!
+//! // Product v1 -> v2: `name` became `title`, `price` went from string to number,
+//! // `stock` was added
+//! let migrations = SchemaMigrations::new().upcast(
+//!     1,
+//!     Upcaster::new()
+//!         .rename_field("name", "title")
+//!         .change_type("price", |v| match v {
+//!             Value::String(s) => s.parse::<f64>().map(Value::from).map_err(|e| e.to_string()),
+//!             other => Ok(other),
+//!         })
+//!         .backfill_default("stock", json!(0)),
+//! );
+//! ```
+//!
+//! Events written before versions were recorded count as version 1, the default
+//! `ModelSpec.version`.
+
+use super::ModelSpec;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Version assumed for events that carry no `schema_version`
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+/// Converts one field value to its new type
+pub type ConvertFn = Arc<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+/// Rewrites a whole payload object
+pub type RewriteFn = Arc<dyn Fn(&mut Map<String, Value>) -> Result<(), String> + Send + Sync>;
+
+/// One transformation applied by an upcaster
+#[derive(Clone)]
+pub enum MigrationStep {
+    /// Move a field to a new name (no-op when the old field is absent)
+    RenameField { from: String, to: String },
+    /// Convert the value of a field (skipped when the field is absent)
+    ChangeType { field: String, convert: ConvertFn },
+    /// Set a field that is absent or null
+    BackfillDefault { field: String, value: Value },
+    /// Drop a field
+    RemoveField { field: String },
+    /// Arbitrary rewrite of the payload
+    Custom(RewriteFn),
+}
+
+impl fmt::Debug for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationStep::RenameField { from, to } => write!(f, "RenameField({from} -> {to})"),
+            MigrationStep::ChangeType { field, .. } => write!(f, "ChangeType({field})"),
+            MigrationStep::BackfillDefault { field, value } => {
+                write!(f, "BackfillDefault({field} = {value})")
+            }
+            MigrationStep::RemoveField { field } => write!(f, "RemoveField({field})"),
+            MigrationStep::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl MigrationStep {
+    fn apply(&self, object: &mut Map<String, Value>) -> Result<(), String> {
+        match self {
+            MigrationStep::RenameField { from, to } => {
+                if let Some(value) = object.remove(from) {
+                    object.insert(to.clone(), value);
+                }
+            }
+            MigrationStep::ChangeType { field, convert } => {
+                if let Some(value) = object.remove(field) {
+                    let converted = convert(value).map_err(|e| format!("field '{field}': {e}"))?;
+                    object.insert(field.clone(), converted);
+                }
+            }
+            MigrationStep::BackfillDefault { field, value } => {
+                let slot = object.entry(field.clone()).or_insert(Value::Null);
+                if slot.is_null() {
+                    *slot = value.clone();
+                }
+            }
+            MigrationStep::RemoveField { field } => {
+                object.remove(field);
+            }
+            MigrationStep::Custom(rewrite) => rewrite(object)?,
+        }
+        Ok(())
+    }
+}
+
+/// Steps upgrading a payload from one schema version to the next
+#[derive(Debug, Clone, Default)]
+pub struct Upcaster {
+    steps: Vec<MigrationStep>,
+}
+
+impl Upcaster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rename `from` to `to`
+    pub fn rename_field(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.steps.push(MigrationStep::RenameField { from: from.into(), to: to.into() });
+        self
+    }
+
+    /// Convert the value of `field`; an error fails the event's migration
+    pub fn change_type<F>(mut self, field: impl Into<String>, convert: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        let convert = Arc::new(convert);
+        self.steps.push(MigrationStep::ChangeType { field: field.into(), convert });
+        self
+    }
+
+    /// Fill `field` with `value` when the old payload lacks it
+    pub fn backfill_default(mut self, field: impl Into<String>, value: Value) -> Self {
+        self.steps.push(MigrationStep::BackfillDefault { field: field.into(), value });
+        self
+    }
+
+    /// Drop `field`
+    pub fn remove_field(mut self, field: impl Into<String>) -> Self {
+        self.steps.push(MigrationStep::RemoveField { field: field.into() });
+        self
+    }
+
+    /// Custom rewrite of the payload object
+    pub fn map<F>(mut self, rewrite: F) -> Self
+    where
+        F: Fn(&mut Map<String, Value>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.steps.push(MigrationStep::Custom(Arc::new(rewrite)));
+        self
+    }
+
+    pub fn steps(&self) -> &[MigrationStep] {
+        &self.steps
+    }
+}
+
+/// Failure to bring an event payload up to the current schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpcastError {
+    /// Version the failing upcaster starts from
+    pub from_version: u32,
+    pub message: String,
+}
+
+impl fmt::Display for UpcastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "upcast from v{} failed: {}", self.from_version, self.message)
+    }
+}
+
+impl std::error::Error for UpcastError {}
+
+/// Upcasters of one model, keyed by the `ModelSpec.version` they upgrade from
+#[derive(Debug, Clone, Default)]
+pub struct SchemaMigrations {
+    upcasters: BTreeMap<u32, Upcaster>,
+}
+
+impl SchemaMigrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the upcaster taking payloads from `from_version` to `from_version + 1`
+    ///
+    /// Versions without an upcaster pass payloads through unchanged, which is what
+    /// additive changes need.
+    pub fn upcast(mut self, from_version: u32, upcaster: Upcaster) -> Self {
+        self.upcasters.insert(from_version, upcaster);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.upcasters.is_empty()
+    }
+
+    /// Versions that have an upcaster registered
+    pub fn versions(&self) -> impl Iterator<Item = u32> + '_ {
+        self.upcasters.keys().copied()
+    }
+
+    /// Bring `payload`, written at schema version `from`, up to version `to`
+    pub fn apply(&self, payload: Value, from: u32, to: u32) -> Result<Value, UpcastError> {
+        if from >= to || self.upcasters.range(from..to).next().is_none() {
+            return Ok(payload);
+        }
+        let Value::Object(mut object) = payload else {
+            return Err(UpcastError {
+                from_version: from,
+                message: "payload is not a JSON object".to_string(),
+            });
+        };
+        for (version, upcaster) in self.upcasters.range(from..to) {
+            for step in &upcaster.steps {
+                step.apply(&mut object)
+                    .map_err(|message| UpcastError { from_version: *version, message })?;
+            }
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Fill fields absent from `payload` with their `#[db(default = X)]` value
+///
+/// Defaults are parsed as JSON (`0`, `false`, `"\"draft\""`) and fall back to a plain
+/// string, so adding a field with a default never needs a hand-written upcaster.
+pub fn backfill_spec_defaults(payload: &mut Value, spec: &ModelSpec) {
+    let Some(object) = payload.as_object_mut() else { return };
+    for (field, constraints) in &spec.fields {
+        let Some(default) = constraints.default_value.as_deref() else { continue };
+        if object.get(field).is_none_or(Value::is_null) {
+            let value = serde_json::from_str(default)
+                .unwrap_or_else(|_| Value::String(default.to_string()));
+            object.insert(field.clone(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{FieldConstraints, FieldPermissions};
+    use serde_json::json;
+
+    fn product_migrations() -> SchemaMigrations {
+        SchemaMigrations::new()
+            .upcast(
+                1,
+                Upcaster::new()
+                    .rename_field("name", "title")
+                    .backfill_default("stock", json!(0)),
+            )
+            .upcast(
+                2,
+                Upcaster::new().change_type("price", |v| match v {
+                    Value::String(s) => {
+                        s.parse::<f64>().map(Value::from).map_err(|e| e.to_string())
+                    }
+                    other => Ok(other),
+                }),
+            )
+    }
+
+    #[test]
+    fn test_upcast_chain() {
+        let migrations = product_migrations();
+        let v1 = json!({"id": "p1", "name": "Lamp", "price": "12.5"});
+
+        let v3 = migrations.apply(v1.clone(), 1, 3).unwrap();
+        assert_eq!(v3, json!({"id": "p1", "title": "Lamp", "price": 12.5, "stock": 0}));
+
+        // Only the steps between the two versions run
+        let v2 = migrations.apply(v1, 1, 2).unwrap();
+        assert_eq!(v2["price"], "12.5");
+        assert_eq!(migrations.apply(v3.clone(), 3, 3).unwrap(), v3);
+    }
+
+    #[test]
+    fn test_upcast_errors() {
+        let migrations = product_migrations();
+        let err = migrations.apply(json!({"price": "cheap"}), 2, 3).unwrap_err();
+        assert_eq!(err.from_version, 2);
+        assert!(err.message.contains("price"));
+        assert!(migrations.apply(json!([1, 2]), 1, 2).is_err());
+    }
+
+    #[test]
+    fn test_backfill_spec_defaults() {
+        let mut spec = ModelSpec {
+            model_name: "Product".to_string(),
+            version: 2,
+            fields: Default::default(),
+            indexes: vec![],
+            foreign_keys: vec![],
+        };
+        let constraints = |default: &str| FieldConstraints {
+            primary_key: false,
+            unique: false,
+            indexed: false,
+            foreign_key: None,
+            nullable: false,
+            immutable: false,
+            audited: false,
+            versioned: 0,
+            retention: 0,
+            snapshot_only: false,
+            validation_rules: vec![],
+            permissions: FieldPermissions {
+                read_permission: None,
+                write_permission: None,
+                owner_field: false,
+            },
+            default_value: Some(default.to_string()),
+            field_type: None,
+        };
+        spec.fields.insert("stock".to_string(), constraints("5"));
+        spec.fields.insert("status".to_string(), constraints("draft"));
+
+        let mut payload = json!({"id": "p1", "stock": 3});
+        backfill_spec_defaults(&mut payload, &spec);
+        assert_eq!(payload, json!({"id": "p1", "stock": 3, "status": "draft"}));
+    }
+}
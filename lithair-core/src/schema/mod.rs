@@ -7,6 +7,13 @@ pub mod relations;
 
 // Module pour la synchronisation de schéma en cluster
 pub mod sync;
+
+// Module pour les migrations de données (upcasting des événements)
+pub mod migration;
+pub use migration::{
+    backfill_spec_defaults, MigrationStep, SchemaMigrations, UpcastError, Upcaster,
+    LEGACY_SCHEMA_VERSION,
+};
 pub use relations::{
     CascadeStrategy, ModelRelationSpec, RelationRegistry, RelationSpec, RelationSpecExtractor,
     RelationType,
@@ -51,6 +51,17 @@ pub struct ForeignKeySpec {
     pub indexed: bool,
 }
 
+impl ForeignKeySpec {
+    /// Nom de la relation pour `?expand=` : `author_id` -> `author`, sinon `parent` ->
+    /// `parent_data` (même convention que l'AutoJoiner)
+    pub fn relation_name(&self) -> String {
+        match self.field_name.strip_suffix("_id") {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("{}_data", self.field_name),
+        }
+    }
+}
+
 /// Spécification d'une relation (inverse d'une foreign key)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelationSpec {
@@ -77,6 +88,13 @@ pub struct ModelRelationSpec {
     pub relations: HashMap<String, RelationSpec>,
 }
 
+impl ModelRelationSpec {
+    /// Foreign key suivie par la relation `relation`
+    pub fn foreign_key_for(&self, relation: &str) -> Option<&ForeignKeySpec> {
+        self.foreign_keys.values().find(|fk| fk.relation_name() == relation)
+    }
+}
+
 /// Trait pour extraire les spécifications de relations d'un modèle
 pub trait RelationSpecExtractor {
     /// Extraire les métadonnées de relations du modèle
@@ -173,6 +191,29 @@ mod tests {
         assert!(fk_spec.nullable);
     }
 
+    #[test]
+    fn test_foreign_key_relation_name() {
+        let fk = |field: &str| ForeignKeySpec {
+            field_name: field.to_string(),
+            referenced_model: "Author".to_string(),
+            referenced_field: "id".to_string(),
+            relation_type: RelationType::ManyToOne,
+            cascade: CascadeStrategy::None,
+            nullable: false,
+            indexed: false,
+        };
+        assert_eq!(fk("author_id").relation_name(), "author");
+        assert_eq!(fk("parent").relation_name(), "parent_data");
+
+        let spec = ModelRelationSpec {
+            model_name: "Article".to_string(),
+            foreign_keys: HashMap::from([("author_id".to_string(), fk("author_id"))]),
+            relations: HashMap::new(),
+        };
+        assert_eq!(spec.foreign_key_for("author").unwrap().field_name, "author_id");
+        assert!(spec.foreign_key_for("author_id").is_none());
+    }
+
     #[test]
     fn test_relation_registry() {
         let mut registry = RelationRegistry::new();
@@ -6,10 +6,12 @@
 use super::events::{SessionCreated, SessionData, SessionDeleted, SessionState};
 use super::{Session, SessionStore};
 use crate::engine::{Event, EventStore, FileStorage};
+use crate::metrics::PersistentSessionMetrics;
 use anyhow::Result;
 use chrono::Utc;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 /// Persistent session store using Lithair's EventStore
@@ -17,6 +19,8 @@ use std::sync::Arc;
 pub struct PersistentSessionStore {
     event_store: Arc<std::sync::Mutex<EventStore>>,
     state: Arc<std::sync::RwLock<SessionState>>,
+    total_created: Arc<AtomicU64>,
+    total_deleted: Arc<AtomicU64>,
 }
 
 impl PersistentSessionStore {
@@ -60,7 +64,49 @@ impl PersistentSessionStore {
 
         log::info!("Loaded {} sessions from event store", count);
 
-        Ok(Self { event_store: Arc::new(std::sync::Mutex::new(event_store)), state })
+        Ok(Self {
+            event_store: Arc::new(std::sync::Mutex::new(event_store)),
+            state,
+            total_created: Arc::new(AtomicU64::new(0)),
+            total_deleted: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Get session metrics
+    pub fn metrics(&self) -> PersistentSessionMetrics {
+        PersistentSessionMetrics {
+            active_sessions: self.state.read().expect("session state lock poisoned").len(),
+            total_created: self.total_created.load(Ordering::Relaxed),
+            total_deleted: self.total_deleted.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Delete every session of `user_id`, except `keep` when given
+    ///
+    /// Used to log a user out everywhere after a password, role or status change.
+    pub fn delete_user_sessions(&self, user_id: &str, keep: Option<&str>) -> Result<usize> {
+        let session_ids: Vec<String> = {
+            let state = self.state.read().expect("session state lock poisoned");
+            state
+                .iter()
+                .filter(|(id, data)| data.user_id == user_id && keep != Some(id.as_str()))
+                .map(|(id, _)| id.clone())
+                .collect()
+        };
+
+        for session_id in &session_ids {
+            self.apply_event(SessionDeleted {
+                event_type: "SessionDeleted.v1".to_string(),
+                session_id: session_id.clone(),
+                user_id: None,
+                role: None,
+                expires_at: None,
+                data: None,
+            })?;
+            self.total_deleted.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(session_ids.len())
     }
 
     /// Apply an event and persist it
@@ -122,6 +168,7 @@ impl SessionStore for PersistentSessionStore {
                 data: Some(session.data.clone()),
             };
             self.apply_event(event)?;
+            self.total_created.fetch_add(1, Ordering::Relaxed);
         }
 
         Ok(())
@@ -138,6 +185,7 @@ impl SessionStore for PersistentSessionStore {
         };
 
         self.apply_event(event)?;
+        self.total_deleted.fetch_add(1, Ordering::Relaxed);
 
         Ok(())
     }
@@ -165,6 +213,7 @@ impl SessionStore for PersistentSessionStore {
                 data: None,
             };
             self.apply_event(event)?;
+            self.total_deleted.fetch_add(1, Ordering::Relaxed);
             removed += 1;
         }
 
@@ -43,6 +43,8 @@ use quote::{quote, ToTokens};
 use std::collections::HashMap;
 use syn::{parse2, Attribute, Data, DeriveInput, Error, Field, Fields, Meta};
 
+use crate::validation::{self, ParsedRule};
+
 /// Parsed field attributes from declarative annotations
 #[derive(Debug, Default)]
 struct FieldAttributes {
@@ -76,6 +78,7 @@ struct FieldAttributes {
     belongs_to: Option<String>,
     cascade_delete: bool,
     cascade_null: bool,
+    cascade_restrict: bool,
     relation_lazy: bool,
 
     // Persistence attributes
@@ -171,6 +174,64 @@ fn parse_firewall_attributes(input: &DeriveInput) -> FirewallAttributes {
     fw
 }
 
+/// Parse struct-level #[db(unique_together = "a, b")] composite unique constraints
+fn parse_model_unique_groups(input: &DeriveInput) -> Vec<Vec<String>> {
+    let mut groups = Vec::new();
+    for attr in &input.attrs {
+        if attr.path().is_ident("db") {
+            if let Meta::List(meta_list) = &attr.meta {
+                let tokens: Vec<String> =
+                    meta_list.tokens.clone().into_iter().map(|t| t.to_string()).collect();
+                for (i, token) in tokens.iter().enumerate() {
+                    if token == "unique_together" && i + 2 < tokens.len() && tokens[i + 1] == "=" {
+                        if let Some(value) = extract_string_value(&tokens[i + 2]) {
+                            let fields = split_csv(&value);
+                            if !fields.is_empty() {
+                                groups.push(fields);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    groups
+}
+
+/// Parse struct-level #[db(soft_delete)]
+fn parse_model_soft_delete(input: &DeriveInput) -> bool {
+    for attr in &input.attrs {
+        if attr.path().is_ident("db") {
+            if let Meta::List(meta_list) = &attr.meta {
+                if meta_list.tokens.clone().into_iter().any(|t| t.to_string() == "soft_delete") {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Parse struct-level #[schema(version = 2)]; models without it are at version 1
+fn parse_model_schema_version(input: &DeriveInput) -> u32 {
+    for attr in &input.attrs {
+        if attr.path().is_ident("schema") {
+            if let Meta::List(meta_list) = &attr.meta {
+                let tokens: Vec<String> =
+                    meta_list.tokens.clone().into_iter().map(|t| t.to_string()).collect();
+                for (i, token) in tokens.iter().enumerate() {
+                    if token == "version" && i + 2 < tokens.len() && tokens[i + 1] == "=" {
+                        if let Ok(version) = tokens[i + 2].parse::<u32>() {
+                            return version;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    1
+}
+
 /// Server-level attributes from #[server(...)]
 #[derive(Debug, Default)]
 struct ServerAttributes {
@@ -321,20 +382,24 @@ fn parse_lifecycle_attributes(attrs: &mut FieldAttributes, attr: &Attribute) {
 fn parse_http_attributes(attrs: &mut FieldAttributes, attr: &Attribute) {
     let meta = &attr.meta;
     if let Meta::List(meta_list) = meta {
-        for nested in meta_list.tokens.clone().into_iter() {
-            let nested_str = nested.to_string();
+        let tokens: Vec<String> =
+            meta_list.tokens.clone().into_iter().map(|t| t.to_string()).collect();
+        for (i, nested_str) in tokens.iter().enumerate() {
             match nested_str.as_str() {
                 "expose" => attrs.expose = true,
                 _ if nested_str.starts_with("expose") && nested_str.contains("false") => {
                     attrs.expose = false;
                 }
-                _ if nested_str.starts_with("validate") => {
-                    if let Some(value) = extract_string_value(&nested_str) {
-                        attrs.validation.push(value);
+                // validate = "rule, rule(args)" arrives as three separate tokens
+                "validate" => {
+                    if i + 2 < tokens.len() && tokens[i + 1] == "=" {
+                        if let Ok(lit) = syn::parse_str::<syn::LitStr>(&tokens[i + 2]) {
+                            attrs.validation.push(lit.value());
+                        }
                     }
                 }
                 _ if nested_str.starts_with("serialize") => {
-                    if let Some(value) = extract_string_value(&nested_str) {
+                    if let Some(value) = extract_string_value(nested_str) {
                         attrs.serialization = Some(value);
                     }
                 }
@@ -415,6 +480,7 @@ fn parse_relation_attributes(attrs: &mut FieldAttributes, attr: &Attribute) {
             match token.as_str() {
                 "cascade_delete" => attrs.cascade_delete = true,
                 "cascade_null" => attrs.cascade_null = true,
+                "restrict" => attrs.cascade_restrict = true,
                 "lazy" => attrs.relation_lazy = true,
                 "eager" => attrs.relation_lazy = false,
                 "indexed" => { /* handled in db attributes */ }
@@ -577,6 +643,7 @@ pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
     // Collect read/write permissions from fields for permission gating
     let mut read_perms_vec: Vec<syn::LitStr> = Vec::new();
     let mut write_perms_vec: Vec<syn::LitStr> = Vec::new();
+    let mut field_write_perm_arms = Vec::new();
     if let Data::Struct(data_struct) = &input.data {
         if let Fields::Named(fields_named) = &data_struct.fields {
             for field in &fields_named.named {
@@ -585,7 +652,12 @@ pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
                     read_perms_vec.push(syn::LitStr::new(&rp, Span::call_site()));
                 }
                 if let Some(wp) = attrs.write_permission {
-                    write_perms_vec.push(syn::LitStr::new(&wp, Span::call_site()));
+                    let wp_lit = syn::LitStr::new(&wp, Span::call_site());
+                    if let Some(ident) = &field.ident {
+                        let field_lit = syn::LitStr::new(&ident.to_string(), Span::call_site());
+                        field_write_perm_arms.push(quote! { #field_lit => Some(#wp_lit), });
+                    }
+                    write_perms_vec.push(wp_lit);
                 }
             }
         }
@@ -640,6 +712,20 @@ pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
         }
     };
 
+    // Generate field_write_permission_fn (per-field checks of PATCH)
+    let field_write_permission_fn = if !field_write_perm_arms.is_empty() {
+        quote! {
+            fn field_write_permission(field: &str) -> Option<&'static str> {
+                match field {
+                    #(#field_write_perm_arms)*
+                    _ => None,
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     // PROCEED TO FULL PARSING (Deleted quick-fix return)
 
     let fields = match &input.data {
@@ -679,6 +765,147 @@ pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
         }
     }
 
+    // Compile #[http(validate = "...")] rules into per-field checks
+    let mut validation_checks: Vec<TokenStream> = Vec::new();
+    for field in fields {
+        let Some(field_ident) = &field.ident else { continue };
+        let field_name_str = field_ident.to_string();
+        let Some(attrs) = field_specs.get(&field_name_str) else { continue };
+        if attrs.validation.is_empty() {
+            continue;
+        }
+
+        let mut builtin_rules: Vec<TokenStream> = Vec::new();
+        let mut custom_checks: Vec<TokenStream> = Vec::new();
+        for spec in &attrs.validation {
+            let parsed = match validation::parse_rules(spec) {
+                Ok(parsed) => parsed,
+                Err(msg) => return Error::new_spanned(field_ident, msg).to_compile_error(),
+            };
+            for rule in parsed {
+                match rule {
+                    ParsedRule::Builtin(rule_ts) => builtin_rules.push(rule_ts),
+                    ParsedRule::Custom(path) => custom_checks.push(quote! {
+                        if let Err(__msg) = #path(&self.#field_ident) {
+                            __errors.add(#field_name_str, "custom", __msg.to_string());
+                        }
+                    }),
+                }
+            }
+        }
+
+        let builtin_block = if builtin_rules.is_empty() {
+            quote! {}
+        } else {
+            quote! {
+                let __value = serde_json::to_value(&self.#field_ident)
+                    .unwrap_or(serde_json::Value::Null);
+                #( __errors.check(#field_name_str, &#builtin_rules, &__value); )*
+            }
+        };
+        validation_checks.push(quote! {
+            {
+                #builtin_block
+                #(#custom_checks)*
+            }
+        });
+    }
+
+    // Unique constraints: single #[db(unique)] fields plus struct-level groups
+    let unique_groups = parse_model_unique_groups(&input);
+    let schema_version = parse_model_schema_version(&input);
+    let soft_delete = parse_model_soft_delete(&input);
+    for group in &unique_groups {
+        if let Some(unknown) = group.iter().find(|f| !field_specs.contains_key(*f)) {
+            return Error::new_spanned(
+                name,
+                format!("unique_together references unknown field `{}`", unknown),
+            )
+            .to_compile_error();
+        }
+    }
+    let mut unique_constraint_tokens: Vec<TokenStream> = field_names
+        .iter()
+        .filter(|f| field_specs.get(*f).map(|a| a.unique && !a.primary_key).unwrap_or(false))
+        .map(|f| quote! { vec![#f.to_string()] })
+        .collect();
+    let indexed_field_names: Vec<&String> = field_names
+        .iter()
+        .filter(|f| field_specs.get(*f).map(|a| a.indexed).unwrap_or(false))
+        .collect();
+    // Relations: foreign keys (`fk`, `foreign_key`, `belongs_to`) and inverse relations
+    // (`has_many`, `has_one`), for `?expand=`
+    let foreign_key_tokens: Vec<TokenStream> = field_names
+        .iter()
+        .filter_map(|f| {
+            let attrs = field_specs.get(f)?;
+            let referenced = attrs.foreign_key.as_ref().or(attrs.belongs_to.as_ref())?;
+            let relation_type = if attrs.unique {
+                quote! { lithair_core::schema::RelationType::OneToOne }
+            } else {
+                quote! { lithair_core::schema::RelationType::ManyToOne }
+            };
+            let cascade = if attrs.cascade_delete {
+                quote! { lithair_core::schema::CascadeStrategy::Delete }
+            } else if attrs.cascade_null {
+                quote! { lithair_core::schema::CascadeStrategy::SetNull }
+            } else if attrs.cascade_restrict {
+                quote! { lithair_core::schema::CascadeStrategy::Restrict }
+            } else {
+                quote! { lithair_core::schema::CascadeStrategy::None }
+            };
+            let (nullable, indexed) = (attrs.nullable, attrs.indexed);
+            Some(quote! {
+                lithair_core::schema::RelationForeignKeySpec {
+                    field_name: #f.to_string(),
+                    referenced_model: #referenced.to_string(),
+                    referenced_field: "id".to_string(),
+                    relation_type: #relation_type,
+                    cascade: #cascade,
+                    nullable: #nullable,
+                    indexed: #indexed,
+                }
+            })
+        })
+        .collect();
+    let relation_tokens: Vec<TokenStream> = field_names
+        .iter()
+        .filter_map(|f| {
+            let attrs = field_specs.get(f)?;
+            let (source, relation_type) = match (&attrs.has_many, &attrs.has_one) {
+                (Some(source), _) => {
+                    (source, quote! { lithair_core::schema::RelationType::OneToMany })
+                }
+                (None, Some(source)) => {
+                    (source, quote! { lithair_core::schema::RelationType::OneToOne })
+                }
+                (None, None) => return None,
+            };
+            let lazy = attrs.relation_lazy;
+            Some(quote! {
+                lithair_core::schema::RelationSpec {
+                    relation_name: #f.to_string(),
+                    source_model: #source.to_string(),
+                    source_field: String::new(),
+                    relation_type: #relation_type,
+                    lazy: #lazy,
+                }
+            })
+        })
+        .collect();
+    let mut composite_index_pushes: Vec<TokenStream> = Vec::new();
+    for group in &unique_groups {
+        let index_suffix = group.join("_");
+        unique_constraint_tokens.push(quote! { vec![#(#group.to_string()),*] });
+        composite_index_pushes.push(quote! {
+            indexes.push(IndexSpec {
+                name: format!("uniq_{}_{}", #name_lit.to_lowercase(), #index_suffix),
+                fields: vec![#(#group.to_string()),*],
+                unique: true,
+            });
+        });
+    }
+
     // Generate model-specific type names
     let spec_name = syn::Ident::new(&format!("{}DeclarativeSpec", name), name.span());
     let attrs_name = syn::Ident::new(&format!("{}FieldAttributes", name), name.span());
@@ -1032,9 +1259,12 @@ pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
                     }
                 }
 
+                // Composite unique constraints from #[db(unique_together = "...")]
+                #(#composite_index_pushes)*
+
                 ModelSpec {
                     model_name: #name_lit.to_string(),
-                    version: 1, // TODO: extraire depuis un attribut #[schema(version = 2)]
+                    version: #schema_version,
                     fields: schema_fields,
                     indexes,
                     foreign_keys,
@@ -1049,7 +1279,7 @@ pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
             }
 
             fn schema_version(&self) -> u32 {
-                1 // TODO: extraire depuis un attribut #[schema(version = 2)]
+                #schema_version
             }
 
             fn field_constraints(&self, field_name: &str) -> Option<lithair_core::schema::FieldConstraints> {
@@ -1131,37 +1361,43 @@ pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
                 self.#pk_field_ident.to_string()
             }
 
-            fn validate(&self) -> Result<(), String> {
-                // Auto-generate basic validation from #[http(validate = "...")] attributes
-                let spec = Self::get_declarative_spec();
+            fn indexed_fields() -> Vec<String> {
+                vec![#(#indexed_field_names.to_string()),*]
+            }
 
-                for (field_name, attrs) in &spec.fields {
-                    if attrs.expose {
-                        for validation_rule in &attrs.validation {
-                            match validation_rule.as_str() {
-                                "non_empty" => {
-                                    // TODO: Use reflection to check if field is empty
-                                    // For now, assume validation passes
-                                }
-                                rule if rule.starts_with("min_value(") => {
-                                    // TODO: Parse min_value and validate
-                                    // For now, assume validation passes
-                                }
-                                _ => {
-                                    // Unknown validation rule, assume valid
-                                }
-                            }
-                        }
-                    }
-                }
+            fn unique_constraints() -> Vec<Vec<String>> {
+                vec![#(#unique_constraint_tokens),*]
+            }
+
+            fn soft_delete() -> bool {
+                #soft_delete
+            }
+
+            fn foreign_keys() -> Vec<lithair_core::schema::RelationForeignKeySpec> {
+                vec![#(#foreign_key_tokens),*]
+            }
+
+            fn relations() -> Vec<lithair_core::schema::RelationSpec> {
+                vec![#(#relation_tokens),*]
+            }
+
+            fn validate(&self) -> Result<(), String> {
+                self.validate_fields().map_err(|errors| errors.to_string())
+            }
 
-                Ok(()) // All validations passed
+            fn validate_fields(&self) -> Result<(), lithair_core::http::ValidationErrors> {
+                // Generated from #[http(validate = "...")] attributes
+                #[allow(unused_mut)]
+                let mut __errors = lithair_core::http::ValidationErrors::new();
+                #(#validation_checks)*
+                __errors.into_result()
             }
 
             // INJECTED FUNCTIONS
             #fw_fn
             #can_read_fn
             #can_write_fn
+            #field_write_permission_fn
         }
     };
 
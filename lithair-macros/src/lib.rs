@@ -12,6 +12,7 @@ mod lifecycle;
 mod lithair_model;
 mod page;
 mod rbac_role;
+mod validation;
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput, ItemImpl};
 
@@ -69,7 +70,18 @@ pub fn derive_lifecycle_aware(input: TokenStream) -> TokenStream {
 /// ```
 #[proc_macro_derive(
     DeclarativeModel,
-    attributes(db, lifecycle, http, permission, rbac, relation, persistence, server, firewall)
+    attributes(
+        db,
+        lifecycle,
+        http,
+        permission,
+        rbac,
+        relation,
+        persistence,
+        server,
+        firewall,
+        schema
+    )
 )]
 pub fn derive_declarative_model(input: TokenStream) -> TokenStream {
     declarative_simple::derive_declarative_model(input.into()).into()
@@ -0,0 +1,239 @@
+//! Compile-time parsing of `#[http(validate = "...")]` rules
+//!
+//! Rules are parsed once by the derive macro and turned into direct calls to
+//! `lithair_core::http::ValidationRule::check` (or to a user function for
+//! `custom(...)`), so an unknown or malformed rule is a compile error instead of a
+//! silently ignored string. `regex(...)` patterns are checked with the syntax of the
+//! `regex` crate, so an invalid pattern is a compile error too.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// A rule parsed from a `validate = "..."` string
+pub enum ParsedRule {
+    /// Built-in rule; tokens construct a `lithair_core::http::ValidationRule`
+    Builtin(TokenStream),
+    /// `custom(path::to::fn)`; the function receives `&FieldType`
+    Custom(syn::Path),
+}
+
+/// Parse a rule string such as `"non_empty, length(1, 80)"` into its rules
+pub fn parse_rules(spec: &str) -> Result<Vec<ParsedRule>, String> {
+    split_top_level(spec).iter().map(|rule| parse_rule(rule)).collect()
+}
+
+fn parse_rule(rule: &str) -> Result<ParsedRule, String> {
+    let (name, args) = match rule.find('(') {
+        Some(open) => {
+            if !rule.ends_with(')') {
+                return Err(format!("unterminated arguments in validation rule `{}`", rule));
+            }
+            (rule[..open].trim(), Some(&rule[open + 1..rule.len() - 1]))
+        }
+        None => (rule.trim(), None),
+    };
+
+    let rule_ts = quote! { lithair_core::http::ValidationRule };
+    let tokens = match (name, args) {
+        ("non_empty" | "required", None) => quote! { #rule_ts::NonEmpty },
+        ("email", None) => quote! { #rule_ts::Email },
+        ("url", None) => quote! { #rule_ts::Url },
+        ("uuid", None) => quote! { #rule_ts::Uuid },
+        ("length", Some(args)) => {
+            let (min, max) = two_args(name, args)?;
+            let min = opt_usize(name, min)?;
+            let max = opt_usize(name, max)?;
+            quote! { #rule_ts::Length { min: #min, max: #max } }
+        }
+        ("min_length", Some(arg)) => {
+            let min = opt_usize(name, Some(arg))?;
+            quote! { #rule_ts::Length { min: #min, max: None } }
+        }
+        ("max_length", Some(arg)) => {
+            let max = opt_usize(name, Some(arg))?;
+            quote! { #rule_ts::Length { min: None, max: #max } }
+        }
+        ("range", Some(args)) => {
+            let (min, max) = two_args(name, args)?;
+            let min = opt_f64(name, min)?;
+            let max = opt_f64(name, max)?;
+            quote! { #rule_ts::Range { min: #min, max: #max } }
+        }
+        ("min_value", Some(arg)) => {
+            let min = opt_f64(name, Some(arg))?;
+            quote! { #rule_ts::Range { min: #min, max: None } }
+        }
+        ("max_value", Some(arg)) => {
+            let max = opt_f64(name, Some(arg))?;
+            quote! { #rule_ts::Range { min: None, max: #max } }
+        }
+        ("regex", Some(pattern)) => {
+            let pattern = unquote(pattern.trim());
+            if pattern.is_empty() {
+                return Err("regex(...) requires a pattern".to_string());
+            }
+            if let Err(e) = regex_syntax::Parser::new().parse(pattern) {
+                return Err(format!("invalid pattern in regex(...): {}", e));
+            }
+            quote! { #rule_ts::Regex(#pattern.to_string()) }
+        }
+        ("one_of", Some(args)) => {
+            let values: Vec<String> = split_top_level(args)
+                .iter()
+                .map(|v| unquote(v).to_string())
+                .filter(|v| !v.is_empty())
+                .collect();
+            if values.is_empty() {
+                return Err("one_of(...) requires at least one value".to_string());
+            }
+            quote! { #rule_ts::OneOf(vec![#(#values.to_string()),*]) }
+        }
+        ("custom", Some(path)) => {
+            let path = syn::parse_str::<syn::Path>(unquote(path.trim()))
+                .map_err(|e| format!("invalid function path in custom(...): {}", e))?;
+            return Ok(ParsedRule::Custom(path));
+        }
+        _ => return Err(format!("unknown validation rule `{}`", rule)),
+    };
+    Ok(ParsedRule::Builtin(tokens))
+}
+
+/// Split on commas that are not nested inside parentheses, quotes or regex character
+/// classes (`[...]`)
+///
+/// A backslash escapes the next character, and a quote only opens a quoted value at the
+/// start of an argument, so `regex(^[(,']+\)$)` stays one rule.
+fn split_top_level(s: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut in_class = false;
+    let mut escaped = false;
+    let mut current = String::new();
+    for c in s.chars() {
+        if escaped {
+            escaped = false;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+        } else if in_class {
+            match c {
+                '\\' => escaped = true,
+                ']' => in_class = false,
+                _ => {}
+            }
+        } else {
+            match c {
+                '\\' => escaped = true,
+                '[' => in_class = true,
+                '\'' | '"' if current.trim().is_empty() || current.trim_end().ends_with('(') => {
+                    quote = Some(c)
+                }
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    parts.push(current.trim().to_string());
+                    current.clear();
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+fn two_args<'a>(name: &str, args: &'a str) -> Result<(Option<&'a str>, Option<&'a str>), String> {
+    let mut it = args.splitn(2, ',');
+    let first = it.next().map(str::trim).filter(|s| !s.is_empty() && *s != "_");
+    let second = it.next().map(str::trim).filter(|s| !s.is_empty() && *s != "_");
+    if first.is_none() && second.is_none() {
+        return Err(format!("{}(min, max) requires at least one bound", name));
+    }
+    Ok((first, second))
+}
+
+fn opt_usize(name: &str, arg: Option<&str>) -> Result<TokenStream, String> {
+    match arg {
+        Some(v) => {
+            let n: usize =
+                v.trim().parse().map_err(|_| format!("{}: `{}` is not an integer", name, v))?;
+            Ok(quote! { Some(#n) })
+        }
+        None => Ok(quote! { None }),
+    }
+}
+
+fn opt_f64(name: &str, arg: Option<&str>) -> Result<TokenStream, String> {
+    match arg {
+        Some(v) => {
+            let n: f64 =
+                v.trim().parse().map_err(|_| format!("{}: `{}` is not a number", name, v))?;
+            Ok(quote! { Some(#n) })
+        }
+        None => Ok(quote! { None }),
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    for q in ['"', '\''] {
+        if s.len() >= 2 && s.starts_with(q) && s.ends_with(q) {
+            return &s[1..s.len() - 1];
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_top_level() {
+        assert_eq!(
+            split_top_level("non_empty, length(1, 80), one_of(a,b)"),
+            vec!["non_empty", "length(1, 80)", "one_of(a,b)"]
+        );
+        // Parentheses, quotes and commas inside a character class, escaped parentheses
+        assert_eq!(
+            split_top_level(r"regex(^[(,']+\)$), regex('^[a-z]+$'), email"),
+            vec![r"regex(^[(,']+\)$)", "regex('^[a-z]+$')", "email"]
+        );
+        assert_eq!(split_top_level(r"regex(^[\]),]+$)"), vec![r"regex(^[\]),]+$)"]);
+    }
+
+    #[test]
+    fn test_parse_known_rules() {
+        let rules = parse_rules(
+            "non_empty, min_value(0.01), range(_, 10), regex(^[a-z]+$), email, custom(crate::check)",
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 6);
+        assert!(matches!(rules[5], ParsedRule::Custom(_)));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_or_malformed() {
+        assert!(parse_rules("not_a_rule").is_err());
+        assert!(parse_rules("length(abc, 3)").is_err());
+        assert!(parse_rules("range(_, _)").is_err());
+        assert!(parse_rules("one_of()").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_regex() {
+        assert!(parse_rules(r"regex(^[(,']+\)$)").is_ok());
+        assert!(parse_rules("regex(\"^[A-Z]{3}-[0-9]{4}$\")").is_ok());
+        let err = parse_rules("regex(a(b)").err().unwrap();
+        assert!(err.contains("invalid pattern"), "{}", err);
+        assert!(parse_rules("regex([a-)").is_err());
+        assert!(parse_rules("regex(x{2,1})").is_err());
+    }
+}